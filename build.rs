@@ -0,0 +1,11 @@
+use std::{env, fs, path::Path};
+
+// Generates the typed accessors for the kinds of `proto/ast.proto` (see `src/generated.rs`).
+fn main() {
+    println!("cargo:rerun-if-changed=proto/ast.proto");
+    let text = fs::read_to_string("proto/ast.proto").expect("could not read proto/ast.proto");
+    let code = linc_schema::codegen::generate_proto(&text)
+        .unwrap_or_else(|e| panic!("could not generate accessors for proto/ast.proto: {}", e));
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not set");
+    fs::write(Path::new(&out_dir).join("ast.rs"), code).expect("could not write ast.rs");
+}
@@ -0,0 +1,467 @@
+use linc::{
+    cargo::*,
+    schema::{FieldType, Kind, Schema},
+    types::{Link, LinkType, Node, NodeStore},
+};
+use std::fmt;
+use toml::{Table, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportError {
+    // Path of the offending node, using field names, e.g. `dependencies[2].features[0]`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "<root>"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+// A node of a known kind, at a given path.
+struct Object<'s> {
+    path: String,
+    kind: &'s Kind,
+    node: Node,
+}
+
+struct Exporter<'s> {
+    node_store: &'s NodeStore,
+    schema: &'s Schema,
+    errors: Vec<ExportError>,
+}
+
+// Walks a tree typed by the `cargo_manifest` kind, and converts it to the equivalent TOML table.
+// All the errors found along the way are reported, not only the first one.
+pub fn export_manifest(
+    node_store: &NodeStore,
+    schema: &Schema,
+    root: &Link,
+) -> Result<Table, Vec<ExportError>> {
+    let mut exporter = Exporter {
+        node_store,
+        schema,
+        errors: vec![],
+    };
+    let manifest = exporter
+        .object(String::new(), CARGO_MANIFEST, root)
+        .map(|manifest| exporter.manifest(&manifest))
+        .unwrap_or_default();
+    if exporter.errors.is_empty() {
+        Ok(manifest)
+    } else {
+        Err(exporter.errors)
+    }
+}
+
+fn field_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn insert<T: Into<Value>>(table: &mut Table, key: &str, value: Option<T>) {
+    if let Some(value) = value {
+        table.insert(key.to_string(), value.into());
+    }
+}
+
+fn insert_strings(table: &mut Table, key: &str, values: Vec<String>) {
+    if !values.is_empty() {
+        table.insert(key.to_string(), values.into());
+    }
+}
+
+impl<'s> Exporter<'s> {
+    fn error(&mut self, path: &str, message: String) {
+        self.errors.push(ExportError {
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    fn object(&mut self, path: String, kind_id: u64, link: &Link) -> Option<Object<'s>> {
+        let kind = match self.schema.get_kind(kind_id) {
+            Some(kind) => kind,
+            None => {
+                self.error(&path, format!("unknown kind {}", kind_id));
+                return None;
+            }
+        };
+        match link.type_ {
+            LinkType::Dag => {}
+            LinkType::Raw => {
+                self.error(
+                    &path,
+                    format!("expected a `{}` node, found a raw value", kind.name),
+                );
+                return None;
+            }
+            LinkType::Encrypted => {
+                self.error(&path, "cannot export an encrypted node".to_string());
+                return None;
+            }
+        }
+        let node = match self.node_store.get_dag(&link.digest) {
+            Some(node) => node,
+            None => {
+                self.error(&path, format!("missing node {}", link.digest));
+                return None;
+            }
+        };
+        for field_id in node.links.keys() {
+            if kind.get_field(*field_id).is_none() {
+                self.error(
+                    &path,
+                    format!("unknown field {} for kind `{}`", field_id, kind.name),
+                );
+            }
+        }
+        Some(Object { path, kind, node })
+    }
+
+    // Links of the given field, with the path of each of them.
+    fn links(&mut self, object: &Object<'s>, field_id: u64) -> Vec<(String, FieldType, Link)> {
+        let field = match object.kind.get_field(field_id) {
+            Some(field) => field,
+            None => return vec![],
+        };
+        let path = field_path(&object.path, &field.name);
+        let links = object
+            .node
+            .links
+            .get(&field_id)
+            .cloned()
+            .unwrap_or_default();
+        if field.repeated {
+            links
+                .into_iter()
+                .enumerate()
+                .map(|(i, link)| (format!("{}[{}]", path, i), field.type_.clone(), link))
+                .collect()
+        } else if links.len() > 1 {
+            self.error(
+                &path,
+                format!("expected at most one value, found {}", links.len()),
+            );
+            vec![]
+        } else {
+            links
+                .into_iter()
+                .map(|link| (path.clone(), field.type_.clone(), link))
+                .collect()
+        }
+    }
+
+    fn text(&mut self, path: &str, link: &Link) -> Option<String> {
+        match link.type_ {
+            LinkType::Raw => {}
+            LinkType::Dag => {
+                self.error(path, "expected a raw value, found a node".to_string());
+                return None;
+            }
+            LinkType::Encrypted => {
+                self.error(path, "cannot export an encrypted value".to_string());
+                return None;
+            }
+        }
+        let raw = match self.node_store.get_raw(&link.digest) {
+            Some(raw) => raw.clone(),
+            None => {
+                self.error(path, format!("missing value {}", link.digest));
+                return None;
+            }
+        };
+        match String::from_utf8(raw) {
+            Ok(text) => Some(text),
+            Err(_) => {
+                self.error(path, "invalid UTF-8 string".to_string());
+                None
+            }
+        }
+    }
+
+    fn scalar(&mut self, path: &str, type_: &FieldType, link: &Link) -> Option<Value> {
+        let text = self.text(path, link)?;
+        let value = match type_ {
+            FieldType::String | FieldType::Bytes => Some(Value::String(text.clone())),
+            FieldType::Bool => text.parse().ok().map(Value::Boolean),
+            FieldType::Int => text.parse().ok().map(Value::Integer),
+            FieldType::Float => text.parse().ok().map(Value::Float),
+            FieldType::Object { .. } => None,
+        };
+        if value.is_none() {
+            self.error(path, format!("invalid {:?} value `{}`", type_, text));
+        }
+        value
+    }
+
+    fn values(&mut self, object: &Object<'s>, field_id: u64) -> Vec<Value> {
+        self.links(object, field_id)
+            .into_iter()
+            .filter_map(|(path, type_, link)| self.scalar(&path, &type_, &link))
+            .collect()
+    }
+
+    fn value(&mut self, object: &Object<'s>, field_id: u64) -> Option<Value> {
+        self.values(object, field_id).pop()
+    }
+
+    fn string(&mut self, object: &Object<'s>, field_id: u64) -> Option<String> {
+        self.value(object, field_id)
+            .and_then(|v| v.as_str().map(|v| v.to_string()))
+    }
+
+    fn strings(&mut self, object: &Object<'s>, field_id: u64) -> Vec<String> {
+        self.values(object, field_id)
+            .into_iter()
+            .filter_map(|v| v.as_str().map(|v| v.to_string()))
+            .collect()
+    }
+
+    fn bool(&mut self, object: &Object<'s>, field_id: u64) -> Option<bool> {
+        self.value(object, field_id).and_then(|v| v.as_bool())
+    }
+
+    fn objects(&mut self, object: &Object<'s>, field_id: u64) -> Vec<Object<'s>> {
+        self.links(object, field_id)
+            .into_iter()
+            .filter_map(|(path, type_, link)| match type_ {
+                FieldType::Object { kind_id } => self.object(path, kind_id, &link),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn required_name(&mut self, object: &Object<'s>, field_id: u64) -> Option<String> {
+        let name = self.string(object, field_id);
+        if name.is_none() {
+            self.error(&object.path, "missing name".to_string());
+        }
+        name
+    }
+
+    fn manifest(&mut self, manifest: &Object<'s>) -> Table {
+        let mut table = Table::new();
+        if let Some(package) = self.objects(manifest, cargo_manifest::PACKAGE).pop() {
+            table.insert("package".to_string(), self.package(&package).into());
+        }
+        if let Some(workspace) = self.objects(manifest, cargo_manifest::WORKSPACE).pop() {
+            table.insert("workspace".to_string(), self.workspace(&workspace).into());
+        }
+        if let Some(lib) = self.objects(manifest, cargo_manifest::LIB).pop() {
+            table.insert("lib".to_string(), self.product(&lib).into());
+        }
+        for (key, field_id) in [
+            ("bin", cargo_manifest::BIN),
+            ("example", cargo_manifest::EXAMPLE),
+            ("test", cargo_manifest::TEST),
+            ("bench", cargo_manifest::BENCH),
+        ] {
+            let products: Vec<Value> = self
+                .objects(manifest, field_id)
+                .iter()
+                .map(|product| self.product(product).into())
+                .collect();
+            if !products.is_empty() {
+                table.insert(key.to_string(), products.into());
+            }
+        }
+        let mut features = Table::new();
+        for feature in self.objects(manifest, cargo_manifest::FEATURES) {
+            if let Some(name) = self.required_name(&feature, cargo_feature::NAME) {
+                let enables = self.strings(&feature, cargo_feature::ENABLES);
+                features.insert(name, enables.into());
+            }
+        }
+        if !features.is_empty() {
+            table.insert("features".to_string(), features.into());
+        }
+        self.dependencies(
+            &mut table,
+            manifest,
+            &[
+                ("dependencies", cargo_manifest::DEPENDENCIES),
+                ("dev-dependencies", cargo_manifest::DEV_DEPENDENCIES),
+                ("build-dependencies", cargo_manifest::BUILD_DEPENDENCIES),
+            ],
+        );
+        let mut targets = Table::new();
+        for target in self.objects(manifest, cargo_manifest::TARGET) {
+            let cfg = match self.string(&target, cargo_target::CFG) {
+                Some(cfg) => cfg,
+                None => {
+                    self.error(&target.path, "missing cfg".to_string());
+                    continue;
+                }
+            };
+            let mut target_table = Table::new();
+            self.dependencies(
+                &mut target_table,
+                &target,
+                &[
+                    ("dependencies", cargo_target::DEPENDENCIES),
+                    ("dev-dependencies", cargo_target::DEV_DEPENDENCIES),
+                    ("build-dependencies", cargo_target::BUILD_DEPENDENCIES),
+                ],
+            );
+            targets.insert(cfg, target_table.into());
+        }
+        if !targets.is_empty() {
+            table.insert("target".to_string(), targets.into());
+        }
+        table
+    }
+
+    fn package(&mut self, package: &Object<'s>) -> Table {
+        let mut table = Table::new();
+        let name = self.required_name(package, cargo_package::NAME);
+        insert(&mut table, "name", name);
+        for (key, field_id) in [
+            ("version", cargo_package::VERSION),
+            ("edition", cargo_package::EDITION),
+            ("rust-version", cargo_package::RUST_VERSION),
+            ("description", cargo_package::DESCRIPTION),
+            ("homepage", cargo_package::HOMEPAGE),
+            ("documentation", cargo_package::DOCUMENTATION),
+            ("readme", cargo_package::README),
+            ("repository", cargo_package::REPOSITORY),
+            ("license", cargo_package::LICENSE),
+            ("build", cargo_package::BUILD),
+            ("default-run", cargo_package::DEFAULT_RUN),
+        ] {
+            let value = self.string(package, field_id);
+            insert(&mut table, key, value);
+        }
+        for (key, field_id) in [
+            ("authors", cargo_package::AUTHORS),
+            ("keywords", cargo_package::KEYWORDS),
+            ("categories", cargo_package::CATEGORIES),
+        ] {
+            let values = self.strings(package, field_id);
+            insert_strings(&mut table, key, values);
+        }
+        let publish = self.bool(package, cargo_package::PUBLISH);
+        insert(&mut table, "publish", publish);
+        table
+    }
+
+    fn workspace(&mut self, workspace: &Object<'s>) -> Table {
+        let mut table = Table::new();
+        for (key, field_id) in [
+            ("members", cargo_workspace::MEMBERS),
+            ("exclude", cargo_workspace::EXCLUDE),
+            ("default-members", cargo_workspace::DEFAULT_MEMBERS),
+        ] {
+            let values = self.strings(workspace, field_id);
+            insert_strings(&mut table, key, values);
+        }
+        let resolver = self.string(workspace, cargo_workspace::RESOLVER);
+        insert(&mut table, "resolver", resolver);
+        self.dependencies(
+            &mut table,
+            workspace,
+            &[("dependencies", cargo_workspace::DEPENDENCIES)],
+        );
+        table
+    }
+
+    fn product(&mut self, product: &Object<'s>) -> Table {
+        let mut table = Table::new();
+        for (key, field_id) in [("name", cargo_product::NAME), ("path", cargo_product::PATH)] {
+            let value = self.string(product, field_id);
+            insert(&mut table, key, value);
+        }
+        for (key, field_id) in [
+            ("required-features", cargo_product::REQUIRED_FEATURES),
+            ("crate-type", cargo_product::CRATE_TYPE),
+        ] {
+            let values = self.strings(product, field_id);
+            insert_strings(&mut table, key, values);
+        }
+        for (key, field_id) in [
+            ("test", cargo_product::TEST),
+            ("doctest", cargo_product::DOCTEST),
+            ("bench", cargo_product::BENCH),
+            ("harness", cargo_product::HARNESS),
+            ("proc-macro", cargo_product::PROC_MACRO),
+        ] {
+            let value = self.bool(product, field_id);
+            insert(&mut table, key, value);
+        }
+        table
+    }
+
+    fn dependencies(&mut self, table: &mut Table, object: &Object<'s>, fields: &[(&str, u64)]) {
+        for (key, field_id) in fields {
+            let mut dependencies = Table::new();
+            for dependency in self.objects(object, *field_id) {
+                if let Some(name) = self.required_name(&dependency, cargo_dependency::NAME) {
+                    if dependencies.contains_key(&name) {
+                        self.error(&dependency.path, format!("duplicate dependency `{}`", name));
+                    }
+                    let value = self.dependency(&dependency);
+                    dependencies.insert(name, value);
+                }
+            }
+            if !dependencies.is_empty() {
+                table.insert(key.to_string(), dependencies.into());
+            }
+        }
+    }
+
+    fn dependency(&mut self, dependency: &Object<'s>) -> Value {
+        let mut table = Table::new();
+        for (key, field_id) in [
+            ("version", cargo_dependency::VERSION),
+            ("path", cargo_dependency::PATH),
+            ("git", cargo_dependency::GIT),
+            ("branch", cargo_dependency::BRANCH),
+            ("tag", cargo_dependency::TAG),
+            ("rev", cargo_dependency::REV),
+            ("package", cargo_dependency::PACKAGE),
+        ] {
+            let value = self.string(dependency, field_id);
+            insert(&mut table, key, value);
+        }
+        let features = self.strings(dependency, cargo_dependency::FEATURES);
+        insert_strings(&mut table, "features", features);
+        for (key, field_id) in [
+            ("optional", cargo_dependency::OPTIONAL),
+            ("default-features", cargo_dependency::DEFAULT_FEATURES),
+            ("workspace", cargo_dependency::WORKSPACE),
+        ] {
+            let value = self.bool(dependency, field_id);
+            insert(&mut table, key, value);
+        }
+        let git_refs = ["branch", "tag", "rev"]
+            .iter()
+            .filter(|key| table.contains_key(**key))
+            .count();
+        if git_refs > 0 && !table.contains_key("git") {
+            self.error(
+                &dependency.path,
+                "branch, tag and rev require git".to_string(),
+            );
+        }
+        if git_refs > 1 {
+            self.error(
+                &dependency.path,
+                "only one of branch, tag and rev may be specified".to_string(),
+            );
+        }
+        // Use the short form when only the version is specified.
+        match (table.len(), table.get("version")) {
+            (1, Some(version)) => version.clone(),
+            _ => table.into(),
+        }
+    }
+}
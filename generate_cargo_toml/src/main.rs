@@ -1,31 +1,33 @@
+use anyhow::{anyhow, bail, Context};
+use base64::Engine;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
-pub struct Node {
-    // UUID.
-    pub kind: String,
-    pub value: String,
-    // Keyed by field id.
-    pub links: BTreeMap<usize, Vec<Digest>>,
-}
+mod export;
+
+#[cfg(test)]
+mod tests;
 
-type Digest = String;
+use linc::{
+    cargo,
+    types::{deserialize_node, Digest, Link, LinkType, NodeStore},
+};
 
+// Same protocol as LINC's `src/ent.rs`.
 #[derive(Serialize, Deserialize)]
-pub struct PutRequest {
-    pub blobs: Vec<Vec<u8>>,
+pub struct GetRequest {
+    pub items: Vec<GetRequestItem>,
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct GetRequest {
-    pub items: Vec<GetItem>,
+pub struct GetRequestItem {
+    pub node_id: NodeID,
+    pub depth: u64,
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct GetItem {
-    pub root: String,
-    // pub path: Vec<Selector>,
+pub struct NodeID {
+    pub root: Link,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -33,105 +35,109 @@ pub struct GetResponse {
     pub items: HashMap<Digest, String>,
 }
 
-pub fn deserialize_node(raw: &[u8]) -> Option<Node> {
-    serde_json::from_slice(raw).ok()
+pub struct EntClient {
+    pub api_url: String,
 }
 
-const API_URL: &str = "http://127.0.0.1:8088";
-pub struct EntClient;
-
 impl EntClient {
-    pub async fn get_blobs(
-        &self,
-        req: &GetRequest,
-    ) -> Result<GetResponse, Box<dyn std::error::Error>> {
+    pub async fn get_blobs(&self, req: &GetRequest) -> anyhow::Result<GetResponse> {
         let client = reqwest::Client::new();
         let req_json = serde_json::to_string(&req)?;
         let res = client
-            .post(&format!("{}/api/v1/blobs/get", API_URL))
+            .post(format!("{}/api/v1/blobs/get", self.api_url))
             .body(req_json)
             .send()
-            .await
-            .unwrap();
-        let res_json = res.json().await.unwrap();
-        Ok(res_json)
+            .await?;
+        Ok(res.json().await?)
+    }
+
+    // Fetches the whole tree reachable from the given root, checking that every blob matches its
+    // digest.
+    pub async fn fetch_tree(&self, root: &str) -> anyhow::Result<NodeStore> {
+        let mut node_store = NodeStore::default();
+        let mut pending = vec![root.to_string()];
+        while !pending.is_empty() {
+            let req = GetRequest {
+                items: pending
+                    .iter()
+                    .map(|digest| GetRequestItem {
+                        node_id: NodeID {
+                            root: Link {
+                                type_: LinkType::Raw,
+                                digest: digest.clone(),
+                            },
+                        },
+                        depth: 0,
+                    })
+                    .collect(),
+            };
+            let res = self.get_blobs(&req).await?;
+            let mut next = vec![];
+            for digest in pending {
+                let blob = res
+                    .items
+                    .get(&digest)
+                    .ok_or_else(|| anyhow!("blob {} not found", digest))?;
+                let blob = base64::engine::general_purpose::STANDARD
+                    .decode(blob)
+                    .with_context(|| format!("invalid base64 for blob {}", digest))?;
+                let actual = node_store.put_raw(&blob);
+                if actual != digest {
+                    bail!("blob {} hashes to {}", digest, actual);
+                }
+                if let Some(node) = deserialize_node(&blob) {
+                    next.extend(
+                        node.links
+                            .into_values()
+                            .flatten()
+                            .map(|link| link.digest)
+                            .filter(|d| node_store.get_raw(d).is_none()),
+                    );
+                }
+            }
+            next.sort();
+            next.dedup();
+            pending = next;
+        }
+        Ok(node_store)
     }
 }
 
+const USAGE: &str = "usage: generate_cargo_toml <root> <store>
+
+  <root>   digest of a `cargo_manifest` node
+  <store>  URL of an Ent server (e.g. http://127.0.0.1:27333), or path to a JSON file containing a
+           node store saved by LINC";
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let root = "sha256:13ebfcba59b6a3f98ec338236eb11da5ddba8b75e9b1a9c079c4e96cf48b7fd6";
-    let client = EntClient;
-    let req = GetRequest {
-        items: vec![GetItem {
-            root: root.to_string(),
-        }],
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (root, store) = match args.as_slice() {
+        [root, store] => (root.clone(), store.clone()),
+        _ => bail!(USAGE),
     };
-    let res = client.get_blobs(&req).await.unwrap();
-    let nodes: HashMap<String, Node> = res
-        .items
-        .iter()
-        .map(|(k, v)| {
-            let b = base64::decode(&v).unwrap();
-            let node = deserialize_node(&b).unwrap();
-            (k.to_string(), node)
-        })
-        .collect();
-
-    let root_node = nodes.get(root).unwrap();
-    let cargo_toml = nodes.get(&root_node.links[&0][0]).unwrap();
-
-    let package = nodes.get(cargo_toml.links[&0][0].as_str()).unwrap();
-
-    let name = nodes.get(package.links[&0][0].as_str()).unwrap();
-    let version = nodes.get(package.links[&1][0].as_str()).unwrap();
-    let authors = nodes.get(package.links[&2][0].as_str()).unwrap();
-    let edition = nodes.get(package.links[&3][0].as_str()).unwrap();
-
-    let manifest = cargo_toml::Manifest::<cargo_toml::Value> {
-        package: Some(cargo_toml::Package {
-            name: name.value.clone(),
-            edition: cargo_toml::Edition::E2021,
-            version: version.value.clone(),
-            build: None,
-            workspace: None,
-            authors: vec![authors.value.clone()],
-            links: None,
-            description: None,
-            homepage: None,
-            documentation: None,
-            readme: None,
-            keywords: Vec::new(),
-            categories: Vec::new(),
-            license: None,
-            license_file: None,
-            repository: None,
-            default_run: None,
-            autobins: true,
-            autoexamples: true,
-            autotests: true,
-            autobenches: true,
-            publish: cargo_toml::Publish::default(),
-            resolver: None,
-            metadata: None,
-        }),
-        workspace: None,
-        dependencies: BTreeMap::new(),
-        dev_dependencies: BTreeMap::new(),
-        build_dependencies: BTreeMap::new(),
-        target: BTreeMap::new(),
-        features: BTreeMap::new(),
-        patch: BTreeMap::new(),
-        lib: None,
-        profile: cargo_toml::Profiles::default(),
-        badges: cargo_toml::Badges::default(),
-        bin: Vec::new(),
-        bench: Vec::new(),
-        test: Vec::new(),
-        example: Vec::new(),
+    let node_store = if store.starts_with("http://") || store.starts_with("https://") {
+        EntClient { api_url: store }.fetch_tree(&root).await?
+    } else {
+        let content = std::fs::read(&store).with_context(|| format!("could not read {}", store))?;
+        serde_json::from_slice(&content).with_context(|| format!("invalid node store {}", store))?
     };
-    let o = toml::to_string_pretty(&manifest).unwrap();
-
-    print!("{}", o);
+    let root = Link {
+        type_: LinkType::Dag,
+        digest: root,
+    };
+    let manifest = match export::export_manifest(&node_store, &cargo::cargo_schema(), &root) {
+        Ok(manifest) => manifest,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}", error);
+            }
+            bail!("{} errors", errors.len());
+        }
+    };
+    let out = toml::to_string_pretty(&manifest)?;
+    // Make sure that Cargo would accept the result.
+    cargo_toml::Manifest::from_str(&out).context("invalid manifest")?;
+    print!("{}", out);
     Ok(())
 }
@@ -0,0 +1,222 @@
+use crate::export::{export_manifest, ExportError};
+use linc::{
+    cargo::*,
+    types::{Link, LinkType, Node, NodeStore},
+};
+use std::collections::BTreeMap;
+
+fn raw(node_store: &mut NodeStore, value: &str) -> Link {
+    Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(value.as_bytes()),
+    }
+}
+
+fn node(node_store: &mut NodeStore, fields: Vec<(u64, Vec<Link>)>) -> Link {
+    Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&Node {
+            links: fields.into_iter().collect::<BTreeMap<_, _>>(),
+            ..Default::default()
+        }),
+    }
+}
+
+fn export(node_store: &NodeStore, root: &Link) -> Result<String, Vec<ExportError>> {
+    export_manifest(node_store, &cargo_schema(), root)
+        .map(|manifest| toml::to_string_pretty(&manifest).unwrap())
+}
+
+#[test]
+fn test_export_manifest() {
+    let mut s = NodeStore::default();
+    let package = vec![
+        (cargo_package::NAME, vec![raw(&mut s, "linc")]),
+        (cargo_package::VERSION, vec![raw(&mut s, "0.1.0")]),
+        (
+            cargo_package::AUTHORS,
+            vec![raw(&mut s, "Tiziano Santoro <tiziano88@gmail.com>")],
+        ),
+        (cargo_package::EDITION, vec![raw(&mut s, "2021")]),
+        (cargo_package::PUBLISH, vec![raw(&mut s, "false")]),
+    ];
+    let package = node(&mut s, package);
+    let base64 = vec![
+        (cargo_dependency::NAME, vec![raw(&mut s, "base64")]),
+        (cargo_dependency::VERSION, vec![raw(&mut s, "*")]),
+    ];
+    let base64 = node(&mut s, base64);
+    let serde = vec![
+        (cargo_dependency::NAME, vec![raw(&mut s, "serde")]),
+        (cargo_dependency::VERSION, vec![raw(&mut s, "1.0")]),
+        (
+            cargo_dependency::FEATURES,
+            vec![raw(&mut s, "derive"), raw(&mut s, "rc")],
+        ),
+        (cargo_dependency::OPTIONAL, vec![raw(&mut s, "true")]),
+    ];
+    let serde = node(&mut s, serde);
+    let local = vec![
+        (cargo_dependency::NAME, vec![raw(&mut s, "linc_derive")]),
+        (cargo_dependency::PATH, vec![raw(&mut s, "linc_derive")]),
+    ];
+    let local = node(&mut s, local);
+    let feature = vec![
+        (cargo_feature::NAME, vec![raw(&mut s, "default")]),
+        (cargo_feature::ENABLES, vec![raw(&mut s, "serde")]),
+    ];
+    let feature = node(&mut s, feature);
+    let bin = vec![
+        (cargo_product::NAME, vec![raw(&mut s, "linc")]),
+        (cargo_product::PATH, vec![raw(&mut s, "src/main.rs")]),
+    ];
+    let bin = node(&mut s, bin);
+    let wasm_bindgen = vec![
+        (cargo_dependency::NAME, vec![raw(&mut s, "wasm-bindgen")]),
+        (
+            cargo_dependency::GIT,
+            vec![raw(&mut s, "https://github.com/rustwasm/wasm-bindgen")],
+        ),
+        (cargo_dependency::TAG, vec![raw(&mut s, "0.2.84")]),
+    ];
+    let wasm_bindgen = node(&mut s, wasm_bindgen);
+    let target = vec![
+        (
+            cargo_target::CFG,
+            vec![raw(&mut s, "cfg(target_arch = \"wasm32\")")],
+        ),
+        (cargo_target::DEPENDENCIES, vec![wasm_bindgen]),
+    ];
+    let target = node(&mut s, target);
+    let root = vec![
+        (cargo_manifest::PACKAGE, vec![package]),
+        (cargo_manifest::DEPENDENCIES, vec![base64, serde]),
+        (cargo_manifest::DEV_DEPENDENCIES, vec![local]),
+        (cargo_manifest::FEATURES, vec![feature]),
+        (cargo_manifest::BIN, vec![bin]),
+        (cargo_manifest::TARGET, vec![target]),
+    ];
+    let root = node(&mut s, root);
+    let out = export(&s, &root).unwrap();
+    assert_eq!(
+        out,
+        r#"[package]
+name = "linc"
+version = "0.1.0"
+edition = "2021"
+authors = ["Tiziano Santoro <tiziano88@gmail.com>"]
+publish = false
+
+[[bin]]
+name = "linc"
+path = "src/main.rs"
+
+[features]
+default = ["serde"]
+
+[dependencies]
+base64 = "*"
+
+[dependencies.serde]
+version = "1.0"
+features = [
+    "derive",
+    "rc",
+]
+optional = true
+
+[dev-dependencies.linc_derive]
+path = "linc_derive"
+
+[target.'cfg(target_arch = "wasm32")'.dependencies.wasm-bindgen]
+git = "https://github.com/rustwasm/wasm-bindgen"
+tag = "0.2.84"
+"#
+    );
+    cargo_toml::Manifest::from_str(&out).unwrap();
+}
+
+#[test]
+fn test_export_manifest_errors() {
+    let mut s = NodeStore::default();
+    let package = vec![(cargo_package::VERSION, vec![raw(&mut s, "0.1.0")])];
+    let package = node(&mut s, package);
+    let dependency = vec![
+        (cargo_dependency::NAME, vec![raw(&mut s, "serde")]),
+        (cargo_dependency::BRANCH, vec![raw(&mut s, "main")]),
+        (cargo_dependency::OPTIONAL, vec![raw(&mut s, "maybe")]),
+        (42, vec![raw(&mut s, "?")]),
+    ];
+    let dependency = node(&mut s, dependency);
+    let missing = Link {
+        type_: LinkType::Dag,
+        digest: "sha256:missing".to_string(),
+    };
+    let encrypted = Link {
+        type_: LinkType::Encrypted,
+        digest: s.put_raw(b"ciphertext"),
+    };
+    let root = vec![
+        (cargo_manifest::PACKAGE, vec![package]),
+        (cargo_manifest::DEPENDENCIES, vec![dependency]),
+        (cargo_manifest::DEV_DEPENDENCIES, vec![missing]),
+        (cargo_manifest::BUILD_DEPENDENCIES, vec![encrypted]),
+    ];
+    let root = node(&mut s, root);
+    let errors: Vec<String> = export(&s, &root)
+        .unwrap_err()
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            "package: missing name",
+            "dependencies[0]: unknown field 42 for kind `cargo_dependency`",
+            "dependencies[0].optional: invalid Bool value `maybe`",
+            "dependencies[0]: branch, tag and rev require git",
+            "dev_dependencies[0]: missing node sha256:missing",
+            "build_dependencies[0]: cannot export an encrypted node",
+        ]
+    );
+}
+
+#[test]
+fn test_read_sequence_node() {
+    // A node written by LINC with a field stored as a sequence, which must be read as such, and
+    // serialized back to the same bytes, since it is addressed by their digest.
+    let raw = br#"{
+  "links": {
+    "1": [
+      {
+        "type": 0,
+        "digest": "sha256:a"
+      }
+    ]
+  },
+  "sequences": {
+    "1": [
+      {
+        "id": {
+          "counter": 1,
+          "replica": "a"
+        }
+      },
+      {
+        "id": {
+          "counter": 2,
+          "replica": "a"
+        },
+        "origin": {
+          "counter": 1,
+          "replica": "a"
+        },
+        "deleted": true
+      }
+    ]
+  }
+}"#;
+    let node = linc::types::deserialize_node(raw).unwrap();
+    assert_eq!(node.sequences[&1].len(), 2);
+    assert_eq!(linc::types::serialize_node(&node), raw.to_vec());
+}
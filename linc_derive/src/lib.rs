@@ -0,0 +1,275 @@
+// Implementation of `#[derive(LincNode)]`; see `src/derive.rs` in the `linc` crate for the traits
+// that it implements and the way types are mapped to kinds.
+//
+// The generated code refers to the public items of the `linc` library as `::linc`, so the derive
+// may be used by any crate depending on it.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Fields, LitInt};
+
+#[proc_macro_derive(LincNode, attributes(linc))]
+pub fn derive_linc_node(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+// Returns the value of `#[linc(<key> = N)]`, if any.
+fn attribute(attrs: &[Attribute], key: &str) -> syn::Result<Option<u64>> {
+    let mut value = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("linc")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") || meta.path.is_ident("kind_id") {
+                let lit: LitInt = meta.value()?.parse()?;
+                if meta.path.is_ident(key) {
+                    value = Some(lit.base10_parse()?);
+                }
+                Ok(())
+            } else {
+                Err(meta.error("expected `id` or `kind_id`"))
+            }
+        })?;
+    }
+    Ok(value)
+}
+
+// Returns the field id given by `#[linc(id = N)]`, checking that it is unique.
+fn field_id(attrs: &[Attribute], span: proc_macro2::Span, ids: &mut Vec<u64>) -> syn::Result<u64> {
+    let id = attribute(attrs, "id")?
+        .ok_or_else(|| syn::Error::new(span, "missing `#[linc(id = N)]` attribute"))?;
+    if ids.contains(&id) {
+        return Err(syn::Error::new(span, format!("duplicate id {}", id)));
+    }
+    ids.push(id);
+    Ok(id)
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn derive(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "generic types are not supported",
+        ));
+    }
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let kind_id = match attribute(&input.attrs, "kind_id")? {
+        Some(kind_id) => quote! { #kind_id },
+        None => quote! { ::linc::proto::kind_id(#name_str) },
+    };
+    let mut ids = vec![];
+    // Field definitions, types (to collect their kinds), and bodies of `to_dag` and `from_dag_at`.
+    let mut fields = vec![];
+    let mut types = vec![];
+    let (to_dag, from_dag) = match &input.data {
+        Data::Struct(data) => {
+            let named = match &data.fields {
+                Fields::Named(named) => named.named.iter().collect(),
+                Fields::Unit => vec![],
+                Fields::Unnamed(_) => {
+                    return Err(syn::Error::new(
+                        data.fields.span(),
+                        "tuple structs are not supported",
+                    ))
+                }
+            };
+            let mut sets = vec![];
+            let mut gets = vec![];
+            for field in named {
+                let id = field_id(&field.attrs, field.span(), &mut ids)?;
+                let ident = field.ident.as_ref().unwrap();
+                let field_name = ident.to_string().trim_start_matches("r#").to_string();
+                let ty = &field.ty;
+                fields.push(quote! {
+                    ::linc::schema::Field {
+                        field_id: #id,
+                        name: #field_name.to_string(),
+                        type_: <#ty as ::linc::derive::LincField>::field_type(),
+                        repeated: <#ty as ::linc::derive::LincField>::REPEATED,
+                        ..::core::default::Default::default()
+                    }
+                });
+                types.push(quote! { #ty });
+                sets.push(quote! {
+                    builder.set_children(
+                        #id,
+                        ::linc::derive::LincField::to_links(&self.#ident, node_store),
+                    );
+                });
+                gets.push(quote! {
+                    #ident: ::linc::derive::LincField::from_links(node_store, &object, #id)?,
+                });
+            }
+            (quote! { #(#sets)* }, quote! { Ok(#name { #(#gets)* }) })
+        }
+        Data::Enum(data) => {
+            let mut sets = vec![];
+            let mut gets = vec![];
+            for variant in data.variants.iter() {
+                let id = field_id(&variant.attrs, variant.span(), &mut ids)?;
+                let ident = &variant.ident;
+                let variant_name = snake_case(&ident.to_string());
+                let ty = match &variant.fields {
+                    Fields::Unit => None,
+                    Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                        Some(&unnamed.unnamed[0].ty)
+                    }
+                    _ => {
+                        return Err(syn::Error::new(
+                            variant.span(),
+                            "only unit and newtype variants are supported",
+                        ))
+                    }
+                };
+                match ty {
+                    Some(ty) => {
+                        fields.push(quote! {
+                            ::linc::schema::Field {
+                                field_id: #id,
+                                name: #variant_name.to_string(),
+                                type_: <#ty as ::linc::derive::LincField>::field_type(),
+                                repeated: <#ty as ::linc::derive::LincField>::REPEATED,
+                                oneof: Some("value".to_string()),
+                                ..::core::default::Default::default()
+                            }
+                        });
+                        types.push(quote! { #ty });
+                        sets.push(quote! {
+                            #name::#ident(value) => builder.set_children(
+                                #id,
+                                ::linc::derive::LincField::to_links(value, node_store),
+                            ),
+                        });
+                        gets.push(quote! {
+                            Some(#id) => Ok(#name::#ident(
+                                ::linc::derive::LincField::from_links(node_store, &object, #id)?,
+                            )),
+                        });
+                    }
+                    None => {
+                        fields.push(quote! {
+                            ::linc::schema::Field {
+                                field_id: #id,
+                                name: #variant_name.to_string(),
+                                type_: ::linc::schema::FieldType::Bool,
+                                repeated: false,
+                                oneof: Some("value".to_string()),
+                                ..::core::default::Default::default()
+                            }
+                        });
+                        sets.push(quote! {
+                            #name::#ident => builder.set_children(
+                                #id,
+                                ::linc::derive::LincField::to_links(&true, node_store),
+                            ),
+                        });
+                        gets.push(quote! {
+                            Some(#id) => Ok(#name::#ident),
+                        });
+                    }
+                }
+            }
+            let all_ids = ids.clone();
+            (
+                quote! {
+                    match self {
+                        #(#sets)*
+                    }
+                },
+                quote! {
+                    match object.oneof(&[#(#all_ids),*])? {
+                        #(#gets)*
+                        _ => Err(::linc::typed::Error {
+                            path: path.to_vec(),
+                            message: "no variant set".to_string(),
+                        }),
+                    }
+                },
+            )
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span(),
+                "unions are not supported",
+            ))
+        }
+    };
+    Ok(quote! {
+        impl ::linc::derive::LincNode for #name {
+            fn kind() -> ::linc::schema::Kind {
+                ::linc::schema::Kind {
+                    kind_id: #kind_id,
+                    name: #name_str.to_string(),
+                    fields: vec![#(#fields),*],
+                }
+            }
+
+            fn kinds(kinds: &mut Vec<::linc::schema::Kind>) {
+                let kind = <Self as ::linc::derive::LincNode>::kind();
+                if kinds.iter().any(|k| k.kind_id == kind.kind_id) {
+                    return;
+                }
+                kinds.push(kind);
+                #(<#types as ::linc::derive::LincField>::kinds(kinds);)*
+            }
+
+            fn to_dag(&self, node_store: &mut ::linc::types::NodeStore) -> ::linc::types::Link {
+                let mut builder = ::linc::typed::Builder::default();
+                #to_dag
+                builder.build(node_store)
+            }
+
+            fn from_dag_at(
+                node_store: &::linc::types::NodeStore,
+                path: &[::linc::types::Selector],
+                link: &::linc::types::Link,
+            ) -> Result<Self, ::linc::typed::Error> {
+                #[allow(unused_variables)]
+                let object = ::linc::typed::Object::load(node_store, path, link)?;
+                #from_dag
+            }
+        }
+
+        impl ::linc::derive::LincValue for #name {
+            fn field_type() -> ::linc::schema::FieldType {
+                ::linc::schema::FieldType::Object {
+                    kind_id: <Self as ::linc::derive::LincNode>::kind().kind_id,
+                }
+            }
+
+            fn kinds(kinds: &mut Vec<::linc::schema::Kind>) {
+                <Self as ::linc::derive::LincNode>::kinds(kinds)
+            }
+
+            fn to_link(&self, node_store: &mut ::linc::types::NodeStore) -> ::linc::types::Link {
+                ::linc::derive::LincNode::to_dag(self, node_store)
+            }
+
+            fn from_link(
+                node_store: &::linc::types::NodeStore,
+                path: &[::linc::types::Selector],
+                link: &::linc::types::Link,
+            ) -> Result<Self, ::linc::typed::Error> {
+                <Self as ::linc::derive::LincNode>::from_dag_at(node_store, path, link)
+            }
+        }
+    })
+}
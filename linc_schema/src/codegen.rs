@@ -0,0 +1,321 @@
+use crate::{proto, Field, FieldType, Kind, Schema};
+use std::fmt::Write;
+
+// Generator of typed Rust accessors for the kinds of a schema, on top of the runtime in
+// `src/typed.rs` of LINC.
+//
+// For each kind, it emits a reader struct with a getter per field, and a builder struct with a
+// setter per field. The fields of a `oneof` are read together, as an enum with a variant per field,
+// and setting one of them clears the others. Type names are the camel case names of the kinds
+// (e.g. `ExpressionIf` for `Expression.If`).
+//
+// The output is meant to be included in LINC as a module, e.g. by a build script: the build script
+// of LINC generates the accessors for `proto/ast.proto` (see `src/generated.rs`).
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+];
+
+pub fn generate_proto(text: &str) -> Result<String, String> {
+    let schema = proto::parse(text).map_err(|e| e.to_string())?;
+    generate(&schema)
+}
+
+pub fn generate(schema: &Schema) -> Result<String, String> {
+    let mut out = String::new();
+    writeln!(out, "// Generated by `codegen::generate`; do not edit.").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use crate::{{").unwrap();
+    writeln!(out, "    typed::{{Builder, Error, Object, Reader}},").unwrap();
+    writeln!(out, "    types::{{Link, NodeStore}},").unwrap();
+    writeln!(out, "}};").unwrap();
+    let mut names = vec![];
+    for kind in schema.kinds.iter() {
+        let name = type_name(&kind.name);
+        if names.contains(&name) {
+            return Err(format!("duplicate type name `{}`", name));
+        }
+        names.push(name);
+    }
+    for kind in schema.kinds.iter() {
+        generate_kind(&mut out, schema, kind)?;
+    }
+    Ok(out)
+}
+
+// The fields of a kind, grouping the fields of each `oneof`.
+enum Member<'a> {
+    Field(&'a Field),
+    Oneof(&'a str, Vec<&'a Field>),
+}
+
+fn members(kind: &Kind) -> Vec<Member<'_>> {
+    let mut members: Vec<Member> = vec![];
+    for field in kind.fields.iter() {
+        match &field.oneof {
+            None => members.push(Member::Field(field)),
+            Some(oneof) => {
+                let existing = members.iter_mut().find_map(|m| match m {
+                    Member::Oneof(name, fields) if name == oneof => Some(fields),
+                    _ => None,
+                });
+                match existing {
+                    Some(fields) => fields.push(field),
+                    None => members.push(Member::Oneof(oneof, vec![field])),
+                }
+            }
+        }
+    }
+    members
+}
+
+fn generate_kind(out: &mut String, schema: &Schema, kind: &Kind) -> Result<(), String> {
+    let name = type_name(&kind.name);
+    let builder = format!("{}Builder", name);
+    let members = members(kind);
+
+    // Reader.
+    writeln!(out).unwrap();
+    writeln!(out, "// `{}`.", kind.name).unwrap();
+    writeln!(out, "pub struct {}<'s> {{", name).unwrap();
+    writeln!(out, "    object: Object<'s>,").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "impl<'s> Reader<'s> for {}<'s> {{", name).unwrap();
+    writeln!(out, "    const KIND_ID: u64 = {};", kind.kind_id).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    fn from_object(object: Object<'s>) -> Self {{").unwrap();
+    writeln!(out, "        {} {{ object }}", name).unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    fn object(&self) -> &Object<'s> {{").unwrap();
+    writeln!(out, "        &self.object").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "impl<'s> {}<'s> {{", name).unwrap();
+    for member in members.iter() {
+        match member {
+            Member::Field(field) => {
+                let (type_, getter) = match (&field.type_, field.repeated) {
+                    (FieldType::Object { kind_id }, false) => (
+                        format!(
+                            "Option<{}<'s>>",
+                            kind_type_name(schema, kind, field, *kind_id)?
+                        ),
+                        "child",
+                    ),
+                    (FieldType::Object { kind_id }, true) => (
+                        format!(
+                            "Vec<{}<'s>>",
+                            kind_type_name(schema, kind, field, *kind_id)?
+                        ),
+                        "children",
+                    ),
+                    (type_, false) => (scalar_type_name(type_).to_string(), "scalar"),
+                    (type_, true) => (format!("Vec<{}>", scalar_type_name(type_)), "scalars"),
+                };
+                writeln!(
+                    out,
+                    "    pub fn {}(&self) -> Result<{}, Error> {{",
+                    ident(&field.name),
+                    type_
+                )
+                .unwrap();
+                writeln!(out, "        self.object.{}({})", getter, field.field_id).unwrap();
+                writeln!(out, "    }}").unwrap();
+                writeln!(out).unwrap();
+            }
+            Member::Oneof(oneof, fields) => {
+                let enum_name = format!("{}{}", name, type_name(oneof));
+                let ids: Vec<String> = fields.iter().map(|f| f.field_id.to_string()).collect();
+                writeln!(
+                    out,
+                    "    pub fn {}(&self) -> Result<Option<{}<'s>>, Error> {{",
+                    ident(oneof),
+                    enum_name
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "        Ok(match self.object.oneof(&[{}])? {{",
+                    ids.join(", ")
+                )
+                .unwrap();
+                for field in fields.iter() {
+                    let variant = type_name(&field.name);
+                    match field.type_ {
+                        FieldType::Object { .. } => writeln!(
+                            out,
+                            "            Some({}) => self.object.child({})?.map({}::{}),",
+                            field.field_id, field.field_id, enum_name, variant
+                        ),
+                        _ => writeln!(
+                            out,
+                            "            Some({}) => Some({}::{}(self.object.scalar({})?)),",
+                            field.field_id, enum_name, variant, field.field_id
+                        ),
+                    }
+                    .unwrap();
+                }
+                writeln!(out, "            _ => None,").unwrap();
+                writeln!(out, "        }})").unwrap();
+                writeln!(out, "    }}").unwrap();
+                writeln!(out).unwrap();
+            }
+        }
+    }
+    writeln!(out, "    pub fn to_builder(&self) -> {} {{", builder).unwrap();
+    writeln!(out, "        {} {{", builder).unwrap();
+    writeln!(out, "            builder: self.object.to_builder(),").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    // Oneof enums.
+    for member in members.iter() {
+        if let Member::Oneof(oneof, fields) = member {
+            writeln!(out).unwrap();
+            writeln!(out, "// `{}.{}`.", kind.name, oneof).unwrap();
+            writeln!(out, "pub enum {}{}<'s> {{", name, type_name(oneof)).unwrap();
+            for field in fields.iter() {
+                let type_ = match field.type_ {
+                    FieldType::Object { kind_id } => {
+                        format!("{}<'s>", kind_type_name(schema, kind, field, kind_id)?)
+                    }
+                    ref type_ => scalar_type_name(type_).to_string(),
+                };
+                writeln!(out, "    {}({}),", type_name(&field.name), type_).unwrap();
+            }
+            writeln!(out, "}}").unwrap();
+        }
+    }
+
+    // Builder.
+    writeln!(out).unwrap();
+    writeln!(out, "#[derive(Debug, PartialEq, Clone, Default)]").unwrap();
+    writeln!(out, "pub struct {} {{", builder).unwrap();
+    writeln!(out, "    builder: Builder,").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "impl {} {{", builder).unwrap();
+    for member in members.iter() {
+        let (fields, oneof_ids) = match member {
+            Member::Field(field) => (vec![*field], vec![]),
+            Member::Oneof(_, fields) => (
+                fields.clone(),
+                fields.iter().map(|f| f.field_id).collect::<Vec<_>>(),
+            ),
+        };
+        for field in fields {
+            let (type_, setter, value) = match (&field.type_, field.repeated) {
+                (FieldType::Object { .. }, false) if field.oneof.is_some() => {
+                    ("Link".to_string(), "set_child", "Some(value)")
+                }
+                (FieldType::Object { .. }, false) => {
+                    ("Option<Link>".to_string(), "set_child", "value")
+                }
+                (FieldType::Object { .. }, true) => {
+                    ("Vec<Link>".to_string(), "set_children", "value")
+                }
+                (type_, false) => (scalar_type_name(type_).to_string(), "set_scalar", "&value"),
+                (type_, true) => (
+                    format!("Vec<{}>", scalar_type_name(type_)),
+                    "set_scalars",
+                    "&value",
+                ),
+            };
+            writeln!(
+                out,
+                "    pub fn set_{}(&mut self, value: {}) -> &mut Self {{",
+                field
+                    .name
+                    .to_lowercase()
+                    .replace(|c: char| !c.is_alphanumeric(), "_"),
+                type_
+            )
+            .unwrap();
+            let others: Vec<String> = oneof_ids
+                .iter()
+                .filter(|id| **id != field.field_id)
+                .map(|id| id.to_string())
+                .collect();
+            if !others.is_empty() {
+                writeln!(out, "        self.builder.clear(&[{}]);", others.join(", ")).unwrap();
+            }
+            writeln!(
+                out,
+                "        self.builder.{}({}, {});",
+                setter, field.field_id, value
+            )
+            .unwrap();
+            writeln!(out, "        self").unwrap();
+            writeln!(out, "    }}").unwrap();
+            writeln!(out).unwrap();
+        }
+    }
+    writeln!(
+        out,
+        "    pub fn build(&self, node_store: &mut NodeStore) -> Link {{"
+    )
+    .unwrap();
+    writeln!(out, "        self.builder.build(node_store)").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    Ok(())
+}
+
+fn kind_type_name(
+    schema: &Schema,
+    kind: &Kind,
+    field: &Field,
+    kind_id: u64,
+) -> Result<String, String> {
+    schema
+        .get_kind(kind_id)
+        .map(|k| type_name(&k.name))
+        .ok_or_else(|| {
+            format!(
+                "field `{}` of `{}` refers to unknown kind {}",
+                field.name, kind.name, kind_id
+            )
+        })
+}
+
+fn scalar_type_name(type_: &FieldType) -> &'static str {
+    match type_ {
+        FieldType::String => "String",
+        FieldType::Bytes => "Vec<u8>",
+        FieldType::Bool => "bool",
+        FieldType::Int => "i64",
+        FieldType::Float => "f64",
+        FieldType::Object { .. } => unreachable!(),
+    }
+}
+
+// Camel case name of a kind, e.g. `ExpressionIf` for `Expression.If`.
+fn type_name(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+// Snake case name of a field, as a raw identifier if it is a keyword.
+fn ident(name: &str) -> String {
+    let name = name
+        .to_lowercase()
+        .replace(|c: char| !c.is_alphanumeric(), "_");
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("r#{}", name)
+    } else {
+        name
+    }
+}
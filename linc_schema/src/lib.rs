@@ -0,0 +1,107 @@
+// Schema types shared by LINC and its build script, with the parser of `.proto` files turning
+// them into schemas, and the generator of typed Rust accessors for the kinds of a schema.
+
+use serde::{Deserialize, Serialize};
+
+pub mod codegen;
+pub mod proto;
+
+// Schema of the schema.
+#[derive(PartialEq, Clone, Serialize, Deserialize, Default, Debug)]
+pub struct Schema {
+    pub kinds: Vec<Kind>,
+}
+
+impl Schema {
+    pub fn get_kind(&self, kind_id: u64) -> Option<&Kind> {
+        self.kinds.iter().find(|k| k.kind_id == kind_id)
+    }
+
+    pub fn root_kind(&self) -> Option<&Kind> {
+        self.kinds.get(0)
+    }
+}
+
+#[derive(PartialEq, Clone, Serialize, Deserialize, Default, Debug)]
+pub struct Kind {
+    pub kind_id: u64,
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+impl Kind {
+    pub fn get_field(&self, field_id: u64) -> Option<&Field> {
+        self.fields.iter().find(|f| f.field_id == field_id)
+    }
+}
+
+#[derive(PartialEq, Clone, Serialize, Deserialize, Default, Debug)]
+pub struct Field {
+    pub field_id: u64,
+    pub name: String,
+    // pub kind_id: u64,
+    pub type_: FieldType,
+    // Whether the field may have more than one link.
+    #[serde(default)]
+    pub repeated: bool,
+    // Name of the `oneof` the field belongs to, if any: at most one field of a `oneof` is set.
+    #[serde(default)]
+    pub oneof: Option<String>,
+    // How the field is passed on a command line, if it is (see `argv.rs` in LINC).
+    #[serde(default)]
+    pub arg: Option<Arg>,
+    // Protobuf type of an `Int` or `Float` field parsed from a `.proto` file, which determines its
+    // wire encoding (see `protobuf.rs` in LINC); `int64` or `double` if unset.
+    #[serde(default)]
+    pub proto_type: Option<ProtoType>,
+}
+
+#[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub enum Arg {
+    // A flag followed by each value, e.g. `--message` or `-m`, or joined to it if the name ends
+    // with `=`, e.g. `--message=`. `Bool` fields are switches, present when true.
+    Flag(String),
+    // A positional argument; if repeated, it takes all the remaining positional arguments.
+    Positional,
+    // A repeated positional argument given after `--`, e.g. the paths of
+    // `git log [<revision-range>] [--] <path>...`; it also takes the positional arguments left
+    // over by the other positional fields.
+    Trailing,
+    // A repeated positional argument taking all the remaining arguments verbatim, even those that
+    // look like flags, e.g. the command of `docker run IMAGE COMMAND...`.
+    Rest,
+    // A subcommand, followed by the arguments of the child node, e.g. `commit` for `git commit`.
+    Subcommand(String),
+}
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize, Debug)]
+pub enum ProtoType {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+}
+
+#[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
+pub enum FieldType {
+    String,
+    Bytes,
+    Bool,
+    Int,
+    Float,
+    Object { kind_id: u64 },
+}
+
+impl Default for FieldType {
+    fn default() -> Self {
+        FieldType::String
+    }
+}
@@ -0,0 +1,574 @@
+use crate::{Field, FieldType, Kind, ProtoType, Schema};
+use sha2::{Digest, Sha256};
+use std::{collections::HashSet, fmt};
+
+// Parser for protobuf `.proto` files, turning messages into kinds.
+//
+// Fields keep their tag numbers as field ids. Kind ids are derived from the fully qualified message
+// name (see `kind_id`), so that they are stable across edits of the file. Fields of a `oneof` are
+// flattened into the enclosing message, keeping the name of the `oneof` in `Field::oneof`, enums
+// are represented as their numeric value (`Int`), numeric scalars keep their exact type (e.g.
+// `sint32`) in `Field::proto_type`, and `map<K, V>` fields become repeated fields of a synthetic
+// `<Field>Entry` kind with `key = 1` and `value = 2`, as in the protobuf wire format.
+// Services, options and imports are ignored.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtoError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ProtoError {}
+
+// Kind id for the message with the given fully qualified name (without leading dot), taken from
+// the first 48 bits of its SHA-256 hash, so that it is exactly representable as a JavaScript
+// number.
+pub fn kind_id(full_name: &str) -> u64 {
+    let hash = Sha256::digest(full_name.as_bytes());
+    hash[..6].iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(u64),
+    Float(f64),
+    Str(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(v) => write!(f, "`{}`", v),
+            Token::Int(v) => write!(f, "`{}`", v),
+            Token::Float(v) => write!(f, "`{}`", v),
+            Token::Str(v) => write!(f, "{:?}", v),
+            Token::Symbol(v) => write!(f, "`{}`", v),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, ProtoError> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let start = line;
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            prev = c;
+                        }
+                        None => {
+                            return Err(ProtoError {
+                                line: start,
+                                message: "unterminated comment".to_string(),
+                            })
+                        }
+                    }
+                }
+            }
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') => value.extend(chars.next()),
+                        Some('\n') | None => {
+                            return Err(ProtoError {
+                                line,
+                                message: "unterminated string".to_string(),
+                            })
+                        }
+                        Some(c) => value.push(c),
+                    }
+                }
+                tokens.push((line, Token::Str(value)));
+            }
+            c if c.is_ascii_digit() => {
+                let mut value = c.to_string();
+                let hex = c == '0' && chars.peek().is_some_and(|c| *c == 'x' || *c == 'X');
+                while let Some(c) = chars.peek().filter(|c| {
+                    c.is_ascii_alphanumeric()
+                        || **c == '.'
+                        // Sign of the exponent of a float, e.g. `1e-5`.
+                        || (!hex && (**c == '+' || **c == '-') && value.ends_with(['e', 'E']))
+                }) {
+                    value.push(*c);
+                    chars.next();
+                }
+                let token = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok().map(Token::Int),
+                    None => value
+                        .parse()
+                        .ok()
+                        .map(Token::Int)
+                        .or_else(|| value.parse().ok().map(Token::Float)),
+                };
+                match token {
+                    Some(token) => tokens.push((line, token)),
+                    None => {
+                        return Err(ProtoError {
+                            line,
+                            message: format!("invalid number `{}`", value),
+                        })
+                    }
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut value = c.to_string();
+                while let Some(c) = chars
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || **c == '_' || **c == '.')
+                {
+                    value.push(*c);
+                    chars.next();
+                }
+                tokens.push((line, Token::Ident(value)));
+            }
+            c => tokens.push((line, Token::Symbol(c))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Message {
+    full_name: String,
+    fields: Vec<FieldDef>,
+}
+
+struct FieldDef {
+    line: usize,
+    name: String,
+    type_name: String,
+    number: u64,
+    repeated: bool,
+    // Fully qualified name of the message in which the field is declared, used to resolve its
+    // type.
+    scope: String,
+    oneof: Option<String>,
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    messages: Vec<Message>,
+    enums: HashSet<String>,
+}
+
+fn scalar_type(name: &str) -> Option<FieldType> {
+    match name {
+        "double" | "float" => Some(FieldType::Float),
+        "int32" | "int64" | "uint32" | "uint64" | "sint32" | "sint64" | "fixed32" | "fixed64"
+        | "sfixed32" | "sfixed64" => Some(FieldType::Int),
+        "bool" => Some(FieldType::Bool),
+        "string" => Some(FieldType::String),
+        "bytes" => Some(FieldType::Bytes),
+        _ => None,
+    }
+}
+
+fn proto_type(name: &str) -> Option<ProtoType> {
+    match name {
+        "double" => Some(ProtoType::Double),
+        "float" => Some(ProtoType::Float),
+        "int32" => Some(ProtoType::Int32),
+        "int64" => Some(ProtoType::Int64),
+        "uint32" => Some(ProtoType::Uint32),
+        "uint64" => Some(ProtoType::Uint64),
+        "sint32" => Some(ProtoType::Sint32),
+        "sint64" => Some(ProtoType::Sint64),
+        "fixed32" => Some(ProtoType::Fixed32),
+        "fixed64" => Some(ProtoType::Fixed64),
+        "sfixed32" => Some(ProtoType::Sfixed32),
+        "sfixed64" => Some(ProtoType::Sfixed64),
+        _ => None,
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(line, _)| *line)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ProtoError> {
+        Err(ProtoError {
+            line: self.line(),
+            message,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<Token, ProtoError> {
+        match self.tokens.get(self.pos) {
+            Some((_, token)) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => self.error("unexpected end of file".to_string()),
+        }
+    }
+
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol))
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), ProtoError> {
+        match self.next()? {
+            Token::Symbol(s) if s == symbol => Ok(()),
+            t => {
+                self.pos -= 1;
+                self.error(format!("expected `{}`, found {}", symbol, t))
+            }
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, ProtoError> {
+        match self.next()? {
+            Token::Ident(v) => Ok(v),
+            t => {
+                self.pos -= 1;
+                self.error(format!("expected identifier, found {}", t))
+            }
+        }
+    }
+
+    fn expect_int(&mut self) -> Result<u64, ProtoError> {
+        match self.next()? {
+            Token::Int(v) => Ok(v),
+            t => {
+                self.pos -= 1;
+                self.error(format!("expected number, found {}", t))
+            }
+        }
+    }
+
+    // Skips a statement up to and including its `;`, or a block up to its matching `}`.
+    fn skip_statement(&mut self) -> Result<(), ProtoError> {
+        let mut depth = 0;
+        loop {
+            match self.next()? {
+                Token::Symbol(';') if depth == 0 => return Ok(()),
+                Token::Symbol('{') => depth += 1,
+                Token::Symbol('}') => {
+                    if depth == 0 {
+                        self.pos -= 1;
+                        return self.error("unexpected `}`".to_string());
+                    }
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Skips field options, e.g. `[deprecated = true]`.
+    fn skip_options(&mut self) -> Result<(), ProtoError> {
+        if self.is_symbol('[') {
+            while !self.is_symbol(']') {
+                self.next()?;
+            }
+            self.next()?;
+        }
+        Ok(())
+    }
+
+    fn parse_file(&mut self) -> Result<(), ProtoError> {
+        let mut package = String::new();
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Ident(keyword) if keyword == "package" => {
+                    self.next()?;
+                    package = self.expect_ident()?;
+                    self.expect_symbol(';')?;
+                }
+                Token::Ident(keyword) if keyword == "message" => {
+                    self.next()?;
+                    self.parse_message(&package)?;
+                }
+                Token::Ident(keyword) if keyword == "enum" => {
+                    self.next()?;
+                    self.parse_enum(&package)?;
+                }
+                Token::Ident(keyword)
+                    if ["syntax", "import", "option", "service", "extend"]
+                        .contains(&keyword.as_str()) =>
+                {
+                    self.skip_statement()?;
+                }
+                Token::Symbol(';') => {
+                    self.next()?;
+                }
+                t => return self.error(format!("unexpected {}", t)),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_enum(&mut self, scope: &str) -> Result<(), ProtoError> {
+        let name = self.expect_ident()?;
+        self.enums.insert(qualify(scope, &name));
+        self.expect_symbol('{')?;
+        self.pos -= 1;
+        self.skip_statement()
+    }
+
+    fn parse_message(&mut self, scope: &str) -> Result<(), ProtoError> {
+        let name = self.expect_ident()?;
+        let full_name = qualify(scope, &name);
+        self.expect_symbol('{')?;
+        let mut fields = vec![];
+        self.parse_body(&full_name, &mut fields, None)?;
+        self.messages.push(Message { full_name, fields });
+        Ok(())
+    }
+
+    // Parses the body of a message or `oneof`, after its `{`, up to and including its `}`.
+    fn parse_body(
+        &mut self,
+        scope: &str,
+        fields: &mut Vec<FieldDef>,
+        oneof: Option<&str>,
+    ) -> Result<(), ProtoError> {
+        loop {
+            let line = self.line();
+            match self.next()? {
+                Token::Symbol('}') => return Ok(()),
+                Token::Symbol(';') => {}
+                Token::Ident(keyword) if keyword == "message" && oneof.is_none() => {
+                    self.parse_message(scope)?
+                }
+                Token::Ident(keyword) if keyword == "enum" && oneof.is_none() => {
+                    self.parse_enum(scope)?
+                }
+                Token::Ident(keyword) if keyword == "oneof" && oneof.is_none() => {
+                    let name = self.expect_ident()?;
+                    self.expect_symbol('{')?;
+                    self.parse_body(scope, fields, Some(&name))?;
+                }
+                Token::Ident(keyword)
+                    if ["option", "reserved", "extensions", "extend"]
+                        .contains(&keyword.as_str()) =>
+                {
+                    self.skip_statement()?
+                }
+                Token::Ident(keyword) if keyword == "group" => {
+                    return self.error("groups are not supported".to_string())
+                }
+                Token::Ident(keyword) if keyword == "map" && self.is_symbol('<') => {
+                    self.next()?;
+                    let key_type = self.expect_ident()?;
+                    self.expect_symbol(',')?;
+                    let value_type = self.expect_ident()?;
+                    self.expect_symbol('>')?;
+                    let name = self.expect_ident()?;
+                    self.expect_symbol('=')?;
+                    let number = self.expect_int()?;
+                    self.skip_options()?;
+                    self.expect_symbol(';')?;
+                    let entry = qualify(scope, &(camel_case(&name) + "Entry"));
+                    self.messages.push(Message {
+                        full_name: entry.clone(),
+                        fields: vec![
+                            FieldDef {
+                                line,
+                                name: "key".to_string(),
+                                type_name: key_type,
+                                number: 1,
+                                repeated: false,
+                                scope: scope.to_string(),
+                                oneof: None,
+                            },
+                            FieldDef {
+                                line,
+                                name: "value".to_string(),
+                                type_name: value_type,
+                                number: 2,
+                                repeated: false,
+                                scope: scope.to_string(),
+                                oneof: None,
+                            },
+                        ],
+                    });
+                    fields.push(FieldDef {
+                        line,
+                        name,
+                        type_name: format!(".{}", entry),
+                        number,
+                        repeated: true,
+                        scope: scope.to_string(),
+                        oneof: oneof.map(str::to_string),
+                    });
+                }
+                Token::Ident(first) => {
+                    let repeated = first == "repeated";
+                    let type_name =
+                        if ["repeated", "optional", "required"].contains(&first.as_str()) {
+                            self.expect_ident()?
+                        } else {
+                            first
+                        };
+                    let name = self.expect_ident()?;
+                    self.expect_symbol('=')?;
+                    let number = self.expect_int()?;
+                    self.skip_options()?;
+                    self.expect_symbol(';')?;
+                    fields.push(FieldDef {
+                        line,
+                        name,
+                        type_name,
+                        number,
+                        repeated,
+                        scope: scope.to_string(),
+                        oneof: oneof.map(str::to_string),
+                    });
+                }
+                t => {
+                    self.pos -= 1;
+                    return self.error(format!("unexpected {}", t));
+                }
+            }
+        }
+    }
+
+    // Resolves a type name relative to the given scope, following protobuf scoping rules: the
+    // innermost enclosing scope that defines the name wins.
+    fn resolve(&self, scope: &str, type_name: &str) -> Option<FieldType> {
+        if let Some(scalar) = scalar_type(type_name) {
+            return Some(scalar);
+        }
+        let lookup = |full_name: &str| {
+            if self.enums.contains(full_name) {
+                Some(FieldType::Int)
+            } else if self.messages.iter().any(|m| m.full_name == full_name) {
+                Some(FieldType::Object {
+                    kind_id: kind_id(full_name),
+                })
+            } else {
+                None
+            }
+        };
+        if let Some(full_name) = type_name.strip_prefix('.') {
+            return lookup(full_name);
+        }
+        let mut scope = scope;
+        loop {
+            if let Some(found) = lookup(&qualify(scope, type_name)) {
+                return Some(found);
+            }
+            if scope.is_empty() {
+                return None;
+            }
+            scope = scope.rfind('.').map_or("", |i| &scope[..i]);
+        }
+    }
+}
+
+// Parses the given `.proto` file into a schema, with one kind per message, in the order in which
+// the messages are closed (i.e. nested messages come before their parent).
+pub fn parse(text: &str) -> Result<Schema, ProtoError> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+        messages: vec![],
+        enums: HashSet::new(),
+    };
+    parser.parse_file()?;
+    let mut kinds = vec![];
+    for message in &parser.messages {
+        let mut fields: Vec<Field> = vec![];
+        for field in &message.fields {
+            let type_ = match parser.resolve(&field.scope, &field.type_name) {
+                Some(type_) => type_,
+                None => {
+                    return Err(ProtoError {
+                        line: field.line,
+                        message: format!("unknown type `{}`", field.type_name),
+                    })
+                }
+            };
+            if fields.iter().any(|f| f.field_id == field.number) {
+                return Err(ProtoError {
+                    line: field.line,
+                    message: format!(
+                        "duplicate field number {} in `{}`",
+                        field.number, message.full_name
+                    ),
+                });
+            }
+            fields.push(Field {
+                field_id: field.number,
+                name: field.name.clone(),
+                type_,
+                repeated: field.repeated,
+                oneof: field.oneof.clone(),
+                arg: None,
+                proto_type: proto_type(&field.type_name),
+            });
+        }
+        let id = kind_id(&message.full_name);
+        if kinds.iter().any(|k: &Kind| k.kind_id == id) {
+            return Err(ProtoError {
+                line: 1,
+                message: format!("duplicate message `{}`", message.full_name),
+            });
+        }
+        kinds.push(Kind {
+            kind_id: id,
+            name: message.full_name.clone(),
+            fields,
+        });
+    }
+    Ok(Schema { kinds })
+}
@@ -0,0 +1,45 @@
+use anyhow::{anyhow, bail, Context};
+use linc::{identity::Identity, types::NodeStore};
+use std::path::Path;
+
+const USAGE: &str = "usage: linc_sign <key> <root> <store> [<author>]
+
+  <key>     path to the key file of the author, created if missing
+  <root>    digest of the node to sign
+  <store>   path to a JSON file containing a node store saved by LINC, with the tree of <root>; the
+            statement is added to it
+  <author>  name of the author, to create the key file";
+
+// Signs a root with the key pair of a key file (see LINC's `src/identity.rs`), and prints the
+// digest of the statement.
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (key, root, store, author) = match args.as_slice() {
+        [key, root, store] => (Path::new(key), root, store, None),
+        [key, root, store, author] => (Path::new(key), root, store, Some(author)),
+        _ => bail!(USAGE),
+    };
+    let content = std::fs::read(store).with_context(|| format!("could not read {}", store))?;
+    let mut node_store: NodeStore = serde_json::from_slice(&content)
+        .with_context(|| format!("invalid node store {}", store))?;
+    if node_store.get_dag(root).is_none() {
+        bail!("node {} not found in {}", root, store);
+    }
+    let identity = if key.exists() {
+        Identity::load(key).map_err(|err| anyhow!("invalid key file {}: {}", key.display(), err))?
+    } else {
+        let author =
+            author.with_context(|| format!("no key file {}\n\n{}", key.display(), USAGE))?;
+        let identity = Identity::generate(author).map_err(|err| anyhow!(err))?;
+        identity
+            .save(key)
+            .map_err(|err| anyhow!("could not write {}: {}", key.display(), err))?;
+        eprintln!("created key file {}", key.display());
+        identity
+    };
+    let statement = identity.sign(&mut node_store, root);
+    let content = serde_json::to_vec(&node_store)?;
+    std::fs::write(store, content).with_context(|| format!("could not write {}", store))?;
+    println!("{}", statement.digest);
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use linc::{
+    collab::{ClientMessage, Recipient, Server},
+    types::{Link, NodeStore},
+};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+    time::Duration,
+};
+use tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+    Message,
+};
+
+#[cfg(test)]
+mod tests;
+
+// Sync server for collaborative editing (see LINC's `src/collab.rs`).
+//
+// Each WebSocket connection is an editor. The text messages it sends are JSON `ClientMessage`s,
+// which are passed to a single `collab::Server`, and the messages it returns are sent, as JSON, to
+// the connections of the clients they are addressed to. Each connection is served by its own
+// thread, which alternates between reading with a short timeout and sending the messages queued
+// for its client.
+
+// Path of the WebSocket endpoint.
+pub const PATH: &str = "/api/v1/collab";
+
+// How long a connection waits for a message before sending the ones queued for it.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+struct Relay {
+    server: Server,
+    // Messages to send to each client that joined, by client id.
+    clients: HashMap<String, mpsc::Sender<String>>,
+}
+
+impl Relay {
+    fn receive(&mut self, message: ClientMessage, sender: &mpsc::Sender<String>) {
+        if let ClientMessage::Join { client_id } = &message {
+            self.clients.insert(client_id.clone(), sender.clone());
+        }
+        for (recipient, message) in self.server.receive(message) {
+            let text = match serde_json::to_string(&message) {
+                Ok(text) => text,
+                Err(err) => {
+                    eprintln!("could not serialize {:?}: {}", message, err);
+                    continue;
+                }
+            };
+            for (client_id, sender) in &self.clients {
+                let to_client = match &recipient {
+                    Recipient::Client(id) => id == client_id,
+                    Recipient::Others(id) => id != client_id,
+                };
+                if to_client {
+                    // The connection may have just been closed.
+                    let _ = sender.send(text.clone());
+                }
+            }
+        }
+    }
+}
+
+// Serves the clients connecting to the given listener, editing the tree at the given root, until
+// the listener fails.
+pub fn serve(listener: TcpListener, node_store: NodeStore, root: Link) {
+    let relay = Arc::new(Mutex::new(Relay {
+        server: Server::new(node_store, root),
+        clients: HashMap::new(),
+    }));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let relay = relay.clone();
+                std::thread::spawn(move || {
+                    if let Err(err) = connection(&relay, stream) {
+                        eprintln!("connection failed: {}", err);
+                    }
+                });
+            }
+            Err(err) => eprintln!("could not accept connection: {}", err),
+        }
+    }
+}
+
+// The error response of the handshake callback is the type required by `tungstenite`.
+#[allow(clippy::result_large_err)]
+fn connection(relay: &Mutex<Relay>, stream: TcpStream) -> anyhow::Result<()> {
+    let mut socket = tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+        if request.uri().path() == PATH {
+            Ok(response)
+        } else {
+            let mut error = ErrorResponse::new(Some(format!("no endpoint at {}", request.uri())));
+            *error.status_mut() = StatusCode::NOT_FOUND;
+            Err(error)
+        }
+    })?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    let (sender, receiver) = mpsc::channel();
+    let mut client_id = None;
+    let result = loop {
+        if let Err(err) = receiver
+            .try_iter()
+            .try_for_each(|text| socket.send(Message::text(text)))
+        {
+            break Err(err.into());
+        }
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let message: ClientMessage = match serde_json::from_str(text.as_str()) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        eprintln!("invalid message {:?}: {}", text.as_str(), err);
+                        continue;
+                    }
+                };
+                if let ClientMessage::Join { client_id: id } = &message {
+                    client_id = Some(id.clone());
+                }
+                relay.lock().unwrap().receive(message, &sender);
+            }
+            Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => break Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(err) => break Err(err.into()),
+        }
+    };
+    if let Some(client_id) = client_id {
+        relay.lock().unwrap().clients.remove(&client_id);
+    }
+    result
+}
@@ -0,0 +1,40 @@
+use anyhow::{bail, Context};
+use linc::types::{Link, LinkType, Node, NodeStore};
+use std::net::TcpListener;
+
+const USAGE: &str = "usage: linc_sync <address> [<root> <store>]
+
+  <address>  address to listen on, e.g. 127.0.0.1:27334
+  <root>     digest of the node to start editing from, by default an empty node
+  <store>    path to a JSON file containing a node store saved by LINC, with the tree of <root>";
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (address, root, node_store) = match args.as_slice() {
+        [address] => {
+            let mut node_store = NodeStore::default();
+            let root = node_store.put_parsed(&Node::default());
+            (address, root, node_store)
+        }
+        [address, root, store] => {
+            let content =
+                std::fs::read(store).with_context(|| format!("could not read {}", store))?;
+            let node_store: NodeStore = serde_json::from_slice(&content)
+                .with_context(|| format!("invalid node store {}", store))?;
+            if node_store.get_dag(root).is_none() {
+                bail!("node {} not found in {}", root, store);
+            }
+            (address, root.clone(), node_store)
+        }
+        _ => bail!(USAGE),
+    };
+    let listener =
+        TcpListener::bind(address).with_context(|| format!("could not listen on {}", address))?;
+    eprintln!("listening on ws://{}{}", address, linc_sync::PATH);
+    let root = Link {
+        type_: LinkType::Dag,
+        digest: root,
+    };
+    linc_sync::serve(listener, node_store, root);
+    Ok(())
+}
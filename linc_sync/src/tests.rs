@@ -0,0 +1,101 @@
+use crate::{serve, PATH};
+use linc::{
+    collab::{Client, ClientMessage, Op, ServerMessage},
+    types::{Link, LinkType, Node, NodeStore, Selector},
+};
+use std::{net::TcpListener, time::Duration};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+type Socket = WebSocket<MaybeTlsStream<std::net::TcpStream>>;
+
+// Starts a relay on a free port, and returns its address.
+fn start(node_store: NodeStore, root: Link) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || serve(listener, node_store, root));
+    address
+}
+
+fn connect(address: &str) -> Socket {
+    let (socket, _) = tungstenite::connect(format!("ws://{}{}", address, PATH)).unwrap();
+    if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+    }
+    socket
+}
+
+fn send(socket: &mut Socket, message: &ClientMessage) {
+    let text = serde_json::to_string(message).unwrap();
+    socket.send(Message::text(text)).unwrap();
+}
+
+// Receives the next message, and has the client handle it.
+fn receive(socket: &mut Socket, client: &mut Client, node_store: &mut NodeStore) -> ServerMessage {
+    let text = socket.read().unwrap().into_text().unwrap();
+    let message: ServerMessage = serde_json::from_str(text.as_str()).unwrap();
+    let (_, replies) = client.receive(node_store, message.clone()).unwrap();
+    assert_eq!(replies, vec![]);
+    message
+}
+
+#[test]
+fn test_relay() {
+    let mut node_store = NodeStore::default();
+    let root = Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&Node::default()),
+    };
+    let address = start(node_store, root.clone());
+
+    let mut a = (
+        connect(&address),
+        Client::new("a", root.clone()),
+        NodeStore::default(),
+    );
+    let mut b = (
+        connect(&address),
+        Client::new("b", root.clone()),
+        NodeStore::default(),
+    );
+    for (socket, client, node_store) in [&mut a, &mut b] {
+        send(socket, &client.join());
+        let welcome = receive(socket, client, node_store);
+        assert!(matches!(
+            welcome,
+            ServerMessage::Welcome { revision: 0, .. }
+        ));
+    }
+
+    // An edit of `a` reaches `b` through the relay.
+    let (socket, client, node_store) = &mut a;
+    let value = Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(b"x"),
+    };
+    let op = Op::Insert {
+        path: vec![Selector {
+            field_id: 1,
+            index: 0,
+            id: None,
+        }],
+        link: value.clone(),
+        id: None,
+    };
+    let submit = client.edit(node_store, op).unwrap().unwrap();
+    send(socket, &submit);
+    let ack = receive(socket, client, node_store);
+    assert_eq!(ack, ServerMessage::Ack { revision: 1 });
+
+    let (socket, client, node_store) = &mut b;
+    let op = receive(socket, client, node_store);
+    assert!(matches!(op, ServerMessage::Op { revision: 1, .. }));
+    assert_eq!(client.root, a.1.root);
+    let node = node_store.get_dag(&client.root.digest).unwrap();
+    assert_eq!(node.links[&1], vec![value]);
+    assert!(a.1.is_synced() && b.1.is_synced());
+
+    // Other paths are not served.
+    assert!(tungstenite::connect(format!("ws://{}/other", address)).is_err());
+}
@@ -0,0 +1,453 @@
+use crate::{
+    schema::{Arg, Field, FieldType, FieldValue, Kind, Schema},
+    types::{append, Link, LinkTarget, LinkType, Node, NodeStore, Path, Selector},
+};
+use std::fmt;
+
+// Conversion between trees and command lines, according to the `arg` of each field (see
+// `schema::Arg`).
+//
+// A node is rendered as its flags, then its positional arguments, then its subcommand followed by
+// the arguments of its child node, each in the order in which they are declared in its kind; `--`
+// is written before the first positional argument that would otherwise be read as a flag, and
+// before `Trailing` arguments. When parsing, flags may be given in any order, positional arguments
+// fill the positional fields in order, `--` ends the flags (and starts the `Trailing` arguments, if
+// any), and a subcommand or a `Rest` field takes all the remaining arguments.
+
+#[derive(Debug, PartialEq)]
+pub struct ArgvError {
+    pub path: Path,
+    pub message: String,
+}
+
+impl fmt::Display for ArgvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self
+            .path
+            .iter()
+            .map(|s| format!("{}[{}]", s.field_id, s.index))
+            .collect();
+        write!(f, "/{}: {}", path.join("/"), self.message)
+    }
+}
+
+impl std::error::Error for ArgvError {}
+
+fn error<T>(path: &[Selector], message: String) -> Result<T, ArgvError> {
+    Err(ArgvError {
+        path: path.to_vec(),
+        message,
+    })
+}
+
+fn get_kind<'a>(
+    schema: &'a Schema,
+    path: &[Selector],
+    kind_id: u64,
+) -> Result<&'a Kind, ArgvError> {
+    match schema.get_kind(kind_id) {
+        Some(kind) => Ok(kind),
+        None => error(path, format!("unknown kind {}", kind_id)),
+    }
+}
+
+// Returns the kind of a subcommand field.
+fn child_kind_id(path: &[Selector], field: &Field) -> Result<u64, ArgvError> {
+    match field.type_ {
+        FieldType::Object { kind_id } => Ok(kind_id),
+        _ => error(
+            path,
+            format!("subcommand `{}` is not a message field", field.name),
+        ),
+    }
+}
+
+// Returns the text of a scalar value, checking that it is valid for the field.
+fn text(path: &[Selector], field: &Field, raw: &[u8]) -> Result<String, ArgvError> {
+    match (&field.type_, FieldValue::from_raw(&field.type_, raw)) {
+        (FieldType::Object { .. }, _) => {
+            error(path, format!("`{}` is a message field", field.name))
+        }
+        (_, Some(_)) => match std::str::from_utf8(raw) {
+            Ok(text) => Ok(text.to_string()),
+            Err(_) => error(path, format!("`{}` is not valid UTF-8", field.name)),
+        },
+        (type_, None) => error(path, format!("invalid {:?} value", type_)),
+    }
+}
+
+pub fn render(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+) -> Result<Vec<String>, ArgvError> {
+    let mut args = vec![];
+    render_node(node_store, schema, kind_id, &[], link, &mut args)?;
+    Ok(args)
+}
+
+// Order in which the arguments of the fields of a node are rendered: flags come first, so that
+// they are not taken by a subcommand, and the subcommand last.
+fn render_order(arg: &Arg) -> u8 {
+    match arg {
+        Arg::Flag(_) => 0,
+        Arg::Positional | Arg::Rest => 1,
+        Arg::Trailing => 2,
+        Arg::Subcommand(_) => 3,
+    }
+}
+
+fn render_node(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    path: &[Selector],
+    link: &Link,
+    args: &mut Vec<String>,
+) -> Result<(), ArgvError> {
+    let kind = get_kind(schema, path, kind_id)?;
+    let node = match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        Some(LinkTarget::Raw(_)) => return error(path, "expected a node".to_string()),
+        None => return error(path, format!("missing node {}", link.digest)),
+    };
+    if let Some(field_id) = node.links.keys().find(|id| kind.get_field(**id).is_none()) {
+        return error(
+            path,
+            format!("unknown field {} of `{}`", field_id, kind.name),
+        );
+    }
+    let mut values = vec![];
+    for field in kind.fields.iter() {
+        for (index, link) in node
+            .links
+            .get(&field.field_id)
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let path = append(
+                path,
+                Selector {
+                    field_id: field.field_id,
+                    index,
+                    id: None,
+                },
+            );
+            match &field.arg {
+                Some(arg) => values.push((field, arg, path, link)),
+                None => {
+                    return error(
+                        &path,
+                        format!("`{}` cannot be passed on a command line", field.name),
+                    )
+                }
+            }
+        }
+    }
+    values.sort_by_key(|(_, arg, _, _)| render_order(arg));
+    // Positional arguments are parsed in order, so one may only be given if the previous ones are.
+    let mut missing: Option<&Field> = None;
+    for field in kind.fields.iter() {
+        if !matches!(field.arg, Some(Arg::Positional | Arg::Rest)) {
+            continue;
+        }
+        match (node.links.contains_key(&field.field_id), missing) {
+            (true, Some(missing)) => {
+                return error(
+                    path,
+                    format!(
+                        "`{}` cannot be given without `{}`",
+                        field.name, missing.name
+                    ),
+                )
+            }
+            (false, None) => missing = Some(field),
+            _ => {}
+        }
+    }
+    let trailing = kind.fields.iter().any(|f| f.arg == Some(Arg::Trailing));
+    // Whether `--` was written, after which arguments are not read as flags.
+    let mut separated = false;
+    let mut positional = false;
+    for (field, arg, path, link) in values {
+        let path = &path;
+        if let Arg::Subcommand(name) = arg {
+            if separated {
+                return error(path, format!("subcommand `{}` cannot follow `--`", name));
+            }
+            args.push(name.clone());
+            let child_kind_id = child_kind_id(path, field)?;
+            render_node(node_store, schema, child_kind_id, path, link, args)?;
+            continue;
+        }
+        let value = match link.get(node_store) {
+            Some(LinkTarget::Raw(raw)) => text(path, field, raw)?,
+            Some(LinkTarget::Parsed(_)) => return error(path, "expected a value".to_string()),
+            None => return error(path, format!("missing value {}", link.digest)),
+        };
+        match arg {
+            Arg::Flag(name) if field.type_ == FieldType::Bool => {
+                if value == "true" {
+                    args.push(name.trim_end_matches('=').to_string());
+                }
+            }
+            Arg::Flag(name) if name.ends_with('=') => args.push(format!("{}{}", name, value)),
+            Arg::Flag(name) => {
+                args.push(name.clone());
+                args.push(value);
+            }
+            Arg::Positional | Arg::Rest => {
+                // A value that looks like a flag needs a `--` before it, unless it is taken
+                // verbatim by a `Rest` field that follows another positional argument.
+                let flag_like = value.starts_with('-') && value != "-";
+                if flag_like && !separated && (*arg == Arg::Positional || !positional) {
+                    if trailing {
+                        return error(path, format!("`{}` cannot start with `-`", field.name));
+                    }
+                    args.push("--".to_string());
+                    separated = true;
+                }
+                args.push(value);
+                positional = true;
+            }
+            Arg::Trailing => {
+                if !separated {
+                    args.push("--".to_string());
+                    separated = true;
+                }
+                args.push(value);
+            }
+            Arg::Subcommand(_) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+pub fn parse(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    args: &[String],
+) -> Result<Link, ArgvError> {
+    let node = parse_node(node_store, schema, kind_id, &[], args)?;
+    Ok(Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&node),
+    })
+}
+
+// Returns the flag field matching the given argument, and the value joined to it, if any.
+fn match_flag<'a>(kind: &'a Kind, arg: &'a str) -> Option<(&'a Field, Option<&'a str>)> {
+    kind.fields.iter().find_map(|field| match &field.arg {
+        Some(Arg::Flag(name)) if name.ends_with('=') => {
+            if arg == name.trim_end_matches('=') && field.type_ == FieldType::Bool {
+                Some((field, None))
+            } else {
+                arg.strip_prefix(name.as_str()).map(|v| (field, Some(v)))
+            }
+        }
+        Some(Arg::Flag(name)) if arg == name => Some((field, None)),
+        Some(Arg::Flag(name)) if name.starts_with("--") => arg
+            .strip_prefix(name.as_str())
+            .and_then(|rest| rest.strip_prefix('='))
+            .map(|v| (field, Some(v))),
+        _ => None,
+    })
+}
+
+fn parse_node(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    path: &[Selector],
+    args: &[String],
+) -> Result<Node, ArgvError> {
+    let kind = get_kind(schema, path, kind_id)?;
+    let positionals: Vec<&Field> = kind
+        .fields
+        .iter()
+        .filter(|f| matches!(f.arg, Some(Arg::Positional | Arg::Rest)))
+        .collect();
+    let trailing = kind.fields.iter().find(|f| f.arg == Some(Arg::Trailing));
+    let mut node = Node::default();
+    let mut next_positional = 0;
+    let mut only_positional = false;
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        i += 1;
+        if !only_positional {
+            if arg == "--" {
+                only_positional = true;
+                continue;
+            }
+            let subcommand = kind
+                .fields
+                .iter()
+                .find(|f| matches!(&f.arg, Some(Arg::Subcommand(name)) if name == arg));
+            if let Some(field) = subcommand {
+                let path = append(
+                    path,
+                    Selector {
+                        field_id: field.field_id,
+                        index: 0,
+                        id: None,
+                    },
+                );
+                let child_kind_id = child_kind_id(&path, field)?;
+                let child = parse_node(node_store, schema, child_kind_id, &path, &args[i..])?;
+                node.links.insert(
+                    field.field_id,
+                    vec![Link {
+                        type_: LinkType::Dag,
+                        digest: node_store.put_parsed(&child),
+                    }],
+                );
+                return Ok(node);
+            }
+            if let Some((field, value)) = match_flag(kind, arg) {
+                let value = match value {
+                    Some(value) => value,
+                    None if field.type_ == FieldType::Bool => "true",
+                    None => match args.get(i) {
+                        Some(value) => {
+                            i += 1;
+                            value
+                        }
+                        None => return error(path, format!("missing value for `{}`", arg)),
+                    },
+                };
+                push_value(node_store, &mut node, path, field, value)?;
+                continue;
+            }
+            if arg.starts_with('-') && arg != "-" {
+                return error(path, format!("unknown flag `{}`", arg));
+            }
+        }
+        let field = match (positionals.get(next_positional), trailing) {
+            (_, Some(trailing)) if only_positional => trailing,
+            (Some(field), _) => *field,
+            (None, Some(trailing)) => trailing,
+            (None, None) => return error(path, format!("unexpected argument `{}`", arg)),
+        };
+        if field.arg == Some(Arg::Rest) {
+            for arg in args[i - 1..].iter() {
+                push_value(node_store, &mut node, path, field, arg)?;
+            }
+            break;
+        }
+        push_value(node_store, &mut node, path, field, arg)?;
+        if field.arg == Some(Arg::Positional) && !field.repeated {
+            next_positional += 1;
+            // The arguments after the one before a `Rest` field are all taken by it.
+            if let Some(rest) = positionals
+                .get(next_positional)
+                .filter(|f| f.arg == Some(Arg::Rest))
+            {
+                for arg in args[i..].iter() {
+                    push_value(node_store, &mut node, path, rest, arg)?;
+                }
+                break;
+            }
+        }
+    }
+    Ok(node)
+}
+
+// Adds a value to a field of the node, checking it.
+fn push_value(
+    node_store: &mut NodeStore,
+    node: &mut Node,
+    path: &[Selector],
+    field: &Field,
+    value: &str,
+) -> Result<(), ArgvError> {
+    let links = node.links.entry(field.field_id).or_default();
+    let path = append(
+        path,
+        Selector {
+            field_id: field.field_id,
+            index: links.len(),
+            id: None,
+        },
+    );
+    if !field.repeated && !links.is_empty() {
+        return error(&path, format!("`{}` given more than once", field.name));
+    }
+    text(&path, field, value.as_bytes())?;
+    links.push(Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(value.as_bytes()),
+    });
+    Ok(())
+}
+
+// Quotes an argument for a POSIX shell, if needed.
+pub fn quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+pub fn shell_line(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Splits a shell command line into arguments, following POSIX quoting rules (without expansions).
+pub fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                args.extend(current.take());
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if "\"\\$`".contains(c) => arg.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
@@ -0,0 +1,259 @@
+use crate::{
+    schema::{FieldType, FieldValue},
+    types::{append, Link, LinkTarget, Node, NodeStore, Path, Selector},
+};
+
+// Messages of `proto/ast.proto`, whose schema is loaded by `proto::ast_schema`.
+//
+// Each module has the ids of the fields of one message that are used by the interpreter, type
+// checker and refactorings, and, if its nodes are created or looked up by kind, a `kind_id`
+// function returning the id that `proto::parse` derives from the message name.
+
+macro_rules! kind {
+    ($name:expr) => {
+        pub const KIND_NAME: &str = $name;
+
+        pub fn kind_id() -> u64 {
+            crate::proto::kind_id(KIND_NAME)
+        }
+    };
+}
+
+pub mod file {
+    kind!("File");
+    pub const NEXT_REF: u64 = 1;
+    pub const FUNCTION_DEFINITIONS: u64 = 5;
+    pub const TYPE_ALIASES: u64 = 6;
+}
+
+pub mod expression {
+    kind!("Expression");
+    pub const REF: u64 = 1;
+    pub const EMPTY_VALUE: u64 = 9;
+    pub const BOOL_VALUE: u64 = 2;
+    pub const INT_VALUE: u64 = 3;
+    pub const FLOAT_VALUE: u64 = 4;
+    pub const STRING_VALUE: u64 = 5;
+    pub const LIST_VALUE: u64 = 6;
+    pub const IF_VALUE: u64 = 7;
+    pub const FUNCTION_APPLICATION_VALUE: u64 = 8;
+    pub const REF_VALUE: u64 = 12;
+    pub const EXTERNAL_REF_VALUE: u64 = 13;
+    pub const HOLE_VALUE: u64 = 14;
+}
+
+// `Expression.Bool`, `Expression.Int`, `Expression.Float` and `Expression.String`.
+pub mod literal {
+    pub const VALUE: u64 = 1;
+}
+
+pub mod list {
+    // `Expression.List`.
+    pub const VALUES: u64 = 1;
+}
+
+pub mod if_ {
+    // `Expression.If`.
+    pub const COND: u64 = 1;
+    pub const TRUE: u64 = 2;
+    pub const FALSE: u64 = 3;
+}
+
+pub mod function_application {
+    kind!("Expression.FunctionApplication");
+    pub const FUNCTION: u64 = 1;
+    pub const ARGUMENTS: u64 = 2;
+}
+
+pub mod ref_ {
+    kind!("Expression.Ref");
+    pub const REF: u64 = 1;
+}
+
+pub mod external_ref {
+    // `Expression.ExternalRef`.
+    pub const PATH: u64 = 1;
+    pub const NAME: u64 = 2;
+}
+
+pub mod function_definition {
+    kind!("FunctionDefinition");
+    pub const REF: u64 = 1;
+    pub const LABEL: u64 = 2;
+    pub const ARGUMENTS: u64 = 3;
+    pub const RETURN_TYPE: u64 = 4;
+    pub const BODY: u64 = 5;
+}
+
+pub mod argument {
+    // `Argument`.
+    pub const REF: u64 = 1;
+    pub const LABEL: u64 = 2;
+    pub const TYPE: u64 = 3;
+}
+
+pub mod type_ {
+    // `Type`.
+    pub const REF: u64 = 1;
+    pub const PRIMITIVE: u64 = 10;
+    pub const COMPOUND: u64 = 11;
+    pub const REF_TYPE: u64 = 12;
+
+    // Values of `Type.PrimitiveType`.
+    pub const PRIMITIVE_TYPE_UNSPECIFIED: i64 = 0;
+    pub const INT: i64 = 1;
+    pub const FLOAT: i64 = 2;
+    pub const BOOL: i64 = 3;
+    pub const STRING: i64 = 4;
+}
+
+pub mod type_alias {
+    // `TypeAlias`.
+    pub const REF: u64 = 1;
+    pub const TYPE1: u64 = 20;
+}
+
+pub mod compound_type {
+    // `Type.CompoundType`.
+    pub const X: u64 = 1;
+    pub const Y: u64 = 2;
+}
+
+pub mod ref_type {
+    // `Type.RefType`.
+    pub const REF: u64 = 1;
+}
+
+pub mod label {
+    kind!("Label");
+    pub const NAME: u64 = 1;
+}
+
+// Helpers to read AST nodes. Missing scalars read as their protobuf default value.
+
+pub fn get_node(node_store: &NodeStore, link: &Link) -> Option<Node> {
+    link.get(node_store)
+        .and_then(|target| target.as_parsed().cloned())
+}
+
+// Returns the path and link of the first value of the given field.
+pub fn get_child<'a>(node: &'a Node, path: &[Selector], field_id: u64) -> Option<(Path, &'a Link)> {
+    node.links
+        .get(&field_id)
+        .and_then(|links| links.first())
+        .map(|link| {
+            (
+                append(
+                    path,
+                    Selector {
+                        field_id,
+                        index: 0,
+                        id: None,
+                    },
+                ),
+                link,
+            )
+        })
+}
+
+// Returns the paths and links of all the values of the given field.
+pub fn get_children(node: &Node, path: &[Selector], field_id: u64) -> Vec<(Path, Link)> {
+    node.links
+        .get(&field_id)
+        .map(|links| {
+            links
+                .iter()
+                .enumerate()
+                .map(|(index, link)| {
+                    (
+                        append(
+                            path,
+                            Selector {
+                                field_id,
+                                index,
+                                id: None,
+                            },
+                        ),
+                        link.clone(),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn get_value(
+    node_store: &NodeStore,
+    node: &Node,
+    field_id: u64,
+    type_: &FieldType,
+) -> Option<FieldValue> {
+    match node.links.get(&field_id).and_then(|links| links.first()) {
+        Some(link) => match link.get(node_store) {
+            Some(LinkTarget::Raw(raw)) => FieldValue::from_raw(type_, raw),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+pub fn get_int(node_store: &NodeStore, node: &Node, field_id: u64) -> Option<i64> {
+    match get_value(node_store, node, field_id, &FieldType::Int) {
+        Some(FieldValue::Int(v)) => Some(v),
+        Some(_) => None,
+        None if node.links.contains_key(&field_id) => None,
+        None => Some(0),
+    }
+}
+
+pub fn get_float(node_store: &NodeStore, node: &Node, field_id: u64) -> Option<f64> {
+    match get_value(node_store, node, field_id, &FieldType::Float) {
+        Some(FieldValue::Float(v)) => Some(v),
+        Some(_) => None,
+        None if node.links.contains_key(&field_id) => None,
+        None => Some(0.0),
+    }
+}
+
+pub fn get_bool(node_store: &NodeStore, node: &Node, field_id: u64) -> Option<bool> {
+    match get_value(node_store, node, field_id, &FieldType::Bool) {
+        Some(FieldValue::Bool(v)) => Some(v),
+        Some(_) => None,
+        None if node.links.contains_key(&field_id) => None,
+        None => Some(false),
+    }
+}
+
+pub fn get_string(node_store: &NodeStore, node: &Node, field_id: u64) -> Option<String> {
+    match get_value(node_store, node, field_id, &FieldType::String) {
+        Some(FieldValue::String(v)) => Some(v),
+        Some(_) => None,
+        None if node.links.contains_key(&field_id) => None,
+        None => Some(String::new()),
+    }
+}
+
+// Name of the label of a `FunctionDefinition`, `Argument` etc.
+pub fn get_label(node_store: &NodeStore, node: &Node, field_id: u64) -> String {
+    node.links
+        .get(&field_id)
+        .and_then(|links| links.first())
+        .and_then(|link| get_node(node_store, link))
+        .and_then(|label| get_string(node_store, &label, label::NAME))
+        .unwrap_or_default()
+}
+
+// Returns the field id and the link of the single value set in the `oneof` of an `Expression` (or
+// `Type`), ignoring its `ref`.
+pub fn get_oneof(node: &Node, ref_field_id: u64) -> Result<(u64, &Link), String> {
+    let fields: Vec<_> = node
+        .links
+        .iter()
+        .filter(|(field_id, _)| **field_id != ref_field_id)
+        .collect();
+    match fields.as_slice() {
+        [] => Err("empty".to_string()),
+        [(field_id, links)] if links.len() == 1 => Ok((**field_id, &links[0])),
+        _ => Err("more than one value set".to_string()),
+    }
+}
@@ -0,0 +1,607 @@
+use crate::schema::{Field, FieldType, Kind, Schema};
+
+// Schema for Cargo manifests (`Cargo.toml`).
+//
+// See https://doc.rust-lang.org/cargo/reference/manifest.html.
+//
+// Maps, such as `[dependencies]` or `[features]`, are represented as repeated fields of entries
+// with a `name` field, so that their order is preserved.
+//
+// `generate_cargo_toml` exports trees of this schema through the `linc` library.
+
+pub const CARGO_MANIFEST: u64 = 8120001;
+pub const CARGO_PACKAGE: u64 = 8120002;
+pub const CARGO_DEPENDENCY: u64 = 8120003;
+pub const CARGO_FEATURE: u64 = 8120004;
+pub const CARGO_PRODUCT: u64 = 8120005;
+pub const CARGO_TARGET: u64 = 8120006;
+pub const CARGO_WORKSPACE: u64 = 8120007;
+
+// Fields of `cargo_manifest`.
+pub mod cargo_manifest {
+    pub const PACKAGE: u64 = 1;
+    pub const WORKSPACE: u64 = 2;
+    pub const DEPENDENCIES: u64 = 3;
+    pub const DEV_DEPENDENCIES: u64 = 4;
+    pub const BUILD_DEPENDENCIES: u64 = 5;
+    pub const FEATURES: u64 = 6;
+    pub const LIB: u64 = 7;
+    pub const BIN: u64 = 8;
+    pub const EXAMPLE: u64 = 9;
+    pub const TEST: u64 = 10;
+    pub const BENCH: u64 = 11;
+    pub const TARGET: u64 = 12;
+}
+
+// Fields of `cargo_package`.
+pub mod cargo_package {
+    pub const NAME: u64 = 1;
+    pub const VERSION: u64 = 2;
+    pub const AUTHORS: u64 = 3;
+    pub const EDITION: u64 = 4;
+    pub const RUST_VERSION: u64 = 5;
+    pub const DESCRIPTION: u64 = 6;
+    pub const HOMEPAGE: u64 = 7;
+    pub const DOCUMENTATION: u64 = 8;
+    pub const README: u64 = 9;
+    pub const REPOSITORY: u64 = 10;
+    pub const LICENSE: u64 = 11;
+    pub const KEYWORDS: u64 = 12;
+    pub const CATEGORIES: u64 = 13;
+    pub const BUILD: u64 = 14;
+    pub const PUBLISH: u64 = 15;
+    pub const DEFAULT_RUN: u64 = 16;
+}
+
+// Fields of `cargo_dependency`.
+pub mod cargo_dependency {
+    pub const NAME: u64 = 1;
+    pub const VERSION: u64 = 2;
+    pub const FEATURES: u64 = 3;
+    pub const OPTIONAL: u64 = 4;
+    pub const DEFAULT_FEATURES: u64 = 5;
+    pub const PATH: u64 = 6;
+    pub const GIT: u64 = 7;
+    pub const BRANCH: u64 = 8;
+    pub const TAG: u64 = 9;
+    pub const REV: u64 = 10;
+    pub const PACKAGE: u64 = 11;
+    pub const WORKSPACE: u64 = 12;
+}
+
+// Fields of `cargo_feature`.
+pub mod cargo_feature {
+    pub const NAME: u64 = 1;
+    pub const ENABLES: u64 = 2;
+}
+
+// Fields of `cargo_product`.
+pub mod cargo_product {
+    pub const NAME: u64 = 1;
+    pub const PATH: u64 = 2;
+    pub const REQUIRED_FEATURES: u64 = 3;
+    pub const CRATE_TYPE: u64 = 4;
+    pub const TEST: u64 = 5;
+    pub const DOCTEST: u64 = 6;
+    pub const BENCH: u64 = 7;
+    pub const HARNESS: u64 = 8;
+    pub const PROC_MACRO: u64 = 9;
+}
+
+// Fields of `cargo_target`.
+pub mod cargo_target {
+    pub const CFG: u64 = 1;
+    pub const DEPENDENCIES: u64 = 2;
+    pub const DEV_DEPENDENCIES: u64 = 3;
+    pub const BUILD_DEPENDENCIES: u64 = 4;
+}
+
+// Fields of `cargo_workspace`.
+pub mod cargo_workspace {
+    pub const MEMBERS: u64 = 1;
+    pub const EXCLUDE: u64 = 2;
+    pub const DEFAULT_MEMBERS: u64 = 3;
+    pub const RESOLVER: u64 = 4;
+    pub const DEPENDENCIES: u64 = 5;
+}
+
+pub fn cargo_schema() -> Schema {
+    Schema {
+        kinds: vec![
+            Kind {
+                kind_id: CARGO_MANIFEST,
+                name: "cargo_manifest".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: cargo_manifest::PACKAGE,
+                        name: "package".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_PACKAGE,
+                        },
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::WORKSPACE,
+                        name: "workspace".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_WORKSPACE,
+                        },
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::DEPENDENCIES,
+                        name: "dependencies".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_DEPENDENCY,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::DEV_DEPENDENCIES,
+                        name: "dev_dependencies".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_DEPENDENCY,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::BUILD_DEPENDENCIES,
+                        name: "build_dependencies".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_DEPENDENCY,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::FEATURES,
+                        name: "features".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_FEATURE,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::LIB,
+                        name: "lib".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_PRODUCT,
+                        },
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::BIN,
+                        name: "bin".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_PRODUCT,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::EXAMPLE,
+                        name: "example".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_PRODUCT,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::TEST,
+                        name: "test".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_PRODUCT,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::BENCH,
+                        name: "bench".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_PRODUCT,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_manifest::TARGET,
+                        name: "target".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_TARGET,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: CARGO_PACKAGE,
+                name: "cargo_package".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: cargo_package::NAME,
+                        name: "name".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::VERSION,
+                        name: "version".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::AUTHORS,
+                        name: "authors".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::EDITION,
+                        name: "edition".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::RUST_VERSION,
+                        name: "rust_version".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::DESCRIPTION,
+                        name: "description".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::HOMEPAGE,
+                        name: "homepage".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::DOCUMENTATION,
+                        name: "documentation".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::README,
+                        name: "readme".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::REPOSITORY,
+                        name: "repository".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::LICENSE,
+                        name: "license".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::KEYWORDS,
+                        name: "keywords".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::CATEGORIES,
+                        name: "categories".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::BUILD,
+                        name: "build".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::PUBLISH,
+                        name: "publish".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_package::DEFAULT_RUN,
+                        name: "default_run".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: CARGO_DEPENDENCY,
+                name: "cargo_dependency".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: cargo_dependency::NAME,
+                        name: "name".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::VERSION,
+                        name: "version".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::FEATURES,
+                        name: "features".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::OPTIONAL,
+                        name: "optional".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::DEFAULT_FEATURES,
+                        name: "default_features".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::PATH,
+                        name: "path".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::GIT,
+                        name: "git".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::BRANCH,
+                        name: "branch".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::TAG,
+                        name: "tag".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::REV,
+                        name: "rev".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::PACKAGE,
+                        name: "package".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_dependency::WORKSPACE,
+                        name: "workspace".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: CARGO_FEATURE,
+                name: "cargo_feature".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: cargo_feature::NAME,
+                        name: "name".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_feature::ENABLES,
+                        name: "enables".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: CARGO_PRODUCT,
+                name: "cargo_product".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: cargo_product::NAME,
+                        name: "name".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_product::PATH,
+                        name: "path".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_product::REQUIRED_FEATURES,
+                        name: "required_features".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_product::CRATE_TYPE,
+                        name: "crate_type".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_product::TEST,
+                        name: "test".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_product::DOCTEST,
+                        name: "doctest".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_product::BENCH,
+                        name: "bench".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_product::HARNESS,
+                        name: "harness".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_product::PROC_MACRO,
+                        name: "proc_macro".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: CARGO_TARGET,
+                name: "cargo_target".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: cargo_target::CFG,
+                        name: "cfg".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_target::DEPENDENCIES,
+                        name: "dependencies".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_DEPENDENCY,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_target::DEV_DEPENDENCIES,
+                        name: "dev_dependencies".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_DEPENDENCY,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_target::BUILD_DEPENDENCIES,
+                        name: "build_dependencies".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_DEPENDENCY,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: CARGO_WORKSPACE,
+                name: "cargo_workspace".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: cargo_workspace::MEMBERS,
+                        name: "members".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_workspace::EXCLUDE,
+                        name: "exclude".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_workspace::DEFAULT_MEMBERS,
+                        name: "default_members".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_workspace::RESOLVER,
+                        name: "resolver".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: cargo_workspace::DEPENDENCIES,
+                        name: "dependencies".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: CARGO_DEPENDENCY,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+        ],
+    }
+}
@@ -0,0 +1,586 @@
+use crate::{
+    crdt::{self, ElementId},
+    types::{Digest, Link, LinkTarget, LinkType, NodeStore, Path, Selector},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Real-time collaboration, by operational transformation of edits on paths.
+//
+// Each edit is an `Op` on the path of a link. Clients apply their own ops immediately, and send
+// them to the server along with the revision of the document they were made on; the server
+// rebases each op over the ops it has applied since that revision, applies it, and broadcasts it.
+// Clients rebase incoming ops over their own ops not yet acknowledged, in the same way, so that
+// all of them converge to the root of the server. Each client has at most one op in flight; the
+// others are buffered until it is acknowledged.
+//
+// When two ops write the same path, the one applied last by the server wins. Ops under a subtree
+// that was concurrently deleted or replaced are dropped.
+//
+// Messages are serialized as JSON, and carry the nodes that their links refer to, since clients do
+// not share a `NodeStore`. `Server` is the algorithm of the sync server, which `linc_sync` runs
+// behind a WebSocket endpoint.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Op {
+    // Inserts a link at the given index of a field, shifting the following links. If the field is
+    // a sequence (see `crdt`), the new element gets the id chosen by the client that made the
+    // edit, so that all the clients store the same node.
+    Insert {
+        path: Path,
+        link: Link,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<ElementId>,
+    },
+    // Removes the link at the given path, shifting the following links back, and keeping its
+    // element as a tombstone if the field is a sequence.
+    Delete {
+        path: Path,
+    },
+    // Sets the raw value at the given path.
+    SetValue {
+        path: Path,
+        value: Vec<u8>,
+    },
+    // Replaces the subtree at the given path.
+    Replace {
+        path: Path,
+        link: Link,
+    },
+}
+
+impl Op {
+    pub fn path(&self) -> &[Selector] {
+        match self {
+            Op::Insert { path, .. }
+            | Op::Delete { path }
+            | Op::SetValue { path, .. }
+            | Op::Replace { path, .. } => path,
+        }
+    }
+
+    // Returns the same op on another path.
+    pub fn with_path(&self, path: Path) -> Op {
+        match self {
+            Op::Insert { link, id, .. } => Op::Insert {
+                path,
+                link: link.clone(),
+                id: id.clone(),
+            },
+            Op::Delete { .. } => Op::Delete { path },
+            Op::SetValue { value, .. } => Op::SetValue {
+                path,
+                value: value.clone(),
+            },
+            Op::Replace { link, .. } => Op::Replace {
+                path,
+                link: link.clone(),
+            },
+        }
+    }
+
+    // The link that the op introduces, if any.
+    fn link(&self) -> Option<&Link> {
+        match self {
+            Op::Insert { link, .. } | Op::Replace { link, .. } => Some(link),
+            _ => None,
+        }
+    }
+}
+
+// Applies an op to the tree at the given root, and returns the new root. Setting or replacing the
+// link just after the last one of a field appends to it, as the editor does.
+pub fn apply(node_store: &mut NodeStore, root: &Link, op: &Op) -> Result<Link, String> {
+    apply_at(node_store, root, op.path(), op)
+}
+
+fn apply_at(
+    node_store: &mut NodeStore,
+    link: &Link,
+    path: &[Selector],
+    op: &Op,
+) -> Result<Link, String> {
+    let (selector, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            return match op {
+                Op::SetValue { value, .. } => Ok(Link {
+                    type_: LinkType::Raw,
+                    digest: node_store.put_raw(value),
+                }),
+                Op::Replace { link, .. } => Ok(link.clone()),
+                _ => Err("cannot insert or delete the root".to_string()),
+            };
+        }
+    };
+    let mut node = match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        Some(LinkTarget::Raw(_)) => return Err(format!("{:?} is a value", selector)),
+        None => return Err(format!("missing node {}", link.digest)),
+    };
+    let field_id = selector.field_id;
+    let sequence = crdt::is_sequence(&node, field_id);
+    let links = node.links.entry(field_id).or_default();
+    match (op, rest.is_empty()) {
+        (Op::Insert { link, id, .. }, true) if selector.index <= links.len() => {
+            match (sequence, id) {
+                (false, _) => links.insert(selector.index, link.clone()),
+                (true, Some(id)) => crdt::insert_as(
+                    &mut node,
+                    field_id,
+                    selector.index,
+                    link.clone(),
+                    id.clone(),
+                ),
+                (true, None) => return Err(format!("no element id to insert at {:?}", selector)),
+            }
+        }
+        (Op::Delete { .. }, true) if selector.index < links.len() => {
+            if sequence {
+                crdt::delete(&mut node, field_id, selector.index);
+            } else {
+                links.remove(selector.index);
+            }
+        }
+        (Op::SetValue { .. } | Op::Replace { .. }, true) if selector.index == links.len() => {
+            let child = apply_at(node_store, link, rest, op)?;
+            node.links.entry(selector.field_id).or_default().push(child);
+        }
+        (_, _) if selector.index < links.len() => {
+            let child = links[selector.index].clone();
+            let child = apply_at(node_store, &child, rest, op)?;
+            node.links.entry(selector.field_id).or_default()[selector.index] = child;
+        }
+        _ => return Err(format!("no link at {:?}", selector)),
+    }
+    node.links.retain(|_, links| !links.is_empty());
+    Ok(Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&node),
+    })
+}
+
+// Whether `prefix` is a strict prefix of `path`.
+fn is_above(prefix: &[Selector], path: &[Selector]) -> bool {
+    prefix.len() < path.len() && path.starts_with(prefix)
+}
+
+// Rebases a path over a concurrent op. If `insertion` is set, the path is the position of a new
+// link rather than that of an existing one. Returns `None` if the path no longer exists.
+fn transform_path(path: &[Selector], against: &Op, insertion: bool, later: bool) -> Option<Path> {
+    let target = against.path();
+    let (selector, parent) = match target.split_last() {
+        Some(split) => split,
+        // Ops on the root replace the whole tree.
+        None => return if path.is_empty() { Some(vec![]) } else { None },
+    };
+    if let Op::SetValue { .. } | Op::Replace { .. } = against {
+        return if is_above(target, path) {
+            None
+        } else {
+            Some(path.to_vec())
+        };
+    }
+    let depth = parent.len();
+    if path.len() <= depth || !path.starts_with(parent) || path[depth].field_id != selector.field_id
+    {
+        return Some(path.to_vec());
+    }
+    let mut path = path.to_vec();
+    let last = path.len() == depth + 1;
+    let index = &mut path[depth].index;
+    match against {
+        // Of two links inserted at the same index, the one inserted first by the server comes
+        // first.
+        Op::Insert { .. } if *index > selector.index => *index += 1,
+        Op::Insert { .. } if *index == selector.index && (!insertion || !last || later) => {
+            *index += 1
+        }
+        Op::Delete { .. } if *index > selector.index => *index -= 1,
+        Op::Delete { .. } if *index == selector.index && !(insertion && last) => return None,
+        _ => {}
+    }
+    Some(path)
+}
+
+// Rebases `op` over a concurrent op `against`, that was applied before it if `later` is set, or
+// after it otherwise. Returns `None` if the op no longer has any effect.
+pub fn transform(op: &Op, against: &Op, later: bool) -> Option<Op> {
+    let insertion = matches!(op, Op::Insert { .. });
+    let conflict = !insertion && op.path() == against.path();
+    match (op, against) {
+        // The target was already deleted.
+        (Op::Delete { .. }, Op::Delete { .. }) if conflict => None,
+        (Op::SetValue { .. } | Op::Replace { .. }, Op::SetValue { .. } | Op::Replace { .. })
+            if conflict =>
+        {
+            if later {
+                Some(op.clone())
+            } else {
+                None
+            }
+        }
+        // A deleted link may have been replaced, but the deletion still applies.
+        (Op::Delete { .. }, Op::SetValue { .. } | Op::Replace { .. }) if conflict => {
+            Some(op.clone())
+        }
+        _ => transform_path(op.path(), against, insertion, later).map(|path| op.with_path(path)),
+    }
+}
+
+// Rebases a selected path over an op, moving it to the closest remaining ancestor if it was
+// removed.
+pub fn transform_selection(path: &[Selector], op: &Op) -> Path {
+    match transform_path(path, op, false, false) {
+        Some(path) => path,
+        None => {
+            let target = op.path();
+            let depth = target.len().min(path.len());
+            match op {
+                Op::Delete { .. } => target[..depth.saturating_sub(1)].to_vec(),
+                _ => target[..depth].to_vec(),
+            }
+        }
+    }
+}
+
+// Returns the serialized nodes and values reachable from a link, with their digests, for the
+// receiver to store.
+pub fn blobs(node_store: &NodeStore, link: &Link) -> Vec<(Digest, Vec<u8>)> {
+    let mut blobs = vec![];
+    let mut pending = vec![link.clone()];
+    while let Some(link) = pending.pop() {
+        if let Some(blob) = node_store.get_raw(&link.digest) {
+            blobs.push((link.digest.clone(), blob.clone()));
+        }
+        if let Some(LinkTarget::Parsed(node)) = link.get(node_store) {
+            pending.extend(node.links.values().flatten().cloned());
+        }
+    }
+    blobs
+}
+
+// Stores the blobs received with a message, checking that each of them has the digest it was sent
+// with.
+pub fn put_blobs(node_store: &mut NodeStore, blobs: &[(Digest, Vec<u8>)]) -> Result<(), String> {
+    for (digest, blob) in blobs {
+        node_store
+            .put_raw_checked(digest, blob)
+            .map_err(|actual| format!("digest mismatch: {} hashes to {}", digest, actual))?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Join {
+        client_id: String,
+    },
+    Submit {
+        client_id: String,
+        // Revision of the server that the op was made on.
+        revision: usize,
+        op: Op,
+        blobs: Vec<(Digest, Vec<u8>)>,
+    },
+    Presence {
+        client_id: String,
+        path: Path,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    Welcome {
+        revision: usize,
+        root: Link,
+        blobs: Vec<(Digest, Vec<u8>)>,
+        presence: BTreeMap<String, Path>,
+    },
+    // The op of the client was applied (or dropped), and the server is now at the given revision.
+    Ack {
+        revision: usize,
+    },
+    Op {
+        client_id: String,
+        revision: usize,
+        op: Op,
+        blobs: Vec<(Digest, Vec<u8>)>,
+    },
+    Presence {
+        client_id: String,
+        path: Path,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recipient {
+    Client(String),
+    // All the clients but the given one.
+    Others(String),
+}
+
+#[derive(Debug)]
+pub struct Server {
+    pub node_store: NodeStore,
+    pub root: Link,
+    history: Vec<Op>,
+    presence: BTreeMap<String, Path>,
+}
+
+impl Server {
+    pub fn new(node_store: NodeStore, root: Link) -> Self {
+        Server {
+            node_store,
+            root,
+            history: vec![],
+            presence: BTreeMap::new(),
+        }
+    }
+
+    pub fn revision(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn receive(&mut self, message: ClientMessage) -> Vec<(Recipient, ServerMessage)> {
+        match message {
+            ClientMessage::Join { client_id } => vec![(
+                Recipient::Client(client_id),
+                ServerMessage::Welcome {
+                    revision: self.revision(),
+                    root: self.root.clone(),
+                    blobs: blobs(&self.node_store, &self.root),
+                    presence: self.presence.clone(),
+                },
+            )],
+            ClientMessage::Submit {
+                client_id,
+                revision,
+                op,
+                blobs,
+            } => {
+                // Ops whose blobs do not match their digests are dropped.
+                let op = match put_blobs(&mut self.node_store, &blobs) {
+                    Ok(()) => Some(op),
+                    Err(err) => {
+                        log::warn!("dropped op of {}: {}", client_id, err);
+                        None
+                    }
+                };
+                let op = op.and_then(|op| {
+                    self.history
+                        .get(revision..)
+                        .unwrap_or_default()
+                        .iter()
+                        .try_fold(op, |op, against| transform(&op, against, true))
+                });
+                let op = op.and_then(|op| {
+                    let root = apply(&mut self.node_store, &self.root, &op).ok()?;
+                    self.root = root;
+                    Some(op)
+                });
+                let mut messages = vec![];
+                if let Some(op) = op {
+                    for path in self.presence.values_mut() {
+                        *path = transform_selection(path, &op);
+                    }
+                    self.history.push(op.clone());
+                    messages.push((
+                        Recipient::Others(client_id.clone()),
+                        ServerMessage::Op {
+                            client_id: client_id.clone(),
+                            revision: self.revision(),
+                            blobs: op
+                                .link()
+                                .map(|link| crate::collab::blobs(&self.node_store, link))
+                                .unwrap_or_default(),
+                            op,
+                        },
+                    ));
+                }
+                messages.push((
+                    Recipient::Client(client_id),
+                    ServerMessage::Ack {
+                        revision: self.revision(),
+                    },
+                ));
+                messages
+            }
+            ClientMessage::Presence { client_id, path } => {
+                self.presence.insert(client_id.clone(), path.clone());
+                vec![(
+                    Recipient::Others(client_id.clone()),
+                    ServerMessage::Presence { client_id, path },
+                )]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Client {
+    pub client_id: String,
+    // Last revision of the server known to the client.
+    pub revision: usize,
+    pub root: Link,
+    // Selected paths of the other clients.
+    pub presence: BTreeMap<String, Path>,
+    // Whether an op was sent to the server and not yet acknowledged.
+    waiting: bool,
+    // Effect of that op, rebased over the remote ops received since; `None` if it was dropped.
+    outstanding: Option<Op>,
+    // Ops made while another one is outstanding.
+    buffer: Vec<Op>,
+}
+
+impl Client {
+    // Creates a client editing the given root, until the server sends its own.
+    pub fn new(client_id: &str, root: Link) -> Self {
+        Client {
+            client_id: client_id.to_string(),
+            revision: 0,
+            root,
+            presence: BTreeMap::new(),
+            waiting: false,
+            outstanding: None,
+            buffer: vec![],
+        }
+    }
+
+    pub fn join(&self) -> ClientMessage {
+        ClientMessage::Join {
+            client_id: self.client_id.clone(),
+        }
+    }
+
+    pub fn presence(&self, path: &[Selector]) -> ClientMessage {
+        ClientMessage::Presence {
+            client_id: self.client_id.clone(),
+            path: path.to_vec(),
+        }
+    }
+
+    // Whether all the local ops have been acknowledged by the server.
+    pub fn is_synced(&self) -> bool {
+        !self.waiting && self.buffer.is_empty()
+    }
+
+    // Applies a local op, and returns the message to send, if any.
+    pub fn edit(
+        &mut self,
+        node_store: &mut NodeStore,
+        op: Op,
+    ) -> Result<Option<ClientMessage>, String> {
+        let root = apply(node_store, &self.root, &op)?;
+        Ok(self.local(node_store, op, root))
+    }
+
+    // Records a local op that was already applied, resulting in the given root.
+    pub fn local(&mut self, node_store: &NodeStore, op: Op, root: Link) -> Option<ClientMessage> {
+        self.root = root;
+        for path in self.presence.values_mut() {
+            *path = transform_selection(path, &op);
+        }
+        if self.waiting {
+            self.buffer.push(op);
+            None
+        } else {
+            Some(self.submit(node_store, op))
+        }
+    }
+
+    fn submit(&mut self, node_store: &NodeStore, op: Op) -> ClientMessage {
+        self.waiting = true;
+        self.outstanding = Some(op.clone());
+        ClientMessage::Submit {
+            client_id: self.client_id.clone(),
+            revision: self.revision,
+            blobs: op
+                .link()
+                .map(|link| blobs(node_store, link))
+                .unwrap_or_default(),
+            op,
+        }
+    }
+
+    // Handles a message of the server. Returns the remote op that was applied, if any, and the
+    // messages to send.
+    pub fn receive(
+        &mut self,
+        node_store: &mut NodeStore,
+        message: ServerMessage,
+    ) -> Result<(Option<Op>, Vec<ClientMessage>), String> {
+        match message {
+            ServerMessage::Welcome {
+                revision,
+                root,
+                blobs,
+                presence,
+            } => {
+                put_blobs(node_store, &blobs)?;
+                self.revision = revision;
+                self.root = root;
+                self.presence = presence;
+                self.presence.remove(&self.client_id);
+                self.waiting = false;
+                self.outstanding = None;
+                self.buffer.clear();
+                Ok((None, vec![]))
+            }
+            ServerMessage::Ack { revision } => {
+                self.revision = revision;
+                self.waiting = false;
+                self.outstanding = None;
+                if self.buffer.is_empty() {
+                    return Ok((None, vec![]));
+                }
+                let op = self.buffer.remove(0);
+                Ok((None, vec![self.submit(node_store, op)]))
+            }
+            ServerMessage::Op {
+                client_id,
+                revision,
+                op,
+                blobs,
+            } => {
+                put_blobs(node_store, &blobs)?;
+                self.revision = revision;
+                // Rebase the remote op over the local ones, and the local ones over it, which the
+                // server will apply after it.
+                let mut remote = op;
+                if let Some(local) = self.outstanding.take() {
+                    self.outstanding = transform(&local, &remote, true);
+                    match transform(&remote, &local, false) {
+                        Some(op) => remote = op,
+                        None => return Ok((None, vec![])),
+                    }
+                }
+                let mut buffer = vec![];
+                for (i, local) in self.buffer.iter().enumerate() {
+                    buffer.extend(transform(local, &remote, true));
+                    match transform(&remote, local, false) {
+                        Some(op) => remote = op,
+                        None => {
+                            buffer.extend(self.buffer[i + 1..].iter().cloned());
+                            self.buffer = buffer;
+                            return Ok((None, vec![]));
+                        }
+                    }
+                }
+                self.buffer = buffer;
+                self.root = apply(node_store, &self.root, &remote)?;
+                for path in self.presence.values_mut() {
+                    *path = transform_selection(path, &remote);
+                }
+                log::debug!("applied op of {}: {:?}", client_id, remote);
+                Ok((Some(remote), vec![]))
+            }
+            ServerMessage::Presence { client_id, path } => {
+                if client_id != self.client_id {
+                    self.presence.insert(client_id, path);
+                }
+                Ok((None, vec![]))
+            }
+        }
+    }
+}
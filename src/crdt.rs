@@ -0,0 +1,334 @@
+use crate::types::{Link, LinkTarget, LinkType, Node, NodeStore, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// Replicated sequences (RGA) for repeated fields, so that copies of a tree edited independently,
+// e.g. offline, can be merged without conflicts.
+//
+// A repeated field may be stored as a sequence, in which case `Node::sequences` holds an element
+// for each link ever inserted in it, in order, with a stable id and the id of the element it was
+// inserted after. Deleted elements are kept as tombstones, so that elements inserted after them can
+// still be placed. The links of the elements that are not deleted are those of the field, in the
+// same order, so that the field is read (and addressed by index) as usual; since the elements are
+// part of the node, they are covered by its digest.
+//
+// A new element gets a counter greater than those of all the elements it has seen, and ids are
+// ordered by counter, then replica. Elements inserted after the same element are ordered by
+// decreasing id, so that all the replicas end up with the same order, whatever the order in which
+// they merge.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ElementId {
+    pub counter: u64,
+    pub replica: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Element {
+    pub id: ElementId,
+    // Element after which this one was inserted, or `None` for the start of the sequence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<ElementId>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+}
+
+pub fn is_sequence(node: &Node, field_id: u64) -> bool {
+    node.sequences.contains_key(&field_id)
+}
+
+// Stores an existing field as a sequence, giving ids to its links.
+pub fn enable(node: &mut Node, field_id: u64, replica: &str) {
+    if is_sequence(node, field_id) {
+        return;
+    }
+    let len = node.links.get(&field_id).map_or(0, Vec::len);
+    let mut origin = None;
+    let elements = (1..=len as u64)
+        .map(|counter| {
+            let id = ElementId {
+                counter,
+                replica: replica.to_string(),
+            };
+            Element {
+                id: id.clone(),
+                origin: origin.replace(id),
+                deleted: false,
+            }
+        })
+        .collect();
+    node.sequences.insert(field_id, elements);
+}
+
+// Returns the position in the sequence of the element at the given index of the field.
+fn position(elements: &[Element], index: usize) -> Option<usize> {
+    elements
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !e.deleted)
+        .nth(index)
+        .map(|(position, _)| position)
+}
+
+pub fn id_at(node: &Node, field_id: u64, index: usize) -> Option<ElementId> {
+    let elements = node.sequences.get(&field_id)?;
+    position(elements, index).map(|p| elements[p].id.clone())
+}
+
+// Returns the index in the field of the element with the given id, unless it was deleted.
+pub fn index_of(node: &Node, field_id: u64, id: &ElementId) -> Option<usize> {
+    let elements = node.sequences.get(&field_id)?;
+    let mut index = 0;
+    for element in elements.iter() {
+        if element.deleted {
+            continue;
+        }
+        if element.id == *id {
+            return Some(index);
+        }
+        index += 1;
+    }
+    None
+}
+
+// Returns a selector addressing the same child as the given one, by id if its field is a sequence.
+pub fn stable(node: &Node, selector: &Selector) -> Selector {
+    let index = node.index(selector).unwrap_or(selector.index);
+    Selector {
+        field_id: selector.field_id,
+        index,
+        id: id_at(node, selector.field_id, index),
+    }
+}
+
+// Returns a path addressing the same node as the given one in the tree at the given root, by id in
+// the fields that are sequences, so that it still does once the tree is merged with another
+// version.
+pub fn stable_path(node_store: &NodeStore, root: &Link, path: &[Selector]) -> Vec<Selector> {
+    let mut link = root.clone();
+    let mut stable_path = vec![];
+    for selector in path {
+        let node = match link.get(node_store) {
+            Some(LinkTarget::Parsed(node)) => node.clone(),
+            _ => break,
+        };
+        stable_path.push(stable(&node, selector));
+        match node.get_link(selector) {
+            Some(child) => link = child.clone(),
+            None => break,
+        }
+    }
+    stable_path.extend(path[stable_path.len()..].iter().cloned());
+    stable_path
+}
+
+// Returns the path by index of the node addressed by the given path, unless it no longer exists.
+// Views compare paths by index, so they only hold such paths.
+pub fn resolve_path(
+    node_store: &NodeStore,
+    root: &Link,
+    path: &[Selector],
+) -> Option<Vec<Selector>> {
+    let mut link = root.clone();
+    let mut resolved = vec![];
+    for selector in path {
+        let node = link.get(node_store)?.as_parsed()?.clone();
+        resolved.push(Selector {
+            field_id: selector.field_id,
+            index: node.index(selector)?,
+            id: None,
+        });
+        link = node.get_link(selector)?.clone();
+    }
+    Some(resolved)
+}
+
+// Returns the id of a new element of a sequence, greater than those of all its elements.
+pub fn next_id(node: &Node, field_id: u64, replica: &str) -> ElementId {
+    let elements = node.sequences.get(&field_id).map_or(&[][..], Vec::as_slice);
+    ElementId {
+        counter: elements.iter().map(|e| e.id.counter).max().unwrap_or(0) + 1,
+        replica: replica.to_string(),
+    }
+}
+
+// Inserts a link at the given index of a sequence, and returns the id of its element.
+pub fn insert(
+    node: &mut Node,
+    field_id: u64,
+    index: usize,
+    link: Link,
+    replica: &str,
+) -> ElementId {
+    let id = next_id(node, field_id, replica);
+    insert_as(node, field_id, index, link, id.clone());
+    id
+}
+
+// Inserts a link at the given index of a sequence, as the element with the given id, e.g. one
+// chosen by the replica that made the edit.
+pub fn insert_as(node: &mut Node, field_id: u64, index: usize, link: Link, id: ElementId) {
+    let elements = node.sequences.entry(field_id).or_default();
+    let origin = index
+        .checked_sub(1)
+        .and_then(|i| position(elements, i))
+        .map(|p| elements[p].id.clone());
+    // The element goes right after its origin, so that its link ends up at the given index; a new
+    // id is greater than all the others, so that is also where merging places it.
+    let position = match &origin {
+        Some(origin) => elements.iter().position(|e| e.id == *origin).unwrap() + 1,
+        None => 0,
+    };
+    elements.insert(
+        position,
+        Element {
+            id: id.clone(),
+            origin,
+            deleted: false,
+        },
+    );
+    let index = elements[..position].iter().filter(|e| !e.deleted).count();
+    node.links.entry(field_id).or_default().insert(index, link);
+}
+
+// Deletes the link at the given index of a sequence, keeping its element as a tombstone.
+pub fn delete(node: &mut Node, field_id: u64, index: usize) -> Option<ElementId> {
+    let elements = node.sequences.get_mut(&field_id)?;
+    let position = position(elements, index)?;
+    elements[position].deleted = true;
+    let id = elements[position].id.clone();
+    let links = node.links.get_mut(&field_id)?;
+    links.remove(index);
+    if links.is_empty() {
+        node.links.remove(&field_id);
+    }
+    Some(id)
+}
+
+// Orders the given elements: each element comes after its origin, and elements with the same
+// origin come by decreasing id. Elements whose origin is unknown are placed at the start.
+fn order(elements: Vec<Element>) -> Vec<Element> {
+    let ids: BTreeSet<ElementId> = elements.iter().map(|e| e.id.clone()).collect();
+    let mut children: BTreeMap<Option<ElementId>, Vec<Element>> = BTreeMap::new();
+    for element in elements {
+        let origin = element.origin.clone().filter(|o| ids.contains(o));
+        children.entry(origin).or_default().push(element);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| b.id.cmp(&a.id));
+    }
+    let mut ordered = vec![];
+    let mut stack: Vec<Element> = children.remove(&None).unwrap_or_default();
+    stack.reverse();
+    while let Some(element) = stack.pop() {
+        let mut next = children
+            .remove(&Some(element.id.clone()))
+            .unwrap_or_default();
+        next.reverse();
+        stack.extend(next);
+        ordered.push(element);
+    }
+    ordered
+}
+
+// Merges two versions of a tree, and returns a link to the merged one. Sequences are merged
+// element by element, an element being deleted if it is deleted in either version, and the
+// children of elements present in both versions are merged in turn. Other fields are merged
+// index by index if they have the same number of links, and otherwise the longer one is kept.
+// Conflicting values are resolved by picking the greatest digest. The result does not depend on
+// the order of the arguments.
+pub fn merge(node_store: &mut NodeStore, a: &Link, b: &Link) -> Link {
+    if a == b {
+        return a.clone();
+    }
+    let (node_a, node_b) = match (a.get(node_store), b.get(node_store)) {
+        (Some(LinkTarget::Parsed(a)), Some(LinkTarget::Parsed(b))) => (a.clone(), b.clone()),
+        _ => return std::cmp::max_by(a, b, |a, b| a.digest.cmp(&b.digest)).clone(),
+    };
+    let node = merge_nodes(node_store, &node_a, &node_b);
+    Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&node),
+    }
+}
+
+fn merge_nodes(node_store: &mut NodeStore, a: &Node, b: &Node) -> Node {
+    let mut node = Node::default();
+    let field_ids: BTreeSet<u64> = a
+        .links
+        .keys()
+        .chain(a.sequences.keys())
+        .chain(b.links.keys())
+        .chain(b.sequences.keys())
+        .copied()
+        .collect();
+    let empty = vec![];
+    for field_id in field_ids {
+        let links_a = a.links.get(&field_id).unwrap_or(&empty);
+        let links_b = b.links.get(&field_id).unwrap_or(&empty);
+        let links = match (a.sequences.get(&field_id), b.sequences.get(&field_id)) {
+            (Some(elements_a), Some(elements_b)) => {
+                let (elements, links) =
+                    merge_sequences(node_store, (elements_a, links_a), (elements_b, links_b));
+                node.sequences.insert(field_id, elements);
+                links
+            }
+            // A field stored as a sequence on one side only is taken from that side, unless it is
+            // empty.
+            (Some(elements), None) if !elements.is_empty() || links_b.is_empty() => {
+                node.sequences.insert(field_id, elements.clone());
+                links_a.clone()
+            }
+            (None, Some(elements)) if !elements.is_empty() || links_a.is_empty() => {
+                node.sequences.insert(field_id, elements.clone());
+                links_b.clone()
+            }
+            _ if links_a.len() == links_b.len() => links_a
+                .iter()
+                .zip(links_b.iter())
+                .map(|(a, b)| merge(node_store, a, b))
+                .collect(),
+            _ if links_a.len() > links_b.len() => links_a.clone(),
+            _ => links_b.clone(),
+        };
+        if !links.is_empty() {
+            node.links.insert(field_id, links);
+        }
+    }
+    node
+}
+
+fn merge_sequences(
+    node_store: &mut NodeStore,
+    (elements_a, links_a): (&[Element], &[Link]),
+    (elements_b, links_b): (&[Element], &[Link]),
+) -> (Vec<Element>, Vec<Link>) {
+    let visible = |elements: &[Element], links: &[Link]| -> HashMap<ElementId, Link> {
+        elements
+            .iter()
+            .filter(|e| !e.deleted)
+            .map(|e| e.id.clone())
+            .zip(links.iter().cloned())
+            .collect()
+    };
+    let visible_a = visible(elements_a, links_a);
+    let visible_b = visible(elements_b, links_b);
+    let mut elements: BTreeMap<ElementId, Element> = BTreeMap::new();
+    for element in elements_a.iter().chain(elements_b.iter()) {
+        elements
+            .entry(element.id.clone())
+            .and_modify(|e| e.deleted |= element.deleted)
+            .or_insert_with(|| element.clone());
+    }
+    let elements = order(elements.into_values().collect());
+    let links = elements
+        .iter()
+        .filter(|e| !e.deleted)
+        .filter_map(|e| match (visible_a.get(&e.id), visible_b.get(&e.id)) {
+            (Some(a), Some(b)) => Some(merge(node_store, a, b)),
+            (Some(link), None) | (None, Some(link)) => Some(link.clone()),
+            (None, None) => None,
+        })
+        .collect();
+    (elements, links)
+}
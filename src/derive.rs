@@ -0,0 +1,177 @@
+use crate::{
+    schema::{FieldType, Kind, Schema},
+    typed::{self, Error, Object, Scalar},
+    types::{Link, LinkType, NodeStore, Path, Selector},
+};
+
+pub use linc_derive::LincNode;
+
+// Conversion of Rust types to and from trees, implemented by `#[derive(LincNode)]`.
+//
+// Each field of a struct needs an id, given as `#[linc(id = N)]`, and is stored as a field of a
+// node; `Option` fields may be missing, and `Vec` fields are repeated. Each variant of an enum
+// needs an id too, and is a field of the `oneof` of the node: newtype variants hold their value,
+// and unit variants hold `true`. Kind ids are derived from the type name, like those of protobuf
+// messages, unless given as `#[linc(kind_id = N)]` on the type. Since nodes are content
+// addressed, equal values are stored once, and unchanged subtrees are shared between versions.
+
+pub trait LincNode: Sized {
+    fn kind() -> Kind;
+
+    // Adds the kind of this type, and those of its fields, to the given kinds.
+    fn kinds(kinds: &mut Vec<Kind>);
+
+    // Returns a schema whose root kind is that of this type.
+    fn schema() -> Schema {
+        let mut kinds = vec![];
+        Self::kinds(&mut kinds);
+        Schema { kinds }
+    }
+
+    fn to_dag(&self, node_store: &mut NodeStore) -> Link;
+
+    fn from_dag(node_store: &NodeStore, link: &Link) -> Result<Self, Error> {
+        Self::from_dag_at(node_store, &[], link)
+    }
+
+    // Same as `from_dag`, with the path of the node used in errors.
+    fn from_dag_at(node_store: &NodeStore, path: &[Selector], link: &Link) -> Result<Self, Error>;
+}
+
+// Types of the values of fields: scalars, and types deriving `LincNode`.
+pub trait LincValue: Sized {
+    fn field_type() -> FieldType;
+
+    fn kinds(_kinds: &mut Vec<Kind>) {}
+
+    fn to_link(&self, node_store: &mut NodeStore) -> Link;
+
+    fn from_link(node_store: &NodeStore, path: &[Selector], link: &Link) -> Result<Self, Error>;
+}
+
+macro_rules! scalar_value {
+    ($t:ty) => {
+        impl LincValue for $t {
+            fn field_type() -> FieldType {
+                <$t as Scalar>::TYPE
+            }
+
+            fn to_link(&self, node_store: &mut NodeStore) -> Link {
+                Link {
+                    type_: LinkType::Raw,
+                    digest: node_store.put_raw(&self.to_value().to_raw().unwrap_or_default()),
+                }
+            }
+
+            fn from_link(
+                node_store: &NodeStore,
+                path: &[Selector],
+                link: &Link,
+            ) -> Result<Self, Error> {
+                typed::value(node_store, path, link)
+            }
+        }
+    };
+}
+
+// There is no value for bytes, since `Vec<u8>` would be ambiguous with a repeated field.
+scalar_value!(String);
+scalar_value!(bool);
+scalar_value!(i64);
+scalar_value!(f64);
+
+// Types of fields: values, optional values and repeated values.
+pub trait LincField: Sized {
+    const REPEATED: bool;
+
+    fn field_type() -> FieldType;
+
+    fn kinds(kinds: &mut Vec<Kind>);
+
+    fn to_links(&self, node_store: &mut NodeStore) -> Vec<Link>;
+
+    fn from_links(node_store: &NodeStore, object: &Object, field_id: u64) -> Result<Self, Error>;
+}
+
+fn links(object: &Object, field_id: u64) -> Vec<(Path, Link)> {
+    object
+        .links(field_id)
+        .map(|(path, link)| (path, link.clone()))
+        .collect()
+}
+
+impl<T: LincValue> LincField for T {
+    const REPEATED: bool = false;
+
+    fn field_type() -> FieldType {
+        T::field_type()
+    }
+
+    fn kinds(kinds: &mut Vec<Kind>) {
+        T::kinds(kinds)
+    }
+
+    fn to_links(&self, node_store: &mut NodeStore) -> Vec<Link> {
+        vec![self.to_link(node_store)]
+    }
+
+    fn from_links(node_store: &NodeStore, object: &Object, field_id: u64) -> Result<Self, Error> {
+        match Option::<T>::from_links(node_store, object, field_id)? {
+            Some(value) => Ok(value),
+            None => Err(Error {
+                path: object.path().to_vec(),
+                message: format!("missing field {}", field_id),
+            }),
+        }
+    }
+}
+
+impl<T: LincValue> LincField for Option<T> {
+    const REPEATED: bool = false;
+
+    fn field_type() -> FieldType {
+        T::field_type()
+    }
+
+    fn kinds(kinds: &mut Vec<Kind>) {
+        T::kinds(kinds)
+    }
+
+    fn to_links(&self, node_store: &mut NodeStore) -> Vec<Link> {
+        self.iter().map(|v| v.to_link(node_store)).collect()
+    }
+
+    fn from_links(node_store: &NodeStore, object: &Object, field_id: u64) -> Result<Self, Error> {
+        match links(object, field_id).as_slice() {
+            [] => Ok(None),
+            [(path, link)] => T::from_link(node_store, path, link).map(Some),
+            [_, (path, _), ..] => Err(Error {
+                path: path.clone(),
+                message: "more than one value".to_string(),
+            }),
+        }
+    }
+}
+
+impl<T: LincValue> LincField for Vec<T> {
+    const REPEATED: bool = true;
+
+    fn field_type() -> FieldType {
+        T::field_type()
+    }
+
+    fn kinds(kinds: &mut Vec<Kind>) {
+        T::kinds(kinds)
+    }
+
+    fn to_links(&self, node_store: &mut NodeStore) -> Vec<Link> {
+        self.iter().map(|v| v.to_link(node_store)).collect()
+    }
+
+    fn from_links(node_store: &NodeStore, object: &Object, field_id: u64) -> Result<Self, Error> {
+        links(object, field_id)
+            .iter()
+            .map(|(path, link)| T::from_link(node_store, path, link))
+            .collect()
+    }
+}
@@ -0,0 +1,795 @@
+use crate::schema::{Arg, Field, FieldType, Kind, Schema};
+
+// Schemas of the docker `build`, `run` and `compose` commands, passed on a command line by
+// `argv.rs`.
+//
+// See https://docs.docker.com/reference/cli/docker/. Flags are given in their long form; short
+// aliases (e.g. `-t` for `--tag`) are not accepted when parsing. Docker stops parsing flags after
+// the image of `run` (and the service of `compose exec`), so the command that follows is a `Rest`
+// field. The ids of `docker_command`, `docker_run` and `docker_build` predate this module, and are
+// kept so that existing trees remain valid.
+
+pub const DOCKER_COMMAND: u64 = 23428;
+pub const DOCKER_BUILD: u64 = 2309471;
+pub const DOCKER_RUN: u64 = 13091823090;
+pub const DOCKER_COMPOSE: u64 = 8140001;
+pub const COMPOSE_UP: u64 = 8140002;
+pub const COMPOSE_DOWN: u64 = 8140003;
+pub const COMPOSE_LOGS: u64 = 8140004;
+pub const COMPOSE_EXEC: u64 = 8140005;
+
+// Fields of `docker_command`.
+pub mod docker_command {
+    pub const HOST: u64 = 1;
+    pub const CONTEXT: u64 = 2;
+    pub const LOG_LEVEL: u64 = 3;
+    pub const BUILD: u64 = 2309471;
+    pub const RUN: u64 = 13091823090;
+    pub const COMPOSE: u64 = 4;
+}
+
+// Fields of `docker_build`.
+pub mod docker_build {
+    pub const TAG: u64 = 1;
+    pub const FILE: u64 = 2;
+    pub const BUILD_ARG: u64 = 3;
+    pub const TARGET: u64 = 4;
+    pub const PLATFORM: u64 = 5;
+    pub const LABEL: u64 = 6;
+    pub const SECRET: u64 = 7;
+    pub const NETWORK: u64 = 8;
+    pub const PROGRESS: u64 = 9;
+    pub const NO_CACHE: u64 = 10;
+    pub const PULL: u64 = 11;
+    pub const QUIET: u64 = 12;
+    pub const CONTEXT: u64 = 13;
+}
+
+// Fields of `docker_run`.
+pub mod docker_run {
+    pub const DETACH: u64 = 1;
+    pub const INTERACTIVE: u64 = 2;
+    pub const TTY: u64 = 3;
+    pub const RM: u64 = 4;
+    pub const NAME: u64 = 5;
+    pub const ENV: u64 = 6;
+    pub const ENV_FILE: u64 = 7;
+    pub const PUBLISH: u64 = 8;
+    pub const VOLUME: u64 = 9;
+    pub const MOUNT: u64 = 10;
+    pub const NETWORK: u64 = 11;
+    pub const WORKDIR: u64 = 12;
+    pub const USER: u64 = 13;
+    pub const ENTRYPOINT: u64 = 14;
+    pub const RESTART: u64 = 15;
+    pub const PLATFORM: u64 = 16;
+    pub const LABEL: u64 = 17;
+    pub const CPUS: u64 = 18;
+    pub const MEMORY: u64 = 19;
+    pub const PRIVILEGED: u64 = 20;
+    pub const INIT: u64 = 21;
+    pub const PULL: u64 = 22;
+    pub const IMAGE: u64 = 23;
+    pub const COMMAND: u64 = 24;
+}
+
+// Fields of `docker_compose`.
+pub mod docker_compose {
+    pub const FILE: u64 = 1;
+    pub const PROJECT_NAME: u64 = 2;
+    pub const PROFILE: u64 = 3;
+    pub const ENV_FILE: u64 = 4;
+    pub const UP: u64 = 5;
+    pub const DOWN: u64 = 6;
+    pub const LOGS: u64 = 7;
+    pub const EXEC: u64 = 8;
+}
+
+// Fields of `compose_up`.
+pub mod compose_up {
+    pub const DETACH: u64 = 1;
+    pub const BUILD: u64 = 2;
+    pub const FORCE_RECREATE: u64 = 3;
+    pub const REMOVE_ORPHANS: u64 = 4;
+    pub const NO_DEPS: u64 = 5;
+    pub const WAIT: u64 = 6;
+    pub const SCALE: u64 = 7;
+    pub const SERVICE: u64 = 8;
+}
+
+// Fields of `compose_down`.
+pub mod compose_down {
+    pub const VOLUMES: u64 = 1;
+    pub const REMOVE_ORPHANS: u64 = 2;
+    pub const RMI: u64 = 3;
+    pub const TIMEOUT: u64 = 4;
+}
+
+// Fields of `compose_logs`.
+pub mod compose_logs {
+    pub const FOLLOW: u64 = 1;
+    pub const TIMESTAMPS: u64 = 2;
+    pub const TAIL: u64 = 3;
+    pub const SINCE: u64 = 4;
+    pub const SERVICE: u64 = 5;
+}
+
+// Fields of `compose_exec`.
+pub mod compose_exec {
+    pub const DETACH: u64 = 1;
+    pub const NO_TTY: u64 = 2;
+    pub const ENV: u64 = 3;
+    pub const USER: u64 = 4;
+    pub const WORKDIR: u64 = 5;
+    pub const SERVICE: u64 = 6;
+    pub const COMMAND: u64 = 7;
+}
+
+pub fn docker_schema() -> Schema {
+    Schema {
+        kinds: vec![
+            Kind {
+                kind_id: DOCKER_COMMAND,
+                name: "docker_command".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: docker_command::HOST,
+                        name: "host".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--host".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_command::CONTEXT,
+                        name: "context".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--context".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_command::LOG_LEVEL,
+                        name: "log_level".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--log-level".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_command::BUILD,
+                        name: "docker_build".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: DOCKER_BUILD,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("build".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_command::RUN,
+                        name: "docker_run".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: DOCKER_RUN,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("run".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_command::COMPOSE,
+                        name: "docker_compose".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: DOCKER_COMPOSE,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("compose".to_string())),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: DOCKER_BUILD,
+                name: "docker_build".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: docker_build::TAG,
+                        name: "tag".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--tag".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::FILE,
+                        name: "file".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--file".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::BUILD_ARG,
+                        name: "build_arg".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--build-arg".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::TARGET,
+                        name: "target".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--target".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::PLATFORM,
+                        name: "platform".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--platform".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::LABEL,
+                        name: "label".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--label".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::SECRET,
+                        name: "secret".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--secret".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::NETWORK,
+                        name: "network".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--network".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::PROGRESS,
+                        name: "progress".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--progress".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::NO_CACHE,
+                        name: "no_cache".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--no-cache".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::PULL,
+                        name: "pull".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--pull".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::QUIET,
+                        name: "quiet".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--quiet".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_build::CONTEXT,
+                        name: "context".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: DOCKER_RUN,
+                name: "docker_run".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: docker_run::DETACH,
+                        name: "detach".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--detach".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::INTERACTIVE,
+                        name: "interactive".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--interactive".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::TTY,
+                        name: "tty".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--tty".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::RM,
+                        name: "rm".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--rm".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::NAME,
+                        name: "name".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--name".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::ENV,
+                        name: "env".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--env".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::ENV_FILE,
+                        name: "env_file".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--env-file".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::PUBLISH,
+                        name: "publish".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--publish".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::VOLUME,
+                        name: "volume".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--volume".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::MOUNT,
+                        name: "mount".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--mount".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::NETWORK,
+                        name: "network".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--network".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::WORKDIR,
+                        name: "workdir".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--workdir".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::USER,
+                        name: "user".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--user".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::ENTRYPOINT,
+                        name: "entrypoint".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--entrypoint".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::RESTART,
+                        name: "restart".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--restart".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::PLATFORM,
+                        name: "platform".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--platform".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::LABEL,
+                        name: "label".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--label".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::CPUS,
+                        name: "cpus".to_string(),
+                        type_: FieldType::Float,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--cpus".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::MEMORY,
+                        name: "memory".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--memory".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::PRIVILEGED,
+                        name: "privileged".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--privileged".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::INIT,
+                        name: "init".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--init".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::PULL,
+                        name: "pull".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--pull".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::IMAGE,
+                        name: "image".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_run::COMMAND,
+                        name: "command".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Rest),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: DOCKER_COMPOSE,
+                name: "docker_compose".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: docker_compose::FILE,
+                        name: "file".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--file".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_compose::PROJECT_NAME,
+                        name: "project_name".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--project-name".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_compose::PROFILE,
+                        name: "profile".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--profile".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_compose::ENV_FILE,
+                        name: "env_file".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--env-file".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_compose::UP,
+                        name: "compose_up".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: COMPOSE_UP,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("up".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_compose::DOWN,
+                        name: "compose_down".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: COMPOSE_DOWN,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("down".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_compose::LOGS,
+                        name: "compose_logs".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: COMPOSE_LOGS,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("logs".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: docker_compose::EXEC,
+                        name: "compose_exec".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: COMPOSE_EXEC,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("exec".to_string())),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: COMPOSE_UP,
+                name: "compose_up".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: compose_up::DETACH,
+                        name: "detach".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--detach".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_up::BUILD,
+                        name: "build".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--build".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_up::FORCE_RECREATE,
+                        name: "force_recreate".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--force-recreate".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_up::REMOVE_ORPHANS,
+                        name: "remove_orphans".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--remove-orphans".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_up::NO_DEPS,
+                        name: "no_deps".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--no-deps".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_up::WAIT,
+                        name: "wait".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--wait".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_up::SCALE,
+                        name: "scale".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--scale".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_up::SERVICE,
+                        name: "service".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: COMPOSE_DOWN,
+                name: "compose_down".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: compose_down::VOLUMES,
+                        name: "volumes".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--volumes".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_down::REMOVE_ORPHANS,
+                        name: "remove_orphans".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--remove-orphans".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_down::RMI,
+                        name: "rmi".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--rmi".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_down::TIMEOUT,
+                        name: "timeout".to_string(),
+                        type_: FieldType::Int,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--timeout".to_string())),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: COMPOSE_LOGS,
+                name: "compose_logs".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: compose_logs::FOLLOW,
+                        name: "follow".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--follow".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_logs::TIMESTAMPS,
+                        name: "timestamps".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--timestamps".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_logs::TAIL,
+                        name: "tail".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--tail".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_logs::SINCE,
+                        name: "since".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--since".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_logs::SERVICE,
+                        name: "service".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: COMPOSE_EXEC,
+                name: "compose_exec".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: compose_exec::DETACH,
+                        name: "detach".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--detach".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_exec::NO_TTY,
+                        name: "no_tty".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--no-TTY".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_exec::ENV,
+                        name: "env".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--env".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_exec::USER,
+                        name: "user".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--user".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_exec::WORKDIR,
+                        name: "workdir".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--workdir".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_exec::SERVICE,
+                        name: "service".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: compose_exec::COMMAND,
+                        name: "command".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Rest),
+                        ..Default::default()
+                    },
+                ],
+            },
+        ],
+    }
+}
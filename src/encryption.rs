@@ -0,0 +1,132 @@
+use crate::types::{serialize_node, Link, LinkTarget, LinkType, NodeStore};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+use std::fmt;
+
+// End-to-end encrypted subtrees.
+//
+// An encrypted link points to a blob holding the ciphertext of its target, so that it is addressed
+// (and checked by `fsck`) by the hash of the ciphertext, and a remote server only ever stores the
+// ciphertext. Keys are held by the client, and added to the `NodeStore`, which does not serialize
+// them: links to blobs that one of them decrypts are followed by `Link::get` as if they were not
+// encrypted, and the others are shown as locked.
+//
+// A blob is `MAGIC`, a random nonce, and the XChaCha20-Poly1305 encryption of the link type of the
+// target (raw or dag) followed by its content. Encrypting a subtree encrypts each of its nodes and
+// leaves, children first, so that the plaintext of the children is not stored in their parent; the
+// blobs that were encrypted are then kept out of those shared with servers.
+
+const MAGIC: &[u8] = b"linc-encrypted-1\n";
+const NONCE_LEN: usize = 24;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key([u8; 32]);
+
+// Keys are never logged.
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key({})", self.fingerprint())
+    }
+}
+
+impl Key {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Key(bytes)
+    }
+
+    pub fn generate() -> Result<Self, String> {
+        let mut bytes = [0; 32];
+        getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+        Ok(Key(bytes))
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    pub fn from_hex(text: &str) -> Result<Self, String> {
+        let bytes = hex::decode(text.trim()).map_err(|e| e.to_string())?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| "key is not 32 bytes long".to_string())?;
+        Ok(Key(bytes))
+    }
+
+    // Short identifier of the key, which does not reveal it.
+    pub fn fingerprint(&self) -> String {
+        let digest = crate::types::digest(&self.0);
+        digest["sha256:".len()..][..16].to_string()
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new_from_slice(&self.0).unwrap()
+    }
+}
+
+// Encrypts the target of a link.
+pub fn seal(key: &Key, type_: &LinkType, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let type_ = match type_ {
+        LinkType::Raw => 0,
+        LinkType::Dag => 1,
+        LinkType::Encrypted => return Err("target is already encrypted".to_string()),
+    };
+    let mut nonce = [0; NONCE_LEN];
+    getrandom::getrandom(&mut nonce).map_err(|e| e.to_string())?;
+    let mut message = vec![type_];
+    message.extend_from_slice(plaintext);
+    let ciphertext = key
+        .cipher()
+        .encrypt(&XNonce::from(nonce), message.as_slice())
+        .map_err(|e| e.to_string())?;
+    Ok([MAGIC, &nonce, &ciphertext].concat())
+}
+
+// Decrypts a blob, returning the link type and content of its target, unless the blob was not
+// encrypted with the given key.
+pub fn open(key: &Key, blob: &[u8]) -> Option<(LinkType, Vec<u8>)> {
+    let blob = blob.strip_prefix(MAGIC)?;
+    if blob.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+    let nonce: [u8; NONCE_LEN] = nonce.try_into().ok()?;
+    let message = key
+        .cipher()
+        .decrypt(&XNonce::from(nonce), ciphertext)
+        .ok()?;
+    let (type_, plaintext) = message.split_first()?;
+    let type_ = match type_ {
+        0 => LinkType::Raw,
+        1 => LinkType::Dag,
+        _ => return None,
+    };
+    Some((type_, plaintext.to_vec()))
+}
+
+// Encrypts the subtree at the given link with the given key, which is added to the store so that
+// the subtree stays readable, and returns an encrypted link to it. Encrypted links in the subtree
+// are kept as they are.
+pub fn encrypt(node_store: &mut NodeStore, key: &Key, link: &Link) -> Result<Link, String> {
+    node_store.add_key(key.clone());
+    let (type_, plaintext) = match link.get(node_store) {
+        _ if link.type_ == LinkType::Encrypted => return Ok(link.clone()),
+        Some(LinkTarget::Raw(raw)) => (LinkType::Raw, raw.clone()),
+        Some(LinkTarget::Parsed(mut node)) => {
+            for links in node.links.values_mut() {
+                for child in links.iter_mut() {
+                    *child = encrypt(node_store, key, child)?;
+                }
+            }
+            (LinkType::Dag, serialize_node(&node))
+        }
+        None => return Err(format!("missing blob {}", link.digest)),
+    };
+    let blob = seal(key, &type_, &plaintext)?;
+    node_store.mark_secret(&link.digest);
+    Ok(Link {
+        type_: LinkType::Encrypted,
+        digest: node_store.put_raw(&blob),
+    })
+}
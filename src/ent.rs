@@ -36,6 +36,8 @@ pub struct GetResponse {
 
 pub const API_URL_LOCALHOST: &str = "http://127.0.0.1:27333";
 pub const API_URL_REMOTE: &str = "https://multiverse-312721.nw.r.appspot.com";
+// WebSocket endpoint of `linc_sync`, when run locally (see `collab.rs`).
+pub const COLLAB_URL_LOCALHOST: &str = "ws://127.0.0.1:27334/api/v1/collab";
 pub struct EntClient {
     pub api_url: String,
 }
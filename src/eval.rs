@@ -0,0 +1,471 @@
+use crate::{
+    ast::{self, expression, external_ref, function_application, function_definition, if_, list},
+    types::{Link, Node, NodeStore, Path, Selector},
+};
+use std::{collections::HashMap, fmt};
+
+// Interpreter for the expression language of `proto/ast.proto`.
+//
+// `Ref`s refer to either the arguments of the function being evaluated, or to the function
+// definitions of the enclosing `File`, by their `ref` number. Functions are called by
+// `FunctionApplication`, and are only bound to their arguments, i.e. there are no closures.
+//
+// Builtin functions are declared as function definitions whose body is an `ExternalRef` with path
+// `builtin`: evaluating the body calls the builtin with the arguments of the function, so that the
+// definition also documents the arguments and return type of the builtin.
+
+// Maximum depth of nested function calls, to catch infinite recursion.
+const MAX_DEPTH: usize = 256;
+
+pub const BUILTIN_PATH: &str = "builtin";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<Value>),
+    Function { ref_: i64, name: String },
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Function { .. } => "function",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{:?}", v),
+            Value::String(v) => write!(f, "{:?}", v),
+            Value::List(values) => {
+                write!(f, "[")?;
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Value::Function { ref_, name } => write!(f, "<function {} #{}>", name, ref_),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    // Path of the node that failed to evaluate.
+    pub path: Path,
+    pub message: String,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self
+            .path
+            .iter()
+            .map(|s| format!("{}[{}]", s.field_id, s.index))
+            .collect();
+        write!(f, "/{}: {}", path.join("/"), self.message)
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+fn error<T>(path: &[Selector], message: String) -> Result<T, EvalError> {
+    Err(EvalError {
+        path: path.to_vec(),
+        message,
+    })
+}
+
+// Bindings of the function being evaluated.
+#[derive(Default)]
+pub struct Env {
+    // Argument values, by `ref`.
+    vars: HashMap<i64, Value>,
+    // Argument values, in order, passed to builtins.
+    args: Vec<Value>,
+}
+
+struct Function {
+    path: Path,
+    node: Node,
+    name: String,
+}
+
+pub struct Evaluator<'a> {
+    node_store: &'a NodeStore,
+    functions: HashMap<i64, Function>,
+    depth: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    // Creates an evaluator for expressions in the given `File`, if any, at the given path.
+    pub fn new(node_store: &'a NodeStore, file: Option<(&[Selector], &Link)>) -> Self {
+        let mut functions = HashMap::new();
+        if let Some((path, link)) = file {
+            if let Some(file) = ast::get_node(node_store, link) {
+                for (path, link) in ast::get_children(&file, path, ast::file::FUNCTION_DEFINITIONS)
+                {
+                    if let Some(node) = ast::get_node(node_store, &link) {
+                        if let Some(ref_) =
+                            ast::get_int(node_store, &node, function_definition::REF)
+                        {
+                            let name =
+                                ast::get_label(node_store, &node, function_definition::LABEL);
+                            functions.insert(ref_, Function { path, node, name });
+                        }
+                    }
+                }
+            }
+        }
+        Evaluator {
+            node_store,
+            functions,
+            depth: 0,
+        }
+    }
+
+    fn get_node(&self, path: &[Selector], link: &Link) -> Result<Node, EvalError> {
+        match ast::get_node(self.node_store, link) {
+            Some(node) => Ok(node),
+            None => error(path, format!("missing node {}", link.digest)),
+        }
+    }
+
+    // Evaluates the `Expression` at the given link and path.
+    pub fn eval(&mut self, env: &Env, path: &[Selector], link: &Link) -> Result<Value, EvalError> {
+        let node = self.get_node(path, link)?;
+        let (field_id, link) = match ast::get_oneof(&node, expression::REF) {
+            Ok(v) => v,
+            Err(e) => return error(path, format!("{} expression", e)),
+        };
+        let path = &crate::types::append(
+            path,
+            Selector {
+                field_id,
+                index: 0,
+                id: None,
+            },
+        );
+        let node_store = self.node_store;
+        let literal = |node: &Node| -> Result<Value, EvalError> {
+            let value = match field_id {
+                expression::BOOL_VALUE => {
+                    ast::get_bool(node_store, node, ast::literal::VALUE).map(Value::Bool)
+                }
+                expression::INT_VALUE => {
+                    ast::get_int(node_store, node, ast::literal::VALUE).map(Value::Int)
+                }
+                expression::FLOAT_VALUE => {
+                    ast::get_float(node_store, node, ast::literal::VALUE).map(Value::Float)
+                }
+                _ => ast::get_string(node_store, node, ast::literal::VALUE).map(Value::String),
+            };
+            match value {
+                Some(value) => Ok(value),
+                None => error(path, "invalid literal".to_string()),
+            }
+        };
+        match field_id {
+            expression::EMPTY_VALUE => error(path, "empty expression".to_string()),
+            expression::HOLE_VALUE => error(path, "hole".to_string()),
+            expression::BOOL_VALUE
+            | expression::INT_VALUE
+            | expression::FLOAT_VALUE
+            | expression::STRING_VALUE => literal(&self.get_node(path, link)?),
+            expression::LIST_VALUE => {
+                let node = self.get_node(path, link)?;
+                ast::get_children(&node, path, list::VALUES)
+                    .iter()
+                    .map(|(path, link)| self.eval(env, path, link))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Value::List)
+            }
+            expression::IF_VALUE => {
+                let node = self.get_node(path, link)?;
+                let (cond_path, cond) = match ast::get_child(&node, path, if_::COND) {
+                    Some(v) => v,
+                    None => return error(path, "missing condition".to_string()),
+                };
+                let branch = match self.eval(env, &cond_path, cond)? {
+                    Value::Bool(true) => if_::TRUE,
+                    Value::Bool(false) => if_::FALSE,
+                    v => {
+                        return error(
+                            &cond_path,
+                            format!("condition must be a bool, found {}", v.type_name()),
+                        )
+                    }
+                };
+                match ast::get_child(&node, path, branch) {
+                    Some((path, link)) => self.eval(env, &path, link),
+                    None => error(path, "missing branch".to_string()),
+                }
+            }
+            expression::FUNCTION_APPLICATION_VALUE => {
+                let node = self.get_node(path, link)?;
+                let ref_ = match ast::get_int(node_store, &node, function_application::FUNCTION) {
+                    Some(ref_) => ref_,
+                    None => return error(path, "invalid function ref".to_string()),
+                };
+                let args = ast::get_children(&node, path, function_application::ARGUMENTS)
+                    .iter()
+                    .map(|(path, link)| self.eval(env, path, link))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(env, path, ref_, args)
+            }
+            expression::REF_VALUE => {
+                let node = self.get_node(path, link)?;
+                let ref_ = match ast::get_int(node_store, &node, ast::ref_::REF) {
+                    Some(ref_) => ref_,
+                    None => return error(path, "invalid ref".to_string()),
+                };
+                if let Some(value) = env.vars.get(&ref_) {
+                    Ok(value.clone())
+                } else if let Some(function) = self.functions.get(&ref_) {
+                    Ok(Value::Function {
+                        ref_,
+                        name: function.name.clone(),
+                    })
+                } else {
+                    error(path, format!("unbound ref #{}", ref_))
+                }
+            }
+            expression::EXTERNAL_REF_VALUE => {
+                let node = self.get_node(path, link)?;
+                let external_path =
+                    ast::get_string(node_store, &node, external_ref::PATH).unwrap_or_default();
+                let name =
+                    ast::get_string(node_store, &node, external_ref::NAME).unwrap_or_default();
+                if external_path != BUILTIN_PATH {
+                    return error(
+                        path,
+                        format!("unknown external ref `{}.{}`", external_path, name),
+                    );
+                }
+                builtin(&name, &env.args).or_else(|e| error(path, e))
+            }
+            field_id => error(path, format!("unknown expression field {}", field_id)),
+        }
+    }
+
+    // Calls the function with the given ref, which is either bound to a function value in the
+    // current environment, or is the ref of a function definition.
+    fn call(
+        &mut self,
+        env: &Env,
+        path: &[Selector],
+        ref_: i64,
+        args: Vec<Value>,
+    ) -> Result<Value, EvalError> {
+        let ref_ = match env.vars.get(&ref_) {
+            Some(Value::Function { ref_, .. }) => *ref_,
+            Some(v) => {
+                return error(
+                    path,
+                    format!("ref #{} is not a function, found {}", ref_, v.type_name()),
+                )
+            }
+            None => ref_,
+        };
+        let function = match self.functions.get(&ref_) {
+            Some(function) => function,
+            None => return error(path, format!("unbound function ref #{}", ref_)),
+        };
+        let params = ast::get_children(
+            &function.node,
+            &function.path,
+            function_definition::ARGUMENTS,
+        );
+        if params.len() != args.len() {
+            return error(
+                path,
+                format!(
+                    "function `{}` expects {} arguments, found {}",
+                    function.name,
+                    params.len(),
+                    args.len()
+                ),
+            );
+        }
+        let (body_path, body) =
+            match ast::get_child(&function.node, &function.path, function_definition::BODY) {
+                Some((path, link)) => (path, link.clone()),
+                None => {
+                    return error(
+                        &function.path,
+                        format!("function `{}` has no body", function.name),
+                    )
+                }
+            };
+        let mut vars = HashMap::new();
+        for ((param_path, param), arg) in params.iter().zip(&args) {
+            let param = self.get_node(param_path, param)?;
+            match ast::get_int(self.node_store, &param, ast::argument::REF) {
+                Some(ref_) => vars.insert(ref_, arg.clone()),
+                None => return error(param_path, "invalid argument ref".to_string()),
+            };
+        }
+        if self.depth >= MAX_DEPTH {
+            return error(path, "maximum call depth exceeded".to_string());
+        }
+        self.depth += 1;
+        let result = self.eval(&Env { vars, args }, &body_path, &body);
+        self.depth -= 1;
+        result
+    }
+}
+
+// Evaluates the `Expression` at the given link and path, in the given `File`.
+pub fn eval(
+    node_store: &NodeStore,
+    file: Option<(&[Selector], &Link)>,
+    path: &[Selector],
+    link: &Link,
+) -> Result<Value, EvalError> {
+    Evaluator::new(node_store, file).eval(&Env::default(), path, link)
+}
+
+fn expect_args(name: &str, args: &[Value], n: usize) -> Result<(), String> {
+    if args.len() == n {
+        Ok(())
+    } else {
+        Err(format!(
+            "builtin `{}` expects {} arguments, found {}",
+            name,
+            n,
+            args.len()
+        ))
+    }
+}
+
+fn type_error(name: &str, args: &[Value]) -> Result<Value, String> {
+    let types: Vec<_> = args.iter().map(|v| v.type_name()).collect();
+    Err(format!(
+        "builtin `{}` does not accept ({})",
+        name,
+        types.join(", ")
+    ))
+}
+
+fn arithmetic(
+    name: &str,
+    args: &[Value],
+    int: fn(i64, i64) -> Option<i64>,
+    float: fn(f64, f64) -> f64,
+) -> Result<Value, String> {
+    expect_args(name, args, 2)?;
+    match (&args[0], &args[1]) {
+        (Value::Int(a), Value::Int(b)) => match int(*a, *b) {
+            Some(v) => Ok(Value::Int(v)),
+            None if *b == 0 && (name == "div" || name == "mod") => {
+                Err("division by zero".to_string())
+            }
+            None => Err("integer overflow".to_string()),
+        },
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(float(*a, *b))),
+        _ => type_error(name, args),
+    }
+}
+
+fn compare(name: &str, args: &[Value]) -> Result<std::cmp::Ordering, String> {
+    expect_args(name, args, 2)?;
+    let ordering = match (&args[0], &args[1]) {
+        (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+        _ => None,
+    };
+    ordering.ok_or_else(|| {
+        let types: Vec<_> = args.iter().map(|v| v.type_name()).collect();
+        format!("builtin `{}` does not accept ({})", name, types.join(", "))
+    })
+}
+
+fn builtin(name: &str, args: &[Value]) -> Result<Value, String> {
+    match name {
+        "add" => arithmetic(name, args, i64::checked_add, |a, b| a + b),
+        "sub" => arithmetic(name, args, i64::checked_sub, |a, b| a - b),
+        "mul" => arithmetic(name, args, i64::checked_mul, |a, b| a * b),
+        "div" => arithmetic(name, args, i64::checked_div, |a, b| a / b),
+        "mod" => arithmetic(name, args, i64::checked_rem, |a, b| a % b),
+        "eq" => {
+            expect_args(name, args, 2)?;
+            Ok(Value::Bool(args[0] == args[1]))
+        }
+        "lt" => compare(name, args).map(|o| Value::Bool(o.is_lt())),
+        "le" => compare(name, args).map(|o| Value::Bool(o.is_le())),
+        "not" => {
+            expect_args(name, args, 1)?;
+            match &args[0] {
+                Value::Bool(v) => Ok(Value::Bool(!v)),
+                _ => type_error(name, args),
+            }
+        }
+        "and" | "or" => {
+            expect_args(name, args, 2)?;
+            match (&args[0], &args[1]) {
+                (Value::Bool(a), Value::Bool(b)) if name == "and" => Ok(Value::Bool(*a && *b)),
+                (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(*a || *b)),
+                _ => type_error(name, args),
+            }
+        }
+        "concat" => {
+            expect_args(name, args, 2)?;
+            match (&args[0], &args[1]) {
+                (Value::String(a), Value::String(b)) => Ok(Value::String(a.clone() + b)),
+                (Value::List(a), Value::List(b)) => {
+                    Ok(Value::List(a.iter().chain(b).cloned().collect()))
+                }
+                _ => type_error(name, args),
+            }
+        }
+        "len" => {
+            expect_args(name, args, 1)?;
+            match &args[0] {
+                Value::String(v) => Ok(Value::Int(v.chars().count() as i64)),
+                Value::List(v) => Ok(Value::Int(v.len() as i64)),
+                _ => type_error(name, args),
+            }
+        }
+        "get" => {
+            expect_args(name, args, 2)?;
+            match (&args[0], &args[1]) {
+                (Value::List(values), Value::Int(i)) => usize::try_from(*i)
+                    .ok()
+                    .and_then(|i| values.get(i))
+                    .cloned()
+                    .ok_or_else(|| {
+                        format!(
+                            "index {} out of range for list of length {}",
+                            i,
+                            values.len()
+                        )
+                    }),
+                _ => type_error(name, args),
+            }
+        }
+        "to_string" => {
+            expect_args(name, args, 1)?;
+            match &args[0] {
+                Value::String(v) => Ok(Value::String(v.clone())),
+                v => Ok(Value::String(v.to_string())),
+            }
+        }
+        _ => Err(format!("unknown builtin `{}`", name)),
+    }
+}
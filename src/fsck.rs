@@ -0,0 +1,182 @@
+use crate::types::{deserialize_node, digest, Digest, Link, LinkType, NodeStore, Selector};
+use std::fmt;
+
+// Integrity checks over the blobs in a NodeStore, along the lines of `git fsck`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    // The blob stored under `digest` actually hashes to `actual`.
+    DigestMismatch {
+        digest: Digest,
+        actual: Digest,
+    },
+    // A link points to a blob that is not in the store.
+    Dangling {
+        parent: Digest,
+        selector: Selector,
+        link: Link,
+    },
+    // A dag link points to a blob that cannot be parsed as a Node.
+    UnparsableNode {
+        parent: Digest,
+        selector: Selector,
+        link: Link,
+    },
+    // A raw link points to a blob that parses as a Node, i.e. it should have been a dag link.
+    LinkTypeMismatch {
+        parent: Digest,
+        selector: Selector,
+        link: Link,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DigestMismatch { digest, actual } => {
+                write!(f, "digest mismatch: {} hashes to {}", digest, actual)
+            }
+            Problem::Dangling {
+                parent,
+                selector,
+                link,
+            } => write!(
+                f,
+                "dangling link: {} {}[{}] -> {}",
+                parent, selector.field_id, selector.index, link.digest
+            ),
+            Problem::UnparsableNode {
+                parent,
+                selector,
+                link,
+            } => write!(
+                f,
+                "unparsable node: {} {}[{}] -> {}",
+                parent, selector.field_id, selector.index, link.digest
+            ),
+            Problem::LinkTypeMismatch {
+                parent,
+                selector,
+                link,
+            } => write!(
+                f,
+                "link type mismatch: {} {}[{}] -> {} is {:?} but the target is a node",
+                parent, selector.field_id, selector.index, link.digest, link.type_
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FsckReport {
+    // Number of blobs that were rehashed.
+    pub checked: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    // Digests that need to be (re-)fetched from a remote in order to repair the store: targets of
+    // dangling links, and blobs whose content does not match their digest.
+    pub fn missing(&self) -> Vec<Digest> {
+        let mut digests: Vec<Digest> = self
+            .problems
+            .iter()
+            .filter_map(|p| match p {
+                Problem::DigestMismatch { digest, .. } => Some(digest.clone()),
+                Problem::Dangling { link, .. } => Some(link.digest.clone()),
+                _ => None,
+            })
+            .collect();
+        digests.sort();
+        digests.dedup();
+        digests
+    }
+
+    // Digests of blobs whose content is known to be wrong, and that should be dropped before
+    // fetching them again.
+    pub fn corrupted(&self) -> Vec<Digest> {
+        self.problems
+            .iter()
+            .filter_map(|p| match p {
+                Problem::DigestMismatch { digest, .. } => Some(digest.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// Rehashes every blob in the store, parses every node, and checks every link of every node.
+pub fn fsck(node_store: &NodeStore) -> FsckReport {
+    let mut report = FsckReport::default();
+    let mut entries: Vec<_> = node_store.iter().collect();
+    // Deterministic output, regardless of the HashMap iteration order.
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    for (d, raw) in entries {
+        report.checked += 1;
+        let actual = digest(raw);
+        if &actual != d {
+            report.problems.push(Problem::DigestMismatch {
+                digest: d.clone(),
+                actual,
+            });
+            // Do not follow the links of a corrupted blob.
+            continue;
+        }
+        let node = match deserialize_node(raw) {
+            Some(node) => node,
+            // Raw leaf.
+            None => continue,
+        };
+        for (field_id, links) in &node.links {
+            for (index, link) in links.iter().enumerate() {
+                let selector = Selector {
+                    field_id: *field_id,
+                    index,
+                    id: None,
+                };
+                if let Some(problem) = check_link(node_store, d, selector, link) {
+                    report.problems.push(problem);
+                }
+            }
+        }
+    }
+    report
+}
+
+fn check_link(
+    node_store: &NodeStore,
+    parent: &str,
+    selector: Selector,
+    link: &Link,
+) -> Option<Problem> {
+    let parent = parent.to_string();
+    let link = link.clone();
+    let raw = match node_store.get_raw(&link.digest) {
+        Some(raw) => raw,
+        None => {
+            return Some(Problem::Dangling {
+                parent,
+                selector,
+                link,
+            })
+        }
+    };
+    let parsed = deserialize_node(raw).is_some();
+    match (&link.type_, parsed) {
+        (LinkType::Dag, false) => Some(Problem::UnparsableNode {
+            parent,
+            selector,
+            link,
+        }),
+        (LinkType::Raw, true) => Some(Problem::LinkTypeMismatch {
+            parent,
+            selector,
+            link,
+        }),
+        _ => None,
+    }
+}
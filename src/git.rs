@@ -0,0 +1,792 @@
+use crate::schema::{Arg, Field, FieldType, Kind, Schema};
+
+// Schemas of the common git subcommands, passed on a command line by `argv.rs`.
+//
+// See https://git-scm.com/docs. Flags are given in their long form where git has one; short
+// aliases (e.g. `-m` for `--message`) are not accepted when parsing. Flags whose value is optional
+// in git (e.g. `--force-with-lease[=<ref>]`) are switches. The ids of `git_command`, `git_add` and
+// `git_push` predate this module, and are kept so that existing trees remain valid.
+
+pub const GIT_COMMAND: u64 = 23427;
+pub const GIT_ADD: u64 = 231849732984;
+pub const GIT_COMMIT: u64 = 8130001;
+pub const GIT_PUSH: u64 = 349872;
+pub const GIT_CHECKOUT: u64 = 8130002;
+pub const GIT_REBASE: u64 = 8130003;
+pub const GIT_LOG: u64 = 8130004;
+
+// Fields of `git_command`.
+pub mod git_command {
+    pub const CONFIG: u64 = 1;
+    pub const DIRECTORY: u64 = 2;
+    pub const NO_PAGER: u64 = 3;
+    pub const ADD: u64 = 131987;
+    pub const COMMIT: u64 = 4;
+    pub const PUSH: u64 = 2429447;
+    pub const CHECKOUT: u64 = 5;
+    pub const REBASE: u64 = 6;
+    pub const LOG: u64 = 7;
+}
+
+// Fields of `git_add`.
+pub mod git_add {
+    pub const ALL: u64 = 1;
+    pub const UPDATE: u64 = 2;
+    pub const PATCH: u64 = 3;
+    pub const FORCE: u64 = 4;
+    pub const DRY_RUN: u64 = 5;
+    pub const VERBOSE: u64 = 6;
+    pub const INTENT_TO_ADD: u64 = 7;
+    pub const PATHSPEC: u64 = 8;
+}
+
+// Fields of `git_commit`.
+pub mod git_commit {
+    pub const ALL: u64 = 1;
+    pub const AMEND: u64 = 2;
+    pub const NO_EDIT: u64 = 3;
+    pub const MESSAGE: u64 = 4;
+    pub const AUTHOR: u64 = 5;
+    pub const DATE: u64 = 6;
+    pub const FIXUP: u64 = 7;
+    pub const SIGNOFF: u64 = 8;
+    pub const ALLOW_EMPTY: u64 = 9;
+    pub const NO_VERIFY: u64 = 10;
+    pub const DRY_RUN: u64 = 11;
+    pub const PATHSPEC: u64 = 12;
+}
+
+// Fields of `git_push`.
+pub mod git_push {
+    pub const ALL: u64 = 1;
+    pub const TAGS: u64 = 2;
+    pub const FORCE: u64 = 3;
+    pub const FORCE_WITH_LEASE: u64 = 4;
+    pub const SET_UPSTREAM: u64 = 5;
+    pub const DELETE: u64 = 6;
+    pub const DRY_RUN: u64 = 7;
+    pub const NO_VERIFY: u64 = 8;
+    pub const PUSH_OPTION: u64 = 9;
+    pub const REPOSITORY: u64 = 10;
+    pub const REFSPEC: u64 = 11;
+}
+
+// Fields of `git_checkout`.
+pub mod git_checkout {
+    pub const NEW_BRANCH: u64 = 1;
+    pub const RESET_BRANCH: u64 = 2;
+    pub const ORPHAN: u64 = 3;
+    pub const TRACK: u64 = 4;
+    pub const DETACH: u64 = 5;
+    pub const FORCE: u64 = 6;
+    pub const QUIET: u64 = 7;
+    pub const BRANCH: u64 = 8;
+    pub const PATHSPEC: u64 = 9;
+}
+
+// Fields of `git_rebase`.
+pub mod git_rebase {
+    pub const INTERACTIVE: u64 = 1;
+    pub const ONTO: u64 = 2;
+    pub const ROOT: u64 = 3;
+    pub const AUTOSQUASH: u64 = 4;
+    pub const AUTOSTASH: u64 = 5;
+    pub const REBASE_MERGES: u64 = 6;
+    pub const EXEC: u64 = 7;
+    pub const CONTINUE: u64 = 8;
+    pub const ABORT: u64 = 9;
+    pub const SKIP: u64 = 10;
+    pub const UPSTREAM: u64 = 11;
+    pub const BRANCH: u64 = 12;
+}
+
+// Fields of `git_log`.
+pub mod git_log {
+    pub const ONELINE: u64 = 1;
+    pub const GRAPH: u64 = 2;
+    pub const ALL: u64 = 3;
+    pub const DECORATE: u64 = 4;
+    pub const STAT: u64 = 5;
+    pub const PATCH: u64 = 6;
+    pub const REVERSE: u64 = 7;
+    pub const FOLLOW: u64 = 8;
+    pub const MAX_COUNT: u64 = 9;
+    pub const AUTHOR: u64 = 10;
+    pub const SINCE: u64 = 11;
+    pub const UNTIL: u64 = 12;
+    pub const GREP: u64 = 13;
+    pub const FORMAT: u64 = 14;
+    pub const REVISION_RANGE: u64 = 15;
+    pub const PATH: u64 = 16;
+}
+
+pub fn git_schema() -> Schema {
+    Schema {
+        kinds: vec![
+            Kind {
+                kind_id: GIT_COMMAND,
+                name: "git_command".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: git_command::CONFIG,
+                        name: "config".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("-c".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_command::DIRECTORY,
+                        name: "directory".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("-C".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_command::NO_PAGER,
+                        name: "no_pager".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--no-pager".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_command::ADD,
+                        name: "git_add".to_string(),
+                        type_: FieldType::Object { kind_id: GIT_ADD },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("add".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_command::COMMIT,
+                        name: "git_commit".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: GIT_COMMIT,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("commit".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_command::PUSH,
+                        name: "git_push".to_string(),
+                        type_: FieldType::Object { kind_id: GIT_PUSH },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("push".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_command::CHECKOUT,
+                        name: "git_checkout".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: GIT_CHECKOUT,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("checkout".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_command::REBASE,
+                        name: "git_rebase".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: GIT_REBASE,
+                        },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("rebase".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_command::LOG,
+                        name: "git_log".to_string(),
+                        type_: FieldType::Object { kind_id: GIT_LOG },
+                        repeated: false,
+                        arg: Some(Arg::Subcommand("log".to_string())),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: GIT_ADD,
+                name: "git_add".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: git_add::ALL,
+                        name: "all".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--all".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_add::UPDATE,
+                        name: "update".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--update".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_add::PATCH,
+                        name: "patch".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--patch".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_add::FORCE,
+                        name: "force".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--force".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_add::DRY_RUN,
+                        name: "dry_run".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--dry-run".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_add::VERBOSE,
+                        name: "verbose".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--verbose".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_add::INTENT_TO_ADD,
+                        name: "intent_to_add".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--intent-to-add".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_add::PATHSPEC,
+                        name: "pathspec".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: GIT_COMMIT,
+                name: "git_commit".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: git_commit::ALL,
+                        name: "all".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--all".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::AMEND,
+                        name: "amend".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--amend".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::NO_EDIT,
+                        name: "no_edit".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--no-edit".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::MESSAGE,
+                        name: "message".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--message".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::AUTHOR,
+                        name: "author".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--author".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::DATE,
+                        name: "date".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--date".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::FIXUP,
+                        name: "fixup".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--fixup".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::SIGNOFF,
+                        name: "signoff".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--signoff".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::ALLOW_EMPTY,
+                        name: "allow_empty".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--allow-empty".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::NO_VERIFY,
+                        name: "no_verify".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--no-verify".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::DRY_RUN,
+                        name: "dry_run".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--dry-run".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_commit::PATHSPEC,
+                        name: "pathspec".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: GIT_PUSH,
+                name: "git_push".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: git_push::ALL,
+                        name: "all".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--all".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::TAGS,
+                        name: "tags".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--tags".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::FORCE,
+                        name: "force".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--force".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::FORCE_WITH_LEASE,
+                        name: "force_with_lease".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--force-with-lease".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::SET_UPSTREAM,
+                        name: "set_upstream".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--set-upstream".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::DELETE,
+                        name: "delete".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--delete".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::DRY_RUN,
+                        name: "dry_run".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--dry-run".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::NO_VERIFY,
+                        name: "no_verify".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--no-verify".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::PUSH_OPTION,
+                        name: "push_option".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--push-option".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::REPOSITORY,
+                        name: "repository".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_push::REFSPEC,
+                        name: "refspec".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: GIT_CHECKOUT,
+                name: "git_checkout".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: git_checkout::NEW_BRANCH,
+                        name: "new_branch".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("-b".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_checkout::RESET_BRANCH,
+                        name: "reset_branch".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("-B".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_checkout::ORPHAN,
+                        name: "orphan".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--orphan".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_checkout::TRACK,
+                        name: "track".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--track".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_checkout::DETACH,
+                        name: "detach".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--detach".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_checkout::FORCE,
+                        name: "force".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--force".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_checkout::QUIET,
+                        name: "quiet".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--quiet".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_checkout::BRANCH,
+                        name: "branch".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_checkout::PATHSPEC,
+                        name: "pathspec".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Trailing),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: GIT_REBASE,
+                name: "git_rebase".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: git_rebase::INTERACTIVE,
+                        name: "interactive".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--interactive".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::ONTO,
+                        name: "onto".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--onto".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::ROOT,
+                        name: "root".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--root".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::AUTOSQUASH,
+                        name: "autosquash".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--autosquash".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::AUTOSTASH,
+                        name: "autostash".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--autostash".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::REBASE_MERGES,
+                        name: "rebase_merges".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--rebase-merges".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::EXEC,
+                        name: "exec".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--exec".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::CONTINUE,
+                        name: "continue".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--continue".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::ABORT,
+                        name: "abort".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--abort".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::SKIP,
+                        name: "skip".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--skip".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::UPSTREAM,
+                        name: "upstream".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_rebase::BRANCH,
+                        name: "branch".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: GIT_LOG,
+                name: "git_log".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: git_log::ONELINE,
+                        name: "oneline".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--oneline".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::GRAPH,
+                        name: "graph".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--graph".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::ALL,
+                        name: "all".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--all".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::DECORATE,
+                        name: "decorate".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--decorate".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::STAT,
+                        name: "stat".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--stat".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::PATCH,
+                        name: "patch".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--patch".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::REVERSE,
+                        name: "reverse".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--reverse".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::FOLLOW,
+                        name: "follow".to_string(),
+                        type_: FieldType::Bool,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--follow".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::MAX_COUNT,
+                        name: "max_count".to_string(),
+                        type_: FieldType::Int,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--max-count".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::AUTHOR,
+                        name: "author".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--author".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::SINCE,
+                        name: "since".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--since".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::UNTIL,
+                        name: "until".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--until".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::GREP,
+                        name: "grep".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Flag("--grep".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::FORMAT,
+                        name: "format".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Flag("--format=".to_string())),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::REVISION_RANGE,
+                        name: "revision_range".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        arg: Some(Arg::Positional),
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: git_log::PATH,
+                        name: "path".to_string(),
+                        type_: FieldType::String,
+                        repeated: true,
+                        arg: Some(Arg::Trailing),
+                        ..Default::default()
+                    },
+                ],
+            },
+        ],
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{
+    schema::{Field, FieldType, Schema},
+    types::{append, Link, LinkTarget, LinkType, Node, NodeStore, Path, Selector},
+};
+
+// Holes are the slots of a tree that are still to be filled in. A link is a hole if its target is
+// missing, if it is an empty node of a kind that has message fields (kinds with only scalar fields
+// are complete, their fields having default values), or if it is a node of a kind with a `oneof`
+// none of whose fields are set, apart from an explicit hole marker (an empty message named `Hole`,
+// see `Expression.Hole` in `proto/ast.proto`).
+//
+// The expected kind of a hole comes from the field that links to it, so that completions only
+// offer values valid for the slot.
+
+pub fn is_hole(node_store: &NodeStore, schema: &Schema, kind_id: u64, link: &Link) -> bool {
+    let node = match link.get(node_store) {
+        // Encrypted subtrees whose key is missing are not holes.
+        None => return link.type_ != LinkType::Encrypted || !node_store.has_raw_node(&link.digest),
+        Some(LinkTarget::Raw(_)) => return false,
+        Some(LinkTarget::Parsed(node)) => node,
+    };
+    let kind = match schema.get_kind(kind_id) {
+        Some(kind) => kind,
+        None => return node.links.is_empty(),
+    };
+    if node.links.is_empty() {
+        return kind
+            .fields
+            .iter()
+            .any(|f| matches!(f.type_, FieldType::Object { .. }));
+    }
+    let variants: Vec<_> = kind.fields.iter().filter(|f| f.oneof.is_some()).collect();
+    !variants.is_empty()
+        && variants
+            .iter()
+            .all(|f| !node.links.contains_key(&f.field_id) || is_hole_marker(schema, f))
+}
+
+fn is_hole_marker(schema: &Schema, field: &Field) -> bool {
+    match field.type_ {
+        FieldType::Object { kind_id } => schema.get_kind(kind_id).is_some_and(|kind| {
+            kind.fields.is_empty() && kind.name.rsplit('.').next() == Some("Hole")
+        }),
+        _ => false,
+    }
+}
+
+// Returns the paths of all the holes under the given node, in the order in which they are
+// displayed. Holes are not descended into.
+pub fn find_holes(node_store: &NodeStore, schema: &Schema, kind_id: u64, link: &Link) -> Vec<Path> {
+    let mut holes = vec![];
+    find_holes_from(node_store, schema, kind_id, link, &[], &mut holes);
+    holes
+}
+
+fn find_holes_from(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+    path: &[Selector],
+    holes: &mut Vec<Path>,
+) {
+    if is_hole(node_store, schema, kind_id, link) {
+        holes.push(path.to_vec());
+        return;
+    }
+    let node = match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        _ => return,
+    };
+    let kind = match schema.get_kind(kind_id) {
+        Some(kind) => kind,
+        None => return,
+    };
+    for (field_id, links) in node.links.iter() {
+        let child_kind_id = match kind.get_field(*field_id).map(|f| &f.type_) {
+            Some(FieldType::Object { kind_id }) => *kind_id,
+            _ => continue,
+        };
+        for (index, child) in links.iter().enumerate() {
+            let child_path = append(
+                path,
+                Selector {
+                    field_id: *field_id,
+                    index,
+                    id: None,
+                },
+            );
+            find_holes_from(node_store, schema, child_kind_id, child, &child_path, holes);
+        }
+    }
+}
+
+// Returns the first hole after the given path, wrapping around to the first hole of the tree.
+pub fn next_hole(holes: &[Path], path: &[Selector]) -> Option<Path> {
+    holes
+        .iter()
+        .find(|hole| is_before(path, hole))
+        .or_else(|| holes.first())
+        .cloned()
+}
+
+// Returns the last hole before the given path, wrapping around to the last hole of the tree.
+pub fn prev_hole(holes: &[Path], path: &[Selector]) -> Option<Path> {
+    holes
+        .iter()
+        .rev()
+        .find(|hole| is_before(hole, path))
+        .or_else(|| holes.last())
+        .cloned()
+}
+
+// Whether `a` is displayed before `b`, i.e. whether it comes first in a pre-order traversal.
+fn is_before(a: &[Selector], b: &[Selector]) -> bool {
+    let key = |path: &[Selector]| {
+        path.iter()
+            .map(|s| (s.field_id, s.index))
+            .collect::<Vec<_>>()
+    };
+    key(a) < key(b)
+}
+
+// A way of filling a hole of a given kind.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Completion {
+    pub label: String,
+    // The field of the `oneof` to set, if the kind has one.
+    pub variant: Option<u64>,
+}
+
+// Returns the completions valid for a hole of the given kind: one per message field of its `oneof`,
+// if it has one, or the kind itself otherwise.
+pub fn completions(schema: &Schema, kind_id: u64) -> Vec<Completion> {
+    let kind = match schema.get_kind(kind_id) {
+        Some(kind) => kind,
+        None => return vec![],
+    };
+    let variants: Vec<_> = kind
+        .fields
+        .iter()
+        .filter(|f| f.oneof.is_some() && matches!(f.type_, FieldType::Object { .. }))
+        .filter(|f| !is_hole_marker(schema, f))
+        .map(|f| Completion {
+            label: f.name.clone(),
+            variant: Some(f.field_id),
+        })
+        .collect();
+    if variants.is_empty() {
+        vec![Completion {
+            label: kind.name.clone(),
+            variant: None,
+        }]
+    } else {
+        variants
+    }
+}
+
+// Creates a node of the given kind, with the given variant set. Singular message fields of the new
+// node (or of its variant) are filled with holes, so that they can be jumped to.
+pub fn fill(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    variant: Option<u64>,
+) -> Node {
+    let mut node = Node::default();
+    let kind = match schema.get_kind(kind_id) {
+        Some(kind) => kind,
+        None => return node,
+    };
+    let variant_field = variant.and_then(|field_id| kind.get_field(field_id));
+    for field in kind.fields.iter() {
+        if field.repeated || (field.oneof.is_some() && variant_field != Some(field)) {
+            continue;
+        }
+        if let FieldType::Object { kind_id } = field.type_ {
+            // The variant is filled in, its own fields being holes.
+            let child = if variant_field == Some(field) {
+                fill(node_store, schema, kind_id, None)
+            } else {
+                Node::default()
+            };
+            let digest = node_store.put_parsed(&child);
+            node.links.insert(
+                field.field_id,
+                vec![Link {
+                    type_: LinkType::Dag,
+                    digest,
+                }],
+            );
+        }
+    }
+    node
+}
@@ -0,0 +1,462 @@
+use crate::{
+    schema::{Field, FieldType, Kind, Schema},
+    types::{Link, LinkTarget, LinkType, Node, NodeStore},
+};
+use std::collections::BTreeMap;
+
+// HTML schema, mapping the DOM parsed by `html_parser` onto nodes.
+//
+// Each DOM node is an `html_node` with exactly one of its fields set, similar to a protobuf
+// `oneof`. Attributes and children are stored as repeated fields, so that their order is preserved
+// by link order. An attribute without a value (e.g. `<input disabled>`) has no `value` link.
+//
+// Text and attribute values are stored unescaped. Note that `html_parser` trims whitespace around
+// text nodes, so that is not preserved.
+pub const HTML_DOCUMENT: u64 = 9120001;
+pub const HTML_NODE: u64 = 9120002;
+pub const HTML_ELEMENT: u64 = 9120003;
+pub const HTML_ATTRIBUTE: u64 = 9120004;
+
+// Field of `html_document`.
+pub const DOCUMENT_CHILDREN: u64 = 1;
+
+// Fields of `html_node`.
+pub const NODE_ELEMENT: u64 = 1;
+pub const NODE_TEXT: u64 = 2;
+pub const NODE_COMMENT: u64 = 3;
+
+// Fields of `html_element`.
+pub const ELEMENT_NAME: u64 = 1;
+pub const ELEMENT_ATTRIBUTES: u64 = 2;
+pub const ELEMENT_CHILDREN: u64 = 3;
+
+// Fields of `html_attribute`.
+pub const ATTRIBUTE_NAME: u64 = 1;
+pub const ATTRIBUTE_VALUE: u64 = 2;
+
+// Elements that never have a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+// Elements whose text content is not escaped.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+pub fn html_schema() -> Schema {
+    Schema {
+        kinds: vec![
+            Kind {
+                kind_id: HTML_DOCUMENT,
+                name: "html_document".to_string(),
+                fields: vec![Field {
+                    field_id: DOCUMENT_CHILDREN,
+                    name: "children".to_string(),
+                    type_: FieldType::Object { kind_id: HTML_NODE },
+                    repeated: true,
+                    ..Default::default()
+                }],
+            },
+            Kind {
+                kind_id: HTML_NODE,
+                name: "html_node".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: NODE_ELEMENT,
+                        name: "element".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: HTML_ELEMENT,
+                        },
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: NODE_TEXT,
+                        name: "text".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: NODE_COMMENT,
+                        name: "comment".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: HTML_ELEMENT,
+                name: "html_element".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: ELEMENT_NAME,
+                        name: "name".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: ELEMENT_ATTRIBUTES,
+                        name: "attributes".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: HTML_ATTRIBUTE,
+                        },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: ELEMENT_CHILDREN,
+                        name: "children".to_string(),
+                        type_: FieldType::Object { kind_id: HTML_NODE },
+                        repeated: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: HTML_ATTRIBUTE,
+                name: "html_attribute".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: ATTRIBUTE_NAME,
+                        name: "name".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: ATTRIBUTE_VALUE,
+                        name: "value".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                ],
+            },
+        ],
+    }
+}
+
+fn dag(node_store: &mut NodeStore, links: Vec<(u64, Vec<Link>)>) -> Link {
+    Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&Node {
+            links: links
+                .into_iter()
+                .filter(|(_, links)| !links.is_empty())
+                .collect(),
+            sequences: BTreeMap::new(),
+        }),
+    }
+}
+
+fn raw(node_store: &mut NodeStore, value: &str) -> Link {
+    Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(value.as_bytes()),
+    }
+}
+
+pub fn parse(text: &str) -> Result<html_parser::Dom, String> {
+    html_parser::Dom::parse(text).map_err(|e| format!("invalid HTML: {}", e))
+}
+
+// Imports an HTML document (or fragment) as an `html_document` node.
+pub fn import(node_store: &mut NodeStore, text: &str) -> Result<Link, String> {
+    let dom = parse(text)?;
+    let children = dom
+        .children
+        .iter()
+        .map(|child| import_node(node_store, child))
+        .collect();
+    Ok(dag(node_store, vec![(DOCUMENT_CHILDREN, children)]))
+}
+
+fn import_node(node_store: &mut NodeStore, node: &html_parser::Node) -> Link {
+    let links = match node {
+        html_parser::Node::Element(element) => {
+            let element = import_element(node_store, element);
+            vec![(NODE_ELEMENT, vec![element])]
+        }
+        html_parser::Node::Text(text) => {
+            vec![(NODE_TEXT, vec![raw(node_store, &unescape(text))])]
+        }
+        html_parser::Node::Comment(comment) => {
+            vec![(NODE_COMMENT, vec![raw(node_store, comment)])]
+        }
+    };
+    dag(node_store, links)
+}
+
+fn import_element(node_store: &mut NodeStore, element: &html_parser::Element) -> Link {
+    // `html_parser` splits out `id` and `class`, and keeps the other attributes in a `HashMap`, so
+    // the original attribute order is lost; `id` and `class` go first, then the other attributes by
+    // name, with attributes without a value last (`html_parser` does not accept them before other
+    // attributes).
+    let mut attributes: Vec<(String, Option<String>)> = vec![];
+    if let Some(id) = &element.id {
+        attributes.push(("id".to_string(), Some(id.clone())));
+    }
+    if !element.classes.is_empty() {
+        attributes.push(("class".to_string(), Some(element.classes.join(" "))));
+    }
+    let mut others: Vec<_> = element.attributes.iter().collect();
+    others.sort_by_key(|(k, v)| (v.is_none(), k.to_string()));
+    attributes.extend(others.into_iter().map(|(k, v)| (k.clone(), v.clone())));
+
+    let name = raw(node_store, &element.name);
+    let attributes = attributes
+        .iter()
+        .map(|(name, value)| {
+            let name = raw(node_store, name);
+            let value = value
+                .iter()
+                .map(|v| raw(node_store, &unescape(v)))
+                .collect();
+            dag(
+                node_store,
+                vec![(ATTRIBUTE_NAME, vec![name]), (ATTRIBUTE_VALUE, value)],
+            )
+        })
+        .collect();
+    let children = element
+        .children
+        .iter()
+        .map(|child| import_node(node_store, child))
+        .collect();
+    dag(
+        node_store,
+        vec![
+            (ELEMENT_NAME, vec![name]),
+            (ELEMENT_ATTRIBUTES, attributes),
+            (ELEMENT_CHILDREN, children),
+        ],
+    )
+}
+
+fn get_node(node_store: &NodeStore, link: &Link, path: &str) -> Result<Node, String> {
+    match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => Ok(node),
+        Some(LinkTarget::Raw(_)) => Err(format!("{}: expected node at {}", path, link.digest)),
+        None => Err(format!("{}: missing node {}", path, link.digest)),
+    }
+}
+
+fn get_text(node_store: &NodeStore, link: &Link, path: &str) -> Result<String, String> {
+    match link.get(node_store) {
+        Some(LinkTarget::Raw(value)) => {
+            String::from_utf8(value.clone()).map_err(|_| format!("{}: invalid UTF-8 string", path))
+        }
+        Some(LinkTarget::Parsed(_)) => {
+            Err(format!("{}: expected raw value at {}", path, link.digest))
+        }
+        None => Err(format!("{}: missing value {}", path, link.digest)),
+    }
+}
+
+fn get_links(node: &Node, field_id: u64) -> Vec<Link> {
+    node.links.get(&field_id).cloned().unwrap_or_default()
+}
+
+fn get_single(node: &Node, field_id: u64, path: &str) -> Result<Link, String> {
+    match node.links.get(&field_id).map(|links| links.as_slice()) {
+        Some([link]) => Ok(link.clone()),
+        _ => Err(format!(
+            "{}: expected exactly one link in field {}",
+            path, field_id
+        )),
+    }
+}
+
+// `html_parser` keeps character references as they appear in the source; this decodes the common
+// named ones and numeric ones, so that text is stored as it is displayed.
+fn unescape(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                r => r
+                    .strip_prefix("#x")
+                    .or_else(|| r.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| r.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape_text(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_attribute(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+// Exports an `html_document` node back to HTML.
+pub fn export(node_store: &NodeStore, link: &Link) -> Result<String, String> {
+    let document = get_node(node_store, link, "document")?;
+    let mut out = String::new();
+    for (i, child) in get_links(&document, DOCUMENT_CHILDREN).iter().enumerate() {
+        let path = format!("children[{}]", i);
+        export_node(node_store, child, &path, None, &mut out)?;
+    }
+    Ok(out)
+}
+
+// Exports a single `html_node` back to HTML.
+pub fn export_fragment(node_store: &NodeStore, link: &Link) -> Result<String, String> {
+    let mut out = String::new();
+    export_node(node_store, link, "node", None, &mut out)?;
+    Ok(out)
+}
+
+// `raw_text` is the name of the enclosing element, if its content is not escaped (e.g. `script`):
+// such elements may only contain text, which must not contain their end tag.
+fn export_node(
+    node_store: &NodeStore,
+    link: &Link,
+    path: &str,
+    raw_text: Option<&str>,
+    out: &mut String,
+) -> Result<(), String> {
+    let node = get_node(node_store, link, path)?;
+    let (field_id, link) = match node.links.iter().collect::<Vec<_>>().as_slice() {
+        [(field_id, links)] if links.len() == 1 => (**field_id, &links[0]),
+        _ => {
+            return Err(format!(
+                "{}: expected exactly one of element, text, comment",
+                path
+            ))
+        }
+    };
+    if let (Some(name), NODE_ELEMENT | NODE_COMMENT) = (raw_text, field_id) {
+        return Err(format!("{}: `{}` can only contain text", path, name));
+    }
+    match field_id {
+        NODE_ELEMENT => export_element(node_store, link, path, out),
+        NODE_TEXT => {
+            let text = get_text(node_store, link, path)?;
+            match raw_text {
+                Some(name) => {
+                    // End tags are matched case-insensitively.
+                    let end_tag = format!("</{}", name);
+                    if text.to_ascii_lowercase().contains(&end_tag) {
+                        return Err(format!(
+                            "{}: text of `{}` cannot contain `{}`",
+                            path, name, end_tag
+                        ));
+                    }
+                    out.push_str(&text);
+                }
+                None => out.push_str(&escape_text(&text)),
+            }
+            Ok(())
+        }
+        NODE_COMMENT => {
+            let comment = get_text(node_store, link, path)?;
+            if comment.contains("-->") {
+                return Err(format!("{}: comment cannot contain `-->`", path));
+            }
+            out.push_str(&format!("<!--{}-->", comment));
+            Ok(())
+        }
+        _ => Err(format!("{}: unknown html_node field {}", path, field_id)),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':' || c == '.')
+}
+
+fn export_element(
+    node_store: &NodeStore,
+    link: &Link,
+    path: &str,
+    out: &mut String,
+) -> Result<(), String> {
+    let element = get_node(node_store, link, path)?;
+    let name = get_text(node_store, &get_single(&element, ELEMENT_NAME, path)?, path)?;
+    if !is_valid_name(&name) {
+        return Err(format!("{}: invalid element name `{}`", path, name));
+    }
+    out.push('<');
+    out.push_str(&name);
+    for (i, attribute) in get_links(&element, ELEMENT_ATTRIBUTES).iter().enumerate() {
+        let path = format!("{}.attributes[{}]", path, i);
+        let attribute = get_node(node_store, attribute, &path)?;
+        let attribute_name = get_text(
+            node_store,
+            &get_single(&attribute, ATTRIBUTE_NAME, &path)?,
+            &path,
+        )?;
+        if !is_valid_name(&attribute_name) {
+            return Err(format!(
+                "{}: invalid attribute name `{}`",
+                path, attribute_name
+            ));
+        }
+        out.push(' ');
+        out.push_str(&attribute_name);
+        if attribute.links.contains_key(&ATTRIBUTE_VALUE) {
+            let value = get_text(
+                node_store,
+                &get_single(&attribute, ATTRIBUTE_VALUE, &path)?,
+                &path,
+            )?;
+            out.push_str(&format!("=\"{}\"", escape_attribute(&value)));
+        }
+    }
+    out.push('>');
+    let children = get_links(&element, ELEMENT_CHILDREN);
+    let lower_name = name.to_ascii_lowercase();
+    if VOID_ELEMENTS.contains(&lower_name.as_str()) {
+        if !children.is_empty() {
+            return Err(format!(
+                "{}: void element `{}` cannot have children",
+                path, name
+            ));
+        }
+        return Ok(());
+    }
+    let raw_text = RAW_TEXT_ELEMENTS
+        .contains(&lower_name.as_str())
+        .then_some(lower_name.as_str());
+    for (i, child) in children.iter().enumerate() {
+        let path = format!("{}.children[{}]", path, i);
+        export_node(node_store, child, &path, raw_text, out)?;
+    }
+    out.push_str(&format!("</{}>", name));
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use crate::{
+    schema::{Field, FieldType, FieldValue, Kind, Schema},
+    types::{Digest, Link, LinkTarget, LinkType, Node, NodeStore},
+};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+// Author identities, and statements signed by them.
+//
+// Each author has an ed25519 key pair, kept in local storage in the browser, or in a key file for
+// command line tools (see `linc_sign` in `linc_sync`). A statement signs the digest of a root (or
+// of any other node) on behalf of its author, and is itself stored as a `signed_statement` node, so
+// that it can be published along with the tree. Nothing in the tree can link to the statements
+// signing its root, so permalinks to the root list them, and loading the root fetches them too
+// (see `permalink.rs`). Anyone can check a statement against the public key it carries; whether
+// that key belongs to the claimed author is up to the reader, e.g. by comparing it with a list of
+// trusted keys before deploying the signed tree.
+pub const SIGNED_STATEMENT: u64 = 7440100;
+
+// Fields of `signed_statement`.
+pub const STATEMENT_SUBJECT: u64 = 1;
+pub const STATEMENT_AUTHOR: u64 = 2;
+pub const STATEMENT_PUBLIC_KEY: u64 = 3;
+pub const STATEMENT_SIGNATURE: u64 = 4;
+
+pub fn identity_schema() -> Schema {
+    let field = |field_id, name: &str, type_| Field {
+        field_id,
+        name: name.to_string(),
+        type_,
+        repeated: false,
+        ..Default::default()
+    };
+    Schema {
+        kinds: vec![Kind {
+            kind_id: SIGNED_STATEMENT,
+            name: "signed_statement".to_string(),
+            fields: vec![
+                field(STATEMENT_SUBJECT, "subject", FieldType::String),
+                field(STATEMENT_AUTHOR, "author", FieldType::String),
+                field(STATEMENT_PUBLIC_KEY, "public_key", FieldType::Bytes),
+                field(STATEMENT_SIGNATURE, "signature", FieldType::Bytes),
+            ],
+        }],
+    }
+}
+
+// The bytes actually signed: the subject is bound to the author name, so that neither can be
+// swapped without invalidating the signature.
+fn message(subject: &str, author: &str) -> Vec<u8> {
+    format!(
+        "linc signed statement\nsubject: {}\nauthor: {}\n",
+        subject, author
+    )
+    .into_bytes()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "KeyFile", into = "KeyFile")]
+pub struct Identity {
+    pub author: String,
+    key: SigningKey,
+}
+
+// Serialized form of an identity, as stored in local storage or in a key file.
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    author: String,
+    // Hex encoded.
+    secret_key: String,
+}
+
+impl From<Identity> for KeyFile {
+    fn from(identity: Identity) -> Self {
+        KeyFile {
+            author: identity.author,
+            secret_key: hex::encode(identity.key.to_bytes()),
+        }
+    }
+}
+
+impl TryFrom<KeyFile> for Identity {
+    type Error = String;
+
+    fn try_from(key_file: KeyFile) -> Result<Self, String> {
+        let seed = hex::decode(&key_file.secret_key).map_err(|e| e.to_string())?;
+        let seed: [u8; 32] = seed
+            .try_into()
+            .map_err(|_| "secret key is not 32 bytes long".to_string())?;
+        Ok(Identity::from_seed(&key_file.author, seed))
+    }
+}
+
+impl Identity {
+    pub fn from_seed(author: &str, seed: [u8; 32]) -> Self {
+        Identity {
+            author: author.to_string(),
+            key: SigningKey::from_bytes(&seed),
+        }
+    }
+
+    // Creates a new key pair for the given author.
+    pub fn generate(author: &str) -> Result<Self, String> {
+        let mut seed = [0; 32];
+        getrandom::getrandom(&mut seed).map_err(|e| e.to_string())?;
+        Ok(Identity::from_seed(author, seed))
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.key.verifying_key().to_bytes()
+    }
+
+    // Stores a statement signing the given digest, and returns a link to it.
+    pub fn sign(&self, node_store: &mut NodeStore, subject: &Digest) -> Link {
+        let signature = self.key.sign(&message(subject, &self.author));
+        let mut node = Node::default();
+        let values = [
+            (STATEMENT_SUBJECT, FieldValue::String(subject.clone())),
+            (STATEMENT_AUTHOR, FieldValue::String(self.author.clone())),
+            (
+                STATEMENT_PUBLIC_KEY,
+                FieldValue::Bytes(self.public_key().to_vec()),
+            ),
+            (
+                STATEMENT_SIGNATURE,
+                FieldValue::Bytes(signature.to_bytes().to_vec()),
+            ),
+        ];
+        for (field_id, value) in values {
+            let link = Link {
+                type_: LinkType::Raw,
+                digest: node_store.put_raw(&value.to_raw().unwrap()),
+            };
+            node.links.insert(field_id, vec![link]);
+        }
+        Link {
+            type_: LinkType::Dag,
+            digest: node_store.put_parsed(&node),
+        }
+    }
+}
+
+// A statement whose signature was checked.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement {
+    pub subject: Digest,
+    pub author: String,
+    pub public_key: [u8; 32],
+}
+
+impl Statement {
+    // Short form of the public key, for display.
+    pub fn fingerprint(&self) -> String {
+        hex::encode(&self.public_key[..8])
+    }
+}
+
+fn raw<'a>(node_store: &'a NodeStore, node: &Node, field_id: u64) -> Result<&'a [u8], String> {
+    match node.links.get(&field_id).map(Vec::as_slice) {
+        Some([link]) => match link.get(node_store) {
+            Some(LinkTarget::Raw(raw)) => Ok(raw),
+            Some(LinkTarget::Parsed(_)) => Err(format!("field {} is not a raw value", field_id)),
+            None => Err(format!("missing value {}", link.digest)),
+        },
+        _ => Err(format!("expected a single value for field {}", field_id)),
+    }
+}
+
+fn string(node_store: &NodeStore, node: &Node, field_id: u64) -> Result<String, String> {
+    String::from_utf8(raw(node_store, node, field_id)?.to_vec())
+        .map_err(|_| format!("field {} is not valid UTF-8", field_id))
+}
+
+// Checks the signature of the statement at the given link, and returns it if it is valid.
+pub fn verify(node_store: &NodeStore, link: &Link) -> Result<Statement, String> {
+    let node = match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        Some(LinkTarget::Raw(_)) => return Err("expected a node".to_string()),
+        None => return Err(format!("missing node {}", link.digest)),
+    };
+    let subject = string(node_store, &node, STATEMENT_SUBJECT)?;
+    let author = string(node_store, &node, STATEMENT_AUTHOR)?;
+    let public_key: [u8; 32] = raw(node_store, &node, STATEMENT_PUBLIC_KEY)?
+        .try_into()
+        .map_err(|_| "public key is not 32 bytes long".to_string())?;
+    let signature = Signature::from_slice(raw(node_store, &node, STATEMENT_SIGNATURE)?)
+        .map_err(|e| e.to_string())?;
+    VerifyingKey::from_bytes(&public_key)
+        .and_then(|key| key.verify(&message(&subject, &author), &signature))
+        .map_err(|_| "invalid signature".to_string())?;
+    Ok(Statement {
+        subject,
+        author,
+        public_key,
+    })
+}
+
+// Returns the valid statements among the given ones that sign the given digest, ordered by author.
+pub fn statements(
+    node_store: &NodeStore,
+    statements: &[Digest],
+    subject: &Digest,
+) -> Vec<Statement> {
+    let mut statements: Vec<Statement> = statements
+        .iter()
+        .filter_map(|digest| {
+            verify(
+                node_store,
+                &Link {
+                    type_: LinkType::Dag,
+                    digest: digest.clone(),
+                },
+            )
+            .ok()
+        })
+        .filter(|statement| statement.subject == *subject)
+        .collect();
+    statements.sort_by(|a, b| (&a.author, a.public_key).cmp(&(&b.author, b.public_key)));
+    statements.dedup();
+    statements
+}
@@ -1,7 +1,6 @@
-use crate::schema::{Field, FieldType, Kind, Schema};
+use crate::schema::{Arg, Field, FieldType, Kind, Schema};
 
 use super::types::*;
-use std::collections::{BTreeMap, HashMap};
 
 pub fn initial() -> (NodeStore, String) {
     let node = Node::default();
@@ -11,78 +10,91 @@ pub fn initial() -> (NodeStore, String) {
 }
 
 pub fn initial_schema() -> Schema {
-    Schema {
-        kinds: vec![
-            Kind {
-                kind_id: 3021731,
-                name: "root".to_string(),
-                fields: vec![
-                    Field {
-                        field_id: 3021731,
-                        name: "git_command".to_string(),
-                        type_: FieldType::Object { kind_id: 23427 },
+    let mut schema = Schema {
+        kinds: vec![Kind {
+            kind_id: 3021731,
+            name: "root".to_string(),
+            fields: vec![
+                Field {
+                    field_id: 3021731,
+                    name: "git_command".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: crate::git::GIT_COMMAND,
                     },
-                    Field {
-                        field_id: 3021732,
-                        name: "docker_command".to_string(),
-                        type_: FieldType::Object { kind_id: 23428 },
+                    repeated: false,
+                    arg: Some(Arg::Subcommand("git".to_string())),
+                    ..Default::default()
+                },
+                Field {
+                    field_id: 3021732,
+                    name: "docker_command".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: crate::docker::DOCKER_COMMAND,
                     },
-                ],
-            },
-            Kind {
-                kind_id: 23427,
-                name: "git_command".to_string(),
-                fields: vec![
-                    Field {
-                        field_id: 131987,
-                        name: "git_add".to_string(),
-                        type_: FieldType::Object {
-                            kind_id: 231849732984,
-                        },
+                    repeated: false,
+                    arg: Some(Arg::Subcommand("docker".to_string())),
+                    ..Default::default()
+                },
+                Field {
+                    field_id: 3021733,
+                    name: "json".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: crate::json::JSON_VALUE,
                     },
-                    Field {
-                        field_id: 2429447,
-                        name: "git_push".to_string(),
-                        type_: FieldType::Object { kind_id: 349872 },
+                    repeated: false,
+                    ..Default::default()
+                },
+                Field {
+                    field_id: 3021734,
+                    name: "cargo_manifest".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: crate::cargo::CARGO_MANIFEST,
                     },
-                ],
-            },
-            Kind {
-                kind_id: 231849732984,
-                name: "git_add".to_string(),
-                fields: vec![],
-            },
-            Kind {
-                kind_id: 349872,
-                name: "git_push".to_string(),
-                fields: vec![],
-            },
-            Kind {
-                kind_id: 23428,
-                name: "docker_command".to_string(),
-                fields: vec![
-                    Field {
-                        field_id: 13091823090,
-                        name: "docker_run".to_string(),
-                        type_: FieldType::Object { kind_id: 130 },
+                    repeated: false,
+                    ..Default::default()
+                },
+                Field {
+                    field_id: 3021735,
+                    name: "html".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: crate::html::HTML_DOCUMENT,
                     },
-                    Field {
-                        field_id: 2309471,
-                        name: "docker_build".to_string(),
-                        type_: FieldType::Object { kind_id: 349872 },
+                    repeated: false,
+                    ..Default::default()
+                },
+                Field {
+                    field_id: 3021736,
+                    name: "ast_file".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: crate::proto::kind_id("File"),
                     },
-                ],
-            },
-            Kind {
-                kind_id: 13091823090,
-                name: "docker_run".to_string(),
-                fields: vec![],
-            },
-            Kind {
-                kind_id: 2309471,
-                name: "docker_build".to_string(),
-                fields: vec![],
-            },
-        ],
+                    repeated: false,
+                    ..Default::default()
+                },
+                Field {
+                    field_id: 3021737,
+                    name: "yaml_stream".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: crate::yaml::YAML_STREAM,
+                    },
+                    repeated: false,
+                    ..Default::default()
+                },
+            ],
+        }],
+    };
+    schema.kinds.extend(crate::git::git_schema().kinds);
+    schema.kinds.extend(crate::docker::docker_schema().kinds);
+    schema.kinds.extend(crate::json::json_schema().kinds);
+    schema.kinds.extend(crate::yaml::yaml_schema().kinds);
+    schema.kinds.extend(crate::cargo::cargo_schema().kinds);
+    schema.kinds.extend(crate::html::html_schema().kinds);
+    match crate::proto::ast_schema() {
+        Ok(ast_schema) => schema.kinds.extend(ast_schema.kinds),
+        Err(e) => log::error!("invalid proto/ast.proto: {}", e),
     }
+    schema
+        .kinds
+        .extend(crate::identity::identity_schema().kinds);
+    schema
 }
@@ -0,0 +1,622 @@
+use crate::{
+    schema::{Field, FieldType, FieldValue, Kind, Schema},
+    types::{Link, LinkTarget, LinkType, Node, NodeStore},
+};
+use base64::Engine;
+use serde_json::{Map, Number, Value};
+use std::{collections::BTreeMap, fmt};
+
+// Generic JSON schema, able to represent any JSON document without further type information.
+//
+// There is a kind for each type of JSON value: `json_object`, `json_array`, `json_string`,
+// `json_number`, `json_bool` and `json_null`. Since nodes do not record their kind, places that
+// may hold any value (the document, array items and object values) link to a `json_value` node,
+// whose `oneof` field tells the type of the value and links to it. Strings, numbers and bools are
+// stored as raw leaves, using their JSON text representation for numbers, so that no precision is
+// lost. Object entries are stored as a repeated field, so that key order is preserved by link
+// order.
+pub const JSON_VALUE: u64 = 7430100;
+pub const JSON_OBJECT: u64 = 7430101;
+pub const JSON_ENTRY: u64 = 7430102;
+pub const JSON_ARRAY: u64 = 7430103;
+pub const JSON_NULL: u64 = 7430104;
+pub const JSON_STRING: u64 = 7430105;
+pub const JSON_NUMBER: u64 = 7430106;
+pub const JSON_BOOL: u64 = 7430107;
+
+// Fields of `json_value`, all in the `value` oneof.
+pub const VALUE_OBJECT: u64 = 1;
+pub const VALUE_ARRAY: u64 = 2;
+pub const VALUE_STRING: u64 = 3;
+pub const VALUE_NUMBER: u64 = 4;
+pub const VALUE_BOOL: u64 = 5;
+pub const VALUE_NULL: u64 = 6;
+
+// Field of `json_object`.
+pub const OBJECT_ENTRIES: u64 = 1;
+
+// Fields of `json_entry`.
+pub const ENTRY_KEY: u64 = 1;
+pub const ENTRY_VALUE: u64 = 2;
+
+// Field of `json_array`.
+pub const ARRAY_ITEMS: u64 = 1;
+
+// Field of `json_string`, `json_number` and `json_bool`.
+pub const SCALAR_VALUE: u64 = 1;
+
+// The kind of each field of `json_value`.
+const VALUE_KINDS: &[(u64, u64, &str)] = &[
+    (VALUE_OBJECT, JSON_OBJECT, "object"),
+    (VALUE_ARRAY, JSON_ARRAY, "array"),
+    (VALUE_STRING, JSON_STRING, "string"),
+    (VALUE_NUMBER, JSON_NUMBER, "number"),
+    (VALUE_BOOL, JSON_BOOL, "bool"),
+    (VALUE_NULL, JSON_NULL, "null"),
+];
+
+fn scalar_kind(kind_id: u64, name: &str, type_: FieldType) -> Kind {
+    Kind {
+        kind_id,
+        name: name.to_string(),
+        fields: vec![Field {
+            field_id: SCALAR_VALUE,
+            name: "value".to_string(),
+            type_,
+            repeated: false,
+            ..Default::default()
+        }],
+    }
+}
+
+pub fn json_schema() -> Schema {
+    Schema {
+        kinds: vec![
+            Kind {
+                kind_id: JSON_VALUE,
+                name: "json_value".to_string(),
+                fields: VALUE_KINDS
+                    .iter()
+                    .map(|(field_id, kind_id, name)| Field {
+                        field_id: *field_id,
+                        name: name.to_string(),
+                        type_: FieldType::Object { kind_id: *kind_id },
+                        repeated: false,
+                        oneof: Some("value".to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+            },
+            Kind {
+                kind_id: JSON_OBJECT,
+                name: "json_object".to_string(),
+                fields: vec![Field {
+                    field_id: OBJECT_ENTRIES,
+                    name: "entries".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: JSON_ENTRY,
+                    },
+                    repeated: true,
+                    ..Default::default()
+                }],
+            },
+            Kind {
+                kind_id: JSON_ENTRY,
+                name: "json_entry".to_string(),
+                fields: vec![
+                    Field {
+                        field_id: ENTRY_KEY,
+                        name: "key".to_string(),
+                        type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
+                    },
+                    Field {
+                        field_id: ENTRY_VALUE,
+                        name: "value".to_string(),
+                        type_: FieldType::Object {
+                            kind_id: JSON_VALUE,
+                        },
+                        repeated: false,
+                        ..Default::default()
+                    },
+                ],
+            },
+            Kind {
+                kind_id: JSON_ARRAY,
+                name: "json_array".to_string(),
+                fields: vec![Field {
+                    field_id: ARRAY_ITEMS,
+                    name: "items".to_string(),
+                    type_: FieldType::Object {
+                        kind_id: JSON_VALUE,
+                    },
+                    repeated: true,
+                    ..Default::default()
+                }],
+            },
+            scalar_kind(JSON_STRING, "json_string", FieldType::String),
+            scalar_kind(JSON_NUMBER, "json_number", FieldType::Float),
+            scalar_kind(JSON_BOOL, "json_bool", FieldType::Bool),
+            Kind {
+                kind_id: JSON_NULL,
+                name: "json_null".to_string(),
+                fields: vec![],
+            },
+        ],
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+    // JSON Pointer (RFC 6901) to the offending value.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        write!(f, "{}: {}", pointer, self.message)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+fn error<T>(pointer: &str, message: String) -> Result<T, JsonError> {
+    Err(JsonError {
+        pointer: pointer.to_string(),
+        message,
+    })
+}
+
+fn child_pointer(pointer: &str, token: &str) -> String {
+    format!(
+        "{}/{}",
+        pointer,
+        token.replace('~', "~0").replace('/', "~1")
+    )
+}
+
+fn value_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn dag(node_store: &mut NodeStore, node: &Node) -> Link {
+    Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(node),
+    }
+}
+
+fn raw(node_store: &mut NodeStore, value: &[u8]) -> Link {
+    Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(value),
+    }
+}
+
+fn single(field_id: u64, link: Link) -> Node {
+    Node {
+        links: maplit::btreemap! { field_id => vec![link] },
+        sequences: BTreeMap::new(),
+    }
+}
+
+// A `json_string`, `json_number` or `json_bool` node holding the given raw value.
+fn scalar(node_store: &mut NodeStore, value: &[u8]) -> Link {
+    let value = raw(node_store, value);
+    dag(node_store, &single(SCALAR_VALUE, value))
+}
+
+// Imports a JSON value as a tree of `json_value` nodes.
+pub fn import(node_store: &mut NodeStore, value: &Value) -> Link {
+    let node = match value {
+        Value::Null => {
+            let null = dag(node_store, &Node::default());
+            single(VALUE_NULL, null)
+        }
+        Value::Bool(v) => single(VALUE_BOOL, scalar(node_store, v.to_string().as_bytes())),
+        Value::Number(v) => single(VALUE_NUMBER, scalar(node_store, v.to_string().as_bytes())),
+        Value::String(v) => single(VALUE_STRING, scalar(node_store, v.as_bytes())),
+        Value::Array(items) => {
+            let items = items.iter().map(|v| import(node_store, v)).collect();
+            let array = dag(
+                node_store,
+                &Node {
+                    links: maplit::btreemap! { ARRAY_ITEMS => items },
+                    sequences: BTreeMap::new(),
+                },
+            );
+            single(VALUE_ARRAY, array)
+        }
+        Value::Object(entries) => {
+            let entries = entries
+                .iter()
+                .map(|(k, v)| {
+                    let key = raw(node_store, k.as_bytes());
+                    let value = import(node_store, v);
+                    dag(
+                        node_store,
+                        &Node {
+                            links: maplit::btreemap! {
+                                ENTRY_KEY => vec![key],
+                                ENTRY_VALUE => vec![value],
+                            },
+                            sequences: BTreeMap::new(),
+                        },
+                    )
+                })
+                .collect();
+            let object = dag(
+                node_store,
+                &Node {
+                    links: maplit::btreemap! { OBJECT_ENTRIES => entries },
+                    sequences: BTreeMap::new(),
+                },
+            );
+            single(VALUE_OBJECT, object)
+        }
+    };
+    dag(node_store, &node)
+}
+
+fn get_node(node_store: &NodeStore, link: &Link, pointer: &str) -> Result<Node, JsonError> {
+    match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => Ok(node),
+        Some(LinkTarget::Raw(_)) => error(pointer, format!("expected node at {}", link.digest)),
+        None => error(pointer, format!("missing node {}", link.digest)),
+    }
+}
+
+fn get_raw(node_store: &NodeStore, link: &Link, pointer: &str) -> Result<Vec<u8>, JsonError> {
+    match link.get(node_store) {
+        Some(LinkTarget::Raw(value)) => Ok(value.clone()),
+        Some(LinkTarget::Parsed(_)) => {
+            error(pointer, format!("expected raw value at {}", link.digest))
+        }
+        None => error(pointer, format!("missing value {}", link.digest)),
+    }
+}
+
+fn get_text(node_store: &NodeStore, link: &Link, pointer: &str) -> Result<String, JsonError> {
+    let raw = get_raw(node_store, link, pointer)?;
+    String::from_utf8(raw).or_else(|_| error(pointer, "invalid UTF-8 string".to_string()))
+}
+
+fn get_single<'a>(node: &'a Node, field_id: u64, pointer: &str) -> Result<&'a Link, JsonError> {
+    match node.links.get(&field_id).map(|links| links.as_slice()) {
+        Some([link]) => Ok(link),
+        _ => error(
+            pointer,
+            format!("expected exactly one link in field {}", field_id),
+        ),
+    }
+}
+
+// Exports a tree of `json_value` nodes back to JSON.
+pub fn export(node_store: &NodeStore, link: &Link) -> Result<Value, JsonError> {
+    export_value(node_store, link, "")
+}
+
+fn export_value(node_store: &NodeStore, link: &Link, pointer: &str) -> Result<Value, JsonError> {
+    let node = get_node(node_store, link, pointer)?;
+    let (field_id, link) = match node.links.iter().collect::<Vec<_>>().as_slice() {
+        [(field_id, links)] if links.len() == 1 => (**field_id, &links[0]),
+        _ => return error(pointer, "expected exactly one value".to_string()),
+    };
+    // The raw value of a scalar node.
+    let scalar = || {
+        let node = get_node(node_store, link, pointer)?;
+        get_text(
+            node_store,
+            get_single(&node, SCALAR_VALUE, pointer)?,
+            pointer,
+        )
+    };
+    match field_id {
+        VALUE_NULL => Ok(Value::Null),
+        VALUE_BOOL => match scalar()?.as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            v => error(pointer, format!("invalid bool `{}`", v)),
+        },
+        VALUE_NUMBER => {
+            let text = scalar()?;
+            match serde_json::from_str::<Number>(&text) {
+                Ok(v) => Ok(Value::Number(v)),
+                Err(_) => error(pointer, format!("invalid number `{}`", text)),
+            }
+        }
+        VALUE_STRING => scalar().map(Value::String),
+        VALUE_ARRAY => {
+            let array = get_node(node_store, link, pointer)?;
+            array
+                .links
+                .get(&ARRAY_ITEMS)
+                .cloned()
+                .unwrap_or_default()
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    export_value(node_store, item, &child_pointer(pointer, &i.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array)
+        }
+        VALUE_OBJECT => {
+            let object = get_node(node_store, link, pointer)?;
+            let mut entries = Map::new();
+            for entry in object
+                .links
+                .get(&OBJECT_ENTRIES)
+                .cloned()
+                .unwrap_or_default()
+            {
+                let entry = get_node(node_store, &entry, pointer)?;
+                let key = get_text(node_store, get_single(&entry, ENTRY_KEY, pointer)?, pointer)?;
+                let value_pointer = child_pointer(pointer, &key);
+                let value = export_value(
+                    node_store,
+                    get_single(&entry, ENTRY_VALUE, &value_pointer)?,
+                    &value_pointer,
+                )?;
+                entries.insert(key, value);
+            }
+            Ok(Value::Object(entries))
+        }
+        _ => error(pointer, format!("unknown json_value field {}", field_id)),
+    }
+}
+
+fn get_kind<'a>(schema: &'a Schema, kind_id: u64, pointer: &str) -> Result<&'a Kind, JsonError> {
+    match schema.get_kind(kind_id) {
+        Some(kind) => Ok(kind),
+        None => error(pointer, format!("unknown kind {}", kind_id)),
+    }
+}
+
+// Imports a JSON object as a node of the given kind, mapping JSON keys to field names (or field
+// ids, as decimal strings).
+pub fn import_typed(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    value: &Value,
+) -> Result<Link, JsonError> {
+    import_object(node_store, schema, kind_id, value, "")
+}
+
+fn import_object(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    value: &Value,
+    pointer: &str,
+) -> Result<Link, JsonError> {
+    let kind = get_kind(schema, kind_id, pointer)?;
+    let entries = match value {
+        Value::Object(entries) => entries,
+        v => {
+            return error(
+                pointer,
+                format!(
+                    "expected object for kind `{}`, found {}",
+                    kind.name,
+                    value_type_name(v)
+                ),
+            )
+        }
+    };
+    let mut node = Node::default();
+    // Key under which each field was given, since it may be given by name or by id.
+    let mut keys: BTreeMap<u64, &str> = BTreeMap::new();
+    for (key, v) in entries {
+        let field_pointer = child_pointer(pointer, key);
+        let field = match kind
+            .fields
+            .iter()
+            .find(|f| &f.name == key || &f.field_id.to_string() == key)
+        {
+            Some(field) => field,
+            None => {
+                return error(
+                    &field_pointer,
+                    format!("unknown field `{}` for kind `{}`", key, kind.name),
+                )
+            }
+        };
+        if let Some(previous) = keys.insert(field.field_id, key) {
+            return error(
+                &field_pointer,
+                format!(
+                    "field `{}` given both as `{}` and as `{}`",
+                    field.name, previous, key
+                ),
+            );
+        }
+        let links = match (v, field.repeated) {
+            (Value::Null, _) => continue,
+            (Value::Array(items), true) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    import_field_value(
+                        node_store,
+                        schema,
+                        &field.type_,
+                        item,
+                        &child_pointer(&field_pointer, &i.to_string()),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            (v, true) => {
+                return error(
+                    &field_pointer,
+                    format!(
+                        "expected array for repeated field `{}`, found {}",
+                        field.name,
+                        value_type_name(v)
+                    ),
+                )
+            }
+            (v, false) => vec![import_field_value(
+                node_store,
+                schema,
+                &field.type_,
+                v,
+                &field_pointer,
+            )?],
+        };
+        node.links.insert(field.field_id, links);
+    }
+    Ok(dag(node_store, &node))
+}
+
+fn import_field_value(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    type_: &FieldType,
+    value: &Value,
+    pointer: &str,
+) -> Result<Link, JsonError> {
+    let field_value = match (type_, value) {
+        (FieldType::Object { kind_id }, v) => {
+            return import_object(node_store, schema, *kind_id, v, pointer)
+        }
+        (FieldType::String, Value::String(v)) => FieldValue::String(v.clone()),
+        (FieldType::Bytes, Value::String(v)) => {
+            match base64::engine::general_purpose::STANDARD.decode(v) {
+                Ok(v) => FieldValue::Bytes(v),
+                Err(e) => return error(pointer, format!("invalid base64 bytes: {}", e)),
+            }
+        }
+        (FieldType::Bool, Value::Bool(v)) => FieldValue::Bool(*v),
+        (FieldType::Int, Value::Number(v)) if v.as_i64().is_some() => {
+            FieldValue::Int(v.as_i64().unwrap_or_default())
+        }
+        (FieldType::Float, Value::Number(v)) => FieldValue::Float(v.as_f64().unwrap_or_default()),
+        (type_, v) => {
+            return error(
+                pointer,
+                format!("expected {:?}, found {}", type_, value_type_name(v)),
+            )
+        }
+    };
+    let raw_value = field_value.to_raw().unwrap_or_default();
+    Ok(raw(node_store, &raw_value))
+}
+
+// Exports a node of the given kind as a JSON object keyed by field names.
+pub fn export_typed(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+) -> Result<Value, JsonError> {
+    export_object(node_store, schema, kind_id, link, "")
+}
+
+fn export_object(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+    pointer: &str,
+) -> Result<Value, JsonError> {
+    let kind = get_kind(schema, kind_id, pointer)?;
+    let node = get_node(node_store, link, pointer)?;
+    let mut entries = Map::new();
+    // Follow the schema field order rather than the field id order.
+    for field in &kind.fields {
+        let links = match node.links.get(&field.field_id) {
+            Some(links) => links,
+            None => continue,
+        };
+        let field_pointer = child_pointer(pointer, &field.name);
+        let value = if field.repeated {
+            links
+                .iter()
+                .enumerate()
+                .map(|(i, link)| {
+                    export_field_value(
+                        node_store,
+                        schema,
+                        &field.type_,
+                        link,
+                        &child_pointer(&field_pointer, &i.to_string()),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array)?
+        } else {
+            match links.as_slice() {
+                [link] => {
+                    export_field_value(node_store, schema, &field.type_, link, &field_pointer)?
+                }
+                _ => {
+                    return error(
+                        &field_pointer,
+                        format!(
+                            "expected exactly one link for field `{}`, found {}",
+                            field.name,
+                            links.len()
+                        ),
+                    )
+                }
+            }
+        };
+        entries.insert(field.name.clone(), value);
+    }
+    if let Some(field_id) = node.links.keys().find(|id| kind.get_field(**id).is_none()) {
+        return error(
+            pointer,
+            format!("unknown field {} for kind `{}`", field_id, kind.name),
+        );
+    }
+    Ok(Value::Object(entries))
+}
+
+fn export_field_value(
+    node_store: &NodeStore,
+    schema: &Schema,
+    type_: &FieldType,
+    link: &Link,
+    pointer: &str,
+) -> Result<Value, JsonError> {
+    if let FieldType::Object { kind_id } = type_ {
+        return export_object(node_store, schema, *kind_id, link, pointer);
+    }
+    let raw_value = get_raw(node_store, link, pointer)?;
+    match FieldValue::from_raw(type_, &raw_value) {
+        Some(FieldValue::String(v)) => Ok(Value::String(v)),
+        Some(FieldValue::Bytes(v)) => Ok(Value::String(
+            base64::engine::general_purpose::STANDARD.encode(v),
+        )),
+        Some(FieldValue::Bool(v)) => Ok(Value::Bool(v)),
+        Some(FieldValue::Int(v)) => Ok(Value::from(v)),
+        Some(FieldValue::Float(v)) => match Number::from_f64(v) {
+            Some(v) => Ok(Value::Number(v)),
+            None => error(pointer, format!("{} is not a valid JSON number", v)),
+        },
+        Some(FieldValue::Object(_)) | None => error(
+            pointer,
+            format!(
+                "invalid {:?} value `{}`",
+                type_,
+                String::from_utf8_lossy(&raw_value)
+            ),
+        ),
+    }
+}
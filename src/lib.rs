@@ -0,0 +1,48 @@
+#![recursion_limit = "512"]
+#![feature(iter_intersperse)]
+#![feature(once_cell)]
+
+// Lets the code generated by `#[derive(LincNode)]`, which refers to this crate as `linc`, be used
+// within it too.
+extern crate self as linc;
+
+mod argv;
+mod ast;
+
+pub mod cargo;
+pub mod collab;
+mod command_line;
+mod crdt;
+pub mod derive;
+mod docker;
+mod encryption;
+mod ent;
+mod eval;
+mod fsck;
+#[allow(dead_code, clippy::all)]
+mod generated;
+mod git;
+mod hole;
+mod html;
+pub mod identity;
+mod initial;
+mod json;
+pub mod model;
+mod node;
+mod permalink;
+mod pretty_print;
+pub mod proto;
+mod protobuf;
+pub mod query;
+mod refactor;
+pub mod schema;
+mod search;
+mod toml;
+mod transform;
+mod typecheck;
+pub mod typed;
+pub mod types;
+mod yaml;
+
+#[cfg(test)]
+mod tests;
@@ -1,27 +1,51 @@
 use crate::{
+    fsck::FsckReport,
+    hole,
     node::NodeComponent,
-    schema::{Field, Schema},
+    permalink::HashState,
+    schema::{Field, FieldType, FieldValue, Schema},
     types::*,
 };
+use base64::Engine;
 use gloo_events::{EventListener, EventListenerOptions};
 use gloo_storage::{LocalStorage, Storage};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
     rc::Rc,
-    str::FromStr,
 };
-use wasm_bindgen::JsCast;
-use web_sys::{window, InputEvent, MouseEvent};
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{window, MessageEvent, MouseEvent};
 use yew::{html, prelude::*, Html, KeyboardEvent};
 
-#[derive(PartialEq, Clone, Serialize, Deserialize)]
+// Digest and kind of an exported subtree, digest of the schema and format.
+pub type ExportKey = (Digest, Option<u64>, Digest, Format);
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GlobalState {
     pub node_store: Rc<NodeStore>,
-    pub schema: Schema,
+    pub schema: Rc<Schema>,
     pub mode: Mode,
     pub show_serialized: bool,
     pub rich_render: bool,
+    #[serde(skip)]
+    pub node_state: HashMap<Path, NodeState>,
+    // Selected paths of the other collaborators, by client id.
+    #[serde(skip)]
+    pub presence: BTreeMap<String, Path>,
+    // Paths matching the current search, or the last query (see `search.rs` and `query.rs`).
+    #[serde(skip)]
+    pub matches: Vec<Path>,
+    // Paths the next operation applies to, when there are several of them.
+    #[serde(skip)]
+    pub selection: Vec<Path>,
+    // Nodes unfolded by the user, with the number of children shown per field (see
+    // `schema::default_renderer`).
+    #[serde(skip)]
+    pub expanded: HashMap<Path, usize>,
+    // The paths above, indexed by each of their prefixes (see `node::index_subtree_paths`).
+    #[serde(skip)]
+    pub subtree_paths: HashMap<Path, crate::node::SubtreePaths>,
 }
 
 impl GlobalState {
@@ -40,69 +64,158 @@ pub struct Model {
 
     pub selected_path: Path,
     pub hover_path: Path,
-
-    pub node_state: HashMap<Path, NodeState>,
+    // Path selected when entering visual mode.
+    pub visual_anchor: Option<Path>,
+    // Nodes added to the selection one by one.
+    pub marked: Vec<Path>,
+
+    // Name under which a root was saved, and that root, to be put in permalinks to it.
+    pub ref_root: Option<(String, Digest)>,
+    // Statements signing each root (see `identity.rs`), by the digest of the root, to be put in
+    // permalinks to it.
+    pub signatures: BTreeMap<Digest, Vec<Digest>>,
+    // Valid statements signing the current root, and root for which they were last checked.
+    pub statements: Vec<crate::identity::Statement>,
+    pub verified_root: Digest,
+
+    // Inverses of the edits made by each message, and of the undos since the last edit, so that
+    // undoing only reverts local edits, even after remote ones (see `collab.rs`).
+    pub undo: Vec<Vec<crate::collab::Op>>,
+    pub redo: Vec<Vec<crate::collab::Op>>,
+    // Inverses of the edits made by the current message.
+    pub edits: Vec<crate::collab::Op>,
+
+    // Root for which `GlobalState::node_state` was last computed, and the type errors of its
+    // subtrees.
+    pub checked_root: Digest,
+    pub type_errors: crate::typecheck::Errors,
+
+    // Current search, if any, and root for which its matches were last computed.
+    pub search: Option<String>,
+    pub search_index: crate::search::Index,
+    pub searched_root: Digest,
+
+    // Whether the paths indexed in `GlobalState::subtree_paths` changed since it was computed.
+    pub subtree_paths_changed: bool,
 
     pub stack: Vec<Link>,
 
+    pub format: Format,
+    // Last export of the selected subtree, and what it was computed from.
+    pub export: Option<(ExportKey, Result<String, String>)>,
+
+    pub fsck_report: Option<FsckReport>,
+
     pub document_keydown_listener: EventListener,
     pub window_hashchange_listener: EventListener,
-}
-
-#[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
-pub struct HashState {
-    pub root: Digest,
-    pub schema_root: Digest,
-}
 
-impl FromStr for HashState {
-    type Err = String;
+    pub collab: Option<Collab>,
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parts = s.split('@');
-        let root = parts.next().unwrap_or_default().to_string();
-        let schema_root = parts.next().unwrap_or_default().to_string();
-        Ok(Self { root, schema_root })
-    }
+    // Id of this session in the sequences it edits (see `crdt.rs`), also used as collaboration
+    // client id.
+    pub replica: String,
 }
 
-impl std::fmt::Display for HashState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}@{}", self.root, self.schema_root)
-    }
+// Connection to the sync server, while editing collaboratively (see `collab.rs`).
+pub struct Collab {
+    pub client: crate::collab::Client,
+    pub socket: web_sys::WebSocket,
+    // Selected path last sent to the other clients.
+    pub presence: Option<Path>,
+    _onopen: Closure<dyn FnMut()>,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Msg {
     Select(Path),
     Hover(Path),
+    // Unfold the node at the path, showing the given number of children per field.
+    Expand(Path, usize),
 
     StoreLocal,
     LoadLocal,
+    // Merge the tree in local storage, e.g. edited offline, into the current one.
+    MergeLocal,
+
+    // Sign the current root with the key pair in local storage, creating one if needed.
+    SignRoot,
+
+    // Encrypt the selected subtree with the key in local storage, creating one if needed.
+    Encrypt,
+    // Add a key, entered by the user, to those in local storage.
+    Unlock,
 
     StoreRemote(String), // API_URL
     LoadRemote(String),
 
+    // Ask for the URL of a sync server, and collaborate through it.
+    PromptCollaborate,
+    // Edit the current tree together with the other clients of the given sync server.
+    Collaborate(String), // COLLAB_URL
+    CollabOpen,
+    CollabMessage(String),
+
     // Add nodes to the store.
     AddNodesRequest(Vec<Digest>, String), // API_URL
-    AddNodesResponse(Vec<Vec<u8>>, String),
+    AddNodesResponse(Vec<(Digest, Vec<u8>)>, String),
 
-    // Set root node from hash fragment.
+    // Verify the integrity of the store, optionally repairing it from the given API_URL.
+    Fsck(Option<String>),
+
+    // Set root node, selected path and view options from hash fragment (see `permalink.rs`).
     SetHashState(HashState),
+    // Ask for a name, and save the root under it.
+    SaveRef,
 
     Parse(String),
 
     Prev,
     Next,
     Parent,
+    // Select the next (or previous) sibling in the same field.
+    NextSibling,
+    PrevSibling,
+    // Add the selected node to the selection, or remove it.
+    ToggleMark,
+    // Select the next (or previous) hole in the tree.
+    NextHole,
+    PrevHole,
+
+    // Ask for a query, then select its matches.
+    PromptQuery,
+    // Highlight the nodes matching the given query, and select the first one.
+    SelectMatches(String),
+    // Ask for a search, then run it.
+    PromptSearch,
+    // Highlight the nodes matching the given search (see `search.rs`), and select the first one;
+    // `n` and `N` then step through them. An empty search clears the current one.
+    Search(String),
+
+    // Apply a refactoring to the node at the given path (see `refactor.rs`).
+    Refactor(Path, crate::refactor::Refactoring),
+    // Ask for a refactoring, then apply it to the node at the given path.
+    PromptRefactor(Path),
+    // Ask for a query and a refactoring, then apply it to every match of the query.
+    PromptRefactorAll,
+    RefactorAll(String, String),
 
     AddItem,
     DeleteItem,
+    // Push the selected nodes onto the stack.
+    Yank,
+    Undo,
+    Redo,
+    // Store the field of the selected item as a sequence, giving its items stable ids.
+    EnableSequence,
 
     SetMode(Mode),
+    // Format used to parse and display the selected subtree.
+    SetFormat(Format),
 
-    ReplaceNode(Path, Node, bool),
     AddField(Path, u64),
+    // Fill the hole at the given path with a node of its kind, setting the given `oneof` field.
+    FillHole(Path, Option<u64>),
 
     SetNodeValue(Path, Vec<u8>),
 
@@ -128,9 +241,11 @@ impl Component for Model {
             e.stop_propagation();
             Msg::Hover(vec![])
         });
+        // Only parsed once the text is committed (e.g. when the text area loses focus), rather than
+        // on every keystroke.
         let parse = ctx
             .link()
-            .callback(move |e: InputEvent| Msg::Parse(get_value_from_input_event(e)));
+            .callback(move |e: Event| Msg::Parse(get_value_from_event(&e)));
 
         let serialized = if self.global_state.show_serialized {
             html! {
@@ -145,22 +260,7 @@ impl Component for Model {
             Msg::CommandKey(vec![], e)
         });
 
-        let root_kind_id = self
-            .global_state
-            .schema
-            .root_kind()
-            .cloned()
-            .unwrap_or_default()
-            .kind_id;
-
-        let cursor = Cursor {
-            parent: None,
-            link: Link {
-                type_: LinkType::Dag,
-                digest: self.root.clone(),
-            },
-            kind_id: root_kind_id,
-        };
+        let cursor = self.root();
         log::info!("root cursor: {:?}", cursor);
 
         html! {
@@ -175,12 +275,25 @@ impl Component for Model {
                     <div>{ "j: select next node" }</div>
                     <div>{ "k: select previous node" }</div>
                     <div>{ "h: select parent node" }</div>
+                    <div>{ "/: search" }</div>
+                    <div>{ "n: select next hole, or next match while searching" }</div>
+                    <div>{ "N: select previous hole, or previous match while searching" }</div>
+                    <div>{ "Escape: stop searching, and clear the selection" }</div>
+                    <div>{ "v: switch to Visual mode, to select a range of siblings with j and k" }</div>
+                    <div>{ "Space: add the selected node to the selection, or remove it" }</div>
+                    <div>{ "d: delete the selection" }</div>
+                    <div>{ "y: yank the selection" }</div>
+                    <div>{ "r: refactor the selection" }</div>
+                    <div>{ "J / K: move the selection down / up" }</div>
+                    <div>{ "u: undo" }</div>
+                    <div>{ "Ctrl-r: redo" }</div>
                     <div>{ "Enter: switch to Edit mode" }</div>
                     <div>{ "Or click on a node to select it, then press Enter to add a link to it" }</div>
                     <div>{ "Edit mode keys:" }</div>
                     <div>{ "Escape: switch to Normal mode" }</div>
                     <div class="column">
                         <div>{ "Mode: " }{ format!("{:?}", self.global_state.mode) }</div>
+                        <div>{ "Format: " }{ format!("{:?}", self.format) }</div>
                         <div class="h-8">{ display_cursor(&self.selected_path) }</div>
                     </div>
 
@@ -198,7 +311,13 @@ impl Component for Model {
                 <div class="h-40">
                     <div>{ format!("Ref: {:?}", self.path(&self.selected_path).map(|c| c.link)) }</div>
                     <div>{ format!("Node: {:?}", self.path(&self.selected_path).and_then(|c| c.link.get(&self.global_state.node_store))) }</div>
-                    <textarea type="text" class="border-solid border-black border" oninput={ parse } />
+                    <textarea type="text" class="border-solid border-black border" onchange={ parse } />
+                    { self.view_export() }
+                    { self.view_command() }
+                    { self.view_eval(ctx) }
+                    { self.view_fsck_report() }
+                    { self.view_signatures() }
+                    { self.view_search_results(ctx) }
                     { serialized }
                 </div>
             </div>
@@ -230,18 +349,30 @@ impl Component for Model {
             "hashchange",
             move |e: &Event| {
                 e.stop_propagation();
-                window_listener.emit(get_location_hash().parse::<HashState>().unwrap());
+                match get_location_hash().parse::<HashState>() {
+                    Ok(hash_state) => window_listener.emit(hash_state),
+                    Err(err) => log::error!("invalid location: {}", err),
+                }
             },
         );
 
-        let (node_store, root) = super::initial::initial();
+        let (mut node_store, root) = super::initial::initial();
+        for key in stored_keys() {
+            node_store.add_key(key);
+        }
         Model {
             global_state: Rc::new(GlobalState {
                 node_store: Rc::new(node_store),
-                schema: super::initial::initial_schema(),
+                schema: Rc::new(super::initial::initial_schema()),
                 mode: Mode::Normal,
                 show_serialized: false,
                 rich_render: true,
+                node_state: HashMap::new(),
+                presence: BTreeMap::new(),
+                matches: vec![],
+                selection: vec![],
+                expanded: HashMap::new(),
+                subtree_paths: HashMap::new(),
             }),
 
             root,
@@ -249,13 +380,39 @@ impl Component for Model {
 
             selected_path: vec![],
             hover_path: vec![],
+            visual_anchor: None,
+            marked: vec![],
+
+            ref_root: None,
+            signatures: BTreeMap::new(),
+            statements: vec![],
+            verified_root: "".to_string(),
+
+            undo: vec![],
+            redo: vec![],
+            edits: vec![],
 
-            node_state: HashMap::new(),
+            checked_root: "".to_string(),
+            type_errors: Default::default(),
+
+            search: None,
+            search_index: Default::default(),
+            searched_root: "".to_string(),
+            subtree_paths_changed: true,
 
             stack: vec![],
 
+            format: Format::Json,
+            export: None,
+
+            fsck_report: None,
+
             document_keydown_listener,
             window_hashchange_listener,
+
+            collab: None,
+
+            replica: uuid::Uuid::new_v4().to_string(),
         }
     }
 
@@ -266,7 +423,7 @@ impl Component for Model {
     fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
         if first_render {
             ctx.link().send_message_batch(vec![
-                Msg::SetHashState(get_location_hash().parse::<HashState>().unwrap()),
+                Msg::SetHashState(get_location_hash().parse::<HashState>().unwrap_or_default()),
                 Msg::LoadRemote(crate::ent::API_URL_LOCALHOST.to_string()),
             ]);
         }
@@ -277,8 +434,14 @@ impl Component for Model {
             return false;
         }
         log::info!("update {:?}", msg);
+        // Each message is one undo step, however many nodes it changes.
+        let (undoing, redoing) = (matches!(msg, Msg::Undo), matches!(msg, Msg::Redo));
+        // The state in the current history entry, e.g. after going back, and remote edits do not
+        // get a new one.
+        let external = matches!(msg, Msg::SetHashState(_) | Msg::CollabMessage(_));
         const GLOBAL_STATE_KEY: &str = "linc_global_state";
         const ROOT_NODE_KEY: &str = "linc_root_node";
+        const IDENTITY_KEY: &str = "linc_identity";
         match msg {
             Msg::ToggleSerialized => {
                 self.global_state_mut().show_serialized = !self.global_state.show_serialized;
@@ -292,6 +455,10 @@ impl Component for Model {
             Msg::Hover(path) => {
                 self.hover_path = path;
             }
+            Msg::Expand(path, shown) => {
+                self.global_state_mut().expanded.insert(path, shown);
+                self.subtree_paths_changed = true;
+            }
             // TODO: sibling vs inner
             Msg::Prev => {
                 self.prev();
@@ -303,6 +470,49 @@ impl Component for Model {
             Msg::Parent => {
                 self.parent();
             }
+            Msg::NextSibling => {
+                self.sibling(1);
+            }
+            Msg::PrevSibling => {
+                self.sibling(-1);
+            }
+            Msg::ToggleMark => {
+                let path = self.selected_path.clone();
+                match self.marked.iter().position(|p| *p == path) {
+                    Some(index) => {
+                        self.marked.remove(index);
+                    }
+                    None => self.marked.push(path),
+                }
+            }
+            Msg::Yank => {
+                for path in self.selected_paths() {
+                    if let Some(cursor) = self.path(&path) {
+                        self.stack.push(cursor.link);
+                    }
+                }
+                self.clear_selection();
+            }
+            Msg::Undo => {
+                for op in self.undo.pop().unwrap_or_default().into_iter().rev() {
+                    self.apply_edit(op);
+                }
+            }
+            Msg::Redo => {
+                for op in self.redo.pop().unwrap_or_default().into_iter().rev() {
+                    self.apply_edit(op);
+                }
+            }
+            Msg::NextHole => {
+                if let Some(next) = hole::next_hole(&self.targets(), &self.selected_path) {
+                    self.selected_path = next;
+                }
+            }
+            Msg::PrevHole => {
+                if let Some(prev) = hole::prev_hole(&self.targets(), &self.selected_path) {
+                    self.selected_path = prev;
+                }
+            }
             Msg::Cut => {
                 if let Some(cursor) = self.path(&self.selected_path) {
                     self.stack.push(cursor.link);
@@ -322,8 +532,130 @@ impl Component for Model {
                 let res: gloo_storage::Result<GlobalState> = LocalStorage::get(GLOBAL_STATE_KEY);
                 if let Ok(global_state) = res {
                     self.global_state = Rc::new(global_state);
+                    self.subtree_paths_changed = true;
+                    for key in stored_keys() {
+                        self.global_state_mut().node_store_mut().add_key(key);
+                    }
+                }
+                let root = LocalStorage::get(ROOT_NODE_KEY).unwrap();
+                self.replace_link(
+                    &[],
+                    Link {
+                        type_: LinkType::Dag,
+                        digest: root,
+                    },
+                );
+            }
+            Msg::MergeLocal => {
+                let res: gloo_storage::Result<GlobalState> = LocalStorage::get(GLOBAL_STATE_KEY);
+                let root: gloo_storage::Result<Digest> = LocalStorage::get(ROOT_NODE_KEY);
+                if let (Ok(global_state), Ok(root)) = (res, root) {
+                    let root_link = self.root_link();
+                    // Keep the same node selected, wherever the merge moves it.
+                    let selected = crate::crdt::stable_path(
+                        &self.global_state.node_store,
+                        &root_link,
+                        &self.selected_path,
+                    );
+                    let node_store = self.global_state_mut().node_store_mut();
+                    for (digest, value) in global_state.node_store.iter() {
+                        let _ = node_store.put_raw_checked(digest, value);
+                    }
+                    let other = Link {
+                        type_: LinkType::Dag,
+                        digest: root,
+                    };
+                    let merged = crate::crdt::merge(node_store, &root_link, &other);
+                    if let Some(node) = merged.get(&self.global_state.node_store) {
+                        let node = node.as_parsed().unwrap().clone();
+                        self.replace_node(&[], &node);
+                        self.selected_path = crate::crdt::resolve_path(
+                            &self.global_state.node_store,
+                            &merged,
+                            &selected,
+                        )
+                        .unwrap_or_default();
+                    }
+                }
+            }
+            Msg::SignRoot => {
+                let stored: gloo_storage::Result<crate::identity::Identity> =
+                    LocalStorage::get(IDENTITY_KEY);
+                let identity = match stored {
+                    Ok(identity) => Some(identity),
+                    Err(_) => gloo_utils::window()
+                        .prompt_with_message("Author name")
+                        .ok()
+                        .flatten()
+                        .and_then(
+                            |author| match crate::identity::Identity::generate(&author) {
+                                Ok(identity) => Some(identity),
+                                Err(err) => {
+                                    log::error!("could not generate key pair: {}", err);
+                                    None
+                                }
+                            },
+                        )
+                        .filter(|identity| LocalStorage::set(IDENTITY_KEY, identity).is_ok()),
+                };
+                if let Some(identity) = identity {
+                    let root = self.root.clone();
+                    let statement = identity.sign(self.global_state_mut().node_store_mut(), &root);
+                    let signatures = self.signatures.entry(root.clone()).or_default();
+                    if !signatures.contains(&statement.digest) {
+                        signatures.push(statement.digest.clone());
+                    }
+                    self.verified_root = "".to_string();
+                    log::info!(
+                        "signed {} as {}: {}",
+                        root,
+                        identity.author,
+                        statement.digest
+                    );
+                }
+            }
+            Msg::Encrypt => {
+                let key = match stored_keys().first() {
+                    Some(key) => Ok(key.clone()),
+                    None => crate::encryption::Key::generate().inspect(|key| {
+                        let _ = LocalStorage::set(KEYS_KEY, vec![key.to_hex()]);
+                    }),
+                };
+                let path = self.selected_path.clone();
+                let link = self.path(&path).map(|cursor| cursor.link);
+                match (key, link) {
+                    // The root is always a plain node.
+                    _ if path.is_empty() => log::warn!("cannot encrypt the root"),
+                    (Ok(key), Some(link)) => {
+                        let node_store = self.global_state_mut().node_store_mut();
+                        match crate::encryption::encrypt(node_store, &key, &link) {
+                            Ok(link) => self.replace_link(&path, link),
+                            Err(err) => log::error!("could not encrypt: {}", err),
+                        }
+                    }
+                    (Err(err), _) => log::error!("could not generate key: {}", err),
+                    (_, None) => {}
+                }
+            }
+            Msg::Unlock => {
+                // The prompt shows the current key, so that it can be copied and shared.
+                let current = stored_keys().first().map(|key| key.to_hex());
+                let text = gloo_utils::window()
+                    .prompt_with_message_and_default("Key (hex)", &current.unwrap_or_default())
+                    .ok()
+                    .flatten();
+                match text.map(|text| crate::encryption::Key::from_hex(&text)) {
+                    Some(Ok(key)) => {
+                        let mut keys: Vec<String> = LocalStorage::get(KEYS_KEY).unwrap_or_default();
+                        if !keys.contains(&key.to_hex()) {
+                            keys.push(key.to_hex());
+                            let _ = LocalStorage::set(KEYS_KEY, keys);
+                        }
+                        self.global_state_mut().node_store_mut().add_key(key);
+                    }
+                    Some(Err(err)) => log::error!("invalid key: {}", err),
+                    None => {}
                 }
-                self.root = LocalStorage::get(ROOT_NODE_KEY).unwrap();
             }
             Msg::StoreRemote(api_url) => {
                 log::info!(
@@ -335,7 +667,7 @@ impl Component for Model {
                     blobs: self
                         .global_state
                         .node_store
-                        .iter()
+                        .iter_public()
                         .map(|(_k, v)| v.clone())
                         .collect(),
                 };
@@ -345,9 +677,63 @@ impl Component for Model {
                     Msg::Next
                 });
             }
+            Msg::PromptCollaborate => {
+                let url = gloo_utils::window()
+                    .prompt_with_message_and_default(
+                        "WebSocket URL of the sync server",
+                        crate::ent::COLLAB_URL_LOCALHOST,
+                    )
+                    .ok()
+                    .flatten()
+                    .filter(|url| !url.is_empty());
+                if let Some(url) = url {
+                    ctx.link().send_message(Msg::Collaborate(url));
+                }
+            }
+            Msg::Collaborate(url) => {
+                let socket = match web_sys::WebSocket::new(&url) {
+                    Ok(socket) => socket,
+                    Err(err) => {
+                        log::error!("could not connect to {}: {:?}", url, err);
+                        return false;
+                    }
+                };
+                let onopen = ctx.link().callback(|()| Msg::CollabOpen);
+                let onopen = Closure::<dyn FnMut()>::new(move || onopen.emit(()));
+                socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+                let onmessage = ctx.link().callback(Msg::CollabMessage);
+                let onmessage = Closure::<dyn FnMut(MessageEvent)>::new(move |e: MessageEvent| {
+                    if let Some(text) = e.data().as_string() {
+                        onmessage.emit(text);
+                    }
+                });
+                socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+                self.collab = Some(Collab {
+                    client: crate::collab::Client::new(&self.replica, self.root_link()),
+                    socket,
+                    presence: None,
+                    _onopen: onopen,
+                    _onmessage: onmessage,
+                });
+            }
+            Msg::CollabOpen => {
+                if let Some(collab) = &self.collab {
+                    let join = collab.client.join();
+                    self.send(join);
+                }
+            }
+            Msg::CollabMessage(text) => self.receive(&text),
             Msg::LoadRemote(api_url) => {
+                let mut digests = vec![self.root.clone()];
+                digests.extend(
+                    self.signatures
+                        .get(&self.root)
+                        .into_iter()
+                        .flatten()
+                        .cloned(),
+                );
                 ctx.link()
-                    .send_message(Msg::AddNodesRequest(vec![self.root.clone()], api_url));
+                    .send_message(Msg::AddNodesRequest(digests, api_url));
             }
             Msg::AddNodesRequest(digests, api_url) => {
                 let req = crate::ent::GetRequest {
@@ -363,8 +749,8 @@ impl Component for Model {
                     match c.get_blobs(&req).await {
                         Ok(res) => Msg::AddNodesResponse(
                             res.items
-                                .values()
-                                .filter_map(|v| base64::decode(&v).map(|v| v.to_vec()).ok())
+                                .into_iter()
+                                .filter_map(|(k, v)| base64::decode(&v).map(|v| (k, v)).ok())
                                 .collect(),
                             api_url,
                         ),
@@ -376,12 +762,28 @@ impl Component for Model {
                 });
             }
             Msg::AddNodesResponse(nodes, api_url) => {
-                self.global_state_mut()
-                    .node_store_mut()
-                    .put_many_raw(&nodes);
+                // Do not trust the server: only keep blobs that match the requested digest.
+                let nodes: Vec<_> = nodes
+                    .into_iter()
+                    .filter(|(d, b)| {
+                        match self
+                            .global_state_mut()
+                            .node_store_mut()
+                            .put_raw_checked(d, b)
+                        {
+                            Ok(()) => true,
+                            Err(actual) => {
+                                log::error!("digest mismatch: {} hashes to {}", d, actual);
+                                false
+                            }
+                        }
+                    })
+                    .collect();
+                // Nodes are read from the store, so that the children of encrypted nodes that
+                // one of the keys decrypts are fetched too.
                 let all_hashes: Vec<_> = nodes
                     .into_iter()
-                    .flat_map(|b| crate::types::deserialize_node(&b))
+                    .flat_map(|(d, _b)| self.global_state.node_store.get_dag(&d))
                     .flat_map(|n| n.links.into_values().flatten())
                     .filter(|link| !self.global_state.node_store.has_raw_node(&link.digest))
                     .map(|link| link.digest)
@@ -390,95 +792,146 @@ impl Component for Model {
                     ctx.link()
                         .send_message(Msg::AddNodesRequest(all_hashes, api_url));
                 }
+                // Statements may have been fetched.
+                self.verified_root = "".to_string();
+            }
+            Msg::Fsck(api_url) => {
+                let report = crate::fsck::fsck(&self.global_state.node_store);
+                log::info!("fsck: {} problems", report.problems.len());
+                if let Some(api_url) = api_url {
+                    for digest in report.corrupted() {
+                        self.global_state_mut().node_store_mut().remove(&digest);
+                    }
+                    let missing = report.missing();
+                    if !missing.is_empty() {
+                        ctx.link()
+                            .send_message(Msg::AddNodesRequest(missing, api_url));
+                    }
+                }
+                self.fsck_report = Some(report);
             }
             Msg::SetHashState(hash_state) => {
+                let ref_root = hash_state
+                    .ref_name
+                    .as_ref()
+                    .and_then(|name| stored_refs().get(name).cloned());
+                let old_root = self.root.clone();
                 if !hash_state.root.is_empty() {
                     self.root = hash_state.root;
+                } else if let Some(root) = &ref_root {
+                    self.root = root.clone();
+                }
+                // Edits of another root cannot be undone on this one.
+                if self.root != old_root {
+                    self.undo.clear();
+                    self.redo.clear();
                 }
                 if !hash_state.schema_root.is_empty() {
                     self.schema_root = hash_state.schema_root;
                 }
-                set_location_hash(&format!("{}@{}", &self.root, &self.schema_root));
+                self.ref_root = hash_state.ref_name.zip(ref_root);
+                if !hash_state.signatures.is_empty() {
+                    let signatures = self.signatures.entry(self.root.clone()).or_default();
+                    for digest in hash_state.signatures {
+                        if !signatures.contains(&digest) {
+                            signatures.push(digest);
+                        }
+                    }
+                    self.verified_root = "".to_string();
+                }
+                if let Some(path) = hash_state.path {
+                    let root_kind = self.root().kind_id().unwrap_or_default();
+                    match crate::permalink::decode_path(&self.global_state.schema, root_kind, &path)
+                    {
+                        Ok(path) => self.selected_path = path,
+                        Err(err) => log::error!("invalid path {:?}: {}", path, err),
+                    }
+                }
+                if let Some(format) = hash_state.format {
+                    self.format = format;
+                }
+                let global_state = self.global_state_mut();
+                global_state.rich_render = !hash_state.raw;
+                global_state.show_serialized = hash_state.show_serialized;
             }
-            Msg::Parse(v) => {
-                /*
-                log::debug!("parse {:?}", v);
-                let html = html_parser::Dom::parse(&v).unwrap();
-                log::debug!("parsed {:?}", html);
-                fn add_string(model: &mut Model, value: &str) -> Hash {
-                    model.node_store.add_node(&Node {
-                        kind: "string".into(),
-                        value: value.into(),
-                        links: BTreeMap::new(),
-                    })
+            Msg::SaveRef => {
+                let name = gloo_utils::window()
+                    .prompt_with_message("Name of this version, e.g. release-1")
+                    .ok()
+                    .flatten()
+                    .filter(|name| !name.is_empty());
+                if let Some(name) = name {
+                    let mut refs = stored_refs();
+                    refs.insert(name.clone(), self.root.clone());
+                    if let Err(err) = LocalStorage::set(REFS_KEY, refs) {
+                        log::error!("could not save ref {:?}: {:?}", name, err);
+                    }
+                    self.ref_root = Some((name, self.root.clone()));
                 }
-                fn add_node(model: &mut Model, node: &html_parser::Node) -> Hash {
-                    match node {
-                        html_parser::Node::Element(e) => {
-                            let mut children: BTreeMap<usize, Vec<String>> = BTreeMap::new();
-                            e.attributes.iter().for_each(|(k, v)| {
-                                children.entry(k.clone()).or_insert_with(Vec::new).push(
-                                    add_string(model, &v.as_ref().cloned().unwrap_or_default()),
-                                );
-                            });
-                            e.children.iter().for_each(|v| {
-                                children
-                                    .entry("children".to_string())
-                                    .or_insert_with(Vec::new)
-                                    .push(add_node(model, v));
-                            });
-                            model.node_store.add_node(&Node {
-                                kind: e.name.clone(),
-                                value: "".into(),
-                                links: children,
-                            })
+            }
+            Msg::Parse(v) => {
+                // Replace the selected subtree, interpreting the value according to its kind.
+                if let Some(cursor) = self.path(&self.selected_path) {
+                    let selected_path = self.selected_path.clone();
+                    match cursor.kind_id() {
+                        Some(kind_id) => match self.import(kind_id, &v) {
+                            Ok(link) => {
+                                let node_store = &self.global_state.node_store;
+                                if let Some(node) = node_store.get_dag(&link.digest) {
+                                    self.replace_node(&selected_path, &node);
+                                }
+                            }
+                            Err(err) => log::warn!("could not import {:?}: {}", self.format, err),
+                        },
+                        // Leaves are set to the value itself, if it is valid for their type.
+                        None if FieldValue::from_raw(&cursor.type_, v.as_bytes()).is_some() => {
+                            self.set_node_value(&selected_path, v.as_bytes());
                         }
-                        html_parser::Node::Text(t) => add_string(model, t),
-                        html_parser::Node::Comment(c) => add_string(model, c),
+                        None => log::warn!("invalid {:?} value", cursor.type_),
                     }
                 }
-                fn add_dom(model: &mut Model, e: &html_parser::Dom) -> Hash {
-                    let mut children: BTreeMap<String, Vec<String>> = BTreeMap::new();
-                    e.children.iter().for_each(|v| {
-                        children
-                            .entry("children".to_string())
-                            .or_insert_with(Vec::new)
-                            .push(add_node(model, v));
-                    });
-                    model.node_store.add_node(&Node {
-                        kind: "dom".into(),
-                        value: "".to_string(),
-                        links: children,
-                    })
-                }
-                let h = add_dom(self, &html);
-                self.node_store.root = h;
-                    */
+            }
+            Msg::SetFormat(format) => {
+                self.format = format;
             }
             Msg::SetMode(mode) => {
+                self.visual_anchor = match mode {
+                    Mode::Visual => Some(self.selected_path.clone()),
+                    _ => None,
+                };
                 Rc::make_mut(&mut self.global_state).mode = mode;
             }
             Msg::AddField(path, field_id) => {
-                let mut node = self
-                    .path(&path)
-                    .unwrap()
+                let cursor = self.path(&path).unwrap();
+                let mut node = cursor
                     .link
                     .get(&self.global_state.node_store)
                     .unwrap()
                     .as_parsed()
                     .unwrap()
                     .clone();
-                let new_digest = self
-                    .global_state_mut()
-                    .node_store_mut()
-                    .put_parsed(&Node::default());
+                let default = cursor
+                    .kind_id()
+                    .and_then(|kind_id| self.global_state.schema.get_kind(kind_id))
+                    .and_then(|kind| kind.get_field(field_id))
+                    .and_then(|field| FieldValue::default_for(&field.type_))
+                    .and_then(|value| value.to_raw());
+                // Primitive fields start with the default value of their type.
+                let node_store = self.global_state_mut().node_store_mut();
+                let new_link = match default {
+                    Some(raw) => Link {
+                        type_: LinkType::Raw,
+                        digest: node_store.put_raw(&raw),
+                    },
+                    None => Link {
+                        type_: LinkType::Dag,
+                        digest: node_store.put_parsed(&Node::default()),
+                    },
+                };
                 node.links
                     .entry(field_id)
                     .or_insert_with(Vec::new)
-                    .push(Link {
-                        type_: LinkType::Dag,
-                        digest: new_digest,
-                    });
+                    .push(new_link);
                 let n = node.links[&field_id].len();
                 self.replace_node(&path, &node);
                 self.selected_path = append(
@@ -486,81 +939,204 @@ impl Component for Model {
                     Selector {
                         field_id,
                         index: n - 1,
+                        id: None,
                     },
                 );
-                set_location_hash(&self.root);
             }
-            Msg::ReplaceNode(path, node, mv) => {
-                log::info!("replace node {:?} {:?}", path, node);
-                self.replace_node(&path, &node);
-                if mv {
-                    ctx.link().send_message(Msg::Next);
-                } else {
-                    ctx.link().send_message(Msg::Select(path));
+            Msg::FillHole(path, variant) => {
+                if let Some(kind_id) = self.path(&path).and_then(|cursor| cursor.kind_id()) {
+                    let schema = self.global_state.schema.clone();
+                    let node = hole::fill(
+                        self.global_state_mut().node_store_mut(),
+                        &schema,
+                        kind_id,
+                        variant,
+                    );
+                    self.replace_node(&path, &node);
+                    self.selected_path = path;
+                    // Move on to the first hole of the new node, if any.
+                    if let Some(next) = hole::next_hole(&self.holes(), &self.selected_path) {
+                        if next.starts_with(&self.selected_path) {
+                            self.selected_path = next;
+                        }
+                    }
                 }
-                set_location_hash(&self.root);
             }
             Msg::SetNodeValue(path, value) => {
                 self.selected_path = path.clone();
                 self.set_node_value(&path, &value);
-                set_location_hash(&self.root);
+            }
+            Msg::PromptQuery => {
+                let text = gloo_utils::window()
+                    .prompt_with_message("Query, e.g. root..docker_run.env")
+                    .ok()
+                    .flatten();
+                if let Some(text) = text {
+                    ctx.link().send_message(Msg::SelectMatches(text));
+                }
+            }
+            Msg::SelectMatches(text) => match crate::query::Query::parse(&text) {
+                Ok(query) => {
+                    let matches = query.select_paths(
+                        &self.global_state.node_store,
+                        &self.global_state.schema,
+                        &self.root(),
+                    );
+                    if let Some(path) = matches.first() {
+                        self.selected_path = path.clone();
+                    }
+                    self.global_state_mut().matches = matches;
+                    self.subtree_paths_changed = true;
+                }
+                Err(err) => log::error!("invalid query {:?}: {}", text, err),
+            },
+            Msg::Refactor(path, refactoring) => {
+                let paths = self.selected_paths();
+                if paths.len() > 1 && paths.contains(&path) {
+                    self.refactor_selection(paths, &refactoring);
+                } else {
+                    self.refactor(&path, &refactoring);
+                }
+            }
+            Msg::PromptRefactor(path) => {
+                let text = gloo_utils::window()
+                    .prompt_with_message(
+                        "Refactoring: wrap KIND FIELD..., unwrap, move FIELD INDEX, swap INDEX, \
+                         extract NAME or rename NAME",
+                    )
+                    .ok()
+                    .flatten();
+                let parent_kind_id = self.path(parent(&path)).and_then(|c| c.kind_id());
+                match text.map(|text| {
+                    crate::refactor::Refactoring::parse(
+                        &self.global_state.schema,
+                        parent_kind_id,
+                        &text,
+                    )
+                }) {
+                    Some(Ok(refactoring)) => {
+                        ctx.link().send_message(Msg::Refactor(path, refactoring))
+                    }
+                    Some(Err(err)) => log::error!("invalid refactoring: {}", err),
+                    None => {}
+                }
+            }
+            Msg::PromptRefactorAll => {
+                let window = gloo_utils::window();
+                let query = window
+                    .prompt_with_message("Query, e.g. root..FunctionDefinition")
+                    .ok()
+                    .flatten();
+                let refactoring = query.as_ref().and_then(|_| {
+                    window
+                        .prompt_with_message("Refactoring to apply to every match")
+                        .ok()
+                        .flatten()
+                });
+                if let (Some(query), Some(refactoring)) = (query, refactoring) {
+                    ctx.link()
+                        .send_message(Msg::RefactorAll(query, refactoring));
+                }
+            }
+            Msg::RefactorAll(query, refactoring) => {
+                let query = crate::query::Query::parse(&query).map_err(|e| e.to_string());
+                let schema = self.global_state.schema.clone();
+                let refactoring = crate::refactor::Refactoring::parse(&schema, None, &refactoring);
+                let root = self.root();
+                let replica = self.replica.clone();
+                let result = query.and_then(|query| {
+                    crate::refactor::refactor_all(
+                        self.global_state_mut().node_store_mut(),
+                        &schema,
+                        &root,
+                        &query,
+                        &refactoring?,
+                        &replica,
+                    )
+                    .map_err(|e| e.to_string())
+                });
+                match result {
+                    Ok(link) => {
+                        self.replace_link(&[], link);
+                    }
+                    Err(err) => log::error!("could not refactor: {}", err),
+                }
+            }
+            Msg::PromptSearch => {
+                let text = gloo_utils::window()
+                    .prompt_with_message_and_default(
+                        "Search: text, /regex/, kind:name, field:name, or ?query",
+                        self.search.as_deref().unwrap_or_default(),
+                    )
+                    .ok()
+                    .flatten();
+                if let Some(text) = text {
+                    ctx.link().send_message(Msg::Search(text));
+                }
+            }
+            Msg::Search(text) => {
+                self.search = Some(text).filter(|text| !text.is_empty());
+                self.searched_root = "".to_string();
+                self.update_search();
+                if let Some(path) = self.global_state.matches.first() {
+                    self.selected_path = path.clone();
+                }
             }
             Msg::AddItem => {
                 let selected_path = self.selected_path.clone();
                 let (selector, parent_path) = selected_path.split_last().unwrap();
                 let new_ref = self.global_state_mut().node_store_mut().put_parsed(&Node {
                     links: BTreeMap::new(),
+                    sequences: BTreeMap::new(),
                 });
-                let mut parent = self
-                    .path(parent_path)
-                    .unwrap()
-                    .link
-                    .get(&self.global_state.node_store)
-                    .unwrap()
-                    .as_parsed()
-                    .unwrap()
-                    .clone();
-                // If the field does not exist, create a default one.
-                let children = parent.links.entry(selector.field_id).or_default();
-                let new_index = selector.index + 1;
-                children.insert(
-                    new_index,
-                    Link {
-                        // TODO: Or should this be raw?
-                        type_: LinkType::Dag,
-                        digest: new_ref,
+                let link = Link {
+                    // TODO: Or should this be raw?
+                    type_: LinkType::Dag,
+                    digest: new_ref,
+                };
+                let new_path = append(
+                    parent_path,
+                    Selector {
+                        field_id: selector.field_id,
+                        index: selector.index + 1,
+                        id: None,
                     },
                 );
-                self.replace_node(parent_path, &parent);
+                self.splice_link(&new_path, Some(link));
                 // Select newly created element.
-                self.selected_path.last_mut().unwrap().index = new_index;
+                if self.path(&new_path).is_some() {
+                    self.selected_path = new_path;
+                }
                 // self.next();
-                set_location_hash(&self.root);
             }
             Msg::DeleteItem => {
                 let selected_path = self.selected_path.clone();
-                if selected_path.is_empty() {
+                let paths = self.selected_paths();
+                if paths.len() > 1 {
+                    self.refactor_selection(paths, &crate::refactor::Refactoring::Delete);
+                } else if selected_path.is_empty() {
                     self.replace_node(&[], &Node::default());
                 } else {
-                    let (selector, parent_path) = selected_path.split_last().unwrap();
-                    let mut parent = self
-                        .path(parent_path)
-                        .unwrap()
-                        .link
-                        .get(&self.global_state.node_store)
-                        .unwrap()
-                        .as_parsed()
-                        .unwrap()
-                        .clone();
-                    // If the field does not exist, create a default one.
-                    let children = parent.links.entry(selector.field_id).or_default();
-                    children.remove(selector.index);
-                    self.replace_node(parent_path, &parent);
+                    self.splice_link(&selected_path, None);
                     // Select parent.
                     self.selected_path =
                         self.selected_path[..self.selected_path.len() - 1].to_vec();
                 }
-                set_location_hash(&self.root);
+            }
+            Msg::EnableSequence => {
+                let selected_path = self.selected_path.clone();
+                if let Some((selector, parent_path)) = selected_path.split_last() {
+                    let parent = self.path(parent_path).and_then(|cursor| {
+                        cursor
+                            .link
+                            .get(&self.global_state.node_store)
+                            .and_then(|target| target.as_parsed().cloned())
+                    });
+                    if let Some(mut parent) = parent {
+                        crate::crdt::enable(&mut parent, selector.field_id, &self.replica);
+                        self.replace_node(parent_path, &parent);
+                    }
+                }
             }
             Msg::CommandKey(_path, e) => {
                 log::info!("key: {}", e.key());
@@ -586,6 +1162,13 @@ impl Component for Model {
                         e.stop_propagation();
                         e.prevent_default();
                     }
+                    "Escape" if self.global_state.mode == Mode::Normal => {
+                        self.marked.clear();
+                        ctx.link().send_message(Msg::Search("".to_string()))
+                    }
+                    "Escape" if self.global_state.mode == Mode::Visual => {
+                        ctx.link().send_message(Msg::SetMode(Mode::Normal))
+                    }
                     "Escape" => {
                         self.global_state_mut().mode = Mode::Normal;
                         // If it is a pure value, select the parent again so another field may be
@@ -620,6 +1203,49 @@ impl Component for Model {
                     "ArrowRight" | "j" if self.global_state.mode == Mode::Normal => {
                         ctx.link().send_message(Msg::Next)
                     }
+                    "ArrowLeft" | "k" if self.global_state.mode == Mode::Visual => {
+                        ctx.link().send_message(Msg::PrevSibling)
+                    }
+                    "ArrowRight" | "j" if self.global_state.mode == Mode::Visual => {
+                        ctx.link().send_message(Msg::NextSibling)
+                    }
+                    "v" if self.global_state.mode == Mode::Normal => {
+                        ctx.link().send_message(Msg::SetMode(Mode::Visual))
+                    }
+                    "r" if e.ctrl_key() && self.global_state.mode != Mode::Edit => {
+                        ctx.link().send_message(Msg::Redo)
+                    }
+                    "u" if self.global_state.mode != Mode::Edit => {
+                        ctx.link().send_message(Msg::Undo)
+                    }
+                    " " if self.global_state.mode != Mode::Edit => {
+                        ctx.link().send_message(Msg::ToggleMark)
+                    }
+                    "d" if self.global_state.mode != Mode::Edit => {
+                        ctx.link().send_message(Msg::DeleteItem)
+                    }
+                    "y" if self.global_state.mode != Mode::Edit => {
+                        ctx.link().send_message(Msg::Yank)
+                    }
+                    "r" if self.global_state.mode != Mode::Edit => ctx
+                        .link()
+                        .send_message(Msg::PromptRefactor(self.selected_path.clone())),
+                    "J" | "K" if self.global_state.mode != Mode::Edit => {
+                        let offset = if e.key() == "J" { 1 } else { -1 };
+                        ctx.link().send_message(Msg::Refactor(
+                            self.selected_path.clone(),
+                            crate::refactor::Refactoring::Shift { offset },
+                        ))
+                    }
+                    "n" if self.global_state.mode == Mode::Normal => {
+                        ctx.link().send_message(Msg::NextHole)
+                    }
+                    "N" if self.global_state.mode == Mode::Normal => {
+                        ctx.link().send_message(Msg::PrevHole)
+                    }
+                    "/" if self.global_state.mode == Mode::Normal => {
+                        ctx.link().send_message(Msg::PromptSearch)
+                    }
                     /*
                     "i" if self.mode == Mode::Normal => {
                         e.prevent_default();
@@ -639,24 +1265,79 @@ impl Component for Model {
             }
         };
         // self.focus_command_line();
-        self.update_errors(ctx);
+        let edits = std::mem::take(&mut self.edits);
+        let edited = !edits.is_empty();
+        if edited {
+            if undoing {
+                self.redo.push(edits);
+            } else {
+                self.undo.push(edits);
+                if !redoing {
+                    self.redo.clear();
+                }
+            }
+        }
+        self.update_errors();
+        self.update_search();
+        self.update_statements();
+        self.update_selection();
+        self.update_export();
+        self.update_location(!edited && !external);
+        self.send_presence();
+        self.update_subtree_paths();
         true
     }
 }
 
+// Renders a node as an escaped shell command line.
+fn command_line(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+) -> Result<String, String> {
+    crate::argv::render(node_store, schema, kind_id, link)
+        .map(|args| crate::argv::shell_line(&args))
+        .map_err(|e| e.to_string())
+}
+
+// Keys of encrypted subtrees (see `encryption.rs`), hex encoded. They are kept apart from the
+// global state, whose node store does not serialize them.
+const KEYS_KEY: &str = "linc_keys";
+
+fn stored_keys() -> Vec<crate::encryption::Key> {
+    let keys: Vec<String> = LocalStorage::get(KEYS_KEY).unwrap_or_default();
+    keys.iter()
+        .filter_map(|key| crate::encryption::Key::from_hex(key).ok())
+        .collect()
+}
+
+// Names under which roots were saved in this browser.
+const REFS_KEY: &str = "linc_refs";
+
+fn stored_refs() -> BTreeMap<String, Digest> {
+    LocalStorage::get(REFS_KEY).unwrap_or_default()
+}
+
 fn get_location_hash() -> String {
     let state = web_sys::window().unwrap().location().hash().unwrap();
     log::info!("state: {:?}", state);
     state.strip_prefix("#").unwrap_or(&state).to_string()
 }
 
-fn set_location_hash(v: &str) {
-    web_sys::window()
-        .unwrap()
-        .history()
-        .unwrap()
-        .replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&format!("#{}", v)))
-        .unwrap();
+// Sets the hash fragment, in a new history entry if `push` is set, so that the browser can go back
+// to the previous one, or else in place of the current one.
+fn set_location_hash(v: &str, push: bool) {
+    let history = web_sys::window().unwrap().history().unwrap();
+    let url = format!("#{}", v);
+    let result = if push {
+        history.push_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url))
+    } else {
+        history.replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url))
+    };
+    if let Err(err) = result {
+        log::error!("could not set location {:?}: {:?}", url, err);
+    }
 }
 
 impl Model {
@@ -667,7 +1348,14 @@ impl Model {
                 type_: LinkType::Dag,
                 digest: self.root.clone(),
             },
-            kind_id: 0, // TODO
+            type_: FieldType::Object {
+                kind_id: self
+                    .global_state
+                    .schema
+                    .root_kind()
+                    .map(|kind| kind.kind_id)
+                    .unwrap_or_default(),
+            },
         }
     }
 
@@ -680,6 +1368,7 @@ impl Model {
     }
 
     pub fn set_node_value(&mut self, path: &[Selector], value: &[u8]) {
+        let inverse = self.inverse_of_write(path);
         let target_digest = self.global_state_mut().node_store_mut().put_raw(value);
         if let Some(root) = self.replace_node_from(
             &self.root.clone(),
@@ -690,23 +1379,248 @@ impl Model {
             },
         ) {
             self.root = root.digest;
+            self.edits.push(inverse);
+            self.record(crate::collab::Op::SetValue {
+                path: path.to_vec(),
+                value: value.to_vec(),
+            });
         }
     }
 
     pub fn replace_node(&mut self, path: &[Selector], node: &Node) {
         let target_digest = self.global_state_mut().node_store_mut().put_parsed(node);
-        if let Some(root) = self.replace_node_from(
-            &self.root.clone(),
-            path,
-            &Link {
-                type_: LinkType::Dag,
-                digest: target_digest,
+        let link = Link {
+            type_: LinkType::Dag,
+            digest: target_digest,
+        };
+        self.replace_link(path, link);
+    }
+
+    pub fn replace_link(&mut self, path: &[Selector], link: Link) {
+        let inverse = self.inverse_of_write(path);
+        if let Some(root) = self.replace_node_from(&self.root.clone(), path, &link) {
+            self.root = root.digest;
+            self.edits.push(inverse);
+            self.record(crate::collab::Op::Replace {
+                path: path.to_vec(),
+                link,
+            });
+        }
+    }
+
+    // Inserts the given link at the given path, or removes the link there, shifting the following
+    // links, through the sequence of the field if it is one (see `crdt.rs`).
+    fn splice_link(&mut self, path: &[Selector], link: Option<Link>) {
+        let (selector, parent_path) = match path.split_last() {
+            Some(split) => split,
+            None => return,
+        };
+        let parent_link = match self.path(parent_path) {
+            Some(cursor) => cursor.link,
+            None => return,
+        };
+        let parent = match parent_link.get(&self.global_state.node_store) {
+            Some(LinkTarget::Parsed(node)) => node,
+            _ => return,
+        };
+        // The op addresses the child by index, as the other collaborators do.
+        let selector = Selector {
+            field_id: selector.field_id,
+            index: match parent.index(selector) {
+                Some(index) => index,
+                None => return,
             },
-        ) {
+            id: None,
+        };
+        let (op, inverse) = match link {
+            Some(link) => {
+                let id = crate::crdt::is_sequence(&parent, selector.field_id)
+                    .then(|| crate::crdt::next_id(&parent, selector.field_id, &self.replica));
+                (
+                    crate::collab::Op::Insert {
+                        path: vec![selector.clone()],
+                        link,
+                        id,
+                    },
+                    crate::collab::Op::Delete {
+                        path: append(parent_path, selector.clone()),
+                    },
+                )
+            }
+            None => match parent.get_link(&selector) {
+                Some(link) => (
+                    crate::collab::Op::Delete {
+                        path: vec![selector.clone()],
+                    },
+                    crate::collab::Op::Insert {
+                        path: append(parent_path, selector.clone()),
+                        link: link.clone(),
+                        id: None,
+                    },
+                ),
+                None => return,
+            },
+        };
+        let node_store = self.global_state_mut().node_store_mut();
+        let parent = match crate::collab::apply(node_store, &parent_link, &op) {
+            Ok(parent) => parent,
+            Err(err) => {
+                log::error!("could not edit {:?}: {}", path, err);
+                return;
+            }
+        };
+        if let Some(root) = self.replace_node_from(&self.root.clone(), parent_path, &parent) {
             self.root = root.digest;
+            self.edits.push(inverse);
+            self.record(op.with_path(append(parent_path, selector)));
+        }
+    }
+
+    // Returns the op that reverts setting or replacing the link at the given path, which appends it
+    // if there is none.
+    fn inverse_of_write(&self, path: &[Selector]) -> crate::collab::Op {
+        match self.path(path) {
+            Some(cursor) => crate::collab::Op::Replace {
+                path: path.to_vec(),
+                link: cursor.link,
+            },
+            None => crate::collab::Op::Delete {
+                path: path.to_vec(),
+            },
+        }
+    }
+
+    // Applies a local edit, e.g. one being undone, as the editor does.
+    fn apply_edit(&mut self, op: crate::collab::Op) {
+        match op {
+            crate::collab::Op::Insert { path, link, .. } => self.splice_link(&path, Some(link)),
+            crate::collab::Op::Delete { path } => self.splice_link(&path, None),
+            crate::collab::Op::SetValue { path, value } => self.set_node_value(&path, &value),
+            crate::collab::Op::Replace { path, link } => self.replace_link(&path, link),
         }
     }
 
+    // Returns the path of the outermost encrypted link on the given path, if any.
+    fn encrypted_prefix(&self, path: &[Selector]) -> Option<Path> {
+        (1..=path.len())
+            .map(|len| &path[..len])
+            .find(|prefix| {
+                self.path(prefix)
+                    .is_some_and(|cursor| cursor.link.type_ == LinkType::Encrypted)
+            })
+            .map(|prefix| prefix.to_vec())
+    }
+
+    fn root_link(&self) -> Link {
+        Link {
+            type_: LinkType::Dag,
+            digest: self.root.clone(),
+        }
+    }
+
+    // Sends a local edit, which was already applied, to the other collaborators.
+    fn record(&mut self, op: crate::collab::Op) {
+        // Edits within an encrypted subtree are sent as a replacement of the whole subtree, so
+        // that the server only sees ciphertext.
+        let op = match self.encrypted_prefix(op.path()) {
+            Some(path) => match self.path(&path) {
+                Some(cursor) => crate::collab::Op::Replace {
+                    path,
+                    link: cursor.link,
+                },
+                None => return,
+            },
+            None => op,
+        };
+        let root = self.root_link();
+        let message = match &mut self.collab {
+            Some(collab) => collab.client.local(&self.global_state.node_store, op, root),
+            None => return,
+        };
+        if let Some(message) = message {
+            self.send(message);
+        }
+    }
+
+    fn send(&self, message: crate::collab::ClientMessage) {
+        let collab = match &self.collab {
+            Some(collab) => collab,
+            None => return,
+        };
+        match serde_json::to_string(&message) {
+            Ok(text) => {
+                if let Err(err) = collab.socket.send_with_str(&text) {
+                    log::error!("could not send {:?}: {:?}", message, err);
+                }
+            }
+            Err(err) => log::error!("could not serialize {:?}: {}", message, err),
+        }
+    }
+
+    // Handles a message of the sync server, applying the remote edit it contains, if any.
+    fn receive(&mut self, text: &str) {
+        let message = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(err) => {
+                log::error!("invalid message {:?}: {}", text, err);
+                return;
+            }
+        };
+        let mut collab = match self.collab.take() {
+            Some(collab) => collab,
+            None => return,
+        };
+        let node_store = self.global_state_mut().node_store_mut();
+        match collab.client.receive(node_store, message) {
+            Ok((applied, replies)) => {
+                if let Some(op) = applied {
+                    self.selected_path =
+                        crate::collab::transform_selection(&self.selected_path, &op);
+                    // Undoing reverts the local edits where they are now, and keeps the remote one.
+                    for steps in [&mut self.undo, &mut self.redo] {
+                        for step in steps.iter_mut() {
+                            *step = step
+                                .iter()
+                                .filter_map(|inverse| crate::collab::transform(inverse, &op, true))
+                                .collect();
+                        }
+                        steps.retain(|step| !step.is_empty());
+                    }
+                }
+                if collab.client.root.digest != self.root {
+                    self.root = collab.client.root.digest.clone();
+                }
+                if collab.client.presence != self.global_state.presence {
+                    self.global_state_mut().presence = collab.client.presence.clone();
+                    self.subtree_paths_changed = true;
+                }
+                self.collab = Some(collab);
+                for reply in replies {
+                    self.send(reply);
+                }
+            }
+            Err(err) => {
+                log::error!("could not apply remote edit: {}", err);
+                self.collab = Some(collab);
+            }
+        }
+    }
+
+    // Sends the selected path to the other collaborators, if it changed.
+    fn send_presence(&mut self) {
+        let message = match &mut self.collab {
+            Some(collab)
+                if collab.socket.ready_state() == web_sys::WebSocket::OPEN
+                    && collab.presence.as_ref() != Some(&self.selected_path) =>
+            {
+                collab.presence = Some(self.selected_path.clone());
+                collab.client.presence(&self.selected_path)
+            }
+            _ => return,
+        };
+        self.send(message);
+    }
+
     #[must_use]
     fn replace_node_from(
         &mut self,
@@ -721,8 +1635,15 @@ impl Model {
             let selector = path[0].clone();
             match new_node.get_link_mut(&selector) {
                 Some(mut old_child_link) => {
-                    let new_child_link =
+                    let mut new_child_link =
                         self.replace_node_from(&old_child_link.digest, &path[1..], link)?;
+                    // Encrypted subtrees stay encrypted, with the same key.
+                    if old_child_link.type_ == LinkType::Encrypted {
+                        let node_store = self.global_state_mut().node_store_mut();
+                        let key = node_store.key_of(&old_child_link.digest)?.clone();
+                        new_child_link =
+                            crate::encryption::encrypt(node_store, &key, &new_child_link).ok()?;
+                    }
                     *old_child_link = new_child_link;
                 }
                 None => {
@@ -776,40 +1697,291 @@ impl Model {
         }
     }
 
+    fn holes(&self) -> Vec<Path> {
+        let root = self.root();
+        hole::find_holes(
+            &self.global_state.node_store,
+            &self.global_state.schema,
+            root.kind_id().unwrap_or_default(),
+            &root.link,
+        )
+    }
+
     fn global_state_mut(&mut self) -> &mut GlobalState {
         Rc::make_mut(&mut self.global_state)
     }
 
-    pub fn update_errors(&mut self, ctx: &Context<Self>) {
-        self.update_errors_node(ctx, &self.selected_path.clone());
+    // Type checks all the `File`s in the tree, and records the errors in the state of the nodes
+    // at which they occur.
+    pub fn update_errors(&mut self) {
+        if self.checked_root == self.root {
+            return;
+        }
+        self.checked_root = self.root.clone();
+        let root = self.root();
+        self.type_errors.update(
+            &self.global_state.node_store,
+            &self.global_state.schema,
+            &root,
+        );
+        let mut node_state: HashMap<Path, NodeState> = HashMap::new();
+        for error in self.type_errors.errors() {
+            node_state
+                .entry(error.path.clone())
+                .or_default()
+                .errors
+                .push(error.message.clone());
+        }
+        self.global_state_mut().node_state = node_state;
+        self.subtree_paths_changed = true;
     }
 
-    pub fn update_errors_node(&mut self, _ctx: &Context<Self>, path: &[Selector]) {
-        /*
-        let node = match self.node_store.lookup(path) {
-            Some(node) => node.clone(),
-            None => return,
+    // Checks the statements signing the root, if it changed.
+    pub fn update_statements(&mut self) {
+        if self.verified_root == self.root {
+            return;
+        }
+        self.verified_root = self.root.clone();
+        self.statements = crate::identity::statements(
+            &self.global_state.node_store,
+            self.signatures
+                .get(&self.root)
+                .map_or(&[][..], Vec::as_slice),
+            &self.root,
+        );
+    }
+
+    // Updates the search index and the matches of the current search, if the root changed.
+    pub fn update_search(&mut self) {
+        if self.searched_root == self.root {
+            return;
+        }
+        self.searched_root = self.root.clone();
+        self.subtree_paths_changed = true;
+        let text = match &self.search {
+            Some(text) => text.clone(),
+            None => {
+                self.global_state_mut().matches = vec![];
+                return;
+            }
+        };
+        let search = match crate::search::Search::parse(&text) {
+            Ok(search) => search,
+            Err(err) => {
+                log::error!("invalid search {:?}: {}", text, err);
+                self.global_state_mut().matches = vec![];
+                return;
+            }
+        };
+        let root = self.root();
+        let node_store = &self.global_state.node_store;
+        let schema = &self.global_state.schema;
+        self.search_index.update(node_store, schema, &root);
+        let matches = self.search_index.search(node_store, schema, &root, &search);
+        self.global_state_mut().matches = matches;
+    }
+
+    // State to put in the URL, linking to the selected node in the current root.
+    pub fn hash_state(&self) -> HashState {
+        let root_kind = self.root().kind_id().unwrap_or_default();
+        HashState {
+            root: self.root.clone(),
+            schema_root: self.schema_root.clone(),
+            ref_name: self
+                .ref_root
+                .as_ref()
+                .filter(|(_, root)| *root == self.root)
+                .map(|(name, _)| name.clone()),
+            path: Some(&self.selected_path)
+                .filter(|path| !path.is_empty())
+                .map(|path| {
+                    crate::permalink::encode_path(&self.global_state.schema, root_kind, path)
+                }),
+            format: Some(self.format).filter(|format| *format != Format::Json),
+            raw: !self.global_state.rich_render,
+            show_serialized: self.global_state.show_serialized,
+            signatures: self.signatures.get(&self.root).cloned().unwrap_or_default(),
+        }
+    }
+
+    // Keeps the URL in sync with the state. Navigating, e.g. to another root, ref, path or view,
+    // gets a new history entry, so that back and forward go through the places visited; edits
+    // replace the current entry, so that going back does not step through every version of the
+    // tree (undo does).
+    fn update_location(&self, navigated: bool) {
+        let hash = self.hash_state().to_string();
+        if hash != get_location_hash() {
+            set_location_hash(&hash, navigated);
+        }
+    }
+
+    // Paths the next operation applies to: the marked nodes, and the range of siblings between the
+    // anchor and the selected node in visual mode, or else the selected node; in document order.
+    pub fn selected_paths(&self) -> Vec<Path> {
+        let mut paths = self.marked.clone();
+        if self.global_state.mode == Mode::Visual {
+            paths.extend(self.visual_range());
+        }
+        if paths.is_empty() {
+            paths.push(self.selected_path.clone());
+        }
+        paths.sort_by_key(|path| {
+            path.iter()
+                .map(|s| (s.field_id, s.index))
+                .collect::<Vec<_>>()
+        });
+        paths.dedup();
+        paths
+    }
+
+    fn visual_range(&self) -> Vec<Path> {
+        let anchor = match &self.visual_anchor {
+            Some(anchor) => anchor,
+            None => return vec![self.selected_path.clone()],
+        };
+        match (anchor.split_last(), self.selected_path.split_last()) {
+            (Some((a, parent_a)), Some((b, parent_b)))
+                if parent_a == parent_b && a.field_id == b.field_id =>
+            {
+                (a.index.min(b.index)..=a.index.max(b.index))
+                    .map(|index| {
+                        append(
+                            parent_a,
+                            Selector {
+                                field_id: a.field_id,
+                                index,
+                                id: None,
+                            },
+                        )
+                    })
+                    .collect()
+            }
+            _ => vec![anchor.clone(), self.selected_path.clone()],
+        }
+    }
+
+    fn clear_selection(&mut self) {
+        self.marked.clear();
+        if self.global_state.mode == Mode::Visual {
+            self.visual_anchor = None;
+            self.global_state_mut().mode = Mode::Normal;
+        }
+    }
+
+    // Indexes the paths of the global state, if they changed, rather than once per node.
+    fn update_subtree_paths(&mut self) {
+        if !self.subtree_paths_changed {
+            return;
+        }
+        self.subtree_paths_changed = false;
+        let subtree_paths = crate::node::index_subtree_paths(&self.global_state);
+        if subtree_paths != self.global_state.subtree_paths {
+            self.global_state_mut().subtree_paths = subtree_paths;
+        }
+    }
+
+    // Shares the selection with the nodes, to highlight it.
+    fn update_selection(&mut self) {
+        let selection = if self.global_state.mode == Mode::Visual || !self.marked.is_empty() {
+            self.selected_paths()
+        } else {
+            vec![]
         };
-        let kind = &node.kind;
+        if selection != self.global_state.selection {
+            self.global_state_mut().selection = selection;
+            self.subtree_paths_changed = true;
+        }
+    }
 
-        if let Some(kind) = SCHEMA.get_kind(kind) {
-            let crate::schema::KindValue::Struct { validator: _, .. } = kind.value;
-            // let errors = validator(&ValidatorContext {
-            //     model: self,
-            //     ctx,
-            //     node: &node,
-            //     path,
-            //     placeholder: "",
-            // });
-            // log::info!("errors: {:?} {:?}", path, errors);
+    // Applies a refactoring to the node at the given path (see `refactor.rs`).
+    fn refactor(&mut self, path: &[Selector], refactoring: &crate::refactor::Refactoring) {
+        let root = self.root();
+        let schema = self.global_state.schema.clone();
+        let replica = self.replica.clone();
+        let result = crate::refactor::refactor(
+            self.global_state_mut().node_store_mut(),
+            &schema,
+            &root,
+            path,
+            refactoring,
+            &replica,
+        );
+        match result {
+            Ok(edit) => {
+                self.replace_link(&edit.path, edit.link);
+                self.selected_path = edit.selection;
+            }
+            Err(err) => log::error!("could not refactor: {}", err),
+        }
+    }
+
+    // Applies a refactoring to all the given paths at once, as a single edit of the root.
+    fn refactor_selection(&mut self, paths: Vec<Path>, refactoring: &crate::refactor::Refactoring) {
+        let root = self.root();
+        let schema = self.global_state.schema.clone();
+        let replica = self.replica.clone();
+        let result = crate::refactor::refactor_paths(
+            self.global_state_mut().node_store_mut(),
+            &schema,
+            &root,
+            &paths,
+            refactoring,
+            &replica,
+        );
+        let link = match result {
+            Ok(link) => link,
+            Err(err) => {
+                log::error!("could not refactor: {}", err);
+                return;
+            }
+        };
+        self.replace_link(&[], link);
+        match refactoring {
+            // Keep the moved range selected, so that it can be moved again.
+            crate::refactor::Refactoring::Shift { offset } => {
+                let shift = |path: &mut Path| {
+                    if let Some(selector) = path.last_mut() {
+                        selector.index = selector.index.saturating_add_signed(*offset);
+                        selector.id = None;
+                    }
+                };
+                shift(&mut self.selected_path);
+                self.visual_anchor.iter_mut().for_each(shift);
+                self.marked.iter_mut().for_each(shift);
+            }
+            _ => {
+                self.clear_selection();
+                let first = paths.first().cloned().unwrap_or_default();
+                self.selected_path = match self.path(&first) {
+                    Some(_) => first,
+                    None => parent(&first).to_vec(),
+                };
+            }
         }
-        for (_, children) in node.children.iter() {
-            for child in children {
-                // TODO
-                // self.update_errors_node(child);
+    }
+
+    // Selects the sibling at the given offset from the selected node, in the same field.
+    fn sibling(&mut self, offset: isize) {
+        let mut path = self.selected_path.clone();
+        if let Some(selector) = path.last_mut() {
+            match selector.index.checked_add_signed(offset) {
+                Some(index) => selector.index = index,
+                None => return,
             }
+            selector.id = None;
+        }
+        if self.path(&path).is_some() {
+            self.selected_path = path;
+        }
+    }
+
+    // Paths stepped through by `n` and `N`: the matches of the current search, or else the holes.
+    fn targets(&self) -> Vec<Path> {
+        if self.search.is_some() {
+            self.global_state.matches.clone()
+        } else {
+            self.holes()
         }
-        */
     }
 }
 
@@ -826,6 +1998,11 @@ impl Model {
                 text: "load(localstorage)".to_string(),
                 msg: Msg::LoadLocal,
             },
+            Action {
+                image: None,
+                text: "merge(localstorage)".to_string(),
+                msg: Msg::MergeLocal,
+            },
             Action {
                 image: None,
                 text: "store(localhost)".to_string(),
@@ -846,6 +2023,31 @@ impl Model {
                 text: "load(remote)".to_string(),
                 msg: Msg::LoadRemote(crate::ent::API_URL_REMOTE.to_string()),
             },
+            Action {
+                image: None,
+                text: "sign".to_string(),
+                msg: Msg::SignRoot,
+            },
+            Action {
+                image: None,
+                text: "save as".to_string(),
+                msg: Msg::SaveRef,
+            },
+            Action {
+                image: None,
+                text: "encrypt".to_string(),
+                msg: Msg::Encrypt,
+            },
+            Action {
+                image: None,
+                text: "unlock".to_string(),
+                msg: Msg::Unlock,
+            },
+            Action {
+                image: None,
+                text: "collaborate".to_string(),
+                msg: Msg::PromptCollaborate,
+            },
             Action {
                 image: None,
                 text: "Normal mode".to_string(),
@@ -881,6 +2083,66 @@ impl Model {
                 text: "delete".to_string(),
                 msg: Msg::DeleteItem,
             },
+            Action {
+                image: None,
+                text: "select all matches".to_string(),
+                msg: Msg::PromptQuery,
+            },
+            Action {
+                image: None,
+                text: "search".to_string(),
+                msg: Msg::PromptSearch,
+            },
+            Action {
+                image: None,
+                text: "refactor all matches".to_string(),
+                msg: Msg::PromptRefactorAll,
+            },
+            Action {
+                image: None,
+                text: "sequence".to_string(),
+                msg: Msg::EnableSequence,
+            },
+            Action {
+                image: None,
+                text: "json".to_string(),
+                msg: Msg::SetFormat(Format::Json),
+            },
+            Action {
+                image: None,
+                text: "yaml".to_string(),
+                msg: Msg::SetFormat(Format::Yaml),
+            },
+            Action {
+                image: None,
+                text: "toml".to_string(),
+                msg: Msg::SetFormat(Format::Toml),
+            },
+            Action {
+                image: None,
+                text: "html".to_string(),
+                msg: Msg::SetFormat(Format::Html),
+            },
+            Action {
+                image: None,
+                text: "protobuf".to_string(),
+                msg: Msg::SetFormat(Format::Protobuf),
+            },
+            Action {
+                image: None,
+                text: "argv".to_string(),
+                msg: Msg::SetFormat(Format::Argv),
+            },
+            Action {
+                image: None,
+                text: "fsck".to_string(),
+                msg: Msg::Fsck(None),
+            },
+            Action {
+                image: None,
+                text: "fsck(repair from localhost)".to_string(),
+                msg: Msg::Fsck(Some(crate::ent::API_URL_LOCALHOST.to_string())),
+            },
             Action {
                 image: None,
                 text: "serialized".to_string(),
@@ -925,6 +2187,301 @@ impl Model {
         }
     }
 
+    // Imports text in the current format as a node of the given kind.
+    fn import(&mut self, kind_id: u64, text: &str) -> Result<Link, String> {
+        let format = self.format;
+        let schema = self.global_state.schema.clone();
+        let node_store = self.global_state_mut().node_store_mut();
+        let value = match format {
+            Format::Yaml if kind_id == crate::yaml::YAML_STREAM => {
+                let documents = crate::yaml::parse_stream(text)?;
+                return Ok(crate::yaml::import_stream(node_store, &documents));
+            }
+            Format::Json => serde_json::from_str(text).map_err(|e| e.to_string())?,
+            Format::Yaml => crate::yaml::parse(text)?,
+            Format::Toml => crate::toml::parse(text)?,
+            Format::Html => {
+                let document = crate::html::import(node_store, text)?;
+                return match kind_id {
+                    crate::html::HTML_DOCUMENT => Ok(document),
+                    // A single top level node may replace an `html_node`.
+                    crate::html::HTML_NODE => {
+                        let document = node_store.get_dag(&document.digest).unwrap_or_default();
+                        match document.links.get(&crate::html::DOCUMENT_CHILDREN) {
+                            Some(children) if children.len() == 1 => Ok(children[0].clone()),
+                            _ => Err("expected exactly one top level HTML node".to_string()),
+                        }
+                    }
+                    _ => Err("HTML can only be imported as html_document or html_node".to_string()),
+                };
+            }
+            // Binary protobuf messages are pasted as base64.
+            Format::Protobuf => {
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(text.trim())
+                    .map_err(|e| format!("invalid base64: {}", e))?;
+                return crate::protobuf::decode(node_store, &schema, kind_id, &bytes)
+                    .map_err(|e| e.to_string());
+            }
+            Format::Argv => {
+                let args = crate::argv::split(text)?;
+                return crate::argv::parse(node_store, &schema, kind_id, &args)
+                    .map_err(|e| e.to_string());
+            }
+        };
+        if kind_id == crate::json::JSON_VALUE {
+            Ok(crate::json::import(node_store, &value))
+        } else {
+            crate::json::import_typed(node_store, &schema, kind_id, &value)
+                .map_err(|e| e.to_string())
+        }
+    }
+
+    // Exports the subtree at the given cursor in the current format.
+    fn export(&self, cursor: &Cursor) -> Result<String, String> {
+        let node_store = &self.global_state.node_store;
+        let kind_id = cursor
+            .kind_id()
+            .ok_or_else(|| format!("a {:?} value cannot be exported", cursor.type_))?;
+        let value = match (self.format, kind_id) {
+            (Format::Html, crate::html::HTML_DOCUMENT) => {
+                return crate::html::export(node_store, &cursor.link)
+            }
+            (Format::Html, crate::html::HTML_NODE) => {
+                return crate::html::export_fragment(node_store, &cursor.link)
+            }
+            (Format::Html, _) => {
+                return Err("only html_document and html_node can be exported as HTML".to_string())
+            }
+            // Text format, followed by the binary encoding as base64.
+            (Format::Protobuf, kind_id) => {
+                let schema = &self.global_state.schema;
+                let text = crate::protobuf::print_text(node_store, schema, kind_id, &cursor.link)
+                    .map_err(|e| e.to_string())?;
+                let bytes = crate::protobuf::encode(node_store, schema, kind_id, &cursor.link)
+                    .map_err(|e| e.to_string())?;
+                return Ok(format!(
+                    "{}\n{}",
+                    text,
+                    base64::engine::general_purpose::STANDARD.encode(bytes)
+                ));
+            }
+            (Format::Argv, kind_id) => {
+                return command_line(node_store, &self.global_state.schema, kind_id, &cursor.link)
+            }
+            (Format::Yaml, crate::yaml::YAML_STREAM) => {
+                let documents = crate::yaml::export_stream(node_store, &cursor.link)?;
+                return crate::yaml::print_stream(&documents);
+            }
+            (_, crate::json::JSON_VALUE) => crate::json::export(node_store, &cursor.link),
+            (_, kind_id) => crate::json::export_typed(
+                node_store,
+                &self.global_state.schema,
+                kind_id,
+                &cursor.link,
+            ),
+        }
+        .map_err(|e| e.to_string())?;
+        match self.format {
+            Format::Json => serde_json::to_string_pretty(&value).map_err(|e| e.to_string()),
+            Format::Yaml => crate::yaml::print(&value),
+            Format::Toml => crate::toml::print(&value),
+            Format::Html | Format::Protobuf | Format::Argv => unreachable!(),
+        }
+    }
+
+    fn export_key(&self, cursor: &Cursor) -> ExportKey {
+        (
+            cursor.link.digest.clone(),
+            cursor.kind_id(),
+            self.schema_root.clone(),
+            self.format,
+        )
+    }
+
+    // Exports the selected subtree in the current format, unless it was already exported.
+    pub fn update_export(&mut self) {
+        let cursor = match self.path(&self.selected_path) {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        let key = self.export_key(&cursor);
+        if self.export.as_ref().is_some_and(|(k, _)| *k == key) {
+            return;
+        }
+        self.export = Some((key, self.export(&cursor)));
+    }
+
+    // Export of the selected subtree in the current format.
+    fn view_export(&self) -> Html {
+        let cursor = match self.path(&self.selected_path) {
+            Some(cursor) => cursor,
+            None => return html! {},
+        };
+        let key = self.export_key(&cursor);
+        let export = match &self.export {
+            Some((k, export)) if *k == key => export.clone(),
+            // Not exported yet, e.g. before the first update.
+            _ => self.export(&cursor),
+        };
+        match export {
+            Ok(text) => html! {
+                <pre>{ text }</pre>
+            },
+            Err(err) => html! {
+                <div>{ format!("{:?}: {}", self.format, err) }</div>
+            },
+        }
+    }
+
+    // Command line of the selected node, if its kind has fields that are passed on command lines.
+    fn view_command(&self) -> Html {
+        let cursor = match self.path(&self.selected_path) {
+            Some(cursor) => cursor,
+            None => return html! {},
+        };
+        let schema = &self.global_state.schema;
+        let kind_id = match cursor.kind_id() {
+            Some(kind_id) => kind_id,
+            None => return html! {},
+        };
+        let is_command = schema
+            .get_kind(kind_id)
+            .is_some_and(|kind| kind.fields.iter().any(|f| f.arg.is_some()));
+        if !is_command {
+            return html! {};
+        }
+        match command_line(&self.global_state.node_store, schema, kind_id, &cursor.link) {
+            Ok(line) => html! {
+                <pre>{ format!("$ {}", line) }</pre>
+            },
+            Err(err) => html! {
+                <div>{ format!("command: {}", err) }</div>
+            },
+        }
+    }
+
+    // Value of the selected expression (or function definition without arguments), evaluated in
+    // the enclosing `File`, if any.
+    fn view_eval(&self, ctx: &Context<Self>) -> Html {
+        let cursor = match self.path(&self.selected_path) {
+            Some(cursor) => cursor,
+            None => return html! {},
+        };
+        let node_store = &self.global_state.node_store;
+        let file = (0..self.selected_path.len())
+            .rev()
+            .map(|i| &self.selected_path[..i])
+            .find_map(|path| {
+                self.path(path)
+                    .filter(|c| c.kind_id() == Some(crate::ast::file::kind_id()))
+                    .map(|c| (path, c.link))
+            });
+        let file = file.as_ref().map(|(path, link)| (*path, link));
+        let result = if cursor.kind_id() == Some(crate::ast::expression::kind_id()) {
+            crate::eval::eval(node_store, file, &self.selected_path, &cursor.link)
+        } else if cursor.kind_id() == Some(crate::ast::function_definition::kind_id()) {
+            let function_definition = match crate::ast::get_node(node_store, &cursor.link) {
+                Some(node) => node,
+                None => return html! {},
+            };
+            let arguments = crate::ast::function_definition::ARGUMENTS;
+            let body = crate::ast::function_definition::BODY;
+            if function_definition.links.contains_key(&arguments) {
+                return html! {};
+            }
+            match crate::ast::get_child(&function_definition, &self.selected_path, body) {
+                Some((path, link)) => crate::eval::eval(node_store, file, &path, link),
+                None => return html! {},
+            }
+        } else {
+            return html! {};
+        };
+        match result {
+            Ok(value) => html! {
+                <pre>{ format!("= {}", value) }</pre>
+            },
+            Err(err) => {
+                let path = err.path.clone();
+                let onclick = ctx.link().callback(move |_| Msg::Select(path.clone()));
+                html! {
+                    <div class="error" onclick={ onclick }>
+                        { display_cursor(&err.path) }
+                        { err.message }
+                    </div>
+                }
+            }
+        }
+    }
+
+    fn view_fsck_report(&self) -> Html {
+        match &self.fsck_report {
+            Some(report) => {
+                let problems = report
+                    .problems
+                    .iter()
+                    .map(|p| html! { <div class="error">{ p.to_string() }</div> });
+                let summary = if report.is_ok() {
+                    format!("fsck: checked {} blobs, ok", report.checked)
+                } else {
+                    format!(
+                        "fsck: checked {} blobs, {} problems",
+                        report.checked,
+                        report.problems.len()
+                    )
+                };
+                html! {
+                    <div>
+                        <div>{ summary }</div>
+                        { for problems }
+                    </div>
+                }
+            }
+            None => html! {},
+        }
+    }
+
+    fn view_signatures(&self) -> Html {
+        let statements = self.statements.iter().map(|statement| {
+            html! {
+                <div>{ format!("signed by {} ({})", statement.author, statement.fingerprint()) }</div>
+            }
+        });
+        html! {
+            <div>{ for statements }</div>
+        }
+    }
+
+    fn view_search_results(&self, ctx: &Context<Self>) -> Html {
+        let search = match &self.search {
+            Some(search) => search,
+            None => return html! {},
+        };
+        let matches: HashSet<&Path> = self.global_state.matches.iter().collect();
+        let results = self
+            .search_index
+            .entries()
+            .iter()
+            .filter(|entry| matches.contains(&entry.path))
+            .map(|entry| {
+                let path = entry.path.clone();
+                let onclick = ctx
+                    .link()
+                    .callback(move |_: MouseEvent| Msg::Select(path.clone()));
+                html! {
+                    <div class="cursor-pointer" onclick={ onclick }>
+                        { display_cursor(&entry.path) }{ " " }{ crate::search::describe(entry) }
+                    </div>
+                }
+            });
+        html! {
+            <div>
+                <div>{ format!("{} matches for {:?}", matches.len(), search) }</div>
+                { for results }
+            </div>
+        }
+    }
+
     pub fn view_node_store(&self, node_store: &NodeStore) -> Html {
         // let serialized = serde_json::to_string_pretty(node_store).expect("could not serialize to
         // JSON");
@@ -1,21 +1,25 @@
 use crate::{
     command_line::{CommandLine, Entry},
+    hole,
     model::{GlobalState, Model, Msg},
+    refactor::{self, Refactoring},
     schema::{default_renderer, Field, Kind, Schema, ValidatorContext, *},
-    types::{parent, Cursor, Link, LinkTarget, LinkType, Mode, Node, Selector},
+    types::{get_value_from_input_event, Cursor, Link, LinkTarget, LinkType, Mode, Path, Selector},
 };
-use std::{collections::BTreeMap, rc::Rc};
+use std::{collections::HashMap, rc::Rc};
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
 pub struct NodeComponent {
     input_node_ref: NodeRef,
-    old_props: Option<NodeProperties>,
+    // Whether the subtree was complete in the store it was last rendered from (see
+    // `NodeStore::is_complete`).
+    complete: bool,
     // Memoize callbacks?
     ondelete: Callback<()>,
 }
 
-#[derive(Properties, PartialEq, Clone)]
+#[derive(Properties, Clone)]
 pub struct NodeProperties {
     pub global_state: Rc<GlobalState>,
     pub cursor: Cursor,
@@ -32,6 +36,107 @@ pub struct NodeProperties {
     pub updatemodel: Callback<Msg>,
 }
 
+// The global state changes with every message, so it is compared by identity, and `changed` then
+// decides whether the change concerns the node.
+impl PartialEq for NodeProperties {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.global_state, &other.global_state)
+            && self.cursor == other.cursor
+            && self.selected_path == other.selected_path
+            && self.placeholder == other.placeholder
+            && self.oninput == other.oninput
+            && self.onselect == other.onselect
+            && self.updatemodel == other.updatemodel
+    }
+}
+
+// What the rendering of a subtree depends on, besides the nodes it is made of: the view options,
+// and the parts of the global state about the paths under it. The cursor of the node is compared
+// without its ancestors, which change with every edit, unless the node is selected, since its
+// commands depend on its siblings.
+#[derive(PartialEq)]
+struct SubtreeState<'a> {
+    link: Link,
+    type_: FieldType,
+    path: Path,
+    parent: Option<Cursor>,
+    mode: Mode,
+    show_serialized: bool,
+    rich_render: bool,
+    selected_path: Option<Path>,
+    paths: Option<&'a SubtreePaths>,
+}
+
+impl<'a> SubtreeState<'a> {
+    fn new(props: &'a NodeProperties) -> Self {
+        let global_state = &props.global_state;
+        let cursor = &props.cursor;
+        let path = cursor.path();
+        SubtreeState {
+            link: cursor.link.clone(),
+            type_: cursor.type_.clone(),
+            parent: if props.selected_path == path {
+                cursor.parent(&global_state.node_store)
+            } else {
+                None
+            },
+            mode: global_state.mode.clone(),
+            show_serialized: global_state.show_serialized,
+            rich_render: global_state.rich_render,
+            selected_path: Some(&props.selected_path)
+                .filter(|p| p.starts_with(&path))
+                .cloned(),
+            paths: global_state.subtree_paths.get(&path),
+            path,
+        }
+    }
+}
+
+// The matches, selection, presence, errors and unfolded nodes under a path.
+#[derive(Clone, Default, PartialEq)]
+pub struct SubtreePaths {
+    matches: Vec<Path>,
+    selection: Vec<Path>,
+    presence: Vec<(String, Path)>,
+    errors: HashMap<Path, Vec<String>>,
+    expanded: HashMap<Path, usize>,
+}
+
+// Indexes the paths of the global state by each of their prefixes, so that each node only looks
+// up its own subtree when deciding whether to re-render.
+pub fn index_subtree_paths(global_state: &GlobalState) -> HashMap<Path, SubtreePaths> {
+    let mut index: HashMap<Path, SubtreePaths> = HashMap::new();
+    let mut add = |path: &Path, add: &dyn Fn(&mut SubtreePaths)| {
+        for len in 0..=path.len() {
+            add(index.entry(path[..len].to_vec()).or_default());
+        }
+    };
+    for p in &global_state.matches {
+        add(p, &|paths| paths.matches.push(p.clone()));
+    }
+    for p in &global_state.selection {
+        add(p, &|paths| paths.selection.push(p.clone()));
+    }
+    for (client_id, p) in &global_state.presence {
+        add(p, &|paths| {
+            paths.presence.push((client_id.clone(), p.clone()))
+        });
+    }
+    for (p, state) in &global_state.node_state {
+        if !state.errors.is_empty() {
+            add(p, &|paths| {
+                paths.errors.insert(p.clone(), state.errors.clone());
+            });
+        }
+    }
+    for (p, shown) in &global_state.expanded {
+        add(p, &|paths| {
+            paths.expanded.insert(p.clone(), *shown);
+        });
+    }
+    index
+}
+
 pub enum NodeMsg {
     Click,
 }
@@ -60,10 +165,14 @@ impl Component for NodeComponent {
     type Properties = NodeProperties;
 
     fn create(ctx: &Context<Self>) -> Self {
-        let updatemodel = ctx.props().updatemodel.clone();
+        let props = ctx.props();
+        let updatemodel = props.updatemodel.clone();
         Self {
             input_node_ref: NodeRef::default(),
-            old_props: None,
+            complete: props
+                .global_state
+                .node_store
+                .is_complete(&props.cursor.link),
             ondelete: Callback::from(move |()| updatemodel.emit(Msg::Parent)),
         }
     }
@@ -83,111 +192,34 @@ impl Component for NodeComponent {
         let global_state = &props.global_state;
         let node_store = &global_state.node_store;
         let cursor = &props.cursor;
-        let kind_id = cursor.kind_id;
+        let kind_id = cursor.kind_id();
         let link = &cursor.link;
         let digest = &link.digest;
         let node_path = cursor.path();
         let _oninput = props.oninput.clone();
         let selected_path = &props.selected_path;
         let selected = selected_path == &node_path;
-        let kind = global_state.schema.get_kind(kind_id);
+        let kind = kind_id.and_then(|kind_id| global_state.schema.get_kind(kind_id));
         let inner = match cursor.link.get(&node_store) {
-            None => {
-                let onupdatemodel = ctx.props().updatemodel.clone();
-                let node_path = node_path.clone();
-                let entries: Vec<Entry> = kind
-                    .cloned()
-                    .unwrap_or_default()
-                    .fields
-                    .iter()
-                    .map(|field| Entry {
-                        label: field.name.to_string(),
-                        description: "".to_string(),
-                        action: Msg::ReplaceNode(node_path.clone(), Node::default(), false),
-                        valid_classes: KIND_CLASSES.iter().map(|v| v.to_string()).collect(),
-                    })
-                    .collect();
-                let onenter = {
-                    let onupdatemodel = onupdatemodel.clone();
-                    Callback::from(move |()| {
-                        onupdatemodel.emit(Msg::Parent);
-                    })
-                };
-                let onupdatemodel0 = onupdatemodel.clone();
-                let placeholder = "<invalid>".to_string();
-                let value = "".to_string();
+            // Encrypted subtrees whose key is missing.
+            None if link.type_ == LinkType::Encrypted && node_store.has_raw_node(digest) => {
                 html! {
-                  <CommandLine
-                    input_node_ref={ self.input_node_ref.clone() }
-                    entries={ entries }
-                    value={ value.clone() }
-                    placeholder={ placeholder }
-                    oninput={ Callback::from(move |v: String| {
-                        onupdatemodel.emit(Msg::SetNodeValue(node_path.clone(), v.as_bytes().to_vec()));
-                     }) }
-                    onselect={ ctx.props().updatemodel.clone() }
-                    ondelete={ Callback::from(move |()| {
-                        onupdatemodel0.emit(Msg::DeleteItem);
-                     }) }
-                    onenter={ onenter }
-                    enabled={ selected && global_state.mode == Mode::Edit }
-                  />
+                    <span class="text-gray-500" title={ digest.clone() }>{ "🔒 locked" }</span>
                 }
             }
-            Some(LinkTarget::Raw(value)) => {
-                let onupdatemodel = ctx.props().updatemodel.clone();
-                let node_path = node_path.clone();
-                let entries: Vec<Entry> = vec![];
-                // kind
-                //     .cloned()
-                //     .unwrap_or_default()
-                //     .fields
-                //     .iter()
-                //     .map(|field| Entry {
-                //         label: global_state
-                //             .schema
-                //             .get_kind(todo!() /* field.kind_id */)
-                //             .map(|k| &(*k.name))
-                //             .unwrap_or("INVALID")
-                //             .to_string(),
-                //         description: "".to_string(),
-                //         action: Msg::ReplaceNode(node_path.clone(), Node::default(), false),
-                //         valid_classes: KIND_CLASSES.iter().map(|v| v.to_string()).collect(),
-                //     })
-                //     .collect();
-                let onenter = {
-                    let onupdatemodel = onupdatemodel.clone();
-                    Callback::from(move |()| {
-                        onupdatemodel.emit(Msg::Parent);
-                    })
-                };
-                let onupdatemodel0 = onupdatemodel.clone();
-                let placeholder = if value.is_empty() {
-                    "***".to_string()
-                } else {
-                    "".to_string()
-                };
-                let value =
-                    String::from_utf8(value.to_vec()).unwrap_or("INVALID STRING".to_string());
-                html! {
-                  <CommandLine
-                    input_node_ref={ self.input_node_ref.clone() }
-                    entries={ entries }
-                    value={ value.clone() }
-                    placeholder={ placeholder }
-                    oninput={ Callback::from(move |v: String| {
-                        onupdatemodel.emit(Msg::SetNodeValue(node_path.clone(), v.as_bytes().to_vec()));
-                     }) }
-                    onselect={ ctx.props().updatemodel.clone() }
-                    ondelete={ Callback::from(move |()| {
-                        onupdatemodel0.emit(Msg::DeleteItem);
-                     }) }
-                    onenter={ onenter }
-                    enabled={ selected && global_state.mode == Mode::Edit }
-                  />
-                }
+            // Holes are filled by picking one of the completions valid for their kind.
+            None if kind_id.is_some() => self.view_hole(ctx),
+            Some(LinkTarget::Parsed(_))
+                if kind_id.is_some_and(|kind_id| {
+                    hole::is_hole(node_store, &global_state.schema, kind_id, link)
+                }) =>
+            {
+                self.view_hole(ctx)
             }
-            Some(LinkTarget::Parsed(node)) => {
+            // Leaves are edited according to their type, even if their value is missing.
+            None => self.view_leaf(ctx, &[]),
+            Some(LinkTarget::Raw(value)) => self.view_leaf(ctx, value),
+            Some(LinkTarget::Parsed(_)) => {
                 let renderer = default_renderer;
                 let validator_context = ValidatorContext {
                     global_state: global_state.clone(),
@@ -215,60 +247,75 @@ impl Component for NodeComponent {
                                     .collect(),
                             })
                             .collect();
-                        // TODO: macros
-                        let mut macro_entries = vec![
-                            Entry {
-                                label: "delete".to_string(),
-                                description: "".to_string(),
-                                action: Msg::DeleteItem,
-                                valid_classes: vec![],
-                            },
-                            // TODO: should apply to literals too.
-                            Entry {
-                                label: "call".to_string(),
-                                description: "".to_string(),
-                                action: Msg::ReplaceNode(
-                                    node_path.clone(),
-                                    Node {
-                                        links: maplit::btreemap! {
-                                            0 => vec![Link {
-                                                type_: LinkType::Raw,
-                                                digest: digest.clone(),
-                                            }],
-                                        },
+                        let refactor = |label: String, refactoring| Entry {
+                            label,
+                            description: "".to_string(),
+                            action: Msg::Refactor(node_path.clone(), refactoring),
+                            valid_classes: vec![],
+                        };
+                        let mut macro_entries = vec![Entry {
+                            label: "delete".to_string(),
+                            description: "".to_string(),
+                            action: Msg::DeleteItem,
+                            valid_classes: vec![],
+                        }];
+                        // Wrappings in a node of the same kind, e.g. a list of expressions.
+                        if let Some(kind) = kind {
+                            for fields in refactor::wrappings(&global_state.schema, kind.kind_id) {
+                                let mut kind_id = kind.kind_id;
+                                let mut names = vec![];
+                                for field_id in fields.iter() {
+                                    let field = global_state
+                                        .schema
+                                        .get_kind(kind_id)
+                                        .and_then(|kind| kind.get_field(*field_id));
+                                    names.push(field.map(|f| f.name.clone()).unwrap_or_default());
+                                    if let Some(FieldType::Object { kind_id: k }) =
+                                        field.map(|f| &f.type_)
+                                    {
+                                        kind_id = *k;
+                                    }
+                                }
+                                macro_entries.push(refactor(
+                                    format!("wrap in {}", names.join(".")),
+                                    Refactoring::Wrap {
+                                        kind_id: kind.kind_id,
+                                        fields,
                                     },
-                                    false,
-                                ),
-                                valid_classes: vec![],
-                            },
-                            Entry {
-                                label: "array".to_string(),
-                                description: "".to_string(),
-                                action: Msg::ReplaceNode(
-                                    node_path.clone(),
-                                    Node {
-                                        links: maplit::btreemap! {
-                                            0 => vec![Link {
-                                                type_: LinkType::Raw,
-                                                digest: digest.clone(),
-                                            }],
-                                        },
-                                    },
-                                    false,
-                                ),
-                                valid_classes: vec![],
-                            },
-                            Entry {
-                                label: "move up".to_string(),
-                                description: "".to_string(),
-                                action: Msg::ReplaceNode(
-                                    parent(&node_path).to_vec(),
-                                    node.clone(),
-                                    false,
-                                ),
-                                valid_classes: vec![],
-                            },
-                        ];
+                                ));
+                            }
+                        }
+                        macro_entries.push(refactor("unwrap".to_string(), Refactoring::Unwrap));
+                        if let Some(selector) = node_path.last() {
+                            let siblings = cursor
+                                .parent
+                                .as_ref()
+                                .and_then(|(parent, _)| match parent.link.get(node_store) {
+                                    Some(LinkTarget::Parsed(parent)) => {
+                                        parent.links.get(&selector.field_id).map(Vec::len)
+                                    }
+                                    _ => None,
+                                })
+                                .unwrap_or(0);
+                            if selector.index > 0 {
+                                macro_entries.push(refactor(
+                                    "move up".to_string(),
+                                    Refactoring::Shift { offset: -1 },
+                                ));
+                            }
+                            if selector.index + 1 < siblings {
+                                macro_entries.push(refactor(
+                                    "move down".to_string(),
+                                    Refactoring::Shift { offset: 1 },
+                                ));
+                            }
+                        }
+                        macro_entries.push(Entry {
+                            label: "refactor".to_string(),
+                            description: "wrap, unwrap, move, swap, extract or rename".to_string(),
+                            action: Msg::PromptRefactor(node_path.clone()),
+                            valid_classes: vec![],
+                        });
                         all_entries.append(&mut field_entries);
                         all_entries.append(&mut macro_entries);
                         all_entries
@@ -300,9 +347,14 @@ impl Component for NodeComponent {
                 }
             }
         };
+        let errors = global_state
+            .node_state
+            .get(&node_path)
+            .map(|state| state.errors.join("\n"))
+            .unwrap_or_default();
         let onselect = ctx.props().onselect.clone();
         let onclick = {
-            let node_path = node_path;
+            let node_path = node_path.clone();
             ctx.link().callback(move |e: MouseEvent| {
                 e.stop_propagation();
                 onselect.emit(node_path.clone());
@@ -320,11 +372,40 @@ impl Component for NodeComponent {
         if selected {
             classes.push("border-blue-500")
         }
+        if !errors.is_empty() {
+            classes.extend(["underline", "decoration-wavy", "decoration-red-500"]);
+        }
+        // Nodes matching the last query.
+        if global_state.matches.contains(&node_path) {
+            classes.push("bg-yellow-100");
+        }
+        // Nodes selected in visual mode, or marked.
+        if global_state.selection.contains(&node_path) {
+            classes.push("bg-blue-100");
+        }
+        // Nodes selected by other collaborators.
+        let collaborators: Vec<&str> = global_state
+            .presence
+            .iter()
+            .filter(|(_, path)| **path == node_path)
+            .map(|(client_id, _)| client_id.as_str())
+            .collect();
+        if !collaborators.is_empty() {
+            classes.extend(["outline-dashed", "outline-2", "outline-green-500"]);
+        }
+        let title = if collaborators.is_empty() {
+            errors
+        } else {
+            format!("selected by {}\n{}", collaborators.join(", "), errors)
+                .trim_end()
+                .to_string()
+        };
         html! {
             <div
               class={ classes.join(" ") }
             //   tabindex="0"
               onclick={ onclick }
+              title={ title }
             >
               { inner }
             </div>
@@ -340,36 +421,19 @@ impl Component for NodeComponent {
         }
     }
 
-    fn changed(&mut self, ctx: &Context<Self>, _old_props: &NodeProperties) -> bool {
-        let new_props = ctx.props();
-        log::debug!("Node changed {:?}", new_props.cursor);
-        let same = if let Some(old_props) = &self.old_props {
-            /*
-            log::debug!(
-                "same global_state: {:?}",
-                old_props.global_state == new_props.global_state
-            );
-            log::debug!("same cursor: {:?}", old_props.cursor == new_props.cursor);
-            log::debug!(
-                "same selected_path: {:?}",
-                old_props.selected_path == new_props.selected_path
-            );
-            log::debug!("new selected_path: {:?}", new_props.selected_path);
-            log::debug!(
-                "same updatemodel: {:?}",
-                old_props.updatemodel == new_props.updatemodel
-            );
-            log::debug!("same oninput: {:?}", old_props.oninput == new_props.oninput);
-            */
-            old_props.global_state == new_props.global_state
-                || old_props.cursor == new_props.cursor
-                || old_props.selected_path == new_props.selected_path
-        } else {
-            false
-        };
-        self.old_props = Some(new_props.clone());
-        // !same
-        true
+    // Only re-renders the node if its subtree, or its state, changed, so that an edit only
+    // re-renders the nodes on the paths to it, and the nodes whose state changed.
+    fn changed(&mut self, ctx: &Context<Self>, old_props: &NodeProperties) -> bool {
+        let props = ctx.props();
+        let node_store = &props.global_state.node_store;
+        // Adding nodes to the store only changes a subtree that was missing some of them.
+        let filled = !Rc::ptr_eq(node_store, &old_props.global_state.node_store) && !self.complete;
+        self.complete = node_store.is_complete(&props.cursor.link);
+        let schema = &props.global_state.schema;
+        let old_schema = &old_props.global_state.schema;
+        filled
+            || !(Rc::ptr_eq(schema, old_schema) || schema == old_schema)
+            || SubtreeState::new(props) != SubtreeState::new(old_props)
     }
 }
 
@@ -381,4 +445,162 @@ impl NodeComponent {
             i.focus().unwrap();
         }
     }
+
+    // An editor for a leaf, according to its type: a toggle for `Bool`, a number input for `Int`
+    // and `Float`, hex for `Bytes`, and text otherwise.
+    fn view_leaf(&self, ctx: &Context<Self>, value: &[u8]) -> Html {
+        let props = ctx.props();
+        let global_state = &props.global_state;
+        let node_path = props.cursor.path();
+        let enabled = props.selected_path == node_path && global_state.mode == Mode::Edit;
+        let type_ = props.cursor.type_.clone();
+        let onupdatemodel = props.updatemodel.clone();
+        let valid = FieldValue::from_raw(&type_, value).is_some();
+        let mut classes = vec!["font-mono"];
+        if !valid {
+            classes.extend(["underline", "decoration-wavy", "decoration-red-500"]);
+        }
+        let classes = classes.join(" ");
+        match type_ {
+            FieldType::Bool => {
+                let checked = value == b"true";
+                let onchange = Callback::from(move |_: Event| {
+                    let value = (!checked).to_string().into_bytes();
+                    onupdatemodel.emit(Msg::SetNodeValue(node_path.clone(), value));
+                });
+                html! {
+                    <label class={ classes }>
+                        <input
+                            type="checkbox"
+                            ref={ self.input_node_ref.clone() }
+                            checked={ checked }
+                            disabled={ !enabled }
+                            onchange={ onchange }
+                        />
+                        { if checked { " true" } else { " false" } }
+                    </label>
+                }
+            }
+            FieldType::Int | FieldType::Float => {
+                let step = if type_ == FieldType::Int { "1" } else { "any" };
+                // Values that do not parse are not stored, the previous value being kept.
+                let oninput = Callback::from(move |e: InputEvent| {
+                    let value = get_value_from_input_event(e);
+                    if FieldValue::from_raw(&type_, value.as_bytes()).is_some() {
+                        onupdatemodel
+                            .emit(Msg::SetNodeValue(node_path.clone(), value.into_bytes()));
+                    }
+                });
+                html! {
+                    <input
+                        type="number"
+                        class={ classes }
+                        ref={ self.input_node_ref.clone() }
+                        step={ step }
+                        value={ String::from_utf8_lossy(value).to_string() }
+                        disabled={ !enabled }
+                        oninput={ oninput }
+                    />
+                }
+            }
+            FieldType::Bytes => {
+                let oninput = Callback::from(move |e: InputEvent| {
+                    let text = get_value_from_input_event(e);
+                    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+                    if let Ok(value) = hex::decode(text) {
+                        onupdatemodel.emit(Msg::SetNodeValue(node_path.clone(), value));
+                    }
+                });
+                html! {
+                    <input
+                        type="text"
+                        class={ classes }
+                        ref={ self.input_node_ref.clone() }
+                        placeholder="hex"
+                        value={ hex::encode(value) }
+                        disabled={ !enabled }
+                        oninput={ oninput }
+                        autocomplete="off"
+                    />
+                }
+            }
+            FieldType::String | FieldType::Object { .. } => {
+                let onenter = {
+                    let onupdatemodel = onupdatemodel.clone();
+                    Callback::from(move |()| {
+                        onupdatemodel.emit(Msg::Parent);
+                    })
+                };
+                let onupdatemodel0 = onupdatemodel.clone();
+                let placeholder = if value.is_empty() {
+                    "***".to_string()
+                } else {
+                    "".to_string()
+                };
+                let value =
+                    String::from_utf8(value.to_vec()).unwrap_or("INVALID STRING".to_string());
+                html! {
+                  <CommandLine
+                    input_node_ref={ self.input_node_ref.clone() }
+                    entries={ vec![] }
+                    value={ value }
+                    placeholder={ placeholder }
+                    oninput={ Callback::from(move |v: String| {
+                        onupdatemodel.emit(Msg::SetNodeValue(node_path.clone(), v.as_bytes().to_vec()));
+                     }) }
+                    onselect={ props.updatemodel.clone() }
+                    ondelete={ Callback::from(move |()| {
+                        onupdatemodel0.emit(Msg::DeleteItem);
+                     }) }
+                    onenter={ onenter }
+                    enabled={ enabled }
+                  />
+                }
+            }
+        }
+    }
+
+    // A placeholder for a node of the kind expected by its parent field.
+    fn view_hole(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let global_state = &props.global_state;
+        let node_path = props.cursor.path();
+        let selected = props.selected_path == node_path;
+        let kind_id = props.cursor.kind_id().unwrap_or_default();
+        let kind_name = global_state
+            .schema
+            .get_kind(kind_id)
+            .map(|kind| kind.name.clone())
+            .unwrap_or("hole".to_string());
+        let entries: Vec<Entry> = hole::completions(&global_state.schema, kind_id)
+            .into_iter()
+            .map(|completion| Entry {
+                label: completion.label,
+                description: kind_name.clone(),
+                action: Msg::FillHole(node_path.clone(), completion.variant),
+                valid_classes: KIND_CLASSES.iter().map(|v| v.to_string()).collect(),
+            })
+            .collect();
+        let onupdatemodel = props.updatemodel.clone();
+        let onenter = {
+            let onupdatemodel = onupdatemodel.clone();
+            Callback::from(move |()| {
+                onupdatemodel.emit(Msg::Parent);
+            })
+        };
+        html! {
+          <CommandLine
+            input_node_ref={ self.input_node_ref.clone() }
+            entries={ entries }
+            value={ "".to_string() }
+            placeholder={ format!("<{}>", kind_name) }
+            onselect={ props.updatemodel.clone() }
+            ondelete={ Callback::from(move |()| {
+                onupdatemodel.emit(Msg::DeleteItem);
+             }) }
+            onenter={ onenter }
+            enabled={ selected && global_state.mode == Mode::Edit }
+          />
+        }
+    }
 }
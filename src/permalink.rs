@@ -0,0 +1,202 @@
+use crate::{
+    schema::{FieldType, Schema},
+    types::{Digest, Format, Path, Selector},
+};
+use std::{fmt, str::FromStr};
+
+// State kept in the fragment of the URL, so that a link points to a node in a given version of a
+// tree, shown in a given way.
+//
+// The current version of the fragment is `v2/ROOT?KEY=VALUE&...`, with the values
+// percent-encoded, and the keys:
+//
+// - `schema`: digest of the schema root;
+// - `ref`: name under which the root was saved, if any; the root is the exact version, the name is
+//   resolved to a root only if the root is missing;
+// - `path`: selected path, as field names with indices, e.g. `docker_command[0].env[2]`, or field
+//   ids where the schema does not know them;
+// - `format`: text format used to import and export subtrees, e.g. `yaml`;
+// - `view`: comma-separated view options, `raw` (plain rendering) and `serialized`;
+// - `signed`: comma-separated digests of statements signing the root (see `identity.rs`), which
+//   nothing in the tree links to, so that they are fetched along with it.
+//
+// Unknown keys are ignored, so that older clients can open links with new options. Fragments
+// without a version are of the original form, `ROOT@SCHEMA_ROOT`.
+
+const VERSION: &str = "v2";
+
+const FORMATS: &[Format] = &[
+    Format::Json,
+    Format::Yaml,
+    Format::Toml,
+    Format::Html,
+    Format::Protobuf,
+    Format::Argv,
+];
+
+fn format_name(format: Format) -> String {
+    format!("{:?}", format).to_lowercase()
+}
+
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct HashState {
+    pub root: Digest,
+    pub schema_root: Digest,
+    pub ref_name: Option<String>,
+    // Encoded with `encode_path`, since decoding it requires the schema.
+    pub path: Option<String>,
+    pub format: Option<Format>,
+    pub raw: bool,
+    pub show_serialized: bool,
+    pub signatures: Vec<Digest>,
+}
+
+impl FromStr for HashState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (version, rest) = match s.split_once('/') {
+            Some((version, rest)) if version.starts_with('v') => (version, rest),
+            _ => {
+                let mut parts = s.split('@');
+                let root = parts.next().unwrap_or_default().to_string();
+                let schema_root = parts.next().unwrap_or_default().to_string();
+                return Ok(Self {
+                    root,
+                    schema_root,
+                    ..Default::default()
+                });
+            }
+        };
+        if version != VERSION {
+            return Err(format!("unsupported version {:?}", version));
+        }
+        let (root, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let mut state = HashState {
+            root: root.to_string(),
+            ..Default::default()
+        };
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "schema" => state.schema_root = value.to_string(),
+                "ref" => state.ref_name = Some(value.to_string()),
+                "path" => state.path = Some(value.to_string()),
+                "format" => {
+                    state.format = FORMATS
+                        .iter()
+                        .find(|format| format_name(**format) == value)
+                        .copied();
+                }
+                "signed" => {
+                    state.signatures = value
+                        .split(',')
+                        .filter(|digest| !digest.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                }
+                "view" => {
+                    for option in value.split(',') {
+                        match option {
+                            "raw" => state.raw = true,
+                            "serialized" => state.show_serialized = true,
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(state)
+    }
+}
+
+impl fmt::Display for HashState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        if !self.schema_root.is_empty() {
+            query.append_pair("schema", &self.schema_root);
+        }
+        if let Some(ref_name) = &self.ref_name {
+            query.append_pair("ref", ref_name);
+        }
+        if let Some(path) = &self.path {
+            query.append_pair("path", path);
+        }
+        if let Some(format) = self.format {
+            query.append_pair("format", &format_name(format));
+        }
+        let view: Vec<&str> = [(self.raw, "raw"), (self.show_serialized, "serialized")]
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, option)| *option)
+            .collect();
+        if !view.is_empty() {
+            query.append_pair("view", &view.join(","));
+        }
+        if !self.signatures.is_empty() {
+            query.append_pair("signed", &self.signatures.join(","));
+        }
+        let query = query.finish();
+        write!(f, "{}/{}", VERSION, self.root)?;
+        if !query.is_empty() {
+            write!(f, "?{}", query)?;
+        }
+        Ok(())
+    }
+}
+
+// Encodes a path under a node of the given kind, naming the fields that the schema knows.
+pub fn encode_path(schema: &Schema, kind_id: u64, path: &[Selector]) -> String {
+    let mut kind_id = Some(kind_id);
+    let mut segments = vec![];
+    for selector in path {
+        let field = kind_id
+            .and_then(|kind_id| schema.get_kind(kind_id))
+            .and_then(|kind| kind.get_field(selector.field_id));
+        let name = match field {
+            Some(field) => field.name.clone(),
+            None => selector.field_id.to_string(),
+        };
+        segments.push(format!("{}[{}]", name, selector.index));
+        kind_id = match field.map(|field| &field.type_) {
+            Some(FieldType::Object { kind_id }) => Some(*kind_id),
+            _ => None,
+        };
+    }
+    segments.join(".")
+}
+
+// Decodes a path encoded by `encode_path`; indices may be omitted, and default to 0.
+pub fn decode_path(schema: &Schema, kind_id: u64, text: &str) -> Result<Path, String> {
+    let mut kind_id = Some(kind_id);
+    let mut path = vec![];
+    for segment in text.split('.').filter(|segment| !segment.is_empty()) {
+        let (name, index) = match segment.strip_suffix(']').and_then(|s| s.split_once('[')) {
+            Some((name, index)) => (
+                name,
+                index
+                    .parse()
+                    .map_err(|_| format!("invalid index in {:?}", segment))?,
+            ),
+            None => (segment, 0),
+        };
+        let kind = kind_id.and_then(|kind_id| schema.get_kind(kind_id));
+        let field = kind.and_then(|kind| kind.fields.iter().find(|f| f.name == name));
+        let field_id = match (field, name.parse::<u64>()) {
+            (Some(field), _) => field.field_id,
+            (None, Ok(field_id)) => field_id,
+            (None, Err(_)) => return Err(format!("unknown field {:?}", name)),
+        };
+        let field = field.or_else(|| kind.and_then(|kind| kind.get_field(field_id)));
+        kind_id = match field.map(|field| &field.type_) {
+            Some(FieldType::Object { kind_id }) => Some(*kind_id),
+            _ => None,
+        };
+        path.push(Selector {
+            field_id,
+            index,
+            id: None,
+        });
+    }
+    Ok(path)
+}
@@ -0,0 +1,8 @@
+pub use linc_schema::proto::*;
+
+use crate::schema::Schema;
+
+// Schema of the LINC AST, from `proto/ast.proto`.
+pub fn ast_schema() -> Result<Schema, ProtoError> {
+    parse(include_str!("../proto/ast.proto"))
+}
@@ -0,0 +1,488 @@
+use crate::{
+    schema::{FieldType, FieldValue, Kind, ProtoType, Schema},
+    types::{Link, LinkTarget, LinkType, Node, NodeStore},
+};
+use std::fmt;
+
+// Protobuf binary and text format for trees typed by a schema (e.g. one parsed by `proto::parse`).
+//
+// Field ids are used as protobuf field numbers, nodes are embedded messages, and leaves are
+// scalars, encoded according to the `proto_type` of their field (e.g. zigzag varints for `sint32`,
+// or four bytes for `float`); without one, integers are encoded as `int64` and floats as `double`.
+// Repeated scalars are encoded packed, and may be decoded either packed or not. Unknown fields are
+// skipped when decoding.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtobufError {
+    // Path of the offending field, e.g. `function_definitions[0].body`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ProtobufError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "<root>"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+impl std::error::Error for ProtobufError {}
+
+fn error<T>(path: &str, message: String) -> Result<T, ProtobufError> {
+    Err(ProtobufError {
+        path: path.to_string(),
+        message,
+    })
+}
+
+fn field_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LENGTH_DELIMITED: u64 = 2;
+const FIXED32: u64 = 5;
+
+fn get_kind<'a>(schema: &'a Schema, kind_id: u64, path: &str) -> Result<&'a Kind, ProtobufError> {
+    match schema.get_kind(kind_id) {
+        Some(kind) => Ok(kind),
+        None => error(path, format!("unknown kind {}", kind_id)),
+    }
+}
+
+fn get_node(node_store: &NodeStore, link: &Link, path: &str) -> Result<Node, ProtobufError> {
+    match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => Ok(node),
+        Some(LinkTarget::Raw(_)) => error(path, format!("expected node at {}", link.digest)),
+        None => error(path, format!("missing node {}", link.digest)),
+    }
+}
+
+fn get_value(
+    node_store: &NodeStore,
+    type_: &FieldType,
+    link: &Link,
+    path: &str,
+) -> Result<FieldValue, ProtobufError> {
+    match link.get(node_store) {
+        Some(LinkTarget::Raw(raw)) => match FieldValue::from_raw(type_, raw) {
+            Some(value) => Ok(value),
+            None => error(path, format!("invalid {:?} value", type_)),
+        },
+        Some(LinkTarget::Parsed(_)) => {
+            error(path, format!("expected raw value at {}", link.digest))
+        }
+        None => error(path, format!("missing value {}", link.digest)),
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_tag(out: &mut Vec<u8>, field_id: u64, wire_type: u64) {
+    put_varint(out, (field_id << 3) | wire_type);
+}
+
+fn put_length_delimited(out: &mut Vec<u8>, field_id: u64, value: &[u8]) {
+    put_tag(out, field_id, LENGTH_DELIMITED);
+    put_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+// Appends a scalar without its tag.
+fn put_scalar(out: &mut Vec<u8>, value: &FieldValue, proto_type: Option<ProtoType>) {
+    match (value, proto_type) {
+        (FieldValue::Int(v), Some(ProtoType::Sint32 | ProtoType::Sint64)) => {
+            put_varint(out, ((v << 1) ^ (v >> 63)) as u64)
+        }
+        (FieldValue::Int(v), Some(ProtoType::Fixed32 | ProtoType::Sfixed32)) => {
+            out.extend_from_slice(&(*v as u32).to_le_bytes())
+        }
+        (FieldValue::Int(v), Some(ProtoType::Fixed64 | ProtoType::Sfixed64)) => {
+            out.extend_from_slice(&v.to_le_bytes())
+        }
+        // Negative `int32` values are sign extended, as in the protobuf spec.
+        (FieldValue::Int(v), _) => put_varint(out, *v as u64),
+        (FieldValue::Float(v), Some(ProtoType::Float)) => {
+            out.extend_from_slice(&(*v as f32).to_le_bytes())
+        }
+        (FieldValue::Float(v), _) => out.extend_from_slice(&v.to_le_bytes()),
+        (FieldValue::Bool(v), _) => put_varint(out, *v as u64),
+        (FieldValue::String(v), _) => put_varint_prefixed(out, v.as_bytes()),
+        (FieldValue::Bytes(v), _) => put_varint_prefixed(out, v),
+        (FieldValue::Object(_), _) => unreachable!(),
+    }
+}
+
+fn put_varint_prefixed(out: &mut Vec<u8>, value: &[u8]) {
+    put_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+fn wire_type(type_: &FieldType, proto_type: Option<ProtoType>) -> u64 {
+    match (type_, proto_type) {
+        (FieldType::Int, Some(ProtoType::Fixed32 | ProtoType::Sfixed32)) => FIXED32,
+        (FieldType::Int, Some(ProtoType::Fixed64 | ProtoType::Sfixed64)) => FIXED64,
+        (FieldType::Int | FieldType::Bool, _) => VARINT,
+        (FieldType::Float, Some(ProtoType::Float)) => FIXED32,
+        (FieldType::Float, _) => FIXED64,
+        (FieldType::String | FieldType::Bytes | FieldType::Object { .. }, _) => LENGTH_DELIMITED,
+    }
+}
+
+// Encodes the node at the given link as a protobuf message of the given kind.
+pub fn encode(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+) -> Result<Vec<u8>, ProtobufError> {
+    let mut out = vec![];
+    encode_message(node_store, schema, kind_id, link, "", &mut out)?;
+    Ok(out)
+}
+
+fn encode_message(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+    path: &str,
+    out: &mut Vec<u8>,
+) -> Result<(), ProtobufError> {
+    let kind = get_kind(schema, kind_id, path)?;
+    let node = get_node(node_store, link, path)?;
+    // Fields are written in field number order, as recommended by the protobuf spec.
+    for (field_id, links) in &node.links {
+        let field = match kind.get_field(*field_id) {
+            Some(field) => field,
+            None => {
+                return error(
+                    path,
+                    format!("unknown field {} for kind `{}`", field_id, kind.name),
+                )
+            }
+        };
+        if !field.repeated && links.len() > 1 {
+            return error(
+                &field_path(path, &field.name),
+                format!("{} values for non repeated field", links.len()),
+            );
+        }
+        let wire_type = wire_type(&field.type_, field.proto_type);
+        let packed = field.repeated && wire_type != LENGTH_DELIMITED;
+        let mut packed_values = vec![];
+        for (i, link) in links.iter().enumerate() {
+            let path = if field.repeated {
+                format!("{}[{}]", field_path(path, &field.name), i)
+            } else {
+                field_path(path, &field.name)
+            };
+            match &field.type_ {
+                FieldType::Object { kind_id } => {
+                    let mut message = vec![];
+                    encode_message(node_store, schema, *kind_id, link, &path, &mut message)?;
+                    put_length_delimited(out, *field_id, &message);
+                }
+                type_ => {
+                    let value = get_value(node_store, type_, link, &path)?;
+                    if packed {
+                        put_scalar(&mut packed_values, &value, field.proto_type);
+                    } else {
+                        put_tag(out, *field_id, wire_type);
+                        put_scalar(out, &value, field.proto_type);
+                    }
+                }
+            }
+        }
+        if packed && !links.is_empty() {
+            put_length_delimited(out, *field_id, &packed_values);
+        }
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn varint(&mut self, path: &str) -> Result<u64, ProtobufError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = match self.bytes.get(self.pos) {
+                Some(byte) => *byte,
+                None => return error(path, "truncated varint".to_string()),
+            };
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        error(path, "varint too long".to_string())
+    }
+
+    fn take(&mut self, len: usize, path: &str) -> Result<&'a [u8], ProtobufError> {
+        if self.bytes.len() - self.pos < len {
+            return error(path, "truncated message".to_string());
+        }
+        let value = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(value)
+    }
+
+    fn fixed<const N: usize>(&mut self, path: &str) -> Result<[u8; N], ProtobufError> {
+        Ok(self.take(N, path)?.try_into().unwrap())
+    }
+
+    fn length_delimited(&mut self, path: &str) -> Result<&'a [u8], ProtobufError> {
+        let len = self.varint(path)?;
+        self.take(len as usize, path)
+    }
+
+    // Skips a value of an unknown field, encoded with the given wire type.
+    fn skip(&mut self, wire_type: u64, path: &str) -> Result<(), ProtobufError> {
+        match wire_type {
+            VARINT => self.varint(path).map(|_| ()),
+            FIXED64 => self.take(8, path).map(|_| ()),
+            LENGTH_DELIMITED => self.length_delimited(path).map(|_| ()),
+            FIXED32 => self.take(4, path).map(|_| ()),
+            wire_type => error(path, format!("unsupported wire type {}", wire_type)),
+        }
+    }
+
+    // Reads a scalar of the given type, which must be encoded with the given wire type.
+    fn scalar(
+        &mut self,
+        type_: &FieldType,
+        proto_type: Option<ProtoType>,
+        wire_type: u64,
+        path: &str,
+    ) -> Result<FieldValue, ProtobufError> {
+        if wire_type != self::wire_type(type_, proto_type) {
+            return error(
+                path,
+                format!("unexpected wire type {} for {:?}", wire_type, type_),
+            );
+        }
+        match (type_, proto_type) {
+            (FieldType::Int, Some(ProtoType::Int32)) => {
+                Ok(FieldValue::Int(self.varint(path)? as i32 as i64))
+            }
+            (FieldType::Int, Some(ProtoType::Uint32)) => {
+                Ok(FieldValue::Int(self.varint(path)? as u32 as i64))
+            }
+            (FieldType::Int, Some(ProtoType::Sint32 | ProtoType::Sint64)) => {
+                let v = self.varint(path)?;
+                Ok(FieldValue::Int((v >> 1) as i64 ^ -((v & 1) as i64)))
+            }
+            (FieldType::Int, Some(ProtoType::Fixed32)) => {
+                Ok(FieldValue::Int(u32::from_le_bytes(self.fixed(path)?) as i64))
+            }
+            (FieldType::Int, Some(ProtoType::Sfixed32)) => {
+                Ok(FieldValue::Int(i32::from_le_bytes(self.fixed(path)?) as i64))
+            }
+            (FieldType::Int, Some(ProtoType::Fixed64 | ProtoType::Sfixed64)) => {
+                Ok(FieldValue::Int(i64::from_le_bytes(self.fixed(path)?)))
+            }
+            (FieldType::Int, _) => Ok(FieldValue::Int(self.varint(path)? as i64)),
+            (FieldType::Bool, _) => Ok(FieldValue::Bool(self.varint(path)? != 0)),
+            (FieldType::Float, Some(ProtoType::Float)) => Ok(FieldValue::Float(
+                f32::from_le_bytes(self.fixed(path)?) as f64,
+            )),
+            (FieldType::Float, _) => Ok(FieldValue::Float(f64::from_le_bytes(self.fixed(path)?))),
+            (FieldType::String, _) => {
+                match String::from_utf8(self.length_delimited(path)?.to_vec()) {
+                    Ok(v) => Ok(FieldValue::String(v)),
+                    Err(_) => error(path, "invalid UTF-8 string".to_string()),
+                }
+            }
+            (FieldType::Bytes, _) => Ok(FieldValue::Bytes(self.length_delimited(path)?.to_vec())),
+            (FieldType::Object { .. }, _) => unreachable!(),
+        }
+    }
+}
+
+fn put_value(node_store: &mut NodeStore, value: &FieldValue) -> Link {
+    Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(&value.to_raw().unwrap()),
+    }
+}
+
+// Decodes a protobuf message of the given kind, adding its nodes to the store.
+pub fn decode(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    bytes: &[u8],
+) -> Result<Link, ProtobufError> {
+    decode_message(node_store, schema, kind_id, bytes, "")
+}
+
+fn decode_message(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    bytes: &[u8],
+    path: &str,
+) -> Result<Link, ProtobufError> {
+    let kind = get_kind(schema, kind_id, path)?;
+    let mut node = Node::default();
+    let mut reader = Reader { bytes, pos: 0 };
+    while !reader.is_empty() {
+        let tag = reader.varint(path)?;
+        let (field_id, wire_type) = (tag >> 3, tag & 7);
+        let field = match kind.get_field(field_id) {
+            Some(field) => field,
+            None => {
+                reader.skip(wire_type, path)?;
+                continue;
+            }
+        };
+        let path = field_path(path, &field.name);
+        let mut links = vec![];
+        match &field.type_ {
+            FieldType::Object { kind_id } => {
+                if wire_type != LENGTH_DELIMITED {
+                    return error(&path, format!("unexpected wire type {}", wire_type));
+                }
+                let message = reader.length_delimited(&path)?;
+                links.push(decode_message(
+                    node_store, schema, *kind_id, message, &path,
+                )?);
+            }
+            type_
+                if wire_type == LENGTH_DELIMITED
+                    && self::wire_type(type_, field.proto_type) != LENGTH_DELIMITED =>
+            {
+                let mut packed = Reader {
+                    bytes: reader.length_delimited(&path)?,
+                    pos: 0,
+                };
+                let wire_type = self::wire_type(type_, field.proto_type);
+                while !packed.is_empty() {
+                    let value = packed.scalar(type_, field.proto_type, wire_type, &path)?;
+                    links.push(put_value(node_store, &value));
+                }
+            }
+            type_ => {
+                let value = reader.scalar(type_, field.proto_type, wire_type, &path)?;
+                links.push(put_value(node_store, &value));
+            }
+        }
+        let entry = node.links.entry(field_id).or_default();
+        if !field.repeated {
+            // For non repeated fields the last value wins.
+            entry.clear();
+            links.drain(..links.len().saturating_sub(1));
+        }
+        entry.extend(links);
+    }
+    Ok(Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&node),
+    })
+}
+
+fn escape(value: &[u8]) -> String {
+    let mut out = String::new();
+    for b in value {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(*b as char),
+            b => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out
+}
+
+// Prints the node at the given link in protobuf text format.
+pub fn print_text(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+) -> Result<String, ProtobufError> {
+    let mut out = String::new();
+    print_message(node_store, schema, kind_id, link, "", 0, &mut out)?;
+    Ok(out)
+}
+
+fn print_message(
+    node_store: &NodeStore,
+    schema: &Schema,
+    kind_id: u64,
+    link: &Link,
+    path: &str,
+    depth: usize,
+    out: &mut String,
+) -> Result<(), ProtobufError> {
+    let kind = get_kind(schema, kind_id, path)?;
+    let node = get_node(node_store, link, path)?;
+    let indent = "  ".repeat(depth);
+    for (field_id, links) in &node.links {
+        let field = match kind.get_field(*field_id) {
+            Some(field) => field,
+            None => {
+                return error(
+                    path,
+                    format!("unknown field {} for kind `{}`", field_id, kind.name),
+                )
+            }
+        };
+        for (i, link) in links.iter().enumerate() {
+            let path = if field.repeated {
+                format!("{}[{}]", field_path(path, &field.name), i)
+            } else {
+                field_path(path, &field.name)
+            };
+            let value = match &field.type_ {
+                FieldType::Object { kind_id } => {
+                    out.push_str(&format!("{}{} {{\n", indent, field.name));
+                    print_message(node_store, schema, *kind_id, link, &path, depth + 1, out)?;
+                    out.push_str(&format!("{}}}\n", indent));
+                    continue;
+                }
+                type_ => match get_value(node_store, type_, link, &path)? {
+                    FieldValue::String(v) => format!("\"{}\"", escape(v.as_bytes())),
+                    FieldValue::Bytes(v) => format!("\"{}\"", escape(&v)),
+                    FieldValue::Float(v) if v.is_nan() => "nan".to_string(),
+                    FieldValue::Float(v) if v.is_infinite() => {
+                        if v > 0.0 { "inf" } else { "-inf" }.to_string()
+                    }
+                    FieldValue::Float(v) => v.to_string(),
+                    FieldValue::Int(v) => v.to_string(),
+                    FieldValue::Bool(v) => v.to_string(),
+                    FieldValue::Object(_) => unreachable!(),
+                },
+            };
+            out.push_str(&format!("{}{}: {}\n", indent, field.name, value));
+        }
+    }
+    Ok(())
+}
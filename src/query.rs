@@ -0,0 +1,369 @@
+use crate::{
+    schema::Schema,
+    types::{Cursor, LinkTarget, NodeStore, Path, Selector},
+};
+use std::fmt;
+
+// Queries selecting nodes by the names of the fields leading to them, along the lines of XPath and
+// jq, e.g. `root.docker_command.docker_run[*].env[.^="FOO="]`.
+//
+// A query starts with the kind of the node it is evaluated on (or `*` for any kind), which may be
+// omitted, followed by steps:
+//
+// - `.name` selects the children in the field `name`, and `.*` those in any field;
+// - `..name` (or `..*`) selects the descendants at any depth in the field `name`;
+// - `:kind` keeps the nodes of the given kind;
+// - `[3]` keeps the nodes at the given index of their field, and `[*]` keeps them all;
+// - `[a.b="x"]` keeps the nodes that have a descendant at the relative path `a.b` whose raw value
+//   is `x`, where `.` is the node itself, and `!=`, `^=` (starts with) and `*=` (contains) may be
+//   used instead of `=`; `[a.b]` keeps the nodes that have such a descendant.
+//
+// Values are quoted strings, or bare words such as numbers. Matches are returned in document
+// order.
+
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    // Offset in the query, in bytes.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Name {
+    Any,
+    Name(String),
+}
+
+impl Name {
+    fn matches(&self, name: Option<&str>) -> bool {
+        match self {
+            Name::Any => true,
+            Name::Name(expected) => name == Some(expected.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    StartsWith,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Child(Name),
+    Descendant(Name),
+    Kind(String),
+    Index(usize),
+    // Relative path of field names, and the value to compare to, if any.
+    Predicate(Vec<String>, Option<(Op, String)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub kind: Name,
+    pub steps: Vec<Step>,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, QueryError> {
+        Err(QueryError {
+            position: self.position,
+            message: message.to_string(),
+        })
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        if self.rest().starts_with(prefix) {
+            self.position += prefix.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || "_-".contains(c)))
+            .unwrap_or(rest.len());
+        self.position += len;
+        &rest[..len]
+    }
+
+    fn identifier(&mut self) -> Result<String, QueryError> {
+        match self.word() {
+            "" => self.error("expected a name"),
+            word => Ok(word.to_string()),
+        }
+    }
+
+    fn name(&mut self) -> Result<Name, QueryError> {
+        if self.eat("*") {
+            Ok(Name::Any)
+        } else {
+            self.identifier().map(Name::Name)
+        }
+    }
+
+    fn value(&mut self) -> Result<String, QueryError> {
+        if !self.eat("\"") {
+            return match self.word() {
+                "" => self.error("expected a value"),
+                word => Ok(word.to_string()),
+            };
+        }
+        let mut value = String::new();
+        let mut chars = self.rest().chars();
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some(c) => value.push(c),
+                    None => return self.error("unterminated string"),
+                },
+                Some(c) => value.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+        self.position = self.text.len() - chars.as_str().len();
+        Ok(value)
+    }
+
+    fn bracket(&mut self) -> Result<Step, QueryError> {
+        self.skip_whitespace();
+        if self.eat("*") {
+            return Ok(Step::Predicate(vec![], None));
+        }
+        if self.rest().starts_with(|c: char| c.is_ascii_digit()) {
+            let start = self.position;
+            let digits = self.word();
+            return match digits.parse() {
+                Ok(index) => Ok(Step::Index(index)),
+                Err(_) => {
+                    self.position = start;
+                    self.error("invalid index")
+                }
+            };
+        }
+        let mut path = vec![];
+        if !self.eat(".") {
+            path.push(self.identifier()?);
+            while self.eat(".") {
+                path.push(self.identifier()?);
+            }
+        }
+        self.skip_whitespace();
+        let op = [
+            ("!=", Op::Ne),
+            ("^=", Op::StartsWith),
+            ("*=", Op::Contains),
+            ("=", Op::Eq),
+        ]
+        .into_iter()
+        .find(|(token, _)| self.eat(token))
+        .map(|(_, op)| op);
+        let comparison = match op {
+            Some(op) => {
+                self.skip_whitespace();
+                Some((op, self.value()?))
+            }
+            None => None,
+        };
+        Ok(Step::Predicate(path, comparison))
+    }
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, QueryError> {
+        let mut parser = Parser { text, position: 0 };
+        parser.skip_whitespace();
+        let kind = if parser.rest().starts_with('.') {
+            Name::Any
+        } else {
+            parser.name()?
+        };
+        let mut steps = vec![];
+        loop {
+            parser.skip_whitespace();
+            if parser.rest().is_empty() {
+                break;
+            } else if parser.eat("..") {
+                steps.push(Step::Descendant(parser.name()?));
+            } else if parser.eat(".") {
+                steps.push(Step::Child(parser.name()?));
+            } else if parser.eat(":") {
+                steps.push(Step::Kind(parser.identifier()?));
+            } else if parser.eat("[") {
+                steps.push(parser.bracket()?);
+                parser.skip_whitespace();
+                if !parser.eat("]") {
+                    return parser.error("expected `]`");
+                }
+            } else {
+                return parser.error("expected `.`, `..`, `:` or `[`");
+            }
+        }
+        Ok(Query { kind, steps })
+    }
+
+    // Returns the nodes under the given cursor (included) that match the query.
+    pub fn select(&self, node_store: &NodeStore, schema: &Schema, root: &Cursor) -> Vec<Cursor> {
+        let mut cursors = vec![];
+        if self.kind.matches(kind_name(schema, root)) {
+            cursors.push(root.clone());
+        }
+        for step in self.steps.iter() {
+            cursors = match step {
+                Step::Child(name) => cursors
+                    .iter()
+                    .flat_map(|cursor| children(node_store, schema, cursor, name))
+                    .collect(),
+                Step::Descendant(name) => {
+                    let mut descendants = vec![];
+                    for cursor in cursors.iter() {
+                        collect_descendants(node_store, schema, cursor, name, &mut descendants);
+                    }
+                    descendants
+                }
+                Step::Kind(kind) => cursors
+                    .into_iter()
+                    .filter(|cursor| kind_name(schema, cursor) == Some(kind.as_str()))
+                    .collect(),
+                Step::Index(index) => cursors
+                    .into_iter()
+                    .filter(|cursor| cursor.path().last().map(|s| s.index) == Some(*index))
+                    .collect(),
+                Step::Predicate(path, comparison) => cursors
+                    .into_iter()
+                    .filter(|cursor| {
+                        matches_predicate(node_store, schema, cursor, path, comparison.as_ref())
+                    })
+                    .collect(),
+            };
+            // Descendants of several nodes may overlap.
+            cursors.sort_by_key(|cursor| key(&cursor.path()));
+            cursors.dedup_by_key(|cursor| cursor.path());
+        }
+        cursors
+    }
+
+    pub fn select_paths(
+        &self,
+        node_store: &NodeStore,
+        schema: &Schema,
+        root: &Cursor,
+    ) -> Vec<Path> {
+        self.select(node_store, schema, root)
+            .iter()
+            .map(Cursor::path)
+            .collect()
+    }
+}
+
+// Document order of paths.
+fn key(path: &[Selector]) -> Vec<(u64, usize)> {
+    path.iter().map(|s| (s.field_id, s.index)).collect()
+}
+
+fn kind_name<'a>(schema: &'a Schema, cursor: &Cursor) -> Option<&'a str> {
+    let kind = schema.get_kind(cursor.kind_id()?)?;
+    Some(kind.name.as_str())
+}
+
+fn field_name<'a>(schema: &'a Schema, cursor: &Cursor, field_id: u64) -> Option<&'a str> {
+    let kind = schema.get_kind(cursor.kind_id()?)?;
+    Some(kind.get_field(field_id)?.name.as_str())
+}
+
+fn children(node_store: &NodeStore, schema: &Schema, cursor: &Cursor, name: &Name) -> Vec<Cursor> {
+    let node = match cursor.link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        _ => return vec![],
+    };
+    node.links
+        .iter()
+        .filter(|(field_id, _)| name.matches(field_name(schema, cursor, **field_id)))
+        .flat_map(|(field_id, links)| {
+            (0..links.len()).map(move |index| Selector {
+                field_id: *field_id,
+                index,
+                id: None,
+            })
+        })
+        .filter_map(|selector| cursor.traverse(node_store, schema, &[selector]))
+        .collect()
+}
+
+fn collect_descendants(
+    node_store: &NodeStore,
+    schema: &Schema,
+    cursor: &Cursor,
+    name: &Name,
+    descendants: &mut Vec<Cursor>,
+) {
+    for child in children(node_store, schema, cursor, &Name::Any) {
+        let field_id = child.path().last().unwrap().field_id;
+        if name.matches(field_name(schema, cursor, field_id)) {
+            descendants.push(child.clone());
+        }
+        collect_descendants(node_store, schema, &child, name, descendants);
+    }
+}
+
+fn matches_predicate(
+    node_store: &NodeStore,
+    schema: &Schema,
+    cursor: &Cursor,
+    path: &[String],
+    comparison: Option<&(Op, String)>,
+) -> bool {
+    let mut cursors = vec![cursor.clone()];
+    for name in path {
+        let name = Name::Name(name.clone());
+        cursors = cursors
+            .iter()
+            .flat_map(|cursor| children(node_store, schema, cursor, &name))
+            .collect();
+    }
+    let (op, expected) = match comparison {
+        Some(comparison) => comparison,
+        None => return !cursors.is_empty(),
+    };
+    cursors.iter().any(|cursor| {
+        let value = match cursor.link.get(node_store) {
+            Some(LinkTarget::Raw(raw)) => String::from_utf8_lossy(raw).to_string(),
+            _ => return false,
+        };
+        match op {
+            Op::Eq => value == *expected,
+            Op::Ne => value != *expected,
+            Op::StartsWith => value.starts_with(expected.as_str()),
+            Op::Contains => value.contains(expected.as_str()),
+        }
+    })
+}
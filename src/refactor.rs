@@ -0,0 +1,826 @@
+use crate::{
+    ast, crdt,
+    query::Query,
+    schema::{Field, FieldType, FieldValue, Schema},
+    types::{append, Cursor, Link, LinkTarget, LinkType, Node, NodeStore, Path, Selector},
+};
+use std::{collections::BTreeSet, fmt};
+
+// Structural refactorings, checked against the schema before they are applied.
+//
+// A refactoring rewrites the tree around a node, and returns the edit to make: a new link for the
+// node or one of its ancestors, which the caller applies (e.g. with `Model::replace_link`, so that
+// it is recorded and re-encrypted like any other edit). Since the type of a link is given by the
+// field holding it, a refactoring may only put a node where the schema expects its type, and only
+// builds nodes whose fields exist in their kind, with at most one link in fields that are not
+// repeated, and at most one field set in each `oneof`.
+//
+// `refactor_paths` applies a refactoring to several nodes, e.g. those selected in visual mode or
+// the matches of a query, producing a single new root; it fails without changing anything if one
+// of them fails.
+
+#[derive(Debug, PartialEq)]
+pub struct RefactorError {
+    pub path: Path,
+    pub message: String,
+}
+
+impl fmt::Display for RefactorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self
+            .path
+            .iter()
+            .map(|s| format!("{}[{}]", s.field_id, s.index))
+            .collect();
+        write!(f, "/{}: {}", path.join("/"), self.message)
+    }
+}
+
+impl std::error::Error for RefactorError {}
+
+fn error<T>(path: &[Selector], message: impl Into<String>) -> Result<T, RefactorError> {
+    Err(RefactorError {
+        path: path.to_vec(),
+        message: message.into(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Refactoring {
+    // Wraps the node in a new node of the given kind, nested through the given fields, e.g. kind
+    // `Expression` and fields `list_value`, `values` turn an expression into a list holding it.
+    Wrap { kind_id: u64, fields: Vec<u64> },
+    // Replaces the node with its first descendant of the same type, through nodes with a single
+    // child node; the leaves of the nodes removed are dropped.
+    Unwrap,
+    // Moves the node to the given index of the given field of its parent.
+    Move { field_id: u64, index: usize },
+    // Swaps the node with its sibling at the given index.
+    Swap { index: usize },
+    // Moves the node by the given number of places within its field, e.g. -1 to move it up.
+    Shift { offset: isize },
+    // Removes the node from its parent.
+    Delete,
+    // Sets a leaf field of the node, by name, to a value given as text.
+    SetField { name: String, value: String },
+    // Moves an expression to a new function definition with the given name, in the file
+    // containing it, and replaces it with a call to that function.
+    Extract { name: String },
+    // Sets the name of the label of a node, e.g. a function definition or an argument. References
+    // go through refs rather than names, so they do not need to be updated.
+    Rename { name: String },
+}
+
+// Result of a refactoring: the link to put at `path`, and the path to select afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub path: Path,
+    pub link: Link,
+    pub selection: Path,
+}
+
+impl Refactoring {
+    // Parses a refactoring as typed by the user, with names rather than ids: `wrap KIND FIELD...`,
+    // `unwrap`, `move FIELD INDEX`, `swap INDEX`, `shift OFFSET`, `delete`, `set FIELD VALUE`,
+    // `extract NAME` or `rename NAME`. Field names of `move` are looked up in the given kind, that
+    // of the parent of the node to move.
+    pub fn parse(schema: &Schema, parent_kind_id: Option<u64>, text: &str) -> Result<Self, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let index = |word: &str| {
+            word.parse::<usize>()
+                .map_err(|_| format!("invalid index {:?}", word))
+        };
+        match words.as_slice() {
+            ["wrap", kind, fields @ ..] if !fields.is_empty() => {
+                let kind = schema
+                    .kinds
+                    .iter()
+                    .find(|k| k.name == *kind)
+                    .ok_or_else(|| format!("unknown kind {:?}", kind))?;
+                let mut kind_id = Some(kind.kind_id);
+                let mut field_ids = vec![];
+                for name in fields {
+                    let field = kind_id
+                        .and_then(|kind_id| schema.get_kind(kind_id))
+                        .and_then(|kind| kind.fields.iter().find(|f| f.name == *name))
+                        .ok_or_else(|| format!("unknown field {:?}", name))?;
+                    field_ids.push(field.field_id);
+                    kind_id = match field.type_ {
+                        FieldType::Object { kind_id } => Some(kind_id),
+                        _ => None,
+                    };
+                }
+                Ok(Refactoring::Wrap {
+                    kind_id: kind.kind_id,
+                    fields: field_ids,
+                })
+            }
+            ["unwrap"] => Ok(Refactoring::Unwrap),
+            ["move", field, i] => {
+                let field = parent_kind_id
+                    .and_then(|kind_id| schema.get_kind(kind_id))
+                    .and_then(|kind| kind.fields.iter().find(|f| f.name == *field))
+                    .ok_or_else(|| format!("unknown field {:?}", field))?;
+                Ok(Refactoring::Move {
+                    field_id: field.field_id,
+                    index: index(i)?,
+                })
+            }
+            ["swap", i] => Ok(Refactoring::Swap { index: index(i)? }),
+            ["shift", offset] => Ok(Refactoring::Shift {
+                offset: offset
+                    .parse()
+                    .map_err(|_| format!("invalid offset {:?}", offset))?,
+            }),
+            ["delete"] => Ok(Refactoring::Delete),
+            // The value is the rest of the text, spaces included.
+            ["set", name, ..] => Ok(Refactoring::SetField {
+                name: name.to_string(),
+                value: text.trim_start()["set".len()..].trim_start()[name.len()..]
+                    .trim_start()
+                    .to_string(),
+            }),
+            ["extract", name] => Ok(Refactoring::Extract {
+                name: name.to_string(),
+            }),
+            ["rename", name] => Ok(Refactoring::Rename {
+                name: name.to_string(),
+            }),
+            _ => Err(format!("unknown refactoring {:?}", text)),
+        }
+    }
+}
+
+// Field paths through which a node of the given kind may be wrapped in another node of the same
+// kind, at most two fields deep, e.g. `list_value`, `values` for an `Expression`.
+pub fn wrappings(schema: &Schema, kind_id: u64) -> Vec<Vec<u64>> {
+    let type_ = FieldType::Object { kind_id };
+    let kind = match schema.get_kind(kind_id) {
+        Some(kind) => kind,
+        None => return vec![],
+    };
+    let mut wrappings = vec![];
+    for field in kind.fields.iter() {
+        if field.type_ == type_ {
+            wrappings.push(vec![field.field_id]);
+        } else if let FieldType::Object { kind_id } = field.type_ {
+            for inner in schema
+                .get_kind(kind_id)
+                .iter()
+                .flat_map(|k| k.fields.iter())
+            {
+                if inner.type_ == type_ {
+                    wrappings.push(vec![field.field_id, inner.field_id]);
+                }
+            }
+        }
+    }
+    wrappings
+}
+
+fn type_name(schema: &Schema, type_: &FieldType) -> String {
+    match type_ {
+        FieldType::Object { kind_id } => schema
+            .get_kind(*kind_id)
+            .map(|kind| kind.name.clone())
+            .unwrap_or_else(|| format!("kind {}", kind_id)),
+        type_ => format!("{:?}", type_).to_lowercase(),
+    }
+}
+
+fn get_field<'a>(
+    schema: &'a Schema,
+    path: &[Selector],
+    kind_id: Option<u64>,
+    field_id: u64,
+) -> Result<&'a Field, RefactorError> {
+    match kind_id.and_then(|kind_id| schema.get_kind(kind_id)) {
+        Some(kind) => match kind.get_field(field_id) {
+            Some(field) => Ok(field),
+            None => error(path, format!("{} has no field {}", kind.name, field_id)),
+        },
+        None => error(path, "not a node of a known kind"),
+    }
+}
+
+fn get_node(node_store: &NodeStore, cursor: &Cursor) -> Result<Node, RefactorError> {
+    match cursor.link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => Ok(node),
+        Some(LinkTarget::Raw(_)) => error(&cursor.path(), "not a node"),
+        None => error(&cursor.path(), "missing node"),
+    }
+}
+
+fn put(node_store: &mut NodeStore, node: &Node) -> Link {
+    Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(node),
+    }
+}
+
+fn put_value(node_store: &mut NodeStore, value: FieldValue) -> Link {
+    Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(&value.to_raw().unwrap_or_default()),
+    }
+}
+
+// Checks the fields of a node built by a refactoring against its kind.
+fn check_node(
+    schema: &Schema,
+    path: &[Selector],
+    kind_id: u64,
+    node: &Node,
+) -> Result<(), RefactorError> {
+    let kind = match schema.get_kind(kind_id) {
+        Some(kind) => kind,
+        None => return error(path, format!("unknown kind {}", kind_id)),
+    };
+    let mut oneofs = BTreeSet::new();
+    for (field_id, links) in node.links.iter() {
+        let field = get_field(schema, path, Some(kind_id), *field_id)?;
+        if !field.repeated && links.len() > 1 {
+            return error(
+                path,
+                format!("{}.{} is not repeated", kind.name, field.name),
+            );
+        }
+        if let Some(oneof) = &field.oneof {
+            if !oneofs.insert(oneof) {
+                return error(
+                    path,
+                    format!("more than one field of {}.{} is set", kind.name, oneof),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// Replaces the link at the given path under the given link, and returns the new link. Encrypted
+// links are not rewritten, since the plaintext of their targets must not be stored.
+pub fn replace(
+    node_store: &mut NodeStore,
+    link: &Link,
+    path: &[Selector],
+    new_link: &Link,
+) -> Result<Link, RefactorError> {
+    let (selector, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Ok(new_link.clone()),
+    };
+    if link.type_ == LinkType::Encrypted {
+        return error(path, "cannot refactor inside an encrypted subtree");
+    }
+    let mut node = match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        _ => return error(path, "not a node"),
+    };
+    let child = match node.get_link(selector) {
+        Some(child) => child.clone(),
+        None => return error(path, "missing child"),
+    };
+    let child = replace(node_store, &child, rest, new_link)?;
+    if let Some(old) = node.get_link_mut(selector) {
+        *old = child;
+    }
+    Ok(put(node_store, &node))
+}
+
+// Computes a refactoring of the node at the given path under the given root.
+pub fn refactor(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    root: &Cursor,
+    path: &[Selector],
+    refactoring: &Refactoring,
+    replica: &str,
+) -> Result<Edit, RefactorError> {
+    let cursor = match root.traverse(node_store, schema, path) {
+        Some(cursor) => cursor,
+        None => return error(path, "no node at this path"),
+    };
+    match refactoring {
+        Refactoring::Wrap { kind_id, fields } => {
+            wrap(node_store, schema, &cursor, *kind_id, fields)
+        }
+        Refactoring::Unwrap => unwrap(node_store, schema, &cursor),
+        Refactoring::Move { field_id, index } => move_to(
+            node_store, schema, root, &cursor, *field_id, *index, replica,
+        ),
+        Refactoring::Shift { offset } => {
+            let (parent, selector) = split_parent(root, node_store, schema, path)?;
+            let node = get_node(node_store, &parent)?;
+            let old_index = node.index(&selector).unwrap_or(selector.index);
+            match old_index.checked_add_signed(*offset) {
+                Some(index) => move_to(
+                    node_store,
+                    schema,
+                    root,
+                    &cursor,
+                    selector.field_id,
+                    index,
+                    replica,
+                ),
+                None => error(path, "cannot move before the first item"),
+            }
+        }
+        Refactoring::Delete => {
+            let (parent, selector) = split_parent(root, node_store, schema, path)?;
+            let mut node = get_node(node_store, &parent)?;
+            let index = node.index(&selector).unwrap_or(selector.index);
+            remove_child(&mut node, selector.field_id, index);
+            let parent_path = parent.path();
+            Ok(Edit {
+                link: put(node_store, &node),
+                selection: parent_path.clone(),
+                path: parent_path,
+            })
+        }
+        Refactoring::SetField { name, value } => {
+            let field = cursor
+                .kind_id()
+                .and_then(|kind_id| schema.get_kind(kind_id))
+                .and_then(|kind| kind.fields.iter().find(|f| f.name == *name));
+            let field = match field {
+                Some(field) => field,
+                None => return error(path, format!("no field {}", name)),
+            };
+            if let FieldType::Object { .. } = field.type_ {
+                return error(path, format!("{} is not a value", name));
+            }
+            if FieldValue::from_raw(&field.type_, value.as_bytes()).is_none() {
+                return error(
+                    path,
+                    format!(
+                        "{:?} is not a valid {}",
+                        value,
+                        type_name(schema, &field.type_)
+                    ),
+                );
+            }
+            let mut node = get_node(node_store, &cursor)?;
+            let link = Link {
+                type_: LinkType::Raw,
+                digest: node_store.put_raw(value.as_bytes()),
+            };
+            node.links.insert(field.field_id, vec![link]);
+            check_node(schema, path, cursor.kind_id().unwrap_or_default(), &node)?;
+            Ok(Edit {
+                path: path.to_vec(),
+                link: put(node_store, &node),
+                selection: path.to_vec(),
+            })
+        }
+        Refactoring::Swap { index } => {
+            let (parent, selector) = split_parent(root, node_store, schema, path)?;
+            let mut node = get_node(node_store, &parent)?;
+            let old_index = node.index(&selector).unwrap_or(selector.index);
+            let field_id = selector.field_id;
+            let len = node.links.get(&field_id).map_or(0, Vec::len);
+            if *index >= len || old_index >= len {
+                return error(path, format!("index {} is out of range", index));
+            }
+            if !crdt::is_sequence(&node, field_id) {
+                node.links
+                    .get_mut(&field_id)
+                    .unwrap()
+                    .swap(old_index, *index);
+            } else if old_index != *index {
+                // Each link becomes a new element at the position of the other one, so that the
+                // element ids keep following their links.
+                let links = node.links[&field_id].clone();
+                let (first, last) = (old_index.min(*index), old_index.max(*index));
+                crdt::delete(&mut node, field_id, last);
+                crdt::delete(&mut node, field_id, first);
+                crdt::insert(&mut node, field_id, first, links[last].clone(), replica);
+                crdt::insert(&mut node, field_id, last, links[first].clone(), replica);
+            }
+            let parent_path = parent.path();
+            Ok(Edit {
+                link: put(node_store, &node),
+                selection: append(
+                    &parent_path,
+                    Selector {
+                        field_id: selector.field_id,
+                        index: *index,
+                        id: None,
+                    },
+                ),
+                path: parent_path,
+            })
+        }
+        Refactoring::Extract { name } => extract(node_store, schema, root, &cursor, name, replica),
+        Refactoring::Rename { name } => rename(node_store, schema, &cursor, name),
+    }
+}
+
+// Removes the link at the given index of a field, keeping a tombstone if the field is a sequence.
+fn remove_child(node: &mut Node, field_id: u64, index: usize) {
+    if crdt::is_sequence(node, field_id) {
+        crdt::delete(node, field_id, index);
+    } else if let Some(links) = node.links.get_mut(&field_id) {
+        if index < links.len() {
+            links.remove(index);
+        }
+        if links.is_empty() {
+            node.links.remove(&field_id);
+        }
+    }
+}
+
+// Moves the node at the given cursor to the given index of a field of its parent.
+fn move_to(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    root: &Cursor,
+    cursor: &Cursor,
+    field_id: u64,
+    index: usize,
+    replica: &str,
+) -> Result<Edit, RefactorError> {
+    let path = cursor.path();
+    let (parent, selector) = split_parent(root, node_store, schema, &path)?;
+    let mut node = get_node(node_store, &parent)?;
+    let field = get_field(schema, &path, parent.kind_id(), field_id)?;
+    if field.type_ != cursor.type_ {
+        return error(
+            &path,
+            format!(
+                "{} holds {}, not {}",
+                field.name,
+                type_name(schema, &field.type_),
+                type_name(schema, &cursor.type_)
+            ),
+        );
+    }
+    let old_index = node.index(&selector).unwrap_or(selector.index);
+    remove_child(&mut node, selector.field_id, old_index);
+    let len = node.links.get(&field_id).map_or(0, Vec::len);
+    if index > len {
+        return error(&path, format!("index {} is out of range", index));
+    }
+    if crdt::is_sequence(&node, field_id) {
+        crdt::insert(&mut node, field_id, index, cursor.link.clone(), replica);
+    } else {
+        node.links
+            .entry(field_id)
+            .or_default()
+            .insert(index, cursor.link.clone());
+    }
+    let parent_path = parent.path();
+    check_node(
+        schema,
+        &parent_path,
+        parent.kind_id().unwrap_or_default(),
+        &node,
+    )?;
+    Ok(Edit {
+        link: put(node_store, &node),
+        selection: append(
+            &parent_path,
+            Selector {
+                field_id,
+                index,
+                id: None,
+            },
+        ),
+        path: parent_path,
+    })
+}
+
+fn split_parent(
+    root: &Cursor,
+    node_store: &NodeStore,
+    schema: &Schema,
+    path: &[Selector],
+) -> Result<(Cursor, Selector), RefactorError> {
+    let (selector, parent_path) = match path.split_last() {
+        Some(split) => split,
+        None => return error(path, "the root has no siblings"),
+    };
+    match root.traverse(node_store, schema, parent_path) {
+        Some(parent) => Ok((parent, selector.clone())),
+        None => error(parent_path, "no node at this path"),
+    }
+}
+
+fn wrap(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    cursor: &Cursor,
+    kind_id: u64,
+    fields: &[u64],
+) -> Result<Edit, RefactorError> {
+    let path = cursor.path();
+    let wrapper_type = FieldType::Object { kind_id };
+    if wrapper_type != cursor.type_ {
+        return error(
+            &path,
+            format!(
+                "expected {}, found {}",
+                type_name(schema, &cursor.type_),
+                type_name(schema, &wrapper_type)
+            ),
+        );
+    }
+    if fields.is_empty() {
+        return error(&path, "no field to wrap the node in");
+    }
+    // Kinds of the nodes to build, from the outermost one, and the fields linking them.
+    let mut kinds = vec![];
+    let mut type_ = wrapper_type;
+    for field_id in fields {
+        let kind_id = match type_ {
+            FieldType::Object { kind_id } => kind_id,
+            _ => return error(&path, format!("field {} is not in a node", field_id)),
+        };
+        let field = get_field(schema, &path, Some(kind_id), *field_id)?;
+        kinds.push((kind_id, *field_id));
+        type_ = field.type_.clone();
+    }
+    if type_ != cursor.type_ {
+        return error(
+            &path,
+            format!(
+                "the innermost field holds {}, not {}",
+                type_name(schema, &type_),
+                type_name(schema, &cursor.type_)
+            ),
+        );
+    }
+    let mut link = cursor.link.clone();
+    for (kind_id, field_id) in kinds.into_iter().rev() {
+        let mut node = Node::default();
+        node.links.insert(field_id, vec![link]);
+        check_node(schema, &path, kind_id, &node)?;
+        link = put(node_store, &node);
+    }
+    Ok(Edit {
+        path: path.clone(),
+        link,
+        selection: path,
+    })
+}
+
+fn unwrap(node_store: &NodeStore, schema: &Schema, cursor: &Cursor) -> Result<Edit, RefactorError> {
+    let path = cursor.path();
+    let mut current = cursor.clone();
+    loop {
+        let node = get_node(node_store, &current)?;
+        let mut children = vec![];
+        for (field_id, links) in node.links.iter() {
+            let field = get_field(schema, &current.path(), current.kind_id(), *field_id)?;
+            if let FieldType::Object { .. } = field.type_ {
+                children.extend((0..links.len()).map(|index| Selector {
+                    field_id: *field_id,
+                    index,
+                    id: None,
+                }));
+            }
+        }
+        let child = match children.as_slice() {
+            [selector] => current.traverse(node_store, schema, std::slice::from_ref(selector)),
+            _ => {
+                return error(
+                    &current.path(),
+                    format!("expected a single child node, found {}", children.len()),
+                )
+            }
+        };
+        current = match child {
+            Some(child) => child,
+            None => return error(&current.path(), "missing child"),
+        };
+        if current.type_ == cursor.type_ {
+            return Ok(Edit {
+                path: path.clone(),
+                link: current.link,
+                selection: path,
+            });
+        }
+    }
+}
+
+// Whether the subtree at the given cursor has a node of the given kind.
+fn contains_kind(node_store: &NodeStore, schema: &Schema, cursor: &Cursor, kind_id: u64) -> bool {
+    if cursor.kind_id() == Some(kind_id) {
+        return true;
+    }
+    let node = match cursor.link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        _ => return false,
+    };
+    node.links.iter().any(|(field_id, links)| {
+        (0..links.len()).any(|index| {
+            let selector = Selector {
+                field_id: *field_id,
+                index,
+                id: None,
+            };
+            cursor
+                .traverse(node_store, schema, &[selector])
+                .is_some_and(|child| contains_kind(node_store, schema, &child, kind_id))
+        })
+    })
+}
+
+fn extract(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    root: &Cursor,
+    cursor: &Cursor,
+    name: &str,
+    replica: &str,
+) -> Result<Edit, RefactorError> {
+    let path = cursor.path();
+    if cursor.kind_id() != Some(ast::expression::kind_id()) {
+        return error(&path, "only expressions can be extracted");
+    }
+    // References to arguments would not be in scope in the new function.
+    if contains_kind(node_store, schema, cursor, ast::ref_::kind_id()) {
+        return error(&path, "the expression refers to arguments of its function");
+    }
+    let file = (0..path.len())
+        .rev()
+        .filter_map(|len| root.traverse(node_store, schema, &path[..len]))
+        .find(|c| c.kind_id() == Some(ast::file::kind_id()));
+    let file = match file {
+        Some(file) => file,
+        None => return error(&path, "the expression is not in a file"),
+    };
+    let file_path = file.path();
+    let mut file_node = get_node(node_store, &file)?;
+    // Pick a ref that is not used by any other definition, even if `next_ref` is out of date.
+    let mut ref_ = ast::get_int(node_store, &file_node, ast::file::NEXT_REF).unwrap_or(0);
+    for (_, link) in ast::get_children(&file_node, &file_path, ast::file::FUNCTION_DEFINITIONS) {
+        if let Some(definition) = ast::get_node(node_store, &link) {
+            let other = ast::get_int(node_store, &definition, ast::function_definition::REF);
+            ref_ = ref_.max(other.unwrap_or(0) + 1);
+        }
+    }
+
+    let mut label = Node::default();
+    let name_link = put_value(node_store, FieldValue::String(name.to_string()));
+    label.links.insert(ast::label::NAME, vec![name_link]);
+    check_node(schema, &path, ast::label::kind_id(), &label)?;
+    let mut definition = Node::default();
+    let ref_link = put_value(node_store, FieldValue::Int(ref_));
+    definition
+        .links
+        .insert(ast::function_definition::REF, vec![ref_link.clone()]);
+    definition.links.insert(
+        ast::function_definition::LABEL,
+        vec![put(node_store, &label)],
+    );
+    definition
+        .links
+        .insert(ast::function_definition::BODY, vec![cursor.link.clone()]);
+    check_node(
+        schema,
+        &path,
+        ast::function_definition::kind_id(),
+        &definition,
+    )?;
+
+    let mut call = Node::default();
+    call.links
+        .insert(ast::function_application::FUNCTION, vec![ref_link]);
+    check_node(schema, &path, ast::function_application::kind_id(), &call)?;
+    let mut expression = Node::default();
+    expression.links.insert(
+        ast::expression::FUNCTION_APPLICATION_VALUE,
+        vec![put(node_store, &call)],
+    );
+    check_node(schema, &path, ast::expression::kind_id(), &expression)?;
+    let expression = put(node_store, &expression);
+
+    // Replace the expression in the file, then add the definition to the new file node.
+    let file_link = replace(
+        node_store,
+        &file.link,
+        &path[file_path.len()..],
+        &expression,
+    )?;
+    file_node = match file_link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        _ => return error(&file_path, "not a node"),
+    };
+    let definition = put(node_store, &definition);
+    let field_id = ast::file::FUNCTION_DEFINITIONS;
+    let len = file_node.links.get(&field_id).map_or(0, Vec::len);
+    if crdt::is_sequence(&file_node, field_id) {
+        crdt::insert(&mut file_node, field_id, len, definition, replica);
+    } else {
+        file_node
+            .links
+            .entry(field_id)
+            .or_default()
+            .push(definition);
+    }
+    let next_ref = put_value(node_store, FieldValue::Int(ref_ + 1));
+    file_node.links.insert(ast::file::NEXT_REF, vec![next_ref]);
+    check_node(schema, &file_path, ast::file::kind_id(), &file_node)?;
+    Ok(Edit {
+        path: file_path,
+        link: put(node_store, &file_node),
+        selection: path,
+    })
+}
+
+fn rename(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    cursor: &Cursor,
+    name: &str,
+) -> Result<Edit, RefactorError> {
+    let path = cursor.path();
+    let label_type = FieldType::Object {
+        kind_id: ast::label::kind_id(),
+    };
+    let field = cursor
+        .kind_id()
+        .and_then(|kind_id| schema.get_kind(kind_id))
+        .and_then(|kind| kind.fields.iter().find(|f| f.type_ == label_type));
+    let field_id = match field {
+        Some(field) => field.field_id,
+        None => return error(&path, "the node has no label"),
+    };
+    let mut node = get_node(node_store, cursor)?;
+    let mut label = node
+        .links
+        .get(&field_id)
+        .and_then(|links| links.first())
+        .and_then(|link| ast::get_node(node_store, link))
+        .unwrap_or_default();
+    let name_link = put_value(node_store, FieldValue::String(name.to_string()));
+    label.links.insert(ast::label::NAME, vec![name_link]);
+    check_node(schema, &path, ast::label::kind_id(), &label)?;
+    node.links.insert(field_id, vec![put(node_store, &label)]);
+    Ok(Edit {
+        path: path.clone(),
+        link: put(node_store, &node),
+        selection: path,
+    })
+}
+
+// Applies a refactoring to several nodes under the given root at once, and returns the new root.
+// Nodes are refactored last first, so that the paths of the others are still valid, except when
+// shifting them towards the start of their fields. Moves to given places and swaps are not
+// supported, since they change the paths of the other nodes.
+pub fn refactor_paths(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    root: &Cursor,
+    paths: &[Path],
+    refactoring: &Refactoring,
+    replica: &str,
+) -> Result<Link, RefactorError> {
+    let key = |path: &[Selector]| {
+        path.iter()
+            .map(|s| (s.field_id, s.index))
+            .collect::<Vec<_>>()
+    };
+    let mut paths = paths.to_vec();
+    paths.sort_by_key(|path| key(path));
+    paths.dedup();
+    match refactoring {
+        Refactoring::Move { .. } | Refactoring::Swap { .. } if paths.len() > 1 => {
+            return error(&root.path(), "cannot move several nodes to the same place");
+        }
+        Refactoring::Shift { .. } => {
+            // Moving a node would change the paths of its descendants.
+            if let Some(path) = paths
+                .windows(2)
+                .find(|pair| pair[1].starts_with(&pair[0]))
+                .map(|pair| &pair[1])
+            {
+                return error(path, "cannot move a node along with its ancestor");
+            }
+        }
+        _ => {}
+    }
+    if !matches!(refactoring, Refactoring::Shift { offset } if *offset < 0) {
+        paths.reverse();
+    }
+    let mut root = root.clone();
+    for path in paths.iter() {
+        let edit = refactor(node_store, schema, &root, path, refactoring, replica)?;
+        root.link = replace(node_store, &root.link, &edit.path, &edit.link)?;
+    }
+    Ok(root.link)
+}
+
+// Applies a refactoring to every node under the given root matching the query, and returns the new
+// root.
+pub fn refactor_all(
+    node_store: &mut NodeStore,
+    schema: &Schema,
+    root: &Cursor,
+    query: &Query,
+    refactoring: &Refactoring,
+    replica: &str,
+) -> Result<Link, RefactorError> {
+    let paths = query.select_paths(node_store, schema, root);
+    refactor_paths(node_store, schema, root, &paths, refactoring, replica)
+}
@@ -4,67 +4,15 @@ use crate::{
     types::{append, display_selector_text, Cursor, LinkTarget, Node, Path, Selector},
 };
 use maplit::hashmap;
-use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, HashMap},
     rc::Rc,
 };
 use yew::prelude::*;
 
-type UUID = String;
-
-// Schema of the schema.
-#[derive(PartialEq, Clone, Serialize, Deserialize, Default)]
-pub struct Schema {
-    pub kinds: Vec<Kind>,
-}
-
-impl Schema {
-    pub fn get_kind(&self, kind_id: u64) -> Option<&Kind> {
-        self.kinds.iter().find(|k| k.kind_id == kind_id)
-    }
-
-    pub fn root_kind(&self) -> Option<&Kind> {
-        self.kinds.get(0)
-    }
-}
-
-#[derive(PartialEq, Clone, Serialize, Deserialize, Default, Debug)]
-pub struct Kind {
-    pub kind_id: u64,
-    pub name: String,
-    pub fields: Vec<Field>,
-}
-
-impl Kind {
-    pub fn get_field(&self, field_id: u64) -> Option<&Field> {
-        self.fields.iter().find(|f| f.field_id == field_id)
-    }
-}
-
-#[derive(PartialEq, Clone, Serialize, Deserialize, Default, Debug)]
-pub struct Field {
-    pub field_id: u64,
-    pub name: String,
-    // pub kind_id: u64,
-    pub type_: FieldType,
-}
-
-#[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
-pub enum FieldType {
-    String,
-    Bytes,
-    Bool,
-    Int,
-    Float,
-    Object { kind_id: u64 },
-}
+pub use linc_schema::{Arg, Field, FieldType, Kind, ProtoType, Schema};
 
-impl Default for FieldType {
-    fn default() -> Self {
-        FieldType::String
-    }
-}
+type UUID = String;
 
 pub enum FieldValue {
     String(String),
@@ -80,6 +28,51 @@ pub struct Object {
     pub fields: Vec<(u64, FieldValue)>,
 }
 
+impl FieldValue {
+    // Parses the raw value of a leaf link according to its field type. Primitive values are
+    // stored as UTF-8 text: decimal for Int and Float, "true" / "false" for Bool. Bytes are stored
+    // as they are.
+    pub fn from_raw(type_: &FieldType, raw: &[u8]) -> Option<FieldValue> {
+        let text = || std::str::from_utf8(raw).ok();
+        match type_ {
+            FieldType::String => text().map(|v| FieldValue::String(v.to_string())),
+            FieldType::Bytes => Some(FieldValue::Bytes(raw.to_vec())),
+            FieldType::Bool => match text()? {
+                "true" => Some(FieldValue::Bool(true)),
+                "false" => Some(FieldValue::Bool(false)),
+                _ => None,
+            },
+            FieldType::Int => text()?.parse().ok().map(FieldValue::Int),
+            FieldType::Float => text()?.parse().ok().map(FieldValue::Float),
+            FieldType::Object { .. } => None,
+        }
+    }
+
+    // Value of a new leaf of the given type, as in protobuf; objects are not leaves.
+    pub fn default_for(type_: &FieldType) -> Option<FieldValue> {
+        match type_ {
+            FieldType::String => Some(FieldValue::String(String::new())),
+            FieldType::Bytes => Some(FieldValue::Bytes(vec![])),
+            FieldType::Bool => Some(FieldValue::Bool(false)),
+            FieldType::Int => Some(FieldValue::Int(0)),
+            FieldType::Float => Some(FieldValue::Float(0.0)),
+            FieldType::Object { .. } => None,
+        }
+    }
+
+    // Inverse of `from_raw`; objects are not leaves, so they have no raw representation.
+    pub fn to_raw(&self) -> Option<Vec<u8>> {
+        match self {
+            FieldValue::String(v) => Some(v.as_bytes().to_vec()),
+            FieldValue::Bytes(v) => Some(v.clone()),
+            FieldValue::Bool(v) => Some(v.to_string().into_bytes()),
+            FieldValue::Int(v) => Some(v.to_string().into_bytes()),
+            FieldValue::Float(v) => Some(v.to_string().into_bytes()),
+            FieldValue::Object(_) => None,
+        }
+    }
+}
+
 fn comma() -> Html {
     html! {
         <span>{ "," }</span>
@@ -147,7 +140,6 @@ impl ValidatorContext {
     fn view_child_index(&self, field_id: u64, index: usize, placeholder: bool) -> Option<Html> {
         log::debug!("view_child: {:?}", field_id);
         log::debug!("cursor: {:?}", self.cursor);
-        let kind_id = self.cursor.kind_id;
         let link_target = self.node()?;
         let node = link_target.as_parsed()?;
         let hash = node
@@ -158,16 +150,16 @@ impl ValidatorContext {
         if hash.is_none() && !placeholder {
             return None;
         }
-        let child_cursor = self
-            .cursor
-            .traverse(
-                &self.global_state.node_store,
-                &self.global_state.schema,
-                &[Selector { field_id, index }],
-            )
-            .unwrap();
-        let kind = self.global_state.schema.get_kind(kind_id);
-        let field = kind.and_then(|k| k.get_field(field_id));
+        // The child may be a node or a leaf; there is nothing to show for a missing link.
+        let child_cursor = self.cursor.traverse(
+            &self.global_state.node_store,
+            &self.global_state.schema,
+            &[Selector {
+                field_id,
+                index,
+                id: None,
+            }],
+        )?;
         Some(html! {
             <NodeComponent
                 global_state={ self.global_state.clone() }
@@ -204,16 +196,19 @@ impl ValidatorContext {
     // TODO: field / child.
 }
 
+// Number of children shown per field of a node, until more are asked for.
+const PAGE_SIZE: usize = 100;
+// Nodes at multiples of this depth are folded, until unfolded or selected within.
+const FOLD_DEPTH: usize = 16;
+
 // Generate valid values.
 type Renderer = fn(&ValidatorContext) -> Html;
 
 pub fn default_renderer(c: &ValidatorContext) -> Html {
     let cursor = &c.cursor;
-    let kind_id = cursor.kind_id;
-    let kind = c
-        .global_state
-        .schema
-        .get_kind(kind_id)
+    let kind = cursor
+        .kind_id()
+        .and_then(|kind_id| c.global_state.schema.get_kind(kind_id))
         .cloned()
         .unwrap_or_default();
     let path = cursor.path();
@@ -235,22 +230,73 @@ pub fn default_renderer(c: &ValidatorContext) -> Html {
                     // </div>
                 </div>
             };
+            let shown = c.global_state.expanded.get(&path).copied();
+            // Index of the child on the way to the selected node, if any, which is always shown.
+            let selected_child = c
+                .selected_path
+                .strip_prefix(path.as_slice())
+                .and_then(|rest| rest.first())
+                .cloned();
+            if shown.is_none()
+                && !path.is_empty()
+                && path.len().is_multiple_of(FOLD_DEPTH)
+                && selected_child.is_none()
+            {
+                let count: usize = node.links.values().map(Vec::len).sum();
+                let updatemodel = c.updatemodel.clone();
+                let path = path.clone();
+                let onclick = Callback::from(move |e: MouseEvent| {
+                    e.stop_propagation();
+                    updatemodel.emit(Msg::Expand(path.clone(), PAGE_SIZE))
+                });
+                return html! {
+                    <>
+                        { header }
+                        <div class="pl-3 text-gray-500 cursor-pointer" onclick={ onclick }>
+                            { format!("… {} children", count) }
+                        </div>
+                    </>
+                };
+            }
+            let shown = shown.unwrap_or(PAGE_SIZE);
             // Node.
             // https://codepen.io/xotonic/pen/JRLAOR
             let children: Vec<_> = node
                 .links
                 .iter()
                 .flat_map(|(field_id, hashes)| {
+                    let limit = match &selected_child {
+                        Some(selector) if selector.field_id == *field_id => {
+                            shown.max(selector.index + 1)
+                        }
+                        _ => shown,
+                    };
                     let field = kind.get_field(*field_id);
                     let field_name = field
                         .map(|f| f.name.clone())
                         .unwrap_or("INVALID".to_string());
                     // let _validators = field_schema.map(|v| v.validators).unwrap_or_default();
                     let path = path.clone();
-                    hashes.iter().enumerate().map(move |(i, _h)| {
+                    let more = if hashes.len() > limit {
+                        let updatemodel = c.updatemodel.clone();
+                        let path = path.clone();
+                        let onclick = Callback::from(move |e: MouseEvent| {
+                            e.stop_propagation();
+                            updatemodel.emit(Msg::Expand(path.clone(), limit + PAGE_SIZE))
+                        });
+                        Some(html! {
+                            <div class="pl-3 text-gray-500 cursor-pointer" onclick={ onclick }>
+                                { format!("… {} more {}", hashes.len() - limit, field_name) }
+                            </div>
+                        })
+                    } else {
+                        None
+                    };
+                    let items = hashes.iter().take(limit).enumerate().map(move |(i, _h)| {
                         let selector = Selector {
                             field_id: *field_id,
                             index: i,
+                            id: None,
                         };
                         let child_path = append(&path, selector.clone());
                         let updatemodel = c.updatemodel.clone();
@@ -270,7 +316,8 @@ pub fn default_renderer(c: &ValidatorContext) -> Html {
                                 { c.view_child_index(*field_id, i, true).unwrap_or_default() }
                             </div>
                         }
-                    })
+                    });
+                    items.chain(more)
                 })
                 .collect();
             html! {
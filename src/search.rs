@@ -0,0 +1,216 @@
+use crate::{
+    query::{Query, QueryError},
+    schema::Schema,
+    types::{Cursor, Digest, LinkTarget, NodeStore, Path, Selector},
+};
+use regex::Regex;
+use std::{collections::HashMap, rc::Rc};
+
+// Search over the nodes of a tree: by raw value (substring or regex), by kind or field name, or by
+// structural pattern (a query, see `query.rs`).
+//
+// Searches other than structural ones run over an index with an entry per node, in document order.
+// The entries of a subtree only depend on its digest and kind, so they are memoized by those: when
+// the root changes, only the nodes on the paths to the edits are read again.
+
+// Indexed information about a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: Path,
+    pub kind: Option<String>,
+    // Name of the field linking to the node, if it is known.
+    pub field: Option<String>,
+    // Raw value of a leaf, if it is valid UTF-8.
+    pub value: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Search {
+    // Case-insensitive substring of raw values.
+    Text(String),
+    Regex(Regex),
+    Kind(String),
+    Field(String),
+    Structural(Query),
+}
+
+impl Search {
+    // Parses a search as typed by the user: `/regex/`, `kind:name`, `field:name`, `?query`, or
+    // text to look for.
+    pub fn parse(text: &str) -> Result<Search, String> {
+        if let Some(pattern) = text.strip_prefix('/').and_then(|t| t.strip_suffix('/')) {
+            Regex::new(pattern)
+                .map(Search::Regex)
+                .map_err(|e| e.to_string())
+        } else if let Some(name) = text.strip_prefix("kind:") {
+            Ok(Search::Kind(name.trim().to_string()))
+        } else if let Some(name) = text.strip_prefix("field:") {
+            Ok(Search::Field(name.trim().to_string()))
+        } else if let Some(query) = text.strip_prefix('?') {
+            Query::parse(query)
+                .map(Search::Structural)
+                .map_err(|e: QueryError| e.to_string())
+        } else {
+            Ok(Search::Text(text.to_lowercase()))
+        }
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        match self {
+            Search::Text(text) => entry
+                .value
+                .as_ref()
+                .is_some_and(|v| v.to_lowercase().contains(text.as_str())),
+            Search::Regex(regex) => entry.value.as_ref().is_some_and(|v| regex.is_match(v)),
+            Search::Kind(name) => entry.kind.as_ref() == Some(name),
+            Search::Field(name) => entry.field.as_ref() == Some(name),
+            Search::Structural(_) => false,
+        }
+    }
+}
+
+// Entries of a subtree, with paths relative to it.
+type Entries = Rc<Vec<Entry>>;
+
+#[derive(Debug, Default)]
+pub struct Index {
+    root: Option<Digest>,
+    entries: Vec<Entry>,
+    memo: HashMap<(Digest, Option<u64>), Entries>,
+}
+
+impl Index {
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    // Brings the index up to date with the given root.
+    pub fn update(&mut self, node_store: &NodeStore, schema: &Schema, root: &Cursor) {
+        if self.root.as_ref() == Some(&root.link.digest) {
+            return;
+        }
+        let mut memo = HashMap::new();
+        let entries = self.subtree(node_store, schema, root, None, &mut memo);
+        // Only keep the subtrees of the current root.
+        self.memo = memo;
+        self.entries = entries
+            .iter()
+            .map(|entry| Entry {
+                path: [root.path(), entry.path.clone()].concat(),
+                ..entry.clone()
+            })
+            .collect();
+        self.root = Some(root.link.digest.clone());
+    }
+
+    fn subtree(
+        &self,
+        node_store: &NodeStore,
+        schema: &Schema,
+        cursor: &Cursor,
+        field: Option<String>,
+        memo: &mut HashMap<(Digest, Option<u64>), Entries>,
+    ) -> Entries {
+        let memo_key = (cursor.link.digest.clone(), cursor.kind_id());
+        if let Some(entries) = memo.get(&memo_key).or_else(|| self.memo.get(&memo_key)) {
+            let entries = entries.clone();
+            memo.insert(memo_key, entries.clone());
+            return with_field(entries, field);
+        }
+        let kind = cursor
+            .kind_id()
+            .and_then(|kind_id| schema.get_kind(kind_id));
+        let mut entries = vec![Entry {
+            path: vec![],
+            kind: kind.map(|kind| kind.name.clone()),
+            field: None,
+            value: None,
+        }];
+        match cursor.link.get(node_store) {
+            Some(LinkTarget::Raw(raw)) => {
+                entries[0].value = String::from_utf8(raw.clone()).ok();
+            }
+            Some(LinkTarget::Parsed(node)) => {
+                // Children are relative to this node.
+                let this = Cursor {
+                    parent: None,
+                    link: cursor.link.clone(),
+                    type_: cursor.type_.clone(),
+                };
+                for (field_id, links) in node.links.iter() {
+                    let field = kind.and_then(|kind| kind.get_field(*field_id));
+                    for index in 0..links.len() {
+                        let selector = Selector {
+                            field_id: *field_id,
+                            index,
+                            id: None,
+                        };
+                        let child = match this.traverse(
+                            node_store,
+                            schema,
+                            std::slice::from_ref(&selector),
+                        ) {
+                            Some(child) => child,
+                            None => continue,
+                        };
+                        let name = field.map(|field| field.name.clone());
+                        let children = self.subtree(node_store, schema, &child, name, memo);
+                        entries.extend(children.iter().map(|entry| Entry {
+                            path: [vec![selector.clone()], entry.path.clone()].concat(),
+                            ..entry.clone()
+                        }));
+                    }
+                }
+            }
+            None => {}
+        }
+        // The field is not part of the memoized entries, since the same subtree may be linked from
+        // different fields.
+        let entries = Rc::new(entries);
+        memo.insert(memo_key, entries.clone());
+        with_field(entries, field)
+    }
+
+    // Returns the paths of the nodes matching the search, in document order.
+    pub fn search(
+        &self,
+        node_store: &NodeStore,
+        schema: &Schema,
+        root: &Cursor,
+        search: &Search,
+    ) -> Vec<Path> {
+        match search {
+            Search::Structural(query) => query.select_paths(node_store, schema, root),
+            search => self
+                .entries
+                .iter()
+                .filter(|entry| search.matches(entry))
+                .map(|entry| entry.path.clone())
+                .collect(),
+        }
+    }
+}
+
+fn with_field(entries: Entries, field: Option<String>) -> Entries {
+    if entries.first().map(|entry| &entry.field) == Some(&field) {
+        return entries;
+    }
+    let mut entries = (*entries).clone();
+    if let Some(entry) = entries.first_mut() {
+        entry.field = field;
+    }
+    Rc::new(entries)
+}
+
+// Short description of a match, for the list of results.
+pub fn describe(entry: &Entry) -> String {
+    let name = entry
+        .field
+        .clone()
+        .or_else(|| entry.kind.clone())
+        .unwrap_or_default();
+    match &entry.value {
+        Some(value) => format!("{}: {}", name, value),
+        None => name,
+    }
+}
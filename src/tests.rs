@@ -1,4 +1,19 @@
-use crate::{pretty_print::*, schema::*};
+use crate::{
+    argv, ast,
+    collab::{self, ClientMessage, Op, Recipient},
+    crdt, encryption,
+    eval::{self, Value},
+    fsck::{fsck, Problem},
+    generated, hole, html, identity, json,
+    permalink::{self, HashState},
+    pretty_print::*,
+    proto, protobuf, query, refactor,
+    schema::*,
+    search, typecheck,
+    typed::Reader,
+    types::*,
+    yaml,
+};
 
 fn schema() -> Schema {
     Schema {
@@ -11,16 +26,22 @@ fn schema() -> Schema {
                         field_id: 1,
                         name: "hello".to_string(),
                         type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
                     },
                     Field {
                         field_id: 2,
                         name: "world".to_string(),
                         type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
                     },
                     Field {
                         field_id: 3,
                         name: "country".to_string(),
                         type_: FieldType::Object { kind_id: 2 },
+                        repeated: false,
+                        ..Default::default()
                     },
                 ],
             },
@@ -32,21 +53,29 @@ fn schema() -> Schema {
                         field_id: 1,
                         name: "size".to_string(),
                         type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
                     },
                     Field {
                         field_id: 2,
                         name: "population".to_string(),
                         type_: FieldType::Int,
+                        repeated: false,
+                        ..Default::default()
                     },
                     Field {
                         field_id: 4,
                         name: "friends_with".to_string(),
                         type_: FieldType::Object { kind_id: 2 },
+                        repeated: false,
+                        ..Default::default()
                     },
                     Field {
                         field_id: 3,
                         name: "name".to_string(),
                         type_: FieldType::String,
+                        repeated: false,
+                        ..Default::default()
                     },
                 ],
             },
@@ -180,3 +209,2402 @@ fn test_format_recursive() {
 }"#
     )
 }
+
+#[test]
+fn test_cursor_leaves() {
+    let schema = schema();
+    let mut node_store = NodeStore::default();
+    let raw = |node_store: &mut NodeStore, value: &str| Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(value.as_bytes()),
+    };
+    let country = Node {
+        links: maplit::btreemap! {
+            2 => vec![raw(&mut node_store, "60")],
+            8 => vec![Link { type_: LinkType::Raw, digest: node_store.put_raw(&[0xff]) }],
+            9 => vec![raw(&mut node_store, "?")],
+        },
+        sequences: Default::default(),
+    };
+    let root = Node {
+        links: maplit::btreemap! {
+            1 => vec![raw(&mut node_store, "hi")],
+            3 => vec![Link { type_: LinkType::Dag, digest: node_store.put_parsed(&country) }],
+        },
+        sequences: Default::default(),
+    };
+    let root = Cursor {
+        parent: None,
+        link: Link {
+            type_: LinkType::Dag,
+            digest: node_store.put_parsed(&root),
+        },
+        type_: FieldType::Object { kind_id: 1 },
+    };
+    let at = |path: &[Selector]| root.traverse(&node_store, &schema, path);
+    let selector = |field_id, index| Selector {
+        field_id,
+        index,
+        id: None,
+    };
+
+    let hello = at(&[selector(1, 0)]).unwrap();
+    assert_eq!(hello.type_, FieldType::String);
+    assert_eq!(hello.kind_id(), None);
+    assert_eq!(root.next(&node_store, &schema), Some(hello));
+
+    let country = at(&[selector(3, 0)]).unwrap();
+    assert_eq!(country.kind_id(), Some(2));
+    let population = at(&[selector(3, 0), selector(2, 0)]).unwrap();
+    assert_eq!(population.type_, FieldType::Int);
+    assert_eq!(population.path(), vec![selector(3, 0), selector(2, 0)]);
+    // Fields missing from the schema are text, or bytes if they are not valid UTF-8.
+    assert_eq!(
+        at(&[selector(3, 0), selector(9, 0)]).unwrap().type_,
+        FieldType::String
+    );
+    assert_eq!(
+        at(&[selector(3, 0), selector(8, 0)]).unwrap().type_,
+        FieldType::Bytes
+    );
+    // Leaves have no children, and missing links are not positions.
+    assert_eq!(at(&[selector(3, 0), selector(2, 0), selector(1, 0)]), None);
+    assert_eq!(at(&[selector(2, 0)]), None);
+
+    assert_eq!(
+        FieldValue::default_for(&FieldType::Int).and_then(|v| v.to_raw()),
+        Some(b"0".to_vec())
+    );
+    assert!(FieldValue::default_for(&FieldType::Object { kind_id: 2 }).is_none());
+}
+
+#[test]
+fn test_fsck_ok() {
+    let mut node_store = NodeStore::default();
+    let value = node_store.put_raw(b"hello");
+    let child = node_store.put_parsed(&Node::default());
+    let _root = node_store.put_parsed(&Node {
+        links: maplit::btreemap! {
+            0 => vec![
+                Link { type_: LinkType::Raw, digest: value },
+                Link { type_: LinkType::Dag, digest: child },
+            ],
+        },
+        sequences: Default::default(),
+    });
+    let report = fsck(&node_store);
+    assert_eq!(report.checked, 3);
+    assert!(report.is_ok(), "{:?}", report);
+}
+
+#[test]
+fn test_fsck_problems() {
+    let mut node_store = NodeStore::default();
+    let value = node_store.put_raw(b"hello");
+    let child = node_store.put_parsed(&Node::default());
+    let missing = digest(b"missing");
+    let root = node_store.put_parsed(&Node {
+        links: maplit::btreemap! {
+            0 => vec![
+                Link { type_: LinkType::Dag, digest: value.clone() },
+                Link { type_: LinkType::Raw, digest: child.clone() },
+                Link { type_: LinkType::Dag, digest: missing.clone() },
+            ],
+        },
+        sequences: Default::default(),
+    });
+    // Store a blob under the wrong digest, as a misbehaving server or local storage could.
+    let mut raw: serde_json::Value = serde_json::to_value(&node_store).unwrap();
+    let corrupted = digest(b"expected");
+    raw["raw_nodes"][&corrupted] = serde_json::to_value(b"actual".to_vec()).unwrap();
+    let node_store: NodeStore = serde_json::from_value(raw).unwrap();
+
+    let report = fsck(&node_store);
+    assert_eq!(report.checked, 4);
+    let expected = vec![
+        Problem::DigestMismatch {
+            digest: corrupted.clone(),
+            actual: digest(b"actual"),
+        },
+        Problem::UnparsableNode {
+            parent: root.clone(),
+            selector: Selector {
+                field_id: 0,
+                index: 0,
+                id: None,
+            },
+            link: Link {
+                type_: LinkType::Dag,
+                digest: value,
+            },
+        },
+        Problem::LinkTypeMismatch {
+            parent: root.clone(),
+            selector: Selector {
+                field_id: 0,
+                index: 1,
+                id: None,
+            },
+            link: Link {
+                type_: LinkType::Raw,
+                digest: child,
+            },
+        },
+        Problem::Dangling {
+            parent: root,
+            selector: Selector {
+                field_id: 0,
+                index: 2,
+                id: None,
+            },
+            link: Link {
+                type_: LinkType::Dag,
+                digest: missing.clone(),
+            },
+        },
+    ];
+    assert_eq!(report.problems.len(), expected.len());
+    for problem in expected {
+        assert!(report.problems.contains(&problem), "{}", problem);
+    }
+    let mut expected_missing = vec![corrupted.clone(), missing];
+    expected_missing.sort();
+    assert_eq!(report.missing(), expected_missing);
+    assert_eq!(report.corrupted(), vec![corrupted]);
+}
+
+#[test]
+fn test_put_raw_checked() {
+    let mut node_store = NodeStore::default();
+    assert_eq!(node_store.put_raw_checked(&digest(b"a"), b"a"), Ok(()));
+    assert_eq!(
+        node_store.put_raw_checked(&digest(b"a"), b"b"),
+        Err(digest(b"b"))
+    );
+    assert_eq!(node_store.len(), 1);
+}
+
+#[test]
+fn test_json_round_trip() {
+    let value = serde_json::json!({
+        "z": [1, 2.5, "three", true, null, {"nested": []}],
+        "a": {},
+        "big": 12345678901234567890u64,
+        "m": "hello",
+    });
+    let mut node_store = NodeStore::default();
+    let link = json::import(&mut node_store, &value);
+    let exported = json::export(&node_store, &link).unwrap();
+    // Key order is preserved too.
+    assert_eq!(
+        serde_json::to_string(&exported).unwrap(),
+        serde_json::to_string(&value).unwrap()
+    );
+    // Each type of value has its own kind.
+    let schema = json::json_schema();
+    let root = Cursor {
+        parent: None,
+        link,
+        type_: FieldType::Object {
+            kind_id: json::JSON_VALUE,
+        },
+    };
+    let mut kinds: Vec<String> = query::Query::parse("..*")
+        .unwrap()
+        .select(&node_store, &schema, &root)
+        .iter()
+        .filter_map(|cursor| schema.get_kind(cursor.kind_id()?))
+        .map(|kind| kind.name.clone())
+        .collect();
+    kinds.sort();
+    kinds.dedup();
+    assert_eq!(
+        kinds,
+        vec![
+            "json_array",
+            "json_bool",
+            "json_entry",
+            "json_null",
+            "json_number",
+            "json_object",
+            "json_string",
+            "json_value",
+        ]
+    );
+}
+
+#[test]
+fn test_json_typed_round_trip() {
+    let value = serde_json::json!({
+        "world": "world_val",
+        "country": {
+            "name": "italy",
+            "population": 1000000000,
+        },
+        "hello": "hello_val",
+    });
+    let mut node_store = NodeStore::default();
+    let link = json::import_typed(&mut node_store, &schema(), 1, &value).unwrap();
+    let root = node_store.get_dag(&link.digest).unwrap();
+    assert_eq!(root.links.keys().collect::<Vec<_>>(), vec![&1, &2, &3]);
+    let exported = json::export_typed(&node_store, &schema(), 1, &link).unwrap();
+    // Fields are exported in schema order.
+    assert_eq!(
+        serde_json::to_string(&exported).unwrap(),
+        r#"{"hello":"hello_val","world":"world_val","country":{"population":1000000000,"name":"italy"}}"#
+    );
+}
+
+#[test]
+fn test_json_typed_errors() {
+    let mut node_store = NodeStore::default();
+    let err = json::import_typed(
+        &mut node_store,
+        &schema(),
+        1,
+        &serde_json::json!({"country": {"population": "many"}}),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/country/population: expected Int, found string"
+    );
+    let err = json::import_typed(
+        &mut node_store,
+        &schema(),
+        1,
+        &serde_json::json!({"country": {"capital": "rome"}}),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/country/capital: unknown field `capital` for kind `country`"
+    );
+    let err = json::import_typed(
+        &mut node_store,
+        &schema(),
+        1,
+        &serde_json::json!({"hello": "a", "1": "b"}),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/1: field `hello` given both as `hello` and as `1`"
+    );
+    let err =
+        json::import_typed(&mut node_store, &schema(), 1, &serde_json::json!([])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/: expected object for kind `root`, found array"
+    );
+}
+
+#[test]
+fn test_yaml_round_trip() {
+    let text = r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: linc
+  labels:
+    app: linc
+spec:
+  replicas: 3
+  template:
+    spec:
+      containers:
+      - name: linc
+        image: linc:latest
+        ports:
+        - containerPort: 8080
+        env: []
+      hostNetwork: false
+      nodeSelector: null
+"#;
+    let mut node_store = NodeStore::default();
+    let link = json::import(&mut node_store, &yaml::parse(text).unwrap());
+    let exported = json::export(&node_store, &link).unwrap();
+    assert_eq!(yaml::print(&exported).unwrap(), text);
+}
+
+#[test]
+fn test_yaml_stream() {
+    let text = r#"---
+apiVersion: v1
+kind: Service
+metadata:
+  name: linc
+---
+apiVersion: v1
+kind: ConfigMap
+data:
+  '1': one
+  'true': yes
+"#;
+    // Several documents are only accepted as a stream.
+    assert_eq!(
+        yaml::parse(text),
+        Err("expected a single YAML document, found 2; import it as a yaml_stream".to_string())
+    );
+    let mut node_store = NodeStore::default();
+    let documents = yaml::parse_stream(text).unwrap();
+    let link = yaml::import_stream(&mut node_store, &documents);
+    let exported = yaml::export_stream(&node_store, &link).unwrap();
+    assert_eq!(yaml::print_stream(&exported).unwrap(), text);
+    // Bool and number keys become strings.
+    assert_eq!(
+        yaml::parse("1: one\ntrue: yes\n").unwrap(),
+        serde_json::json!({"1": "one", "true": "yes"})
+    );
+    assert_eq!(
+        yaml::parse_stream("a: 1\n---\nb:\n  [x]: 2\n"),
+        Err("[1].b: unsupported sequence mapping key".to_string())
+    );
+    assert_eq!(
+        yaml::parse("a: [1"),
+        Err("invalid YAML: did not find expected ',' or ']' at line 2 column 1, while parsing a flow sequence at line 1 column 4".to_string())
+    );
+}
+
+#[test]
+fn test_toml_round_trip() {
+    let text = r#"[package]
+name = "linc"
+version = "0.1.0"
+authors = ["Tiziano Santoro <tiziano88@gmail.com>"]
+edition = "2021"
+
+[dependencies]
+base64 = "*"
+serde = { version = "1.0", features = ["derive", "rc"] }
+"#;
+    let mut node_store = NodeStore::default();
+    let link = json::import(&mut node_store, &crate::toml::parse(text).unwrap());
+    let exported = crate::toml::print(&json::export(&node_store, &link).unwrap()).unwrap();
+    let original: ::toml::Table = text.parse().unwrap();
+    let round_trip: ::toml::Table = exported.parse().unwrap();
+    assert_eq!(original, round_trip);
+    // Key order is preserved.
+    assert_eq!(
+        round_trip["package"]
+            .as_table()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>(),
+        vec!["name", "version", "authors", "edition"]
+    );
+    assert_eq!(
+        round_trip["dependencies"]["serde"]
+            .as_table()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>(),
+        vec!["version", "features"]
+    );
+}
+
+#[test]
+fn test_toml_errors() {
+    assert_eq!(
+        crate::toml::print(&serde_json::json!({"a": {"b": null}})),
+        Err(".a.b: TOML has no null value".to_string())
+    );
+    assert_eq!(
+        crate::toml::print(&serde_json::json!([1])),
+        Err("a TOML document must be a table".to_string())
+    );
+}
+
+#[test]
+fn test_html_round_trip() {
+    let text = r#"<div id="main" class="a b"><p>Hello<b>world</b>&lt;&amp;&gt;</p><!--note--><a href="?a=1&amp;b=2" title="&quot;x&quot;">&#x41;</a><input type="checkbox" disabled><br></div>"#;
+    let mut node_store = NodeStore::default();
+    let link = html::import(&mut node_store, text).unwrap();
+    let exported = html::export(&node_store, &link).unwrap();
+    assert_eq!(exported, text.replace("&#x41;", "A"));
+    // Exporting is stable across a second import.
+    let link2 = html::import(&mut node_store, &exported).unwrap();
+    assert_eq!(link, link2);
+}
+
+#[test]
+fn test_html_export_errors() {
+    let mut node_store = NodeStore::default();
+    let name = node_store.put_raw(b"br");
+    let text = node_store.put_raw(b"x");
+    let child = node_store.put_parsed(&Node {
+        links: maplit::btreemap! { html::NODE_TEXT => vec![Link { type_: LinkType::Raw, digest: text }] },
+        sequences: Default::default(),
+    });
+    let element = node_store.put_parsed(&Node {
+        links: maplit::btreemap! {
+            html::ELEMENT_NAME => vec![Link { type_: LinkType::Raw, digest: name }],
+            html::ELEMENT_CHILDREN => vec![Link { type_: LinkType::Dag, digest: child }],
+        },
+        sequences: Default::default(),
+    });
+    let node = node_store.put_parsed(&Node {
+        links: maplit::btreemap! { html::NODE_ELEMENT => vec![Link { type_: LinkType::Dag, digest: element }] },
+        sequences: Default::default(),
+    });
+    let link = Link {
+        type_: LinkType::Dag,
+        digest: node,
+    };
+    assert_eq!(
+        html::export_fragment(&node_store, &link),
+        Err("node: void element `br` cannot have children".to_string())
+    );
+
+    // Text that would close a raw text element early, and inject whatever follows.
+    let name = node_store.put_raw(b"script");
+    let text = node_store.put_raw(b"x</SCRIPT><b>");
+    let child = node_store.put_parsed(&Node {
+        links: maplit::btreemap! { html::NODE_TEXT => vec![Link { type_: LinkType::Raw, digest: text }] },
+        sequences: Default::default(),
+    });
+    let element = node_store.put_parsed(&Node {
+        links: maplit::btreemap! {
+            html::ELEMENT_NAME => vec![Link { type_: LinkType::Raw, digest: name }],
+            html::ELEMENT_CHILDREN => vec![Link { type_: LinkType::Dag, digest: child }],
+        },
+        sequences: Default::default(),
+    });
+    let node = node_store.put_parsed(&Node {
+        links: maplit::btreemap! { html::NODE_ELEMENT => vec![Link { type_: LinkType::Dag, digest: element }] },
+        sequences: Default::default(),
+    });
+    let link = Link {
+        type_: LinkType::Dag,
+        digest: node,
+    };
+    assert_eq!(
+        html::export_fragment(&node_store, &link),
+        Err("node.children[0]: text of `script` cannot contain `</script`".to_string())
+    );
+}
+
+#[test]
+fn test_proto_ast_schema() {
+    let schema = proto::ast_schema().unwrap();
+    let file = schema.get_kind(proto::kind_id("File")).unwrap();
+    assert_eq!(file.name, "File");
+    assert_eq!(
+        file.get_field(5).unwrap(),
+        &Field {
+            field_id: 5,
+            name: "function_definitions".to_string(),
+            type_: FieldType::Object {
+                kind_id: proto::kind_id("FunctionDefinition"),
+            },
+            repeated: true,
+            ..Default::default()
+        }
+    );
+    let expression = schema.get_kind(proto::kind_id("Expression")).unwrap();
+    // Oneof fields are flattened, and nested messages are resolved in the enclosing scope.
+    assert_eq!(
+        expression.get_field(5).unwrap().type_,
+        FieldType::Object {
+            kind_id: proto::kind_id("Expression.String"),
+        }
+    );
+    assert_eq!(expression.get_field(9).unwrap().type_, FieldType::Int);
+    let float = schema.get_kind(proto::kind_id("Expression.Float")).unwrap();
+    assert_eq!(float.get_field(1).unwrap().type_, FieldType::Float);
+    // Enums are represented as integers.
+    let type_ = schema.get_kind(proto::kind_id("Type")).unwrap();
+    assert_eq!(type_.get_field(10).unwrap().type_, FieldType::Int);
+    assert_eq!(schema.kinds.len(), 22);
+}
+
+#[test]
+fn test_proto_parse() {
+    let schema = proto::parse(
+        r#"
+        syntax = "proto3";
+        package example;
+        import "other.proto";
+        option java_package = "com.example";
+        option (ratio) = 1.5e-3;
+
+        message Person {
+          reserved 3, 4;
+          string name = 1 [deprecated = true, (max) = 1E5];
+          map<string, Person> friends = 2;
+          optional bytes photo = 5;
+        }
+        "#,
+    )
+    .unwrap();
+    let entry = proto::kind_id("example.Person.FriendsEntry");
+    assert_eq!(
+        schema
+            .get_kind(proto::kind_id("example.Person"))
+            .unwrap()
+            .fields,
+        vec![
+            Field {
+                field_id: 1,
+                name: "name".to_string(),
+                type_: FieldType::String,
+                repeated: false,
+                ..Default::default()
+            },
+            Field {
+                field_id: 2,
+                name: "friends".to_string(),
+                type_: FieldType::Object { kind_id: entry },
+                repeated: true,
+                ..Default::default()
+            },
+            Field {
+                field_id: 5,
+                name: "photo".to_string(),
+                type_: FieldType::Bytes,
+                repeated: false,
+                ..Default::default()
+            },
+        ]
+    );
+    assert_eq!(
+        schema.get_kind(entry).unwrap().get_field(2).unwrap().type_,
+        FieldType::Object {
+            kind_id: proto::kind_id("example.Person"),
+        }
+    );
+
+    let errors = [
+        ("message A { B b = 1; }", "line 1: unknown type `B`"),
+        (
+            "message A {\n int32 a = 1;\n int32 b = 1;\n}",
+            "line 3: duplicate field number 1 in `A`",
+        ),
+        (
+            "message A {\n int32 a 1;\n}",
+            "line 2: expected `=`, found `1`",
+        ),
+        ("message A {", "line 1: unexpected end of file"),
+        ("option a = 1 }\nmessage A {}", "line 1: unexpected `}`"),
+        (
+            "message A {\n int32 a = 1e5;\n}",
+            "line 2: expected number, found `100000`",
+        ),
+    ];
+    for (text, error) in errors {
+        assert_eq!(proto::parse(text).unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn test_protobuf_round_trip() {
+    let schema = proto::parse(
+        r#"
+        message M {
+          string name = 1;
+          repeated int64 values = 2;
+          double ratio = 3;
+          bool flag = 4;
+          repeated M children = 5;
+          bytes data = 6;
+        }
+        "#,
+    )
+    .unwrap();
+    let kind_id = proto::kind_id("M");
+    let bytes = [
+        0x0a, 0x02, b'h', b'i', // name: "hi"
+        0x12, 0x03, 0x01, 0x96, 0x01, // values: [1, 150], packed
+        0x19, 0, 0, 0, 0, 0, 0, 0xf8, 0x3f, // ratio: 1.5
+        0x20, 0x01, // flag: true
+        0x2a, 0x02, 0x10, 0x07, // children { values: 7 }, not packed
+        0x32, 0x02, 0x00, b'"', // data: "\000\""
+    ];
+    let mut node_store = NodeStore::default();
+    let link = protobuf::decode(&mut node_store, &schema, kind_id, &bytes).unwrap();
+    assert_eq!(
+        protobuf::print_text(&node_store, &schema, kind_id, &link).unwrap(),
+        r#"name: "hi"
+values: 1
+values: 150
+ratio: 1.5
+flag: true
+children {
+  values: 7
+}
+data: "\000\""
+"#
+    );
+    // The nested repeated scalar is re-encoded packed.
+    let mut expected = bytes.to_vec();
+    expected.splice(20..24, [0x2a, 0x03, 0x12, 0x01, 0x07]);
+    assert_eq!(
+        protobuf::encode(&node_store, &schema, kind_id, &link).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_protobuf_scalar_types() {
+    let schema = proto::parse(
+        r#"
+        message S {
+          float f = 1;
+          sint32 s32 = 2;
+          sint64 s64 = 3;
+          fixed32 x32 = 4;
+          fixed64 x64 = 5;
+          sfixed32 y32 = 6;
+          sfixed64 y64 = 7;
+          repeated sint32 packed = 8;
+          int32 i = 9;
+        }
+        "#,
+    )
+    .unwrap();
+    let kind_id = proto::kind_id("S");
+    let bytes = [
+        0x0d, 0, 0, 0xc0, 0x3f, // f: 1.5
+        0x10, 0x01, // s32: -1
+        0x18, 0x03, // s64: -2
+        0x25, 1, 0, 0, 0, // x32: 1
+        0x29, 2, 0, 0, 0, 0, 0, 0, 0, // x64: 2
+        0x35, 0xff, 0xff, 0xff, 0xff, // y32: -1
+        0x39, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // y64: -2
+        0x42, 0x02, 0x02, 0x01, // packed: [1, -1]
+        0x48, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, // i: -1
+    ];
+    let mut node_store = NodeStore::default();
+    // An unknown field is skipped.
+    let mut with_unknown = bytes.to_vec();
+    with_unknown.extend([0x50, 0x05]);
+    let link = protobuf::decode(&mut node_store, &schema, kind_id, &with_unknown).unwrap();
+    assert_eq!(
+        protobuf::print_text(&node_store, &schema, kind_id, &link).unwrap(),
+        "f: 1.5\ns32: -1\ns64: -2\nx32: 1\nx64: 2\ny32: -1\ny64: -2\npacked: 1\npacked: -1\ni: -1\n"
+    );
+    assert_eq!(
+        protobuf::encode(&node_store, &schema, kind_id, &link).unwrap(),
+        bytes
+    );
+}
+
+#[test]
+fn test_protobuf_server_proto() {
+    let schema = proto::parse(include_str!("../proto/server.proto")).unwrap();
+    let kind_id = proto::kind_id("ast.UpdateFileRequest");
+    let mut node_store = NodeStore::default();
+    let bytes = b"\x0a\x05a.txt\x12\x02{}";
+    let link = protobuf::decode(&mut node_store, &schema, kind_id, bytes).unwrap();
+    assert_eq!(
+        protobuf::encode(&node_store, &schema, kind_id, &link).unwrap(),
+        bytes
+    );
+
+    let errors: [&[u8]; 3] = [b"\x0a\x05a", b"\x08\x01", b"\x23"];
+    let errors: Vec<String> = errors
+        .iter()
+        .map(|bytes| {
+            protobuf::decode(&mut node_store, &schema, kind_id, bytes)
+                .unwrap_err()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            "path: truncated message",
+            "path: unexpected wire type 0 for String",
+            "<root>: unsupported wire type 3",
+        ]
+    );
+}
+
+#[test]
+fn test_eval() {
+    fn ast_file(node_store: &mut NodeStore, value: serde_json::Value) -> Link {
+        json::import_typed(
+            node_store,
+            &proto::ast_schema().unwrap(),
+            ast::file::kind_id(),
+            &value,
+        )
+        .unwrap()
+    }
+
+    fn builtin(ref_: i64, name: &str, arguments: &[i64]) -> serde_json::Value {
+        let arguments: Vec<_> = arguments
+            .iter()
+            .map(|r| serde_json::json!({ "ref": r }))
+            .collect();
+        serde_json::json!({
+            "ref": ref_,
+            "label": { "name": name },
+            "arguments": arguments,
+            "body": { "external_ref_value": { "path": "builtin", "name": name } },
+        })
+    }
+
+    fn apply(function: i64, arguments: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({
+            "function_application_value": { "function": function, "arguments": arguments }
+        })
+    }
+
+    fn ref_(r: i64) -> serde_json::Value {
+        serde_json::json!({ "ref_value": { "ref": r } })
+    }
+
+    fn int(v: i64) -> serde_json::Value {
+        serde_json::json!({ "int_value": { "value": v } })
+    }
+
+    // fact(n) = if le(n, 0) then 1 else mul(n, fact(sub(n, 1)))
+    let fact = serde_json::json!({
+        "ref": 10,
+        "label": { "name": "fact" },
+        "arguments": [{ "ref": 11, "label": { "name": "n" } }],
+        "body": { "if_value": {
+            "cond": apply(3, vec![ref_(11), int(0)]),
+            "true": int(1),
+            "false": apply(2, vec![ref_(11), apply(10, vec![apply(1, vec![ref_(11), int(1)])])]),
+        }},
+    });
+    let mut node_store = NodeStore::default();
+    let file = ast_file(
+        &mut node_store,
+        serde_json::json!({
+            "function_definitions": [
+                builtin(1, "sub", &[100, 101]),
+                builtin(2, "mul", &[100, 101]),
+                builtin(3, "le", &[100, 101]),
+                builtin(4, "concat", &[100, 101]),
+                fact,
+            ],
+        }),
+    );
+    let mut eval = |expression: serde_json::Value| {
+        let link = json::import_typed(
+            &mut node_store,
+            &proto::ast_schema().unwrap(),
+            ast::expression::kind_id(),
+            &expression,
+        )
+        .unwrap();
+        eval::eval(&node_store, Some((&[], &file)), &[], &link)
+    };
+    assert_eq!(eval(apply(10, vec![int(10)])), Ok(Value::Int(3628800)));
+    assert_eq!(
+        eval(apply(
+            4,
+            vec![
+                serde_json::json!({ "list_value": { "values": [int(1)] } }),
+                serde_json::json!({ "list_value": { "values": [
+                    { "string_value": { "value": "a" } },
+                    { "float_value": { "value": 1.5 } },
+                    { "bool_value": {} },
+                ] } }),
+            ]
+        ))
+        .map(|v| v.to_string()),
+        Ok("[1, \"a\", 1.5, false]".to_string())
+    );
+
+    let err = eval(apply(
+        4,
+        vec![
+            int(1),
+            serde_json::json!({ "string_value": { "value": "a" } }),
+        ],
+    ))
+    .unwrap_err();
+    // The error is reported at the builtin's body, in the `File`.
+    assert_eq!(
+        err.to_string(),
+        "/5[3]/5[0]/13[0]: builtin `concat` does not accept (int, string)"
+    );
+    let err = eval(serde_json::json!({ "if_value": { "cond": int(1) } })).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/7[0]/1[0]: condition must be a bool, found int"
+    );
+    let err = eval(apply(10, vec![])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/8[0]: function `fact` expects 1 arguments, found 0"
+    );
+    assert_eq!(
+        eval(ref_(42)).unwrap_err().to_string(),
+        "/12[0]: unbound ref #42"
+    );
+}
+
+#[test]
+fn test_typecheck() {
+    use serde_json::json;
+    let int = json!({ "primitive": 1 });
+    let float = json!({ "primitive": 2 });
+    let file = json!({
+        "type_aliases": [
+            { "ref": 1, "label": { "name": "number" }, "type1": int },
+            // Aliases to aliases are resolved.
+            { "ref": 2, "type1": { "ref_type": { "ref": 1 } } },
+            { "ref": 3, "type1": { "ref_type": { "ref": 3 } } },
+        ],
+        "function_definitions": [
+            // add(a: number, b: int) -> int = builtin.add
+            {
+                "ref": 10,
+                "label": { "name": "add" },
+                "arguments": [
+                    { "ref": 11, "label": { "name": "a" }, "type": { "ref_type": { "ref": 2 } } },
+                    { "ref": 12, "label": { "name": "b" }, "type": int },
+                ],
+                "return_type": int,
+                "body": { "external_ref_value": { "path": "builtin", "name": "add" } },
+            },
+            // f(x: float) -> int = if x then add(1, x) else add(1)
+            {
+                "ref": 20,
+                "label": { "name": "f" },
+                "arguments": [{ "ref": 21, "label": { "name": "x" }, "type": float }],
+                "return_type": int,
+                "body": { "if_value": {
+                    "cond": { "ref_value": { "ref": 21 } },
+                    "true": { "function_application_value": { "function": 10, "arguments": [
+                        { "int_value": { "value": 1 } },
+                        { "ref_value": { "ref": 21 } },
+                    ] } },
+                    "false": { "function_application_value": { "function": 10, "arguments": [
+                        { "int_value": { "value": 1 } },
+                    ] } },
+                } },
+            },
+            // g() -> float = [1, "a", unknown]
+            {
+                "ref": 30,
+                "label": { "name": "g" },
+                "return_type": float,
+                "body": { "list_value": { "values": [
+                    { "int_value": { "value": 1 } },
+                    { "string_value": { "value": "a" } },
+                    { "ref_value": { "ref": 99 } },
+                ] } },
+            },
+            // h(k: int -> int) = k(1.5)
+            {
+                "ref": 40,
+                "label": { "name": "h" },
+                "arguments": [
+                    { "ref": 41, "type": { "compound": { "x": int, "y": int } } },
+                ],
+                "body": { "function_application_value": { "function": 41, "arguments": [
+                    { "float_value": { "value": 1.5 } },
+                ] } },
+            },
+        ],
+    });
+    let mut node_store = NodeStore::default();
+    let link = json::import_typed(
+        &mut node_store,
+        &proto::ast_schema().unwrap(),
+        ast::file::kind_id(),
+        &file,
+    )
+    .unwrap();
+    let errors: Vec<String> = typecheck::check_file(&node_store, &[], &link)
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            "/6[2]/20[0]/12[0]: cyclic type alias #3",
+            "/5[1]/5[0]/7[0]/1[0]: condition expects bool, found float",
+            "/5[1]/5[0]/7[0]/2[0]/8[0]/2[1]: argument `b` of `add` expects int, found float",
+            "/5[1]/5[0]/7[0]/3[0]/8[0]: function `add` expects 2 arguments, found 1",
+            "/5[2]/5[0]/6[0]/1[1]: list element has type string, expected int",
+            "/5[2]/5[0]/6[0]/1[2]/12[0]: unbound ref #99",
+            "/5[2]/5[0]: return type expects float, found [int]",
+            "/5[3]/5[0]/8[0]/2[0]: argument expects int, found float",
+        ]
+    );
+
+    // The errors of a tree are kept up to date as it is edited.
+    let schema = proto::ast_schema().unwrap();
+    let cursor = |link: &Link| Cursor {
+        parent: None,
+        link: link.clone(),
+        type_: FieldType::Object {
+            kind_id: ast::file::kind_id(),
+        },
+    };
+    let mut index = typecheck::Errors::default();
+    index.update(&node_store, &schema, &cursor(&link));
+    assert_eq!(
+        index.errors(),
+        typecheck::check_file(&node_store, &[], &link)
+    );
+    let mut edited = file.clone();
+    edited["function_definitions"]
+        .as_array_mut()
+        .unwrap()
+        .remove(2);
+    let edited =
+        json::import_typed(&mut node_store, &schema, ast::file::kind_id(), &edited).unwrap();
+    index.update(&node_store, &schema, &cursor(&edited));
+    assert_eq!(index.errors().len(), 5);
+    assert_eq!(
+        index.errors(),
+        typecheck::check_file(&node_store, &[], &edited)
+    );
+}
+
+#[test]
+fn test_holes() {
+    use serde_json::json;
+    let file = json!({
+        "function_definitions": [
+            {
+                "ref": 1,
+                "label": {},
+                "body": { "list_value": { "values": [
+                    { "ref": 2 },
+                    { "int_value": { "value": 1 } },
+                    { "hole_value": {} },
+                ] } },
+            },
+            { "ref": 3 },
+        ],
+    });
+    let schema = proto::ast_schema().unwrap();
+    let mut node_store = NodeStore::default();
+    let link = json::import_typed(&mut node_store, &schema, ast::file::kind_id(), &file).unwrap();
+    let path = |selectors: &[(u64, usize)]| -> Path {
+        selectors
+            .iter()
+            .map(|(field_id, index)| Selector {
+                field_id: *field_id,
+                index: *index,
+                id: None,
+            })
+            .collect()
+    };
+    // Labels have only scalar fields, so an empty one is complete.
+    let holes = hole::find_holes(&node_store, &schema, ast::file::kind_id(), &link);
+    assert_eq!(
+        holes,
+        vec![
+            path(&[(5, 0), (5, 0), (6, 0), (1, 0)]),
+            path(&[(5, 0), (5, 0), (6, 0), (1, 2)]),
+        ]
+    );
+    assert_eq!(hole::next_hole(&holes, &[]), Some(holes[0].clone()));
+    assert_eq!(hole::next_hole(&holes, &holes[0]), Some(holes[1].clone()));
+    assert_eq!(hole::next_hole(&holes, &holes[1]), Some(holes[0].clone()));
+    assert_eq!(hole::prev_hole(&holes, &holes[0]), Some(holes[1].clone()));
+
+    // Only the variants of the expression are offered, not the hole marker.
+    let labels: Vec<String> = hole::completions(&schema, ast::expression::kind_id())
+        .into_iter()
+        .map(|c| c.label)
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            "bool_value",
+            "int_value",
+            "float_value",
+            "string_value",
+            "list_value",
+            "if_value",
+            "function_application_value",
+            "ref_value",
+            "external_ref_value",
+        ]
+    );
+    assert_eq!(
+        hole::completions(&schema, ast::function_definition::kind_id()),
+        vec![hole::Completion {
+            label: "FunctionDefinition".to_string(),
+            variant: None,
+        }]
+    );
+
+    // Filling a hole creates holes for the singular message fields of the new node.
+    let node = hole::fill(
+        &mut node_store,
+        &schema,
+        ast::expression::kind_id(),
+        Some(ast::expression::IF_VALUE),
+    );
+    let digest = node_store.put_parsed(&node);
+    let link = Link {
+        type_: LinkType::Dag,
+        digest,
+    };
+    assert_eq!(
+        hole::find_holes(&node_store, &schema, ast::expression::kind_id(), &link),
+        vec![
+            path(&[(7, 0), (1, 0)]),
+            path(&[(7, 0), (2, 0)]),
+            path(&[(7, 0), (3, 0)])
+        ]
+    );
+}
+
+#[test]
+fn test_codegen() {
+    use generated::{
+        ExpressionBuilder, ExpressionInt, ExpressionIntBuilder, ExpressionValue, File,
+        FunctionDefinitionBuilder,
+    };
+    use serde_json::json;
+
+    let file = json!({
+        "name": "main",
+        "function_definitions": [
+            {
+                "ref": 1,
+                "label": { "name": "one" },
+                "body": { "int_value": { "value": 1 } },
+            },
+            { "ref": 2 },
+        ],
+    });
+    let mut node_store = NodeStore::default();
+    let link = json::import_typed(
+        &mut node_store,
+        &proto::ast_schema().unwrap(),
+        ast::file::kind_id(),
+        &file,
+    )
+    .unwrap();
+    let (name, function_definitions, label) = {
+        let file = File::load(&node_store, &link).unwrap();
+        assert_eq!(file.name().unwrap(), "main");
+        // Missing scalars read as their default value.
+        assert_eq!(file.next_ref().unwrap(), 0);
+        let function_definitions = file.function_definitions().unwrap();
+        assert_eq!(function_definitions.len(), 2);
+        let one = &function_definitions[0];
+        let label = one.label().unwrap().unwrap();
+        assert_eq!(label.name().unwrap(), "one");
+        match one.body().unwrap().unwrap().value().unwrap() {
+            Some(ExpressionValue::IntValue(v)) => assert_eq!(v.value().unwrap(), 1),
+            _ => panic!("expected an int"),
+        }
+        (
+            file.name().unwrap(),
+            function_definitions
+                .iter()
+                .map(|f| f.object().link().clone())
+                .collect::<Vec<_>>(),
+            label.to_builder(),
+        )
+    };
+
+    // Invalid values are errors, with the path at which they occur.
+    let invalid = Node {
+        links: maplit::btreemap! {
+            ast::literal::VALUE => vec![Link {
+                type_: LinkType::Raw,
+                digest: node_store.put_raw(b"x"),
+            }],
+        },
+        sequences: Default::default(),
+    };
+    let invalid = Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&invalid),
+    };
+    let err = ExpressionInt::load(&node_store, &invalid)
+        .unwrap()
+        .value()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "/1[0]: invalid Int value");
+
+    // Builders create new nodes, sharing the unchanged ones.
+    let label = label
+        .clone()
+        .set_name("two".to_string())
+        .build(&mut node_store);
+    let int = ExpressionIntBuilder::default()
+        .set_value(2)
+        .build(&mut node_store);
+    let body = ExpressionBuilder::default()
+        .set_int_value(int)
+        .build(&mut node_store);
+    let two = FunctionDefinitionBuilder::default()
+        .set_ref(3)
+        .set_label(Some(label))
+        .set_body(Some(body.clone()))
+        .build(&mut node_store);
+    let new_link = File::load(&node_store, &link)
+        .unwrap()
+        .to_builder()
+        .set_function_definitions(vec![function_definitions[0].clone(), two])
+        .build(&mut node_store);
+    let file = File::load(&node_store, &new_link).unwrap();
+    assert_eq!(file.name().unwrap(), name);
+    let new_function_definitions = file.function_definitions().unwrap();
+    assert_eq!(
+        new_function_definitions[0].object().link(),
+        &function_definitions[0]
+    );
+    assert_eq!(new_function_definitions[1].r#ref().unwrap(), 3);
+    let label = new_function_definitions[1].label().unwrap().unwrap();
+    assert_eq!(label.name().unwrap(), "two");
+    assert_eq!(eval::eval(&node_store, None, &[], &body), Ok(Value::Int(2)));
+}
+
+#[test]
+fn test_derive() {
+    use crate::derive::LincNode;
+
+    #[derive(LincNode, Debug, PartialEq, Clone)]
+    struct Config {
+        #[linc(id = 1)]
+        name: String,
+        #[linc(id = 2)]
+        port: Option<i64>,
+        #[linc(id = 3)]
+        backends: Vec<Backend>,
+        #[linc(id = 5)]
+        policy: Policy,
+    }
+
+    #[derive(LincNode, Debug, PartialEq, Clone)]
+    #[linc(kind_id = 42)]
+    struct Backend {
+        #[linc(id = 1)]
+        host: String,
+        #[linc(id = 2)]
+        weight: f64,
+        #[linc(id = 3)]
+        enabled: bool,
+    }
+
+    #[derive(LincNode, Debug, PartialEq, Clone)]
+    enum Policy {
+        #[linc(id = 1)]
+        RoundRobin,
+        #[linc(id = 2)]
+        Fixed(Backend),
+    }
+
+    let schema = Config::schema();
+    let names: Vec<&str> = schema.kinds.iter().map(|k| k.name.as_str()).collect();
+    assert_eq!(names, vec!["Config", "Backend", "Policy"]);
+    assert_eq!(schema.kinds[0].kind_id, proto::kind_id("Config"));
+    assert_eq!(
+        schema.kinds[0].get_field(3),
+        Some(&Field {
+            field_id: 3,
+            name: "backends".to_string(),
+            type_: FieldType::Object { kind_id: 42 },
+            repeated: true,
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        schema.kinds[2].get_field(1).unwrap().oneof,
+        Some("value".to_string())
+    );
+
+    let backend = Backend {
+        host: "localhost".to_string(),
+        weight: 0.5,
+        enabled: true,
+    };
+    let config = Config {
+        name: "lb".to_string(),
+        port: Some(8080),
+        backends: vec![backend.clone(), backend.clone()],
+        policy: Policy::Fixed(backend),
+    };
+    let mut node_store = NodeStore::default();
+    let link = config.to_dag(&mut node_store);
+    assert_eq!(Config::from_dag(&node_store, &link), Ok(config.clone()));
+
+    // Equal values are stored once, and unchanged subtrees are shared.
+    let node = node_store.get_dag(&link.digest).unwrap();
+    assert_eq!(node.links[&3][0], node.links[&3][1]);
+    let renamed = Config {
+        name: "lb2".to_string(),
+        port: None,
+        policy: Policy::RoundRobin,
+        ..config
+    };
+    let renamed_link = renamed.to_dag(&mut node_store);
+    let renamed_node = node_store.get_dag(&renamed_link.digest).unwrap();
+    assert_eq!(renamed_node.links[&3], node.links[&3]);
+    assert!(!renamed_node.links.contains_key(&2));
+    assert_eq!(Config::from_dag(&node_store, &renamed_link), Ok(renamed));
+
+    // The tree is typed by the derived schema.
+    let bytes = protobuf::encode(&node_store, &schema, schema.kinds[0].kind_id, &link).unwrap();
+    let decoded = protobuf::decode(&mut node_store, &schema, schema.kinds[0].kind_id, &bytes);
+    assert_eq!(decoded, Ok(link));
+
+    let missing = node_store.put_parsed(&Node::default());
+    let missing = Link {
+        type_: LinkType::Dag,
+        digest: missing,
+    };
+    assert_eq!(
+        Config::from_dag(&node_store, &missing)
+            .unwrap_err()
+            .to_string(),
+        "/: missing field 1"
+    );
+}
+
+#[test]
+fn test_argv() {
+    let field = |field_id: u64, name: &str, type_: FieldType, repeated: bool, arg: Arg| Field {
+        field_id,
+        name: name.to_string(),
+        type_,
+        repeated,
+        arg: Some(arg),
+        ..Default::default()
+    };
+    let flag = |name: &str| Arg::Flag(name.to_string());
+    let schema = Schema {
+        kinds: vec![
+            Kind {
+                kind_id: 1,
+                name: "git".to_string(),
+                // Flags are rendered before the subcommand, even if declared after it.
+                fields: vec![
+                    field(
+                        2,
+                        "commit",
+                        FieldType::Object { kind_id: 2 },
+                        false,
+                        Arg::Subcommand("commit".to_string()),
+                    ),
+                    field(1, "config", FieldType::String, true, flag("-c")),
+                ],
+            },
+            Kind {
+                kind_id: 2,
+                name: "commit".to_string(),
+                fields: vec![
+                    field(1, "all", FieldType::Bool, false, flag("--all")),
+                    field(2, "message", FieldType::String, false, flag("-m")),
+                    field(3, "author", FieldType::String, false, flag("--author=")),
+                    field(4, "depth", FieldType::Int, false, flag("--depth")),
+                    field(5, "pathspec", FieldType::String, true, Arg::Positional),
+                ],
+            },
+        ],
+    };
+    let args = |line: &str| argv::split(line).unwrap();
+    let line = "git -c user.name=me commit --all -m 'it'\\''s done' '--author=A <a@b.c>' \
+                --depth 3 src 'a b'";
+    assert_eq!(
+        args(line),
+        vec![
+            "git",
+            "-c",
+            "user.name=me",
+            "commit",
+            "--all",
+            "-m",
+            "it's done",
+            "--author=A <a@b.c>",
+            "--depth",
+            "3",
+            "src",
+            "a b",
+        ]
+    );
+    let mut node_store = NodeStore::default();
+    let link = argv::parse(&mut node_store, &schema, 1, &args(line)[1..]).unwrap();
+    let rendered = argv::render(&node_store, &schema, 1, &link).unwrap();
+    assert_eq!(rendered, args(line)[1..].to_vec());
+    assert_eq!(
+        argv::shell_line(&rendered),
+        "-c user.name=me commit --all -m 'it'\\''s done' '--author=A <a@b.c>' --depth 3 src 'a b'"
+    );
+
+    // Flags may be given in any order, and long flags may have joined values; `--` ends flags.
+    let other = argv::parse(
+        &mut node_store,
+        &schema,
+        1,
+        &args("-c user.name=me commit src --depth=3 -m \"it's done\" --all --author='A <a@b.c>' -- 'a b'"),
+    )
+    .unwrap();
+    assert_eq!(other, link);
+
+    // `--` is written before positional arguments that look like flags.
+    let line = args("commit --all -- -x src");
+    let link = argv::parse(&mut node_store, &schema, 1, &line).unwrap();
+    assert_eq!(argv::render(&node_store, &schema, 1, &link).unwrap(), line);
+
+    let parse = |line: &str| {
+        argv::parse(&mut NodeStore::default(), &schema, 2, &args(line))
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(parse("--depth x"), "/4[0]: invalid Int value");
+    assert_eq!(parse("-m a -m b"), "/2[1]: `message` given more than once");
+    assert_eq!(parse("--amend"), "/: unknown flag `--amend`");
+    assert_eq!(parse("-m"), "/: missing value for `-m`");
+    assert_eq!(
+        argv::split("'a"),
+        Err("unterminated single quote".to_string())
+    );
+}
+
+#[test]
+fn test_git_docker() {
+    let schema = crate::initial::initial_schema();
+    // Every message field refers to a kind of the schema, and kind ids are unique.
+    for kind in schema.kinds.iter() {
+        assert_eq!(
+            schema
+                .kinds
+                .iter()
+                .filter(|k| k.kind_id == kind.kind_id)
+                .count(),
+            1,
+            "duplicate kind {}",
+            kind.kind_id
+        );
+        for field in kind.fields.iter() {
+            if let FieldType::Object { kind_id } = field.type_ {
+                assert!(
+                    schema.get_kind(kind_id).is_some(),
+                    "`{}.{}` refers to unknown kind {}",
+                    kind.name,
+                    field.name,
+                    kind_id
+                );
+            }
+        }
+    }
+
+    let root = schema.root_kind().unwrap().kind_id;
+    let round_trip = |line: &str| {
+        let args = argv::split(line).unwrap();
+        let mut node_store = NodeStore::default();
+        let link = argv::parse(&mut node_store, &schema, root, &args).unwrap();
+        argv::shell_line(&argv::render(&node_store, &schema, root, &link).unwrap())
+    };
+    assert_eq!(
+        round_trip("git commit --message 'fix: typo' --amend --no-edit src"),
+        "git commit --amend --no-edit --message 'fix: typo' src"
+    );
+    assert_eq!(
+        round_trip("git -C repo push origin main --force-with-lease"),
+        "git -C repo push --force-with-lease origin main"
+    );
+    assert_eq!(
+        round_trip("git log --max-count=5 --oneline main -- src"),
+        "git log --oneline --max-count 5 main -- src"
+    );
+    // Paths alone are kept apart from the revision range and the branch by `--`.
+    for line in [
+        "git log -- src",
+        "git log main",
+        "git checkout -- a.txt b.txt",
+        "git checkout main",
+        "git checkout main -- a.txt",
+        "git add -- -file",
+    ] {
+        assert_eq!(round_trip(line), line);
+    }
+    assert_eq!(
+        round_trip("docker build --tag app:latest --build-arg V=1 --no-cache ."),
+        "docker build --tag app:latest --build-arg V=1 --no-cache ."
+    );
+    // The command of `docker run` is taken verbatim, even if it looks like flags.
+    assert_eq!(
+        round_trip("docker run --rm --env A=1 --tty alpine ls -la --color"),
+        "docker run --tty --rm --env A=1 alpine ls -la --color"
+    );
+    assert_eq!(
+        round_trip("docker compose --file dev.yml exec --no-TTY web sh -c 'echo hi'"),
+        "docker compose --file dev.yml exec --no-TTY web sh -c 'echo hi'"
+    );
+    for line in [
+        "docker run alpine",
+        "docker run alpine --version",
+        "docker build -- -context",
+        "docker compose up web db",
+        "docker compose logs -- -web",
+        "docker compose exec web",
+    ] {
+        assert_eq!(round_trip(line), line);
+    }
+
+    let mut node_store = NodeStore::default();
+    assert_eq!(
+        argv::parse(
+            &mut node_store,
+            &schema,
+            root,
+            &argv::split("docker run --cpus lots alpine").unwrap()
+        )
+        .unwrap_err()
+        .to_string(),
+        "/3021732[0]/13091823090[0]/18[0]: invalid Float value"
+    );
+}
+
+#[test]
+fn test_collab() {
+    let raw = |node_store: &mut NodeStore, value: &str| Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(value.as_bytes()),
+    };
+    let dag = |node_store: &mut NodeStore, node: &Node| Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(node),
+    };
+    let selector = |field_id, index| Selector {
+        field_id,
+        index,
+        id: None,
+    };
+    let mut node_store = NodeStore::default();
+    let inner = Node {
+        links: maplit::btreemap! { 1 => vec![raw(&mut node_store, "inner")] },
+        sequences: Default::default(),
+    };
+    let root = Node {
+        links: maplit::btreemap! {
+            1 => vec![
+                raw(&mut node_store, "a"),
+                raw(&mut node_store, "b"),
+                raw(&mut node_store, "c"),
+            ],
+            2 => vec![dag(&mut node_store, &inner)],
+        },
+        sequences: Default::default(),
+    };
+    let root = dag(&mut node_store, &root);
+    let mut server = collab::Server::new(node_store, root.clone());
+
+    // Delivers messages until there are none left, going through JSON as on the wire.
+    fn deliver(
+        server: &mut collab::Server,
+        clients: &mut [(collab::Client, NodeStore)],
+        mut queue: Vec<ClientMessage>,
+    ) {
+        while !queue.is_empty() {
+            let message = queue.remove(0);
+            let message = serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap();
+            for (recipient, message) in server.receive(message) {
+                let json = serde_json::to_string(&message).unwrap();
+                for (client, node_store) in clients.iter_mut() {
+                    let to_client = match &recipient {
+                        Recipient::Client(id) => *id == client.client_id,
+                        Recipient::Others(id) => *id != client.client_id,
+                    };
+                    if to_client {
+                        let message = serde_json::from_str(&json).unwrap();
+                        let (_, replies) = client.receive(node_store, message).unwrap();
+                        queue.extend(replies);
+                    }
+                }
+            }
+        }
+    }
+    let values = |node_store: &NodeStore, root: &Link| -> Vec<String> {
+        let node = node_store.get_dag(&root.digest).unwrap();
+        node.links[&1]
+            .iter()
+            .map(|l| String::from_utf8(node_store.get_raw(&l.digest).unwrap().clone()).unwrap())
+            .collect()
+    };
+
+    let mut clients = vec![
+        (collab::Client::new("a", root.clone()), NodeStore::default()),
+        (collab::Client::new("b", root.clone()), NodeStore::default()),
+    ];
+    let joins = clients.iter().map(|(c, _)| c.join()).collect();
+    deliver(&mut server, &mut clients, joins);
+    let presence = clients[0].0.presence(&[selector(1, 2)]);
+    deliver(&mut server, &mut clients, vec![presence]);
+    assert_eq!(clients[1].0.presence["a"], vec![selector(1, 2)]);
+
+    // Concurrent edits, made before any of them reaches the server.
+    let mut queue = vec![];
+    let (a, a_store) = &mut clients[0];
+    let x = raw(a_store, "x");
+    let edits = [
+        Op::Insert {
+            path: vec![selector(1, 1)],
+            link: x,
+            id: None,
+        },
+        Op::SetValue {
+            path: vec![selector(1, 0)],
+            value: b"A".to_vec(),
+        },
+        Op::SetValue {
+            path: vec![selector(2, 0), selector(1, 0)],
+            value: b"lost".to_vec(),
+        },
+    ];
+    for op in edits {
+        queue.extend(a.edit(a_store, op).unwrap());
+    }
+    let (b, b_store) = &mut clients[1];
+    let replaced = Node::default();
+    let replaced = dag(b_store, &replaced);
+    let edits = [
+        Op::Delete {
+            path: vec![selector(1, 1)],
+        },
+        Op::SetValue {
+            path: vec![selector(1, 0)],
+            value: b"B".to_vec(),
+        },
+        Op::Replace {
+            path: vec![selector(2, 0)],
+            link: replaced.clone(),
+        },
+    ];
+    for op in edits {
+        queue.extend(b.edit(b_store, op).unwrap());
+    }
+    assert_eq!(queue.len(), 2);
+    deliver(&mut server, &mut clients, queue);
+
+    for (client, node_store) in clients.iter() {
+        assert!(client.is_synced());
+        assert_eq!(client.revision, server.revision());
+        assert_eq!(client.root, server.root);
+        assert_eq!(values(node_store, &client.root)[1..], ["x", "c"]);
+    }
+    assert!(["A", "B"].contains(&values(&server.node_store, &server.root)[0].as_str()));
+    // The edit inside the replaced subtree was dropped.
+    let root = server.node_store.get_dag(&server.root.digest).unwrap();
+    assert_eq!(root.links[&2], vec![replaced]);
+    // Presence follows the edits.
+    assert_eq!(clients[1].0.presence["a"], vec![selector(1, 2)]);
+    assert_eq!(
+        collab::transform_selection(
+            &[selector(2, 0), selector(1, 0)],
+            &Op::Delete {
+                path: vec![selector(2, 0)]
+            }
+        ),
+        vec![]
+    );
+
+    // A client joining later gets the current tree.
+    let mut late = vec![(
+        collab::Client::new("c", server.root.clone()),
+        NodeStore::default(),
+    )];
+    let join = late[0].0.join();
+    deliver(&mut server, &mut late, vec![join]);
+    assert_eq!(late[0].0.root, server.root);
+    assert_eq!(
+        values(&late[0].1, &late[0].0.root),
+        values(&server.node_store, &server.root)
+    );
+
+    // Ops whose blobs do not match their digests are dropped by the server.
+    let (c, c_store) = &mut late[0];
+    let y = raw(c_store, "y");
+    let mut submit = c
+        .edit(
+            c_store,
+            Op::Insert {
+                path: vec![selector(1, 0)],
+                link: y,
+                id: None,
+            },
+        )
+        .unwrap()
+        .unwrap();
+    if let ClientMessage::Submit { blobs, .. } = &mut submit {
+        blobs[0].1 = b"z".to_vec();
+    }
+    let revision = server.revision();
+    deliver(&mut server, &mut late, vec![submit]);
+    assert_eq!(server.revision(), revision);
+    assert!(late[0].0.is_synced());
+}
+
+#[test]
+fn test_crdt() {
+    fn raw(node_store: &mut NodeStore, value: &str) -> Link {
+        Link {
+            type_: LinkType::Raw,
+            digest: node_store.put_raw(value.as_bytes()),
+        }
+    }
+    fn dag(node_store: &mut NodeStore, node: &Node) -> Link {
+        Link {
+            type_: LinkType::Dag,
+            digest: node_store.put_parsed(node),
+        }
+    }
+    fn values(node_store: &NodeStore, link: &Link) -> Vec<String> {
+        let node = node_store.get_dag(&link.digest).unwrap();
+        node.links
+            .get(&1)
+            .into_iter()
+            .flatten()
+            .map(|l| String::from_utf8(node_store.get_raw(&l.digest).unwrap().clone()).unwrap())
+            .collect()
+    }
+    let mut node_store = NodeStore::default();
+    let mut base = Node::default();
+    for value in ["a", "b", "c"] {
+        let link = raw(&mut node_store, value);
+        base.links.entry(1).or_default().push(link);
+    }
+    // Nodes without sequences are serialized, and so hashed, as before.
+    let plain = serialize_node(&base);
+    assert!(!String::from_utf8(plain.clone())
+        .unwrap()
+        .contains("sequences"));
+    crdt::enable(&mut base, 1, "base");
+    assert_ne!(serialize_node(&base), plain);
+    let b_id = crdt::id_at(&base, 1, 1).unwrap();
+
+    // Two replicas edit the same version offline.
+    let mut x = base.clone();
+    let x1 = raw(&mut node_store, "x1");
+    crdt::insert(&mut x, 1, 1, x1, "x");
+    let x2 = raw(&mut node_store, "x2");
+    crdt::insert(&mut x, 1, 2, x2, "x");
+    crdt::delete(&mut x, 1, 4);
+    let mut y = base.clone();
+    let y1 = raw(&mut node_store, "y1");
+    crdt::insert(&mut y, 1, 1, y1, "y");
+    crdt::delete(&mut y, 1, 0);
+    let x = dag(&mut node_store, &x);
+    let y = dag(&mut node_store, &y);
+    assert_eq!(values(&node_store, &x), ["a", "x1", "x2", "b"]);
+    assert_eq!(values(&node_store, &y), ["y1", "b", "c"]);
+
+    // Merging converges, whatever the order.
+    let xy = crdt::merge(&mut node_store, &x, &y);
+    let yx = crdt::merge(&mut node_store, &y, &x);
+    assert_eq!(xy, yx);
+    assert_eq!(values(&node_store, &xy), ["y1", "x1", "x2", "b"]);
+    assert_eq!(crdt::merge(&mut node_store, &xy, &x), xy);
+    assert_eq!(crdt::merge(&mut node_store, &xy, &xy), xy);
+
+    // Stable selectors address the same child after the indices shift.
+    let selector = Selector {
+        field_id: 1,
+        index: 1,
+        id: Some(b_id),
+    };
+    let merged = node_store.get_dag(&xy.digest).unwrap();
+    assert_eq!(merged.index(&selector), Some(3));
+    assert_eq!(merged.get_link(&selector), Some(&raw(&mut node_store, "b")));
+    let stable = crdt::stable(
+        &merged,
+        &Selector {
+            id: None,
+            ..selector.clone()
+        },
+    );
+    assert_eq!(stable.id, crdt::id_at(&merged, 1, 1));
+
+    // Collaborative edits of a sequence keep its elements.
+    let z = raw(&mut node_store, "z");
+    let id = crdt::next_id(&merged, 1, "z");
+    let insert = Op::Insert {
+        path: vec![Selector {
+            field_id: 1,
+            index: 1,
+            id: None,
+        }],
+        link: z,
+        id: Some(id.clone()),
+    };
+    let inserted = collab::apply(&mut node_store, &xy, &insert).unwrap();
+    assert_eq!(values(&node_store, &inserted), ["y1", "z", "x1", "x2", "b"]);
+    let node = node_store.get_dag(&inserted.digest).unwrap();
+    assert_eq!(crdt::index_of(&node, 1, &id), Some(1));
+    let delete = Op::Delete {
+        path: vec![Selector {
+            field_id: 1,
+            index: 1,
+            id: None,
+        }],
+    };
+    let deleted = collab::apply(&mut node_store, &inserted, &delete).unwrap();
+    assert_eq!(values(&node_store, &deleted), ["y1", "x1", "x2", "b"]);
+    let node = node_store.get_dag(&deleted.digest).unwrap();
+    assert_eq!(crdt::index_of(&node, 1, &id), None);
+    assert!(node.sequences[&1].iter().any(|e| e.id == id && e.deleted));
+
+    // A newly added item is selected by index, as the paths built by the views, and merging keeps
+    // it selected.
+    let at_index = |index| Selector {
+        field_id: 1,
+        index,
+        id: None,
+    };
+    let schema = schema();
+    let cursor = Cursor {
+        parent: None,
+        link: inserted.clone(),
+        type_: FieldType::Object { kind_id: 1 },
+    };
+    let selected = vec![at_index(1)];
+    let item = cursor.traverse(&node_store, &schema, &selected).unwrap();
+    assert_eq!(item.link, raw(&mut node_store, "z"));
+    let first = cursor
+        .traverse(&node_store, &schema, &[at_index(0)])
+        .unwrap();
+    assert_eq!(first.next(&node_store, &schema).unwrap().path(), selected);
+    let stable_selected = crdt::stable_path(&node_store, &inserted, &selected);
+    assert_eq!(stable_selected[0].id, Some(id.clone()));
+    let mut other = node_store.get_dag(&xy.digest).unwrap();
+    let w = raw(&mut node_store, "w");
+    crdt::insert(&mut other, 1, 0, w, "w");
+    let other = dag(&mut node_store, &other);
+    let merged = crdt::merge(&mut node_store, &inserted, &other);
+    assert_eq!(
+        values(&node_store, &merged),
+        ["w", "y1", "z", "x1", "x2", "b"]
+    );
+    assert_eq!(
+        crdt::resolve_path(&node_store, &merged, &stable_selected),
+        Some(vec![at_index(2)])
+    );
+    assert_eq!(
+        crdt::resolve_path(&node_store, &deleted, &stable_selected),
+        None
+    );
+
+    // Inserting into a sequence needs an element id.
+    let insert_link = raw(&mut node_store, "z");
+    assert!(collab::apply(
+        &mut node_store,
+        &xy,
+        &Op::Insert {
+            path: insert.path().to_vec(),
+            link: insert_link,
+            id: None,
+        }
+    )
+    .is_err());
+    assert_eq!(
+        serde_json::from_str::<Selector>(r#"{"field_id":1,"index":2}"#).unwrap(),
+        Selector {
+            field_id: 1,
+            index: 2,
+            id: None,
+        }
+    );
+}
+
+#[test]
+fn test_identity() {
+    let mut node_store = NodeStore::default();
+    let root = node_store.put_parsed(&Node::default());
+    let alice = identity::Identity::from_seed("alice", [1; 32]);
+    let statement = alice.sign(&mut node_store, &root);
+    let verified = identity::verify(&node_store, &statement).unwrap();
+    assert_eq!(verified.subject, root);
+    assert_eq!(verified.author, "alice");
+    assert_eq!(verified.public_key, alice.public_key());
+    assert_eq!(
+        identity::statements(&node_store, std::slice::from_ref(&statement.digest), &root),
+        vec![verified.clone()]
+    );
+    assert_eq!(
+        identity::statements(
+            &node_store,
+            std::slice::from_ref(&statement.digest),
+            &"other".to_string()
+        ),
+        vec![]
+    );
+
+    // Changing any field of the statement invalidates it.
+    let mut forged = node_store.get_dag(&statement.digest).unwrap();
+    forged.links.insert(
+        identity::STATEMENT_AUTHOR,
+        vec![Link {
+            type_: LinkType::Raw,
+            digest: node_store.put_raw(b"mallory"),
+        }],
+    );
+    let forged = Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&forged),
+    };
+    assert_eq!(
+        identity::verify(&node_store, &forged),
+        Err("invalid signature".to_string())
+    );
+    assert_eq!(
+        identity::statements(
+            &node_store,
+            &[
+                statement.digest.clone(),
+                forged.digest.clone(),
+                root.clone()
+            ],
+            &root
+        ),
+        vec![verified.clone()]
+    );
+
+    // Key files keep the key pair.
+    let key_file = serde_json::to_string(&alice).unwrap();
+    let loaded: identity::Identity = serde_json::from_str(&key_file).unwrap();
+    assert_eq!(loaded.public_key(), alice.public_key());
+    let path = std::env::temp_dir().join(format!("linc_test_identity_{}.json", std::process::id()));
+    alice.save(&path).unwrap();
+    let loaded = identity::Identity::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        (loaded.author.as_str(), loaded.public_key()),
+        ("alice", alice.public_key())
+    );
+    assert!(identity::Identity::load(&path).is_err());
+    let generated = identity::Identity::generate("bob").unwrap();
+    assert_ne!(generated.public_key(), alice.public_key());
+}
+
+#[test]
+fn test_encryption() {
+    let mut node_store = NodeStore::default();
+    let secret = Link {
+        type_: LinkType::Raw,
+        digest: node_store.put_raw(b"hunter2"),
+    };
+    let mut credentials = Node::default();
+    credentials.links.insert(1, vec![secret.clone()]);
+    let credentials = Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&credentials),
+    };
+    let key = encryption::Key::from_bytes([7; 32]);
+    let encrypted = encryption::encrypt(&mut node_store, &key, &credentials).unwrap();
+    assert_eq!(encrypted.type_, LinkType::Encrypted);
+    // Encrypted links are addressed by the hash of the ciphertext.
+    assert_eq!(
+        digest(node_store.get_raw(&encrypted.digest).unwrap()),
+        encrypted.digest
+    );
+    let mut root = Node::default();
+    root.links.insert(2, vec![encrypted.clone()]);
+    let root = Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&root),
+    };
+
+    // With the key, the subtree is read as usual.
+    let node = encrypted
+        .get(&node_store)
+        .unwrap()
+        .as_parsed()
+        .unwrap()
+        .clone();
+    assert_eq!(node.links[&1][0].type_, LinkType::Encrypted);
+    match node.links[&1][0].get(&node_store) {
+        Some(LinkTarget::Raw(raw)) => assert_eq!(raw, b"hunter2"),
+        target => panic!("unexpected target {:?}", target),
+    }
+    assert_eq!(node_store.key_of(&encrypted.digest), Some(&key));
+    assert!(fsck(&node_store).is_ok());
+
+    // The plaintext is not shared.
+    let mut remote = NodeStore::default();
+    for (d, blob) in node_store.iter_public() {
+        remote.put_raw_checked(d, blob).unwrap();
+    }
+    assert!(!remote.has_raw_node(&secret.digest));
+    assert!(!remote.has_raw_node(&credentials.digest));
+    assert!(remote.has_raw_node(&encrypted.digest));
+    assert!(remote.has_raw_node(&root.digest));
+    assert!(remote
+        .iter()
+        .all(|(_, blob)| !String::from_utf8_lossy(blob).contains("hunter2")));
+
+    // Without the key, the subtree is locked, rather than a hole.
+    assert!(encrypted.get(&remote).is_none());
+    assert!(!hole::is_hole(
+        &remote,
+        &Schema { kinds: vec![] },
+        0,
+        &encrypted
+    ));
+    remote.add_key(encryption::Key::from_bytes([8; 32]));
+    assert!(encrypted.get(&remote).is_none());
+    assert!(remote.get_dag(&encrypted.digest).is_none());
+    remote.add_key(encryption::Key::from_hex(&key.to_hex()).unwrap());
+    assert_eq!(
+        encrypted.get(&remote).and_then(|t| t.as_parsed().cloned()),
+        Some(node.clone())
+    );
+    // The fetched node leads to its children, which are fetched in turn.
+    assert_eq!(remote.get_dag(&encrypted.digest), Some(node));
+}
+
+#[test]
+fn test_query() {
+    let schema = crate::initial::initial_schema();
+    let mut node_store = NodeStore::default();
+    let args: Vec<String> = "docker run --name web --env FOO=1 --env BAR=2 nginx"
+        .split(' ')
+        .map(str::to_string)
+        .collect();
+    let root = Cursor {
+        parent: None,
+        link: argv::parse(&mut node_store, &schema, 3021731, &args).unwrap(),
+        type_: FieldType::Object { kind_id: 3021731 },
+    };
+    let select = |text: &str| -> Vec<String> {
+        query::Query::parse(text)
+            .unwrap()
+            .select(&node_store, &schema, &root)
+            .iter()
+            .map(|cursor| match cursor.link.get(&node_store) {
+                Some(LinkTarget::Raw(raw)) => String::from_utf8(raw.clone()).unwrap(),
+                _ => format!("{:?}", cursor.kind_id()),
+            })
+            .collect()
+    };
+    let docker_run = format!("{:?}", Some(crate::docker::DOCKER_RUN));
+    assert_eq!(
+        select(r#"root.docker_command.docker_run[*].env[.^="FOO="]"#),
+        ["FOO=1"]
+    );
+    assert_eq!(select("root..env"), ["FOO=1", "BAR=2"]);
+    assert_eq!(select("..env[1]"), ["BAR=2"]);
+    assert_eq!(select("*..*:docker_run"), vec![docker_run.clone()]);
+    assert_eq!(
+        select("root..docker_run[name=web]"),
+        vec![docker_run.clone()]
+    );
+    assert_eq!(
+        select(r#"root..docker_run[name!="web"]"#),
+        Vec::<String>::new()
+    );
+    assert_eq!(select("root..docker_run[env *= AR]"), [docker_run]);
+    assert_eq!(select("other..env"), Vec::<String>::new());
+    assert_eq!(
+        query::Query::parse("root..[name]").unwrap_err().to_string(),
+        "at 6: expected a name"
+    );
+    assert_eq!(
+        query::Query::parse(r#"root[name="web]"#)
+            .unwrap_err()
+            .message,
+        "unterminated string"
+    );
+}
+
+#[test]
+fn test_search() {
+    let schema = crate::initial::initial_schema();
+    let mut node_store = NodeStore::default();
+    let mut parse = |command: &str| -> Cursor {
+        let args: Vec<String> = command.split(' ').map(str::to_string).collect();
+        Cursor {
+            parent: None,
+            link: argv::parse(&mut node_store, &schema, 3021731, &args).unwrap(),
+            type_: FieldType::Object { kind_id: 3021731 },
+        }
+    };
+    let root = parse("docker run --name web --env FOO=1 --env BAR=2 nginx");
+    let edited = parse("docker run --name web --env FOO=1 --env BAZ=3 nginx");
+    let search = |index: &search::Index, root: &Cursor, text: &str| -> Vec<String> {
+        let search = search::Search::parse(text).unwrap();
+        index
+            .search(&node_store, &schema, root, &search)
+            .iter()
+            .map(|path| {
+                let cursor = root.traverse(&node_store, &schema, path).unwrap();
+                match cursor.link.get(&node_store) {
+                    Some(LinkTarget::Raw(raw)) => String::from_utf8(raw.clone()).unwrap(),
+                    _ => format!("{:?}", cursor.kind_id()),
+                }
+            })
+            .collect()
+    };
+    let mut index = search::Index::default();
+    index.update(&node_store, &schema, &root);
+    assert_eq!(search(&index, &root, "foo"), ["FOO=1"]);
+    assert_eq!(search(&index, &root, "/^[A-Z]+=2$/"), ["BAR=2"]);
+    assert_eq!(search(&index, &root, "field:env"), ["FOO=1", "BAR=2"]);
+    assert_eq!(
+        search(&index, &root, "kind:docker_run"),
+        [format!("{:?}", Some(crate::docker::DOCKER_RUN))]
+    );
+    assert_eq!(search(&index, &root, "?root..env[1]"), ["BAR=2"]);
+    assert_eq!(search(&index, &root, "missing"), Vec::<String>::new());
+    assert!(search::Search::parse("/(/").is_err());
+
+    // The index follows edits, and ends up the same as one built from scratch.
+    index.update(&node_store, &schema, &edited);
+    assert_eq!(search(&index, &edited, "field:env"), ["FOO=1", "BAZ=3"]);
+    let mut fresh = search::Index::default();
+    fresh.update(&node_store, &schema, &edited);
+    assert_eq!(index.entries(), fresh.entries());
+}
+
+#[test]
+fn test_refactor() {
+    use refactor::{RefactorError, Refactoring};
+    use serde_json::json;
+    let schema = proto::ast_schema().unwrap();
+    let mut node_store = NodeStore::default();
+    let int = |v: i64| json!({ "int_value": { "value": v } });
+    let file = json::import_typed(
+        &mut node_store,
+        &schema,
+        ast::file::kind_id(),
+        &json!({
+            "next_ref": 2,
+            "function_definitions": [
+                { "ref": 1, "label": { "name": "f" }, "body": { "list_value": { "values": [
+                    int(1), int(2),
+                ] } } },
+            ],
+        }),
+    )
+    .unwrap();
+    let root = |link: &Link| Cursor {
+        parent: None,
+        link: link.clone(),
+        type_: FieldType::Object {
+            kind_id: ast::file::kind_id(),
+        },
+    };
+    let selector = |field_id, index| Selector {
+        field_id,
+        index,
+        id: None,
+    };
+    // Path of the given value of the list in the body of `f`.
+    let value = |index| {
+        vec![
+            selector(ast::file::FUNCTION_DEFINITIONS, 0),
+            selector(ast::function_definition::BODY, 0),
+            selector(ast::expression::LIST_VALUE, 0),
+            selector(ast::list::VALUES, index),
+        ]
+    };
+    // The same file, with the values of the list stored as a sequence.
+    let list_path = &value(0)[..3];
+    let list = root(&file)
+        .traverse(&node_store, &schema, list_path)
+        .unwrap()
+        .link;
+    let mut list = node_store.get_dag(&list.digest).unwrap();
+    crdt::enable(&mut list, ast::list::VALUES, "a");
+    let list = Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&list),
+    };
+    let sequence_file = refactor::replace(&mut node_store, &file, list_path, &list).unwrap();
+    let mut apply = |link: &Link, path: &[Selector], refactoring: &Refactoring| {
+        let edit = refactor::refactor(
+            &mut node_store,
+            &schema,
+            &root(link),
+            path,
+            refactoring,
+            "a",
+        )?;
+        let link = refactor::replace(&mut node_store, link, &edit.path, &edit.link)?;
+        Ok::<_, RefactorError>((link, edit.selection))
+    };
+
+    let wrap_in_list = Refactoring::Wrap {
+        kind_id: ast::expression::kind_id(),
+        fields: vec![ast::expression::LIST_VALUE, ast::list::VALUES],
+    };
+    assert_eq!(
+        Refactoring::parse(&schema, None, "wrap Expression list_value values"),
+        Ok(wrap_in_list.clone())
+    );
+    assert_eq!(
+        refactor::wrappings(&schema, ast::expression::kind_id()).len(),
+        5
+    );
+    let (wrapped, selection) = apply(&file, &value(1), &wrap_in_list).unwrap();
+    assert_eq!(selection, value(1));
+    let (unwrapped, _) = apply(&wrapped, &value(1), &Refactoring::Unwrap).unwrap();
+    assert_eq!(unwrapped, file);
+    // Only a node of the expected kind may take the place of another.
+    let err = apply(
+        &file,
+        &value(1),
+        &Refactoring::Wrap {
+            kind_id: ast::function_definition::kind_id(),
+            fields: vec![ast::function_definition::BODY],
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err.message, "expected Expression, found FunctionDefinition");
+    assert!(apply(&file, &value(1), &Refactoring::Unwrap).is_err());
+
+    let (swapped, selection) = apply(&file, &value(1), &Refactoring::Swap { index: 0 }).unwrap();
+    assert_eq!(selection, value(0));
+    let (swapped_back, _) = apply(&swapped, &value(1), &Refactoring::Swap { index: 0 }).unwrap();
+    assert_eq!(swapped_back, file);
+    let (sequence_swapped, selection) =
+        apply(&sequence_file, &value(1), &Refactoring::Swap { index: 0 }).unwrap();
+    assert_eq!(selection, value(0));
+
+    let extract = Refactoring::Extract {
+        name: "two".to_string(),
+    };
+    let (extracted, _) = apply(&file, &value(1), &extract).unwrap();
+    assert_eq!(
+        json::export_typed(&node_store, &schema, ast::file::kind_id(), &extracted).unwrap(),
+        json!({
+            "next_ref": 3,
+            "function_definitions": [
+                { "ref": 1, "label": { "name": "f" }, "body": { "list_value": { "values": [
+                    int(1),
+                    { "function_application_value": { "function": 2 } },
+                ] } } },
+                { "ref": 2, "label": { "name": "two" }, "body": int(2) },
+            ],
+        })
+    );
+    assert_eq!(typecheck::check_file(&node_store, &[], &extracted), vec![]);
+
+    // Swapped links of a sequence are deleted and inserted again, so that its elements still match
+    // the links of the field.
+    let exported = json::export_typed(
+        &node_store,
+        &schema,
+        ast::file::kind_id(),
+        &sequence_swapped,
+    )
+    .unwrap();
+    assert_eq!(
+        exported["function_definitions"][0]["body"]["list_value"]["values"],
+        json!([int(2), int(1)])
+    );
+    let list = root(&sequence_swapped)
+        .traverse(&node_store, &schema, list_path)
+        .unwrap()
+        .link;
+    let list = node_store.get_dag(&list.digest).unwrap();
+    let elements = &list.sequences[&ast::list::VALUES];
+    assert_eq!(elements.iter().filter(|e| e.deleted).count(), 2);
+    assert_eq!(elements.iter().filter(|e| !e.deleted).count(), 2);
+
+    // Renaming every definition named `f`.
+    let query = query::Query::parse("File.function_definitions[label.name=f]").unwrap();
+    let rename = Refactoring::Rename {
+        name: "g".to_string(),
+    };
+    let renamed =
+        refactor::refactor_all(&mut node_store, &schema, &root(&file), &query, &rename, "a")
+            .unwrap();
+    let definitions = [ast::file::FUNCTION_DEFINITIONS];
+    let definition = node_store.get_dag(&renamed.digest).unwrap().links[&definitions[0]][0].clone();
+    let definition = node_store.get_dag(&definition.digest).unwrap();
+    assert_eq!(
+        ast::get_label(&node_store, &definition, ast::function_definition::LABEL),
+        "g"
+    );
+}
+
+#[test]
+fn test_refactor_selection() {
+    use refactor::Refactoring;
+    let schema = crate::initial::initial_schema();
+    let root_kind = schema.root_kind().unwrap().kind_id;
+    let mut node_store = NodeStore::default();
+    let line = "docker build --build-arg A=1 --build-arg B=2 --build-arg C=3 .";
+    let link = argv::parse(
+        &mut node_store,
+        &schema,
+        root_kind,
+        &argv::split(line).unwrap(),
+    )
+    .unwrap();
+    let root = |link: &Link| Cursor {
+        parent: None,
+        link: link.clone(),
+        type_: FieldType::Object { kind_id: root_kind },
+    };
+    let selector = |field_id, index| Selector {
+        field_id,
+        index,
+        id: None,
+    };
+    let build = vec![
+        selector(3021732, 0),
+        selector(crate::docker::docker_command::BUILD, 0),
+    ];
+    let build_arg = |index| {
+        append(
+            &build,
+            selector(crate::docker::docker_build::BUILD_ARG, index),
+        )
+    };
+    let mut apply = |paths: &[Path], refactoring: &Refactoring| {
+        refactor::refactor_paths(
+            &mut node_store,
+            &schema,
+            &root(&link),
+            paths,
+            refactoring,
+            "a",
+        )
+        .map(|link| {
+            argv::shell_line(&argv::render(&node_store, &schema, root_kind, &link).unwrap())
+        })
+    };
+
+    assert_eq!(
+        apply(&[build_arg(2), build_arg(0)], &Refactoring::Delete),
+        Ok("docker build --build-arg B=2 .".to_string())
+    );
+    assert_eq!(
+        apply(
+            &[build_arg(1), build_arg(2)],
+            &Refactoring::Shift { offset: -1 }
+        ),
+        Ok("docker build --build-arg B=2 --build-arg C=3 --build-arg A=1 .".to_string())
+    );
+    assert_eq!(
+        apply(
+            &[build_arg(0), build_arg(1)],
+            &Refactoring::Shift { offset: 1 }
+        ),
+        Ok("docker build --build-arg C=3 --build-arg A=1 --build-arg B=2 .".to_string())
+    );
+    // The whole selection is refactored, or none of it.
+    assert!(apply(
+        &[build_arg(0), build_arg(1)],
+        &Refactoring::Shift { offset: -1 }
+    )
+    .is_err());
+    assert!(apply(
+        &[build.clone(), build_arg(1)],
+        &Refactoring::Shift { offset: 1 }
+    )
+    .is_err());
+    assert!(apply(
+        &[build_arg(0), build_arg(1)],
+        &Refactoring::Swap { index: 2 }
+    )
+    .is_err());
+
+    let set_field = Refactoring::parse(&schema, None, "set network host  bridge").unwrap();
+    assert_eq!(
+        set_field,
+        Refactoring::SetField {
+            name: "network".to_string(),
+            value: "host  bridge".to_string(),
+        }
+    );
+    assert_eq!(
+        apply(std::slice::from_ref(&build), &set_field),
+        Ok("docker build --build-arg A=1 --build-arg B=2 --build-arg C=3 --network 'host  bridge' ."
+            .to_string())
+    );
+    let set_pull = Refactoring::parse(&schema, None, "set pull maybe").unwrap();
+    assert_eq!(
+        apply(&[build], &set_pull).unwrap_err().message,
+        "\"maybe\" is not a valid bool"
+    );
+}
+
+#[test]
+fn test_permalink() {
+    let state = HashState {
+        root: "abc".to_string(),
+        schema_root: "def".to_string(),
+        ref_name: Some("release 1".to_string()),
+        path: Some("docker_command[0].env[2]".to_string()),
+        format: Some(Format::Yaml),
+        raw: true,
+        show_serialized: false,
+        signatures: vec!["s1".to_string(), "s2".to_string()],
+    };
+    let text = state.to_string();
+    assert_eq!(
+        text,
+        "v2/abc?schema=def&ref=release+1&path=docker_command%5B0%5D.env%5B2%5D&format=yaml&view=raw&signed=s1%2Cs2"
+    );
+    assert_eq!(text.parse(), Ok(state));
+    // Unknown keys are ignored.
+    assert_eq!(
+        "v2/abc?schema=def&zoom=2".parse(),
+        Ok(HashState {
+            root: "abc".to_string(),
+            schema_root: "def".to_string(),
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        "abc@def".parse(),
+        Ok(HashState {
+            root: "abc".to_string(),
+            schema_root: "def".to_string(),
+            ..Default::default()
+        })
+    );
+    assert!("v3/abc".parse::<HashState>().is_err());
+
+    let schema = crate::initial::initial_schema();
+    let root_kind = schema.root_kind().unwrap().kind_id;
+    let selector = |field_id, index| Selector {
+        field_id,
+        index,
+        id: None,
+    };
+    let path = vec![
+        selector(3021732, 0),
+        selector(crate::docker::docker_command::BUILD, 0),
+        selector(crate::docker::docker_build::BUILD_ARG, 2),
+    ];
+    let text = permalink::encode_path(&schema, root_kind, &path);
+    assert_eq!(text, "docker_command[0].docker_build[0].build_arg[2]");
+    assert_eq!(permalink::decode_path(&schema, root_kind, &text), Ok(path));
+    // Fields unknown to the schema are kept by id, and missing indices default to 0.
+    assert_eq!(
+        permalink::decode_path(&schema, root_kind, "99.98[3]"),
+        Ok(vec![selector(99, 0), selector(98, 3)])
+    );
+    assert!(permalink::decode_path(&schema, root_kind, "nope").is_err());
+}
+
+#[test]
+fn test_is_complete() {
+    let mut node_store = NodeStore::default();
+    let raw = |digest: Digest| Link {
+        type_: LinkType::Raw,
+        digest,
+    };
+    let present = raw(node_store.put_raw(b"present"));
+    let missing = raw(digest(b"missing"));
+    let node = Node {
+        links: maplit::btreemap! {
+            1 => vec![present.clone(), missing.clone()],
+        },
+        ..Default::default()
+    };
+    let root = Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&node),
+    };
+    assert!(node_store.is_complete(&present));
+    assert!(!node_store.is_complete(&missing));
+    assert!(!node_store.is_complete(&root));
+    let _ = node_store.put_raw(b"missing");
+    assert!(node_store.is_complete(&root));
+    // Removing a node makes the subtrees that included it incomplete again.
+    node_store.remove(&missing.digest);
+    assert!(!node_store.is_complete(&root));
+}
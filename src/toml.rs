@@ -0,0 +1,70 @@
+use serde_json::{Map, Number, Value};
+
+// TOML documents are mapped onto the generic JSON schema, going through `serde_json::Value`; key
+// order is preserved by link order. Datetimes are represented as strings, since JSON has no
+// equivalent type, and are therefore exported back as strings.
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let table: ::toml::Table = text.parse().map_err(|e| format!("invalid TOML: {}", e))?;
+    toml_to_json(::toml::Value::Table(table))
+}
+
+pub fn print(value: &Value) -> Result<String, String> {
+    match json_to_toml(value, "")? {
+        ::toml::Value::Table(table) => {
+            ::toml::to_string(&table).map_err(|e| format!("could not print TOML: {}", e))
+        }
+        _ => Err("a TOML document must be a table".to_string()),
+    }
+}
+
+fn toml_to_json(value: ::toml::Value) -> Result<Value, String> {
+    Ok(match value {
+        ::toml::Value::String(v) => Value::String(v),
+        ::toml::Value::Integer(v) => Value::from(v),
+        ::toml::Value::Float(v) => match Number::from_f64(v) {
+            Some(v) => Value::Number(v),
+            None => return Err(format!("{} is not supported", v)),
+        },
+        ::toml::Value::Boolean(v) => Value::Bool(v),
+        ::toml::Value::Datetime(v) => Value::String(v.to_string()),
+        ::toml::Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(toml_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        ::toml::Value::Table(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| toml_to_json(v).map(|v| (k, v)))
+                .collect::<Result<Map<_, _>, _>>()?,
+        ),
+    })
+}
+
+fn json_to_toml(value: &Value, path: &str) -> Result<::toml::Value, String> {
+    Ok(match value {
+        Value::Null => return Err(format!("{}: TOML has no null value", path)),
+        Value::Bool(v) => ::toml::Value::Boolean(*v),
+        Value::Number(v) => match (v.as_i64(), v.as_f64()) {
+            (Some(v), _) => ::toml::Value::Integer(v),
+            (None, Some(v)) => ::toml::Value::Float(v),
+            _ => return Err(format!("{}: {} is out of range", path, v)),
+        },
+        Value::String(v) => ::toml::Value::String(v.clone()),
+        Value::Array(items) => ::toml::Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| json_to_toml(v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(entries) => ::toml::Value::Table(
+            entries
+                .iter()
+                .map(|(k, v)| json_to_toml(v, &format!("{}.{}", path, k)).map(|v| (k.clone(), v)))
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
@@ -0,0 +1,595 @@
+use crate::{
+    ast::{
+        self, argument, compound_type, expression, function_application, function_definition, if_,
+        list, ref_type, type_, type_alias,
+    },
+    eval::BUILTIN_PATH,
+    schema::Schema,
+    types::{append, Cursor, Digest, Link, LinkTarget, Node, NodeStore, Path, Selector},
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    rc::Rc,
+};
+
+// Static type checker for the expression language of `proto/ast.proto`.
+//
+// Types are gradual: a missing type annotation, an empty expression or the result of a builtin have
+// the unknown type, which is compatible with any other type. `CompoundType { x, y }` is the type of
+// functions from `x` to `y`; functions with more arguments are curried.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Unknown,
+    Int,
+    Float,
+    Bool,
+    String,
+    List(Box<Type>),
+    Function(Box<Type>, Box<Type>),
+}
+
+impl Type {
+    fn compatible(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::List(a), Type::List(b)) => a.compatible(b),
+            (Type::Function(a, b), Type::Function(c, d)) => a.compatible(c) && b.compatible(d),
+            (a, b) => a == b,
+        }
+    }
+
+    // Curried type of a function with the given argument and return types.
+    fn function(args: &[Type], ret: &Type) -> Type {
+        args.iter().rev().fold(ret.clone(), |ret, arg| {
+            Type::Function(Box::new(arg.clone()), Box::new(ret))
+        })
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Unknown => write!(f, "?"),
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::List(t) => write!(f, "[{}]", t),
+            Type::Function(a, b) => write!(f, "({} -> {})", a, b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub path: Path,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self
+            .path
+            .iter()
+            .map(|s| format!("{}[{}]", s.field_id, s.index))
+            .collect();
+        write!(f, "/{}: {}", path.join("/"), self.message)
+    }
+}
+
+struct Signature {
+    name: String,
+    // Names and types of the arguments.
+    args: Vec<(String, Type)>,
+    ret: Type,
+}
+
+struct Checker<'a> {
+    node_store: &'a NodeStore,
+    aliases: HashMap<i64, (Path, Node)>,
+    // Resolved aliases; `None` while an alias is being resolved, to detect cycles.
+    resolved_aliases: HashMap<i64, Option<Type>>,
+    functions: HashMap<i64, Signature>,
+    errors: Vec<TypeError>,
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, path: &[Selector], message: String) {
+        self.errors.push(TypeError {
+            path: path.to_vec(),
+            message,
+        });
+    }
+
+    fn get_node(&mut self, path: &[Selector], link: &Link) -> Option<Node> {
+        let node = ast::get_node(self.node_store, link);
+        if node.is_none() {
+            self.error(path, format!("missing node {}", link.digest));
+        }
+        node
+    }
+
+    fn get_ref(&mut self, path: &[Selector], node: &Node, field_id: u64) -> Option<i64> {
+        let ref_ = ast::get_int(self.node_store, node, field_id);
+        if ref_.is_none() {
+            self.error(path, "invalid ref".to_string());
+        }
+        ref_
+    }
+
+    // Resolves the optional `Type` in the given field of a node.
+    fn resolve_field(&mut self, node: &Node, path: &[Selector], field_id: u64) -> Type {
+        match ast::get_child(node, path, field_id) {
+            Some((path, link)) => self.resolve(&path, link),
+            None => Type::Unknown,
+        }
+    }
+
+    fn resolve(&mut self, path: &[Selector], link: &Link) -> Type {
+        let node = match self.get_node(path, link) {
+            Some(node) => node,
+            None => return Type::Unknown,
+        };
+        let (field_id, link) = match ast::get_oneof(&node, type_::REF) {
+            Ok(v) => v,
+            Err(e) if e == "empty" => return Type::Unknown,
+            Err(e) => {
+                self.error(path, format!("{} type", e));
+                return Type::Unknown;
+            }
+        };
+        let path = &append(
+            path,
+            Selector {
+                field_id,
+                index: 0,
+                id: None,
+            },
+        );
+        match field_id {
+            type_::PRIMITIVE => match ast::get_int(self.node_store, &node, type_::PRIMITIVE) {
+                Some(type_::PRIMITIVE_TYPE_UNSPECIFIED) => Type::Unknown,
+                Some(type_::INT) => Type::Int,
+                Some(type_::FLOAT) => Type::Float,
+                Some(type_::BOOL) => Type::Bool,
+                Some(type_::STRING) => Type::String,
+                _ => {
+                    self.error(path, "unknown primitive type".to_string());
+                    Type::Unknown
+                }
+            },
+            type_::COMPOUND => {
+                let node = match self.get_node(path, link) {
+                    Some(node) => node,
+                    None => return Type::Unknown,
+                };
+                let x = self.resolve_field(&node, path, compound_type::X);
+                let y = self.resolve_field(&node, path, compound_type::Y);
+                Type::Function(Box::new(x), Box::new(y))
+            }
+            type_::REF_TYPE => {
+                let ref_ = match self
+                    .get_node(path, link)
+                    .and_then(|node| self.get_ref(path, &node, ref_type::REF))
+                {
+                    Some(ref_) => ref_,
+                    None => return Type::Unknown,
+                };
+                self.resolve_alias(path, ref_)
+            }
+            field_id => {
+                self.error(path, format!("unknown type field {}", field_id));
+                Type::Unknown
+            }
+        }
+    }
+
+    fn resolve_alias(&mut self, path: &[Selector], ref_: i64) -> Type {
+        match self.resolved_aliases.get(&ref_) {
+            Some(Some(type_)) => return type_.clone(),
+            Some(None) => {
+                self.error(path, format!("cyclic type alias #{}", ref_));
+                return Type::Unknown;
+            }
+            None => {}
+        }
+        let (alias_path, alias) = match self.aliases.get(&ref_) {
+            Some(alias) => alias.clone(),
+            None => {
+                self.error(path, format!("unbound type ref #{}", ref_));
+                return Type::Unknown;
+            }
+        };
+        self.resolved_aliases.insert(ref_, None);
+        let type_ = self.resolve_field(&alias, &alias_path, type_alias::TYPE1);
+        self.resolved_aliases.insert(ref_, Some(type_.clone()));
+        type_
+    }
+
+    // Checks that an inferred type is compatible with the expected one, reporting an error at the
+    // given path otherwise.
+    fn expect(&mut self, path: &[Selector], expected: &Type, actual: &Type, what: &str) {
+        if !expected.compatible(actual) {
+            self.error(
+                path,
+                format!("{} expects {}, found {}", what, expected, actual),
+            );
+        }
+    }
+
+    // Infers the type of the `Expression` at the given link, in an environment mapping argument
+    // refs to their types.
+    fn infer(&mut self, env: &HashMap<i64, Type>, path: &[Selector], link: &Link) -> Type {
+        let node = match self.get_node(path, link) {
+            Some(node) => node,
+            None => return Type::Unknown,
+        };
+        let (field_id, link) = match ast::get_oneof(&node, expression::REF) {
+            Ok(v) => v,
+            // Empty expressions are holes, to be filled.
+            Err(e) if e == "empty" => return Type::Unknown,
+            Err(e) => {
+                self.error(path, format!("{} expression", e));
+                return Type::Unknown;
+            }
+        };
+        let path = &append(
+            path,
+            Selector {
+                field_id,
+                index: 0,
+                id: None,
+            },
+        );
+        let node = match field_id {
+            expression::EMPTY_VALUE | expression::HOLE_VALUE => return Type::Unknown,
+            _ => match self.get_node(path, link) {
+                Some(node) => node,
+                None => return Type::Unknown,
+            },
+        };
+        let node_store = self.node_store;
+        let literal = |valid: bool, type_: Type| if valid { Some(type_) } else { None };
+        let value = ast::literal::VALUE;
+        let literal_type = match field_id {
+            expression::BOOL_VALUE => literal(
+                ast::get_bool(node_store, &node, value).is_some(),
+                Type::Bool,
+            ),
+            expression::INT_VALUE => {
+                literal(ast::get_int(node_store, &node, value).is_some(), Type::Int)
+            }
+            expression::FLOAT_VALUE => literal(
+                ast::get_float(node_store, &node, value).is_some(),
+                Type::Float,
+            ),
+            expression::STRING_VALUE => literal(
+                ast::get_string(node_store, &node, value).is_some(),
+                Type::String,
+            ),
+            _ => Some(Type::Unknown),
+        };
+        match (field_id, literal_type) {
+            (
+                expression::BOOL_VALUE
+                | expression::INT_VALUE
+                | expression::FLOAT_VALUE
+                | expression::STRING_VALUE,
+                Some(type_),
+            ) => type_,
+            (_, None) => {
+                self.error(path, "invalid literal".to_string());
+                Type::Unknown
+            }
+            (expression::LIST_VALUE, _) => {
+                let mut element = Type::Unknown;
+                for (path, link) in ast::get_children(&node, path, list::VALUES) {
+                    let type_ = self.infer(env, &path, &link);
+                    if !element.compatible(&type_) {
+                        self.error(
+                            &path,
+                            format!("list element has type {}, expected {}", type_, element),
+                        );
+                    } else if element == Type::Unknown {
+                        element = type_;
+                    }
+                }
+                Type::List(Box::new(element))
+            }
+            (expression::IF_VALUE, _) => {
+                if let Some((path, link)) = ast::get_child(&node, path, if_::COND) {
+                    let type_ = self.infer(env, &path, link);
+                    self.expect(&path, &Type::Bool, &type_, "condition");
+                }
+                let t = match ast::get_child(&node, path, if_::TRUE) {
+                    Some((path, link)) => self.infer(env, &path, link),
+                    None => Type::Unknown,
+                };
+                let f = match ast::get_child(&node, path, if_::FALSE) {
+                    Some((path, link)) => {
+                        let f = self.infer(env, &path, link);
+                        if !t.compatible(&f) {
+                            self.error(
+                                &path,
+                                format!("branches have different types: {} and {}", t, f),
+                            );
+                        }
+                        f
+                    }
+                    None => Type::Unknown,
+                };
+                if t == Type::Unknown {
+                    f
+                } else {
+                    t
+                }
+            }
+            (expression::FUNCTION_APPLICATION_VALUE, _) => {
+                let args: Vec<(Path, Type)> =
+                    ast::get_children(&node, path, function_application::ARGUMENTS)
+                        .into_iter()
+                        .map(|(path, link)| {
+                            let type_ = self.infer(env, &path, &link);
+                            (path, type_)
+                        })
+                        .collect();
+                match self.get_ref(path, &node, function_application::FUNCTION) {
+                    Some(ref_) => self.check_application(env, path, ref_, &args),
+                    None => Type::Unknown,
+                }
+            }
+            (expression::REF_VALUE, _) => {
+                let ref_ = match self.get_ref(path, &node, ast::ref_::REF) {
+                    Some(ref_) => ref_,
+                    None => return Type::Unknown,
+                };
+                if let Some(type_) = env.get(&ref_) {
+                    type_.clone()
+                } else if let Some(signature) = self.functions.get(&ref_) {
+                    let args: Vec<_> = signature.args.iter().map(|(_, t)| t.clone()).collect();
+                    Type::function(&args, &signature.ret)
+                } else {
+                    self.error(path, format!("unbound ref #{}", ref_));
+                    Type::Unknown
+                }
+            }
+            (expression::EXTERNAL_REF_VALUE, _) => {
+                let external_path = ast::get_string(node_store, &node, ast::external_ref::PATH);
+                if external_path.as_deref() != Some(BUILTIN_PATH) {
+                    self.error(path, "unknown external ref".to_string());
+                }
+                Type::Unknown
+            }
+            (field_id, _) => {
+                self.error(path, format!("unknown expression field {}", field_id));
+                Type::Unknown
+            }
+        }
+    }
+
+    fn check_application(
+        &mut self,
+        env: &HashMap<i64, Type>,
+        path: &[Selector],
+        ref_: i64,
+        args: &[(Path, Type)],
+    ) -> Type {
+        // A function passed as an argument.
+        if let Some(type_) = env.get(&ref_) {
+            let mut type_ = type_.clone();
+            for (arg_path, arg) in args {
+                type_ = match type_ {
+                    Type::Function(param, ret) => {
+                        self.expect(arg_path, &param, arg, "argument");
+                        *ret
+                    }
+                    Type::Unknown => Type::Unknown,
+                    t => {
+                        self.error(
+                            path,
+                            format!("ref #{} is not a function, found {}", ref_, t),
+                        );
+                        return Type::Unknown;
+                    }
+                };
+            }
+            return type_;
+        }
+        let signature = match self.functions.get(&ref_) {
+            Some(signature) => signature,
+            None => {
+                self.error(path, format!("unbound function ref #{}", ref_));
+                return Type::Unknown;
+            }
+        };
+        let name = signature.name.clone();
+        let params = signature.args.clone();
+        let ret = signature.ret.clone();
+        if params.len() != args.len() {
+            self.error(
+                path,
+                format!(
+                    "function `{}` expects {} arguments, found {}",
+                    name,
+                    params.len(),
+                    args.len()
+                ),
+            );
+        }
+        for ((param_name, param), (arg_path, arg)) in params.iter().zip(args) {
+            let what = format!("argument `{}` of `{}`", param_name, name);
+            self.expect(arg_path, param, arg, &what);
+        }
+        ret
+    }
+}
+
+// Type checks the `File` at the given link and path, returning all the errors found.
+pub fn check_file(node_store: &NodeStore, path: &[Selector], link: &Link) -> Vec<TypeError> {
+    let mut checker = Checker {
+        node_store,
+        aliases: HashMap::new(),
+        resolved_aliases: HashMap::new(),
+        functions: HashMap::new(),
+        errors: vec![],
+    };
+    let file = match checker.get_node(path, link) {
+        Some(file) => file,
+        None => return checker.errors,
+    };
+
+    let aliases = ast::get_children(&file, path, ast::file::TYPE_ALIASES);
+    for (path, link) in &aliases {
+        if let Some(alias) = checker.get_node(path, link) {
+            if let Some(ref_) = checker.get_ref(path, &alias, type_alias::REF) {
+                match checker.aliases.entry(ref_) {
+                    Entry::Occupied(_) => {
+                        checker.error(path, format!("duplicate type ref #{}", ref_))
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert((path.clone(), alias));
+                    }
+                }
+            }
+        }
+    }
+    let mut refs: Vec<_> = checker.aliases.keys().cloned().collect();
+    refs.sort();
+    for ref_ in refs {
+        let alias_path = checker.aliases[&ref_].0.clone();
+        checker.resolve_alias(&alias_path, ref_);
+    }
+
+    // Collect all the signatures first, since functions may be (mutually) recursive.
+    let mut definitions = vec![];
+    for (path, link) in ast::get_children(&file, path, ast::file::FUNCTION_DEFINITIONS) {
+        let definition = match checker.get_node(&path, &link) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        let ref_ = match checker.get_ref(&path, &definition, function_definition::REF) {
+            Some(ref_) => ref_,
+            None => continue,
+        };
+        if checker.functions.contains_key(&ref_) {
+            checker.error(&path, format!("duplicate function ref #{}", ref_));
+            continue;
+        }
+        let mut env = HashMap::new();
+        let mut args = vec![];
+        for (arg_path, arg) in ast::get_children(&definition, &path, function_definition::ARGUMENTS)
+        {
+            let arg = match checker.get_node(&arg_path, &arg) {
+                Some(arg) => arg,
+                None => continue,
+            };
+            let type_ = checker.resolve_field(&arg, &arg_path, argument::TYPE);
+            if let Some(arg_ref) = checker.get_ref(&arg_path, &arg, argument::REF) {
+                env.insert(arg_ref, type_.clone());
+            }
+            args.push((ast::get_label(node_store, &arg, argument::LABEL), type_));
+        }
+        let ret = checker.resolve_field(&definition, &path, function_definition::RETURN_TYPE);
+        let name = ast::get_label(node_store, &definition, function_definition::LABEL);
+        checker
+            .functions
+            .insert(ref_, Signature { name, args, ret });
+        definitions.push((path, definition, ref_, env));
+    }
+
+    for (path, definition, ref_, env) in definitions {
+        if let Some((body_path, body)) =
+            ast::get_child(&definition, &path, function_definition::BODY)
+        {
+            let type_ = checker.infer(&env, &body_path, body);
+            let ret = checker.functions[&ref_].ret.clone();
+            checker.expect(&body_path, &ret, &type_, "return type");
+        }
+    }
+    checker.errors
+}
+
+// Type errors of all the `File`s in a tree. As for the search index, the errors of a subtree only
+// depend on its digest and kind, so they are memoized by those, with paths relative to the subtree:
+// when the root changes, only the files on the paths to the edits are checked again.
+#[derive(Debug, Default)]
+pub struct Errors {
+    root: Option<Digest>,
+    errors: Vec<TypeError>,
+    memo: HashMap<(Digest, Option<u64>), Rc<Vec<TypeError>>>,
+}
+
+impl Errors {
+    pub fn errors(&self) -> &[TypeError] {
+        &self.errors
+    }
+
+    // Brings the errors up to date with the given root.
+    pub fn update(&mut self, node_store: &NodeStore, schema: &Schema, root: &Cursor) {
+        if self.root.as_ref() == Some(&root.link.digest) {
+            return;
+        }
+        let mut memo = HashMap::new();
+        let errors = self.subtree(node_store, schema, root, &mut memo);
+        // Only keep the subtrees of the current root.
+        self.memo = memo;
+        self.errors = errors
+            .iter()
+            .map(|error| TypeError {
+                path: [root.path(), error.path.clone()].concat(),
+                message: error.message.clone(),
+            })
+            .collect();
+        self.root = Some(root.link.digest.clone());
+    }
+
+    fn subtree(
+        &self,
+        node_store: &NodeStore,
+        schema: &Schema,
+        cursor: &Cursor,
+        memo: &mut HashMap<(Digest, Option<u64>), Rc<Vec<TypeError>>>,
+    ) -> Rc<Vec<TypeError>> {
+        let memo_key = (cursor.link.digest.clone(), cursor.kind_id());
+        if let Some(errors) = memo.get(&memo_key).or_else(|| self.memo.get(&memo_key)) {
+            let errors = errors.clone();
+            memo.insert(memo_key, errors.clone());
+            return errors;
+        }
+        let mut errors = vec![];
+        if cursor.kind_id() == Some(ast::file::kind_id()) {
+            errors = check_file(node_store, &[], &cursor.link);
+        } else if let Some(LinkTarget::Parsed(node)) = cursor.link.get(node_store) {
+            // Children are relative to this node.
+            let this = Cursor {
+                parent: None,
+                link: cursor.link.clone(),
+                type_: cursor.type_.clone(),
+            };
+            for (field_id, links) in node.links.iter() {
+                for index in 0..links.len() {
+                    let selector = Selector {
+                        field_id: *field_id,
+                        index,
+                        id: None,
+                    };
+                    let child =
+                        match this.traverse(node_store, schema, std::slice::from_ref(&selector)) {
+                            Some(child) => child,
+                            None => continue,
+                        };
+                    let children = self.subtree(node_store, schema, &child, memo);
+                    errors.extend(children.iter().map(|error| TypeError {
+                        path: [vec![selector.clone()], error.path.clone()].concat(),
+                        message: error.message.clone(),
+                    }));
+                }
+            }
+        }
+        let errors = Rc::new(errors);
+        memo.insert(memo_key, errors.clone());
+        errors
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{
+    schema::{FieldType, FieldValue},
+    types::{append, Link, LinkTarget, LinkType, Node, NodeStore, Path, Selector},
+};
+use std::{collections::BTreeMap, fmt};
+
+// Runtime of the typed accessors emitted by `linc_schema::codegen` (see `src/generated.rs`).
+//
+// A generated reader wraps an `Object`, which holds a single parsed node; its children are only
+// loaded from the `NodeStore` when their getter is called. Singular scalars that are not set read
+// as their default value, as in protobuf. A generated builder wraps a `Builder`, which is turned
+// into a new node (and its new scalar leaves) in the store by `build`.
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Error {
+    pub path: Path,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self
+            .path
+            .iter()
+            .map(|s| format!("{}[{}]", s.field_id, s.index))
+            .collect();
+        write!(f, "/{}: {}", path.join("/"), self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn error<T>(path: &[Selector], message: String) -> Result<T, Error> {
+    Err(Error {
+        path: path.to_vec(),
+        message,
+    })
+}
+
+// Types that may be stored as raw leaves.
+pub trait Scalar: Sized + Default {
+    const TYPE: FieldType;
+    fn from_value(value: FieldValue) -> Option<Self>;
+    fn to_value(&self) -> FieldValue;
+}
+
+macro_rules! scalar {
+    ($t:ty, $variant:ident) => {
+        impl Scalar for $t {
+            const TYPE: FieldType = FieldType::$variant;
+
+            fn from_value(value: FieldValue) -> Option<Self> {
+                match value {
+                    FieldValue::$variant(v) => Some(v),
+                    _ => None,
+                }
+            }
+
+            fn to_value(&self) -> FieldValue {
+                FieldValue::$variant(self.clone())
+            }
+        }
+    };
+}
+
+scalar!(String, String);
+scalar!(Vec<u8>, Bytes);
+scalar!(bool, Bool);
+scalar!(i64, Int);
+scalar!(f64, Float);
+
+// Reads the scalar at the given link.
+pub fn value<T: Scalar>(
+    node_store: &NodeStore,
+    path: &[Selector],
+    link: &Link,
+) -> Result<T, Error> {
+    match link.get(node_store) {
+        Some(LinkTarget::Raw(raw)) => FieldValue::from_raw(&T::TYPE, raw)
+            .and_then(T::from_value)
+            .map_or_else(|| error(path, format!("invalid {:?} value", T::TYPE)), Ok),
+        Some(LinkTarget::Parsed(_)) => {
+            error(path, "expected a raw value, found a node".to_string())
+        }
+        None => error(path, format!("missing value {}", link.digest)),
+    }
+}
+
+// Generated readers of a kind.
+pub trait Reader<'s>: Sized {
+    const KIND_ID: u64;
+
+    fn from_object(object: Object<'s>) -> Self;
+
+    fn object(&self) -> &Object<'s>;
+
+    // Loads the node at the given link, e.g. the root of a tree.
+    fn load(node_store: &'s NodeStore, link: &Link) -> Result<Self, Error> {
+        Object::load(node_store, &[], link).map(Self::from_object)
+    }
+
+    fn path(&self) -> Path {
+        self.object().path.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Object<'s> {
+    node_store: &'s NodeStore,
+    path: Path,
+    link: Link,
+    node: Node,
+}
+
+impl<'s> Object<'s> {
+    pub fn load(node_store: &'s NodeStore, path: &[Selector], link: &Link) -> Result<Self, Error> {
+        match link.get(node_store) {
+            Some(LinkTarget::Parsed(node)) => Ok(Object {
+                node_store,
+                path: path.to_vec(),
+                link: link.clone(),
+                node: node.clone(),
+            }),
+            Some(LinkTarget::Raw(_)) => {
+                error(path, "expected a node, found a raw value".to_string())
+            }
+            None => error(path, format!("missing node {}", link.digest)),
+        }
+    }
+
+    pub fn path(&self) -> &[Selector] {
+        &self.path
+    }
+
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    pub fn has(&self, field_id: u64) -> bool {
+        self.node.links.contains_key(&field_id)
+    }
+
+    pub fn links(&self, field_id: u64) -> impl Iterator<Item = (Path, &Link)> {
+        self.node
+            .links
+            .get(&field_id)
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(move |(index, link)| {
+                (
+                    append(
+                        &self.path,
+                        Selector {
+                            field_id,
+                            index,
+                            id: None,
+                        },
+                    ),
+                    link,
+                )
+            })
+    }
+
+    fn single(&self, field_id: u64) -> Result<Option<(Path, &Link)>, Error> {
+        let mut links = self.links(field_id);
+        match (links.next(), links.next()) {
+            (Some(_), Some((path, _))) => error(&path, "more than one value".to_string()),
+            (link, _) => Ok(link),
+        }
+    }
+
+    fn value<T: Scalar>(&self, path: &[Selector], link: &Link) -> Result<T, Error> {
+        value(self.node_store, path, link)
+    }
+
+    pub fn scalar<T: Scalar>(&self, field_id: u64) -> Result<T, Error> {
+        match self.single(field_id)? {
+            Some((path, link)) => self.value(&path, link),
+            None => Ok(T::default()),
+        }
+    }
+
+    pub fn scalars<T: Scalar>(&self, field_id: u64) -> Result<Vec<T>, Error> {
+        self.links(field_id)
+            .map(|(path, link)| self.value(&path, link))
+            .collect()
+    }
+
+    pub fn child<R: Reader<'s>>(&self, field_id: u64) -> Result<Option<R>, Error> {
+        self.single(field_id)?
+            .map(|(path, link)| Object::load(self.node_store, &path, link).map(R::from_object))
+            .transpose()
+    }
+
+    pub fn children<R: Reader<'s>>(&self, field_id: u64) -> Result<Vec<R>, Error> {
+        self.links(field_id)
+            .map(|(path, link)| Object::load(self.node_store, &path, link).map(R::from_object))
+            .collect()
+    }
+
+    // Returns the id of the single field of the given `oneof` fields that is set, if any.
+    pub fn oneof(&self, field_ids: &[u64]) -> Result<Option<u64>, Error> {
+        let set: Vec<u64> = field_ids
+            .iter()
+            .copied()
+            .filter(|field_id| self.has(*field_id))
+            .collect();
+        match set.as_slice() {
+            [] => Ok(None),
+            [field_id] => Ok(Some(*field_id)),
+            _ => error(&self.path, "more than one value set in oneof".to_string()),
+        }
+    }
+
+    pub fn to_builder(&self) -> Builder {
+        Builder {
+            links: self
+                .node
+                .links
+                .iter()
+                .map(|(field_id, links)| {
+                    (
+                        *field_id,
+                        links.iter().cloned().map(Pending::Link).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Pending {
+    Link(Link),
+    Raw(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Builder {
+    links: BTreeMap<u64, Vec<Pending>>,
+}
+
+impl Builder {
+    pub fn clear(&mut self, field_ids: &[u64]) {
+        for field_id in field_ids {
+            self.links.remove(field_id);
+        }
+    }
+
+    fn set(&mut self, field_id: u64, values: Vec<Pending>) {
+        if values.is_empty() {
+            self.links.remove(&field_id);
+        } else {
+            self.links.insert(field_id, values);
+        }
+    }
+
+    pub fn set_scalar<T: Scalar>(&mut self, field_id: u64, value: &T) {
+        self.set_scalars(field_id, std::slice::from_ref(value));
+    }
+
+    pub fn set_scalars<T: Scalar>(&mut self, field_id: u64, values: &[T]) {
+        let values = values
+            .iter()
+            .filter_map(|v| v.to_value().to_raw())
+            .map(Pending::Raw)
+            .collect();
+        self.set(field_id, values);
+    }
+
+    pub fn set_child(&mut self, field_id: u64, link: Option<Link>) {
+        self.set(field_id, link.into_iter().map(Pending::Link).collect());
+    }
+
+    pub fn set_children(&mut self, field_id: u64, links: Vec<Link>) {
+        self.set(field_id, links.into_iter().map(Pending::Link).collect());
+    }
+
+    // Stores the new node and its raw values, and returns a link to it.
+    pub fn build(&self, node_store: &mut NodeStore) -> Link {
+        let mut node = Node::default();
+        for (field_id, values) in self.links.iter() {
+            let links = values
+                .iter()
+                .map(|value| match value {
+                    Pending::Link(link) => link.clone(),
+                    Pending::Raw(raw) => Link {
+                        type_: LinkType::Raw,
+                        digest: node_store.put_raw(raw),
+                    },
+                })
+                .collect();
+            node.links.insert(*field_id, links);
+        }
+        Link {
+            type_: LinkType::Dag,
+            digest: node_store.put_parsed(&node),
+        }
+    }
+}
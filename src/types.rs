@@ -1,4 +1,6 @@
 use crate::{
+    crdt::{self, Element, ElementId},
+    encryption::{self, Key},
     model::Msg,
     node::FIELD_CLASSES,
     schema::{FieldType, Schema},
@@ -7,7 +9,7 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use sha2::Sha256;
 use std::{
-    collections::{hash_map::Entry, BTreeMap, HashMap},
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
     convert::TryInto,
     ops::Deref,
     rc::Rc,
@@ -21,6 +23,10 @@ use yew::{html, prelude::*, Html};
 pub struct Selector {
     pub field_id: u64,
     pub index: usize,
+    // Stable id of the child, if the field is a sequence (see `crdt`); takes precedence over
+    // `index`, which may be out of date after concurrent edits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<ElementId>,
 }
 
 pub type Digest = String;
@@ -44,6 +50,21 @@ pub fn parent(path: &[Selector]) -> &[Selector] {
 pub enum Mode {
     Normal,
     Edit,
+    // Selecting a range of siblings, from the node selected when entering the mode to the
+    // selected one.
+    Visual,
+}
+
+// Text format used to import and export subtrees.
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+    Html,
+    Protobuf,
+    // A shell command line, according to the `arg`s of the fields.
+    Argv,
 }
 
 pub fn digest(value: &[u8]) -> Digest {
@@ -70,23 +91,54 @@ pub fn node_digest(node: &Node) -> Digest {
 
 #[derive(Default, PartialEq, Clone)]
 pub struct NodeState {
-    // TODO: Errors.
+    // Errors found at this node, e.g. by the type checker.
+    pub errors: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct NodeStore {
     raw_nodes: HashMap<Digest, Vec<u8>>,
     parsed_nodes: Arc<Mutex<HashMap<Digest, Node>>>,
+    // Keys of encrypted links (see `encryption.rs`), held by the client only.
+    #[serde(skip)]
+    keys: Vec<Key>,
+    // Targets of the encrypted blobs that one of `keys` decrypts, keyed by the digest of the
+    // ciphertext.
+    #[serde(skip)]
+    decrypted: HashMap<Digest, Decrypted>,
+    // Blobs that were encrypted, which must not be shared.
+    #[serde(default)]
+    secret: HashSet<Digest>,
+    // Digests of the subtrees known to be complete, i.e. with all their nodes present and
+    // decrypted. Nodes are only added, so this only grows, until one is removed.
+    #[serde(skip)]
+    complete: Arc<Mutex<HashSet<Digest>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Decrypted {
+    key: usize,
+    type_: LinkType,
+    plaintext: Vec<u8>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Cursor {
     pub parent: Option<(Box<Cursor>, Selector)>,
     pub link: Link,
-    pub kind_id: u64,
+    // Type of the value at this position: the kind of a node, or the primitive type of a leaf.
+    pub type_: FieldType,
 }
 
 impl Cursor {
+    // Kind of the node at this position, if it is not a leaf.
+    pub fn kind_id(&self) -> Option<u64> {
+        match self.type_ {
+            FieldType::Object { kind_id } => Some(kind_id),
+            _ => None,
+        }
+    }
+
     pub fn next(&self, node_store: &NodeStore, schema: &Schema) -> Option<Cursor> {
         // This must work even if the current hash / reference is invalid.
         self.link
@@ -95,19 +147,17 @@ impl Cursor {
                 match link_target {
                     LinkTarget::Raw(_) => None,
                     LinkTarget::Parsed(node) => {
-                        node.links.iter().next().map(|(field_id, links)| {
-                            // Depth first.
-                            Cursor {
-                                parent: Some((
-                                    Box::new(self.clone()),
-                                    Selector {
-                                        field_id: *field_id,
-                                        index: 0,
-                                    },
-                                )),
-                                link: links[0].clone(),
-                                kind_id: 0, // TODO
-                            }
+                        // Depth first.
+                        node.links.keys().next().and_then(|field_id| {
+                            self.traverse(
+                                node_store,
+                                schema,
+                                &[Selector {
+                                    field_id: *field_id,
+                                    index: 0,
+                                    id: None,
+                                }],
+                            )
                         })
                     }
                 }
@@ -134,6 +184,7 @@ impl Cursor {
                     let next_selector = Selector {
                         field_id: selector.field_id,
                         index: selector.index + 1,
+                        id: None,
                     };
                     self.traverse(node_store, schema, &[next_selector])
                 } else if let Some((next_field_id, _next_children)) = node
@@ -148,6 +199,7 @@ impl Cursor {
                     let next_selector = Selector {
                         field_id: *next_field_id,
                         index: 0,
+                        id: None,
                     };
                     self.traverse(node_store, schema, &[next_selector])
                 } else {
@@ -181,6 +233,7 @@ impl Cursor {
                 let prev_selector = Selector {
                     field_id: selector.field_id,
                     index: selector.index - 1,
+                    id: None,
                 };
                 self.traverse(node_store, schema, &[prev_selector])
             } else if let Some((prev_field_id, _prev_children)) = node
@@ -197,6 +250,7 @@ impl Cursor {
                 let prev_selector = Selector {
                     field_id: *prev_field_id,
                     index: 0,
+                    id: None,
                 };
                 self.traverse(node_store, schema, &[prev_selector])
             } else {
@@ -215,29 +269,35 @@ impl Cursor {
         schema: &Schema,
         path: &[Selector],
     ) -> Option<Cursor> {
-        let kind = schema.get_kind(self.kind_id).cloned().unwrap_or_default();
         match path.split_first() {
             Some((selector, rest)) => {
                 match self.link.get(node_store)? {
                     LinkTarget::Raw(_) => None,
                     LinkTarget::Parsed(node) => {
                         // child_hash may or may not be valid at this point.
-                        let child_link = node.links.get(&selector.field_id)?.get(selector.index)?;
-                        let child_type = kind
-                            .get_field(selector.field_id)
-                            .cloned()
-                            .unwrap_or_default()
-                            .type_;
-                        if let FieldType::Object { kind_id } = child_type {
-                            let child = Cursor {
-                                parent: Some((Box::new(self.clone()), selector.clone())),
-                                link: child_link.clone(),
-                                kind_id,
-                            };
-                            child.traverse(node_store, schema, rest)
-                        } else {
-                            None
-                        }
+                        let child_link = node.get_link(selector)?;
+                        let field = self
+                            .kind_id()
+                            .and_then(|kind_id| schema.get_kind(kind_id))
+                            .and_then(|kind| kind.get_field(selector.field_id));
+                        // Fields missing from the schema are shown as text, or as bytes if they
+                        // are not valid UTF-8.
+                        let type_ = match (field, &child_link.type_) {
+                            (Some(field), _) => field.type_.clone(),
+                            (None, LinkType::Raw) => match child_link.get(node_store) {
+                                Some(LinkTarget::Raw(raw)) if std::str::from_utf8(raw).is_err() => {
+                                    FieldType::Bytes
+                                }
+                                _ => FieldType::String,
+                            },
+                            (None, _) => FieldType::Object { kind_id: 0 },
+                        };
+                        let child = Cursor {
+                            parent: Some((Box::new(self.clone()), selector.clone())),
+                            link: child_link.clone(),
+                            type_,
+                        };
+                        child.traverse(node_store, schema, rest)
                     }
                 }
             }
@@ -256,13 +316,6 @@ impl Cursor {
     }
 }
 
-impl PartialEq for NodeStore {
-    fn eq(&self, other: &Self) -> bool {
-        // Only compare the size of the hashmap, since it is effectively append-only.
-        self.raw_nodes.len() == other.raw_nodes.len()
-    }
-}
-
 impl NodeStore {
     // TODO: remove.
     pub fn get_raw(&self, digest: &str) -> Option<&Vec<u8>> {
@@ -275,7 +328,10 @@ impl NodeStore {
         match entry {
             Entry::Occupied(o) => Some(o.get().clone()),
             Entry::Vacant(v) => {
-                let raw_node = self.raw_nodes.get(digest)?;
+                let raw_node = match self.decrypted.get(digest) {
+                    Some(decrypted) if decrypted.type_ == LinkType::Dag => &decrypted.plaintext,
+                    _ => self.raw_nodes.get(digest)?,
+                };
                 // TODO: Put into cache.
                 let node = crate::types::deserialize_node(raw_node)?;
                 let r = v.insert(node.clone());
@@ -284,6 +340,27 @@ impl NodeStore {
         }
     }
 
+    // Whether all the nodes under the link are present and decrypted, so that adding nodes to the
+    // store cannot change how the subtree is shown.
+    pub fn is_complete(&self, link: &Link) -> bool {
+        if self.complete.lock().unwrap().contains(&link.digest) {
+            return true;
+        }
+        let complete = match link.get(self) {
+            None => false,
+            Some(LinkTarget::Raw(_)) => true,
+            Some(LinkTarget::Parsed(node)) => node
+                .links
+                .values()
+                .flatten()
+                .all(|child| self.is_complete(child)),
+        };
+        if complete {
+            self.complete.lock().unwrap().insert(link.digest.clone());
+        }
+        complete
+    }
+
     pub fn has_raw_node(&self, digest: &str) -> bool {
         self.raw_nodes.contains_key(digest)
     }
@@ -296,6 +373,10 @@ impl NodeStore {
         self.raw_nodes.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.raw_nodes.is_empty()
+    }
+
     #[must_use]
     pub fn put_parsed(&mut self, node: &Node) -> Digest {
         let d = node_digest(node);
@@ -312,9 +393,91 @@ impl NodeStore {
     pub fn put_raw(&mut self, value: &[u8]) -> Digest {
         let d = digest(value);
         self.raw_nodes.insert(d.clone(), value.to_vec());
+        self.decrypt(&d);
         d
     }
 
+    // Only stores the value if it actually hashes to the expected digest, otherwise returns the
+    // actual digest as an error.
+    pub fn put_raw_checked(&mut self, expected: &str, value: &[u8]) -> Result<(), Digest> {
+        let d = digest(value);
+        if d != expected {
+            return Err(d);
+        }
+        self.raw_nodes.insert(d.clone(), value.to_vec());
+        self.decrypt(&d);
+        Ok(())
+    }
+
+    // Adds a key for encrypted links, and decrypts the blobs it opens.
+    pub fn add_key(&mut self, key: Key) {
+        if self.keys.contains(&key) {
+            return;
+        }
+        self.keys.push(key);
+        let digests: Vec<Digest> = self.raw_nodes.keys().cloned().collect();
+        for d in digests.iter() {
+            self.decrypt(d);
+        }
+    }
+
+    // Returns the key that decrypts the given blob, if any.
+    pub fn key_of(&self, digest: &str) -> Option<&Key> {
+        let decrypted = self.decrypted.get(digest)?;
+        self.keys.get(decrypted.key)
+    }
+
+    fn decrypt(&mut self, digest: &str) {
+        if self.decrypted.contains_key(digest) {
+            return;
+        }
+        let blob = match self.raw_nodes.get(digest) {
+            Some(blob) => blob,
+            None => return,
+        };
+        let opened = self
+            .keys
+            .iter()
+            .enumerate()
+            .find_map(|(i, key)| encryption::open(key, blob).map(|target| (i, target)));
+        if let Some((key, (type_, plaintext))) = opened {
+            self.decrypted.insert(
+                digest.to_string(),
+                Decrypted {
+                    key,
+                    type_,
+                    plaintext,
+                },
+            );
+        }
+    }
+
+    // Marks a blob as the plaintext of an encrypted one.
+    pub fn mark_secret(&mut self, digest: &str) {
+        self.secret.insert(digest.to_string());
+    }
+
+    // Same as `iter`, without the blobs that are the plaintext of an encrypted blob, i.e. the
+    // blobs that may be shared with a server.
+    pub fn iter_public(&self) -> impl Iterator<Item = (&Digest, &Vec<u8>)> {
+        let decrypted: HashSet<Digest> = self
+            .decrypted
+            .values()
+            .map(|decrypted| digest(&decrypted.plaintext))
+            .collect();
+        self.raw_nodes
+            .iter()
+            .filter(move |(d, _)| !self.secret.contains(*d) && !decrypted.contains(*d))
+    }
+
+    pub fn remove(&mut self, digest: &str) {
+        self.raw_nodes.remove(digest);
+        self.decrypted.remove(digest);
+        self.parsed_nodes.lock().unwrap().remove(digest);
+        // The subtrees that included it are not complete any more.
+        self.complete.lock().unwrap().clear();
+    }
+
     pub fn put_many(&mut self, nodes: &[Node]) {
         for node in nodes {
             self.put_parsed(node);
@@ -334,18 +497,31 @@ impl NodeStore {
 pub struct Node {
     // Keyed by field id.
     pub links: BTreeMap<u64, Vec<Link>>,
+    // Element ids of the fields stored as sequences, keyed by field id (see `crdt`). Skipped when
+    // empty, so that the digests of other nodes are unchanged.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sequences: BTreeMap<u64, Vec<Element>>,
 }
 
 impl Node {
+    // Returns the index in its field of the child addressed by the selector.
+    pub fn index(&self, selector: &Selector) -> Option<usize> {
+        match &selector.id {
+            Some(id) => crdt::index_of(self, selector.field_id, id),
+            None => Some(selector.index),
+        }
+    }
     pub fn get_link(&self, selector: &Selector) -> Option<&Link> {
+        let index = self.index(selector)?;
         self.links
             .get(&selector.field_id)
-            .and_then(|links| links.get(selector.index))
+            .and_then(|links| links.get(index))
     }
     pub fn get_link_mut(&mut self, selector: &Selector) -> Option<&mut Link> {
+        let index = self.index(selector)?;
         self.links
             .get_mut(&selector.field_id)
-            .and_then(|links| links.get_mut(selector.index))
+            .and_then(|links| links.get_mut(index))
     }
 }
 
@@ -354,12 +530,15 @@ impl Node {
 pub enum LinkType {
     Raw = 0,
     Dag = 1,
+    // The target is encrypted, and is either raw or a node (see `encryption.rs`).
+    Encrypted = 2,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Link {
     // 0: raw
     // 1: dag
+    // 2: encrypted
     #[serde(rename = "type")]
     pub type_: LinkType,
     pub digest: Digest,
@@ -370,6 +549,13 @@ impl Link {
         match self.type_ {
             LinkType::Raw => node_store.get_raw(&self.digest).map(LinkTarget::Raw),
             LinkType::Dag => node_store.get_dag(&self.digest).map(LinkTarget::Parsed),
+            LinkType::Encrypted => match node_store.decrypted.get(&self.digest)? {
+                Decrypted {
+                    type_: LinkType::Dag,
+                    ..
+                } => node_store.get_dag(&self.digest).map(LinkTarget::Parsed),
+                decrypted => Some(LinkTarget::Raw(&decrypted.plaintext)),
+            },
         }
     }
 }
@@ -420,7 +606,11 @@ pub struct Action {
 }
 
 pub fn get_value_from_input_event(e: InputEvent) -> String {
-    let event: Event = e.dyn_into().unwrap_throw();
+    get_value_from_event(&e)
+}
+
+// Value of the input or text area that is the target of the event.
+pub fn get_value_from_event(event: &Event) -> String {
     let event_target = event.target().unwrap_throw();
     if let Ok(target) = event_target.clone().dyn_into::<HtmlInputElement>() {
         return target.value();
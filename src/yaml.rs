@@ -0,0 +1,178 @@
+use crate::{
+    json,
+    schema::{Field, FieldType, Kind, Schema},
+    types::{Link, LinkTarget, LinkType, Node, NodeStore},
+};
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+// YAML documents are mapped onto the generic JSON schema, going through `serde_json::Value`; key
+// order is preserved by link order.
+//
+// A stream of several documents separated by `---`, as is usual for e.g. Kubernetes manifests, is
+// a `yaml_stream` node, with a `json_value` per document. Mapping keys that are bools or numbers
+// become strings, since JSON keys are strings; other keys (null, sequences and mappings) are
+// rejected. Tags are dropped.
+pub const YAML_STREAM: u64 = 7430200;
+
+// Field of `yaml_stream`.
+pub const STREAM_DOCUMENTS: u64 = 1;
+
+pub fn yaml_schema() -> Schema {
+    Schema {
+        kinds: vec![Kind {
+            kind_id: YAML_STREAM,
+            name: "yaml_stream".to_string(),
+            fields: vec![Field {
+                field_id: STREAM_DOCUMENTS,
+                name: "documents".to_string(),
+                type_: FieldType::Object {
+                    kind_id: json::JSON_VALUE,
+                },
+                repeated: true,
+                ..Default::default()
+            }],
+        }],
+    }
+}
+
+// Parses a single YAML document.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut documents = parse_stream(text)?;
+    match documents.len() {
+        1 => Ok(documents.remove(0)),
+        n => Err(format!(
+            "expected a single YAML document, found {}; import it as a yaml_stream",
+            n
+        )),
+    }
+}
+
+// Parses a stream of YAML documents.
+pub fn parse_stream(text: &str) -> Result<Vec<Value>, String> {
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(text)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("invalid YAML: {}", e))?;
+    let several = documents.len() > 1;
+    documents
+        .into_iter()
+        .enumerate()
+        .map(|(i, document)| {
+            // Paths in errors start with the index of the document, if there are several.
+            let path = if several {
+                format!("[{}]", i)
+            } else {
+                String::new()
+            };
+            to_json(document, &path)
+        })
+        .collect()
+}
+
+fn to_json(value: serde_yaml::Value, path: &str) -> Result<Value, String> {
+    Ok(match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(v) => Value::Bool(v),
+        serde_yaml::Value::Number(v) => {
+            if let Some(v) = v.as_i64() {
+                Value::from(v)
+            } else if let Some(v) = v.as_u64() {
+                Value::from(v)
+            } else {
+                match v.as_f64().and_then(Number::from_f64) {
+                    Some(v) => Value::Number(v),
+                    None => return Err(format!("{}: {} is not supported", display(path), v)),
+                }
+            }
+        }
+        serde_yaml::Value::String(v) => Value::String(v),
+        serde_yaml::Value::Sequence(items) => Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| to_json(v, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        serde_yaml::Value::Mapping(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        serde_yaml::Value::String(k) => k,
+                        serde_yaml::Value::Bool(k) => k.to_string(),
+                        serde_yaml::Value::Number(k) => k.to_string(),
+                        k => {
+                            let type_ = match k {
+                                serde_yaml::Value::Null => "null",
+                                serde_yaml::Value::Sequence(_) => "sequence",
+                                serde_yaml::Value::Mapping(_) => "mapping",
+                                _ => "tagged",
+                            };
+                            return Err(format!(
+                                "{}: unsupported {} mapping key",
+                                display(path),
+                                type_
+                            ));
+                        }
+                    };
+                    let v = to_json(v, &format!("{}.{}", path, key))?;
+                    Ok((key, v))
+                })
+                .collect::<Result<Map<_, _>, _>>()?,
+        ),
+        serde_yaml::Value::Tagged(tagged) => to_json(tagged.value, path)?,
+    })
+}
+
+fn display(path: &str) -> &str {
+    if path.is_empty() {
+        "."
+    } else {
+        path
+    }
+}
+
+pub fn print(value: &Value) -> Result<String, String> {
+    serde_yaml::to_string(value).map_err(|e| format!("could not print YAML: {}", e))
+}
+
+// Prints a stream of documents, each of them preceded by `---`.
+pub fn print_stream(documents: &[Value]) -> Result<String, String> {
+    documents
+        .iter()
+        .map(|document| print(document).map(|text| format!("---\n{}", text)))
+        .collect()
+}
+
+pub fn import_stream(node_store: &mut NodeStore, documents: &[Value]) -> Link {
+    let documents = documents
+        .iter()
+        .map(|document| json::import(node_store, document))
+        .collect();
+    let node = Node {
+        links: maplit::btreemap! { STREAM_DOCUMENTS => documents },
+        ..Default::default()
+    };
+    Link {
+        type_: LinkType::Dag,
+        digest: node_store.put_parsed(&node),
+    }
+}
+
+pub fn export_stream(node_store: &NodeStore, link: &Link) -> Result<Vec<Value>, String> {
+    let node = match link.get(node_store) {
+        Some(LinkTarget::Parsed(node)) => node,
+        _ => return Err(format!("missing yaml_stream node {}", link.digest)),
+    };
+    node.links
+        .get(&STREAM_DOCUMENTS)
+        .cloned()
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(i, document)| {
+            json::export(node_store, document).map_err(|e| format!("[{}]{}", i, e))
+        })
+        .collect()
+}